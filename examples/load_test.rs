@@ -0,0 +1,53 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Signer Service Load Test
+
+use manta_signer::{
+    load_test::{self, LoadTestOptions},
+    service::Error,
+};
+
+/// Parses the optional argument at `index` or returns `default`.
+#[inline]
+fn arg<T>(index: usize, default: T) -> T
+where
+    T: core::str::FromStr,
+{
+    std::env::args()
+        .nth(index)
+        .map(|arg| {
+            arg.parse()
+                .unwrap_or_else(|_| panic!("Invalid argument: {}", arg))
+        })
+        .unwrap_or(default)
+}
+
+#[async_std::main]
+async fn main() -> Result<(), Error> {
+    let defaults = LoadTestOptions::default();
+    let options = LoadTestOptions {
+        requests: arg(1, defaults.requests),
+        concurrency: arg(2, defaults.concurrency),
+        approval_delay_ms: arg(3, defaults.approval_delay_ms),
+    };
+    let report = load_test::run(options).await?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("Unable to serialize the load test report.")
+    );
+    Ok(())
+}
@@ -0,0 +1,224 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Air-Gapped Transport
+//!
+//! Payloads which cross an air gap as animated QR codes are split into frames of the form
+//!
+//! ```text
+//! MSQR/<INDEX>/<TOTAL>/<DIGEST>/<DATA>
+//! ```
+//!
+//! where `INDEX` counts from one, `DIGEST` is the first [`DIGEST_LENGTH`] bytes of the SHA-256
+//! hash of the whole payload and `DATA` is a chunk of the payload, both upper-case hex encoded.
+//! Every character of a frame is in the QR alphanumeric set, so frames encode compactly. Frames
+//! can be scanned in any order and more than once, and the payload is only accepted once every
+//! frame was scanned and the digest matches.
+//!
+//! Machines without a camera can exchange the serialized requests and responses as files instead,
+//! see [`SignFileRequest`].
+
+use data_encoding::HEXUPPER;
+use manta_util::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Frame Prefix
+pub const FRAME_PREFIX: &str = "MSQR";
+
+/// Maximum Number of Payload Bytes per Frame
+pub const MAX_FRAME_DATA: usize = 512;
+
+/// Maximum Number of Frames per Payload
+pub const MAX_FRAMES: usize = 1024;
+
+/// Payload Digest Length in Bytes
+pub const DIGEST_LENGTH: usize = 8;
+
+/// Air-Gap Error
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum Error {
+    /// Invalid Frame
+    ///
+    /// The frame is not of the form described in the [module documentation](self).
+    InvalidFrame,
+
+    /// Mismatched Frame
+    ///
+    /// The frame belongs to a different payload than the frames scanned before it.
+    MismatchedFrame,
+
+    /// Missing Frames
+    MissingFrames,
+
+    /// Digest Mismatch
+    ///
+    /// Every frame was scanned but the assembled payload does not match their digest.
+    DigestMismatch,
+
+    /// Invalid Payload
+    ///
+    /// The assembled payload could not be deserialized.
+    InvalidPayload,
+}
+
+/// Air-Gapped Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct AirgapRequest {
+    /// Scanned Frames
+    pub frames: Vec<String>,
+}
+
+/// File Signing Request
+///
+/// The signer reads the JSON-serialized sign request from the `input` file and writes the
+/// JSON-serialized response to the `output` file.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SignFileRequest {
+    /// Input File Path
+    pub input: PathBuf,
+
+    /// Output File Path
+    pub output: PathBuf,
+}
+
+/// Returns the payload digest of `payload`.
+#[inline]
+fn digest(payload: &[u8]) -> [u8; DIGEST_LENGTH] {
+    let mut digest = [0; DIGEST_LENGTH];
+    digest.copy_from_slice(&Sha256::digest(payload)[..DIGEST_LENGTH]);
+    digest
+}
+
+/// Splits `payload` into frames.
+#[inline]
+pub fn encode_frames(payload: &[u8]) -> Vec<String> {
+    let digest = HEXUPPER.encode(&digest(payload));
+    let chunks = if payload.is_empty() {
+        vec![payload]
+    } else {
+        payload.chunks(MAX_FRAME_DATA).collect()
+    };
+    let total = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            format!(
+                "{}/{}/{}/{}/{}",
+                FRAME_PREFIX,
+                index + 1,
+                total,
+                digest,
+                HEXUPPER.encode(chunk)
+            )
+        })
+        .collect()
+}
+
+/// Frame Assembler
+#[derive(Clone, Debug, Default)]
+pub struct FrameAssembler {
+    /// Payload Digest
+    digest: Option<[u8; DIGEST_LENGTH]>,
+
+    /// Payload Chunks
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl FrameAssembler {
+    /// Adds the scanned `frame` to the assembler. Frames which were already scanned are ignored.
+    #[inline]
+    pub fn push(&mut self, frame: &str) -> Result<(), Error> {
+        let mut parts = frame.trim().splitn(5, '/');
+        if parts.next() != Some(FRAME_PREFIX) {
+            return Err(Error::InvalidFrame);
+        }
+        let mut number = || {
+            parts
+                .next()
+                .and_then(|part| part.parse::<usize>().ok())
+                .ok_or(Error::InvalidFrame)
+        };
+        let (index, total) = (number()?, number()?);
+        if index == 0 || index > total || total > MAX_FRAMES {
+            return Err(Error::InvalidFrame);
+        }
+        let mut digest = [0; DIGEST_LENGTH];
+        match parts.next().map(|part| HEXUPPER.decode(part.as_bytes())) {
+            Some(Ok(bytes)) if bytes.len() == DIGEST_LENGTH => digest.copy_from_slice(&bytes),
+            _ => return Err(Error::InvalidFrame),
+        }
+        let data = parts
+            .next()
+            .and_then(|part| HEXUPPER.decode(part.as_bytes()).ok())
+            .ok_or(Error::InvalidFrame)?;
+        match self.digest {
+            Some(expected) if expected != digest || self.chunks.len() != total => {
+                return Err(Error::MismatchedFrame)
+            }
+            Some(_) => {}
+            None => {
+                self.digest = Some(digest);
+                self.chunks = vec![None; total];
+            }
+        }
+        self.chunks[index - 1].get_or_insert(data);
+        Ok(())
+    }
+
+    /// Returns the number of scanned frames and the total number of frames.
+    #[inline]
+    pub fn progress(&self) -> (usize, usize) {
+        (
+            self.chunks.iter().filter(|chunk| chunk.is_some()).count(),
+            self.chunks.len(),
+        )
+    }
+
+    /// Assembles the payload once every frame was scanned.
+    #[inline]
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        let expected = self.digest.ok_or(Error::MissingFrames)?;
+        let payload = self
+            .chunks
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::MissingFrames)?
+            .concat();
+        if digest(&payload) != expected {
+            return Err(Error::DigestMismatch);
+        }
+        Ok(payload)
+    }
+}
+
+/// Assembles the payload from the scanned `frames`.
+#[inline]
+pub fn decode_frames<I, S>(frames: I) -> Result<Vec<u8>, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut assembler = FrameAssembler::default();
+    for frame in frames {
+        assembler.push(frame.as_ref())?;
+    }
+    assembler.finish()
+}
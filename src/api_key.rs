@@ -0,0 +1,309 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Account-Scoped API Keys
+//!
+//! API keys let bots and scripts use the service without the wallet password. Each key is named,
+//! carries a list of [`Scope`]s restricting what it can do, and can be revoked individually. Only
+//! the SHA-256 hash of every key is stored on disk, and the store is encrypted under the storage
+//! key so that the scopes and limits of the keys cannot be changed while the signer is locked.
+
+use crate::{
+    secret::ConstantTimeEq,
+    storage::cipher::{self, FileKey},
+};
+use data_encoding::HEXLOWER;
+use manta_crypto::rand::{CryptoRng, OsRng, RngCore};
+use manta_util::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{io, path::Path};
+
+/// API Key Prefix
+pub const KEY_PREFIX: &str = "msk_";
+
+/// API Key Entropy in Bytes
+pub const KEY_LENGTH: usize = 32;
+
+/// API Key Scope
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    content = "content",
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum Scope {
+    /// View-Only Access
+    ///
+    /// Allows synchronizing the wallet.
+    ViewOnly,
+
+    /// Invoice Creation
+    ///
+    /// Allows requesting receiving keys.
+    InvoiceCreate,
+
+    /// Low-Value Send
+    ///
+    /// Allows signing transactions moving at most the given value without prompting for the
+    /// wallet password.
+    LowValueSend(u128),
+}
+
+/// Service Permission
+///
+/// Every service endpoint requires one of these permissions from API key callers.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Permission {
+    /// View Permission
+    View,
+
+    /// Invoice Permission
+    Invoice,
+
+    /// Send Permission
+    Send,
+
+    /// Owner Permission
+    ///
+    /// This permission is never granted to API keys.
+    Owner,
+}
+
+/// API Key Record
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ApiKey {
+    /// Key Name
+    pub name: String,
+
+    /// Hex-Encoded SHA-256 Hash of the Key
+    pub hash: String,
+
+    /// Key Scopes
+    pub scopes: Vec<Scope>,
+
+    /// Creation Time as a UNIX Timestamp
+    pub created_at: i64,
+
+    /// Revocation Flag
+    pub revoked: bool,
+}
+
+impl ApiKey {
+    /// Returns `true` if `self` grants `permission`.
+    #[inline]
+    pub fn allows(&self, permission: Permission) -> bool {
+        !self.revoked
+            && self.scopes.iter().any(|scope| {
+                matches!(
+                    (scope, permission),
+                    (Scope::ViewOnly, Permission::View)
+                        | (Scope::InvoiceCreate, Permission::Invoice)
+                        | (Scope::LowValueSend(_), Permission::Send)
+                )
+            })
+    }
+
+    /// Returns the largest value `self` is allowed to send without prompting for the password.
+    #[inline]
+    pub fn send_limit(&self) -> Option<u128> {
+        if self.revoked {
+            return None;
+        }
+        self.scopes
+            .iter()
+            .filter_map(|scope| match scope {
+                Scope::LowValueSend(limit) => Some(*limit),
+                _ => None,
+            })
+            .max()
+    }
+
+    /// Returns the public information of `self`.
+    #[inline]
+    pub fn info(&self) -> ApiKeyInfo {
+        ApiKeyInfo {
+            name: self.name.clone(),
+            scopes: self.scopes.clone(),
+            created_at: self.created_at,
+            revoked: self.revoked,
+        }
+    }
+}
+
+/// API Key Information
+///
+/// This is the public view of an [`ApiKey`] returned when listing keys.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ApiKeyInfo {
+    /// Key Name
+    pub name: String,
+
+    /// Key Scopes
+    pub scopes: Vec<Scope>,
+
+    /// Creation Time as a UNIX Timestamp
+    pub created_at: i64,
+
+    /// Revocation Flag
+    pub revoked: bool,
+}
+
+/// API Key Creation Request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct CreateRequest {
+    /// Key Name
+    pub name: String,
+
+    /// Key Scopes
+    pub scopes: Vec<Scope>,
+}
+
+/// API Key Creation Response
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct CreateResponse {
+    /// Key Name
+    pub name: String,
+
+    /// API Key
+    ///
+    /// This is the only time the key is revealed.
+    pub key: String,
+}
+
+/// API Key Revocation Request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct RevokeRequest {
+    /// Key Name
+    pub name: String,
+}
+
+/// Returns the hex-encoded SHA-256 hash of `key`.
+#[inline]
+fn hash(key: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(key.as_bytes()))
+}
+
+/// API Key Store
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ApiKeyStore {
+    /// API Keys
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeyStore {
+    /// Loads the store encrypted under `key` at `path`, returning an empty store if it does not
+    /// exist.
+    #[inline]
+    pub fn load<P>(path: P, key: &FileKey) -> Result<Self, cipher::Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(cipher::load_or_quarantine(path, key)?.unwrap_or_default())
+    }
+
+    /// Loads the plaintext store written by earlier versions at `path`, returning `None` if it
+    /// does not exist.
+    #[inline]
+    pub fn load_legacy<P>(path: P) -> io::Result<Option<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the store encrypted under `key` to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P, key: &FileKey) -> Result<(), cipher::Error>
+    where
+        P: AsRef<Path>,
+    {
+        cipher::save(path, key, self, 0, &mut OsRng)
+    }
+
+    /// Creates a new API key with `name` and `scopes` using `rng`, returning the key or `None` if
+    /// a key named `name` already exists.
+    #[inline]
+    pub fn create<R>(&mut self, name: String, scopes: Vec<Scope>, rng: &mut R) -> Option<String>
+    where
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        if name.is_empty() || self.keys.iter().any(|key| key.name == name) {
+            return None;
+        }
+        let mut bytes = [0; KEY_LENGTH];
+        rng.fill_bytes(&mut bytes);
+        let key = format!("{}{}", KEY_PREFIX, HEXLOWER.encode(&bytes));
+        self.keys.push(ApiKey {
+            name,
+            hash: hash(&key),
+            scopes,
+            created_at: chrono::offset::Utc::now().timestamp(),
+            revoked: false,
+        });
+        Some(key)
+    }
+
+    /// Revokes the key named `name`, returning `true` if it was found and not already revoked.
+    #[inline]
+    pub fn revoke(&mut self, name: &str) -> bool {
+        match self
+            .keys
+            .iter_mut()
+            .find(|key| key.name == name && !key.revoked)
+        {
+            Some(key) => {
+                key.revoked = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Finds the unrevoked API key matching `key`.
+    #[inline]
+    pub fn authenticate(&self, key: &str) -> Option<&ApiKey> {
+        let hash = hash(key);
+        self.keys
+            .iter()
+            .find(|entry| bool::from(entry.hash.as_bytes().ct_eq(hash.as_bytes())))
+            .filter(|entry| !entry.revoked)
+    }
+
+    /// Returns `true` if the store holds any unrevoked key.
+    #[inline]
+    pub fn has_active_keys(&self) -> bool {
+        self.keys.iter().any(|key| !key.revoked)
+    }
+
+    /// Lists the public information of every key in the store.
+    #[inline]
+    pub fn list(&self) -> Vec<ApiKeyInfo> {
+        self.keys.iter().map(ApiKey::info).collect()
+    }
+}
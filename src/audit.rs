@@ -0,0 +1,528 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Audit Log
+//!
+//! Every authorization decision of the signer is appended to the audit log as one JSON record
+//! per line. Each record holds the hash of the record before it, so editing, removing or
+//! reordering records breaks the chain, which [`verify`] detects. Records which are cut from the
+//! end of the log leave a valid chain behind, so users who want to detect truncation should keep
+//! the [`head`](Verification::head) hash reported by [`verify`] somewhere else.
+//!
+//! The log never holds the prompts themselves, only their SHA-256 digests and the amounts of
+//! transactions. The records can be exported as CSV or JSON with [`export`].
+
+use crate::label::Labels;
+use core::fmt;
+use data_encoding::HEXLOWER;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// Hash of the Record before the First Record
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Authorization Outcome
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum Outcome {
+    /// The request was authorized.
+    Approved,
+
+    /// The user rejected the request.
+    Rejected,
+
+    /// The authorization prompt expired.
+    Expired,
+
+    /// The request could not be authorized.
+    Failed,
+}
+
+impl fmt::Display for Outcome {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Transaction Amount
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Amount {
+    /// Asset Identifier
+    pub asset_id: u32,
+
+    /// Asset Value in Base Units
+    pub value: u128,
+
+    /// Asset Decimals
+    ///
+    /// This is only known if the request carried asset metadata.
+    pub decimals: Option<u32>,
+
+    /// Asset Symbol
+    ///
+    /// This is only known if the request carried asset metadata.
+    pub symbol: Option<String>,
+}
+
+impl Amount {
+    /// Returns the value of `self` as a decimal number if the asset decimals are known.
+    #[inline]
+    pub fn decoded(&self) -> Option<String> {
+        Some(decode_value(self.value, self.decimals?))
+    }
+}
+
+/// Returns `value` in base units as a decimal number with the given number of `decimals`.
+#[inline]
+pub fn decode_value(value: u128, decimals: u32) -> String {
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return value.to_string();
+    }
+    let digits = format!("{:0>width$}", value, width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        integer.into()
+    } else {
+        format!("{}.{}", integer, fraction)
+    }
+}
+
+/// Audit Record
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct AuditRecord {
+    /// Record Index
+    pub index: u64,
+
+    /// Decision Time as a UNIX Timestamp
+    pub timestamp: i64,
+
+    /// Request Kind
+    ///
+    /// This is the name of the service endpoint which received the request.
+    pub kind: String,
+
+    /// Request Origin
+    ///
+    /// This is the `Origin` header of the request or the name of the API key which authenticated
+    /// it, if any.
+    pub origin: Option<String>,
+
+    /// Hex-Encoded SHA-256 Digest of the Authorization Prompt
+    pub request_digest: String,
+
+    /// Transaction Amount
+    ///
+    /// This is only recorded for transaction signing requests.
+    #[serde(default)]
+    pub amount: Option<Amount>,
+
+    /// Authorization Outcome
+    pub outcome: Outcome,
+
+    /// Hash of the Previous Record
+    pub previous: String,
+
+    /// Hash of this Record
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Computes the hash of `self` over every field but [`hash`](Self::hash).
+    #[inline]
+    pub fn compute_hash(&self) -> String {
+        let fields = serde_json::to_vec(&(
+            self.index,
+            self.timestamp,
+            &self.kind,
+            &self.origin,
+            &self.request_digest,
+            &self.amount,
+            self.outcome,
+            &self.previous,
+        ))
+        .expect("Serializing the record fields is not allowed to fail.");
+        HEXLOWER.encode(&Sha256::digest(fields))
+    }
+}
+
+/// Chain Head
+#[derive(Clone, Debug)]
+struct Head {
+    /// Next Record Index
+    index: u64,
+
+    /// Hash of the Last Record
+    hash: String,
+}
+
+impl Default for Head {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            index: 0,
+            hash: GENESIS_HASH.into(),
+        }
+    }
+}
+
+/// Audit Log
+#[derive(Debug)]
+pub struct AuditLog {
+    /// Log Path
+    path: PathBuf,
+
+    /// Chain Head
+    head: Mutex<Head>,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, continuing the chain after its last readable record. The
+    /// file is created by the first call to [`append`](Self::append) if it does not exist.
+    #[inline]
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_owned();
+        let head = match File::open(&path) {
+            Ok(file) => match read_records(file).into_iter().flatten().last() {
+                Some(record) => Head {
+                    index: record.index + 1,
+                    hash: record.hash,
+                },
+                _ => Head::default(),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Head::default(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            path,
+            head: Mutex::new(head),
+        })
+    }
+
+    /// Appends the `outcome` of the request of `kind` from `origin` which was authorized with
+    /// `prompt` to the log, along with the `amount` of the transaction it signs, if any.
+    #[inline]
+    pub fn append(
+        &self,
+        kind: &str,
+        origin: Option<String>,
+        prompt: &str,
+        amount: Option<Amount>,
+        outcome: Outcome,
+    ) -> io::Result<AuditRecord> {
+        let mut head = self.head.lock();
+        let mut record = AuditRecord {
+            index: head.index,
+            timestamp: chrono::offset::Utc::now().timestamp(),
+            kind: kind.into(),
+            origin,
+            request_digest: HEXLOWER.encode(&Sha256::digest(prompt.as_bytes())),
+            amount,
+            outcome,
+            previous: head.hash.clone(),
+            hash: String::new(),
+        };
+        record.hash = record.compute_hash();
+        let mut line =
+            serde_json::to_vec(&record).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+        head.index += 1;
+        head.hash = record.hash.clone();
+        Ok(record)
+    }
+}
+
+/// Reads the records of the audit log in `file`, in order.
+#[inline]
+fn read_records(file: File) -> Vec<Result<AuditRecord, serde_json::Error>> {
+    BufReader::new(file)
+        .lines()
+        .filter_map(|line| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(serde_json::from_str(&line)),
+            Err(err) => Some(Err(serde_json::Error::io(err))),
+        })
+        .collect()
+}
+
+/// Reads every record of the audit log at `path`, in order, returning no records if it does not
+/// exist.
+#[inline]
+pub fn read<P>(path: P) -> io::Result<Vec<AuditRecord>>
+where
+    P: AsRef<Path>,
+{
+    match File::open(path) {
+        Ok(file) => read_records(file)
+            .into_iter()
+            .map(|record| record.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)))
+            .collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Audit Log Verification
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Verification {
+    /// Number of Records before the Chain Breaks
+    pub records: u64,
+
+    /// Hash of the Last Record before the Chain Breaks
+    pub head: String,
+
+    /// Line Number of the First Record which breaks the Chain, not counting Empty Lines
+    ///
+    /// This is `None` if the chain is intact.
+    pub broken_at: Option<u64>,
+}
+
+impl Verification {
+    /// Returns `true` if the chain is intact.
+    #[inline]
+    pub fn is_intact(&self) -> bool {
+        self.broken_at.is_none()
+    }
+}
+
+/// Verifies the hash chain of the audit log at `path`.
+#[inline]
+pub fn verify<P>(path: P) -> io::Result<Verification>
+where
+    P: AsRef<Path>,
+{
+    let records = match File::open(path) {
+        Ok(file) => read_records(file),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err),
+    };
+    let mut head = Head::default();
+    for (line, record) in records.into_iter().enumerate() {
+        match record {
+            Ok(record)
+                if record.index == head.index
+                    && record.previous == head.hash
+                    && record.hash == record.compute_hash() =>
+            {
+                head.index += 1;
+                head.hash = record.hash;
+            }
+            _ => {
+                return Ok(Verification {
+                    records: head.index,
+                    head: head.hash,
+                    broken_at: Some(line as u64 + 1),
+                })
+            }
+        }
+    }
+    Ok(Verification {
+        records: head.index,
+        head: head.hash,
+        broken_at: None,
+    })
+}
+
+/// Export Format
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum ExportFormat {
+    /// Comma-Separated Values
+    Csv,
+
+    /// JSON Array of Records
+    Json,
+}
+
+impl ExportFormat {
+    /// Returns the file extension of `self`.
+    #[inline]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Export Range
+///
+/// Both bounds are inclusive UNIX timestamps, and a missing bound leaves its side of the range
+/// open.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ExportRange {
+    /// Earliest Decision Time
+    pub from: Option<i64>,
+
+    /// Latest Decision Time
+    pub to: Option<i64>,
+}
+
+impl ExportRange {
+    /// Returns `true` if `timestamp` lies in the range.
+    #[inline]
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.from.map_or(true, |from| from <= timestamp)
+            && self.to.map_or(true, |to| timestamp <= to)
+    }
+}
+
+/// Exported Record
+///
+/// This is an [`AuditRecord`] with its decoded amount and asset label, as written by [`export`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde")]
+pub struct ExportedRecord {
+    /// Audit Record
+    #[serde(flatten)]
+    pub record: AuditRecord,
+
+    /// Decision Time in RFC 3339 Format
+    pub time: String,
+
+    /// Decoded Amount
+    ///
+    /// This is only known if the asset decimals were recorded with the amount.
+    pub decoded_amount: Option<String>,
+
+    /// Asset Label
+    ///
+    /// This is only known if the user labeled the asset of the amount.
+    pub asset_label: Option<String>,
+}
+
+impl ExportedRecord {
+    /// Builds a new [`ExportedRecord`] from `record`, looking up its asset label in `labels`.
+    #[inline]
+    pub fn new(record: AuditRecord, labels: &Labels) -> Self {
+        Self {
+            time: format_time(record.timestamp),
+            decoded_amount: record.amount.as_ref().and_then(Amount::decoded),
+            asset_label: record
+                .amount
+                .as_ref()
+                .and_then(|amount| labels.asset(amount.asset_id))
+                .map(Into::into),
+            record,
+        }
+    }
+}
+
+/// CSV Header of [`export`]
+pub const CSV_HEADER: &str = "index,time,kind,origin,outcome,asset_id,symbol,asset_label,value,\
+                              amount,request_digest,hash";
+
+/// Formats the UNIX `timestamp` in RFC 3339 format.
+#[inline]
+fn format_time(timestamp: i64) -> String {
+    match chrono::NaiveDateTime::from_timestamp_opt(timestamp, 0) {
+        Some(time) => time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        _ => timestamp.to_string(),
+    }
+}
+
+/// Quotes `field` for CSV if it holds a separator, a quote or a line break.
+#[inline]
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+/// Writes `record` as a CSV line.
+#[inline]
+fn csv_line(record: &ExportedRecord) -> String {
+    let amount = record.record.amount.as_ref();
+    [
+        record.record.index.to_string(),
+        record.time.clone(),
+        record.record.kind.clone(),
+        record.record.origin.clone().unwrap_or_default(),
+        record.record.outcome.to_string(),
+        amount
+            .map(|amount| amount.asset_id.to_string())
+            .unwrap_or_default(),
+        amount
+            .and_then(|amount| amount.symbol.clone())
+            .unwrap_or_default(),
+        record.asset_label.clone().unwrap_or_default(),
+        amount
+            .map(|amount| amount.value.to_string())
+            .unwrap_or_default(),
+        record.decoded_amount.clone().unwrap_or_default(),
+        record.record.request_digest.clone(),
+        record.record.hash.clone(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Exports the records of the audit log at `path` which lie in `range` in the given `format`,
+/// naming their assets with `labels`.
+#[inline]
+pub fn export<P>(
+    path: P,
+    format: ExportFormat,
+    range: ExportRange,
+    labels: &Labels,
+) -> io::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let records = read(path)?
+        .into_iter()
+        .filter(|record| range.contains(record.timestamp))
+        .map(|record| ExportedRecord::new(record, labels))
+        .collect::<Vec<_>>();
+    match format {
+        ExportFormat::Csv => {
+            let mut output = String::from(CSV_HEADER);
+            output.push('\n');
+            for record in &records {
+                output.push_str(&csv_line(record));
+                output.push('\n');
+            }
+            Ok(output.into_bytes())
+        }
+        ExportFormat::Json => serde_json::to_vec_pretty(&records)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err)),
+    }
+}
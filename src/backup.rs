@@ -0,0 +1,159 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Recovery Phrase Backup Verification
+//!
+//! After a new account is created, the user proves that they wrote down the recovery phrase by
+//! entering some of its words. The signer picks the positions of the words to ask for and checks
+//! the entered words against the mnemonic which it generated, so that frontends cannot skip the
+//! check, and the account is only marked as backed up in the [`BackupStatus`] once they match.
+//! Frontends only ever receive the positions.
+
+use crate::secret::{Choice, ConstantTimeEq, ExposeSecret, Secret, SecretVec};
+use manta_crypto::rand::{CryptoRng, RngCore};
+use manta_pay::key::Mnemonic;
+use manta_util::serde::{Deserialize, Serialize};
+use std::{io, path::Path};
+
+/// Number of Words the User has to Enter
+pub const QUIZ_WORDS: usize = 3;
+
+/// Recovery Phrase Quiz
+///
+/// Holds the words of a newly generated recovery phrase and the positions the user is asked for
+/// until the user has entered the words at those positions. The quiz cannot be serialized, only
+/// its [`indices`](Self::indices) are handed to frontends.
+#[derive(Debug)]
+pub struct BackupQuiz {
+    /// Recovery Phrase Words
+    words: SecretVec<String>,
+
+    /// Positions of the Asked Words, Starting at Zero
+    indices: Vec<usize>,
+}
+
+impl BackupQuiz {
+    /// Builds a new quiz over the recovery phrase of `mnemonic`, asking for [`QUIZ_WORDS`]
+    /// distinct words sampled with `rng`.
+    #[inline]
+    pub fn new<R>(mnemonic: &Mnemonic, rng: &mut R) -> Self
+    where
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        let words: Vec<String> = serde_json::to_value(mnemonic)
+            .ok()
+            .and_then(|phrase| {
+                phrase
+                    .as_str()
+                    .map(|phrase| phrase.split_whitespace().map(Into::into).collect())
+            })
+            .unwrap_or_default();
+        let mut indices = Vec::with_capacity(QUIZ_WORDS);
+        while indices.len() < QUIZ_WORDS.min(words.len()) {
+            let index = (rng.next_u64() % words.len() as u64) as usize;
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+        indices.sort_unstable();
+        Self {
+            words: Secret::new(words),
+            indices,
+        }
+    }
+
+    /// Returns the positions of the words the user is asked for, starting at zero.
+    #[inline]
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Checks that the `words` entered by the user are the words of the recovery phrase at the
+    /// asked [`indices`](Self::indices), in that order.
+    ///
+    /// The words are compared ignoring case and surrounding whitespace.
+    #[inline]
+    pub fn verify(&self, words: &[String]) -> bool {
+        let phrase = self.words.expose_secret();
+        if self.indices.len() != QUIZ_WORDS || words.len() != self.indices.len() {
+            return false;
+        }
+        self.indices
+            .iter()
+            .zip(words)
+            .fold(Choice::from(1), |matches, (index, word)| {
+                matches
+                    & phrase[*index]
+                        .as_bytes()
+                        .ct_eq(word.trim().to_lowercase().as_bytes())
+            })
+            .into()
+    }
+}
+
+/// Backup Status
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct BackupStatus {
+    /// Time of the Successful Verification as a UNIX Timestamp
+    ///
+    /// This is `None` if the recovery phrase was never verified.
+    #[serde(default)]
+    pub backed_up_at: Option<i64>,
+}
+
+impl BackupStatus {
+    /// Returns the status of an account whose recovery phrase was verified just now.
+    #[inline]
+    pub fn now() -> Self {
+        Self {
+            backed_up_at: Some(chrono::offset::Utc::now().timestamp()),
+        }
+    }
+
+    /// Returns `true` if the recovery phrase was verified.
+    #[inline]
+    pub fn is_backed_up(&self) -> bool {
+        self.backed_up_at.is_some()
+    }
+
+    /// Loads the backup status from `path`, returning the status of an account which is not
+    /// backed up if it does not exist or is malformed.
+    #[inline]
+    pub fn load<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the backup status to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        crate::storage::write_atomic(
+            path.as_ref(),
+            &serde_json::to_vec_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )
+    }
+}
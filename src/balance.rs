@@ -0,0 +1,296 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Balances
+//!
+//! The signer keeps the shielded balance of every asset from the balance updates of its own
+//! synchronization responses, so that the UI and trusted dapps do not have to trust an external
+//! indexer. The shielded balances are stored encrypted next to the wallet file. Since they are
+//! only a cache of the balance updates, a lost cache is marked as stale and rebuilt by
+//! resynchronizing the wallet from the ledger. The public
+//! balance of the native asset held by the polkadot.js compatible account is read from the
+//! configured node.
+
+use crate::{
+    storage::cipher::{self, FileKey},
+    sync::NodeClient,
+};
+use blake2::{digest::consts::U16, Blake2b, Digest};
+use core::sync::atomic::{AtomicBool, Ordering};
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use manta_accounting::wallet::signer::BalanceUpdate;
+use manta_crypto::rand::OsRng;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use serde_json::json;
+use std::{collections::BTreeMap, io, path::PathBuf, sync::Arc};
+
+/// Storage Key Prefix of the `System::Account` Map
+///
+/// This is the concatenation of the `twox128` hashes of `System` and `Account`.
+const SYSTEM_ACCOUNT_PREFIX: &str =
+    "26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9";
+
+/// Offset of the Free Balance in the SCALE-Encoded `AccountInfo`
+///
+/// The balance data follows the nonce and the three reference counters.
+const ACCOUNT_DATA_OFFSET: usize = 16;
+
+/// Asset Balance
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct AssetBalance {
+    /// Asset Identifier
+    pub asset_id: u32,
+
+    /// Shielded Value in Base Units
+    pub value: u128,
+}
+
+/// Public Balance
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PublicBalance {
+    /// SS58-Encoded Account Address
+    pub address: String,
+
+    /// Free Native Balance in Base Units
+    pub free: u128,
+
+    /// Reserved Native Balance in Base Units
+    pub reserved: u128,
+}
+
+/// Balance Report
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct BalanceReport {
+    /// Shielded Balances by Asset
+    pub shielded: Vec<AssetBalance>,
+
+    /// Stale Shielded Balances Flag
+    ///
+    /// This flag is set when the balance cache was lost after the wallet was synchronized, in
+    /// which case the shielded balances are incomplete until the wallet is resynchronized.
+    pub stale: bool,
+
+    /// Public Balance of the Native Asset
+    ///
+    /// This is `None` if there is no node configured or the node could not be reached.
+    pub public: Option<PublicBalance>,
+}
+
+/// Shielded Balances
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Balances {
+    /// Shielded Values by Asset Identifier
+    assets: BTreeMap<u32, u128>,
+}
+
+impl Balances {
+    /// Applies the balance `update` of a synchronization response.
+    #[inline]
+    pub fn apply(&mut self, update: &BalanceUpdate) {
+        match update {
+            BalanceUpdate::Partial { deposit, withdraw } => {
+                for asset in deposit {
+                    let value = self.assets.entry(asset.id.0).or_default();
+                    *value = value.saturating_add(asset.value.0);
+                }
+                for asset in withdraw {
+                    let value = self.assets.entry(asset.id.0).or_default();
+                    *value = value.saturating_sub(asset.value.0);
+                }
+            }
+            BalanceUpdate::Full { assets } => {
+                self.assets.clear();
+                for asset in assets {
+                    let value = self.assets.entry(asset.id.0).or_default();
+                    *value = value.saturating_add(asset.value.0);
+                }
+            }
+        }
+        self.assets.retain(|_, value| *value != 0);
+    }
+
+    /// Lists the assets with a nonzero shielded balance.
+    #[inline]
+    pub fn list(&self) -> Vec<AssetBalance> {
+        self.assets
+            .iter()
+            .map(|(asset_id, value)| AssetBalance {
+                asset_id: *asset_id,
+                value: *value,
+            })
+            .collect()
+    }
+}
+
+/// Returns the hex-encoded storage key of the `System::Account` entry of `public_key`.
+#[inline]
+pub fn account_storage_key(public_key: &[u8]) -> String {
+    let mut key = String::from("0x");
+    key.push_str(SYSTEM_ACCOUNT_PREFIX);
+    key.push_str(&HEXLOWER.encode(&Blake2b::<U16>::digest(public_key)));
+    key.push_str(&HEXLOWER.encode(public_key));
+    key
+}
+
+/// Reads the public balance of the account with `public_key` and `address` from the node behind
+/// `client`.
+///
+/// This function blocks on the network and should be run on a blocking task.
+#[inline]
+pub fn query_public(
+    client: &NodeClient,
+    address: String,
+    public_key: &[u8],
+) -> io::Result<PublicBalance> {
+    let response = serde_json::from_str::<serde_json::Value>(
+        &client.send("state_getStorage", json!([account_storage_key(public_key)]))?,
+    )
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let (free, reserved) = match response.get("result") {
+        Some(serde_json::Value::String(info)) => {
+            let info = HEXLOWER_PERMISSIVE
+                .decode(info.trim_start_matches("0x").as_bytes())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let read = |offset: usize| {
+                info.get(offset..offset + 16)
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .map(u128::from_le_bytes)
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "truncated account information")
+                    })
+            };
+            (read(ACCOUNT_DATA_OFFSET)?, read(ACCOUNT_DATA_OFFSET + 16)?)
+        }
+        Some(serde_json::Value::Null) => (0, 0),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                response
+                    .get("error")
+                    .map(ToString::to_string)
+                    .unwrap_or_else(|| "missing result".into()),
+            ))
+        }
+    };
+    Ok(PublicBalance {
+        address,
+        free,
+        reserved,
+    })
+}
+
+/// Balance Store
+///
+/// The store is shared with the frontend through the service [`Handle`](crate::service::Handle)
+/// and is opened by the service for the active wallet once the signer is unlocked.
+#[derive(Debug, Default)]
+pub struct BalanceStore {
+    /// Shielded Balances
+    balances: Mutex<Balances>,
+
+    /// Stale Shielded Balances Flag
+    stale: AtomicBool,
+
+    /// Encrypted Balance File Path and Storage Key
+    storage: Mutex<Option<(PathBuf, Arc<FileKey>)>>,
+
+    /// Public Account Address and Public Key
+    account: Mutex<Option<(String, Vec<u8>)>>,
+}
+
+impl BalanceStore {
+    /// Loads the shielded balances stored encrypted under `key` at `path`, starting without
+    /// balances if it does not exist, and saves later updates there. Returns `false` if the
+    /// balances do not exist although the wallet was already `synced`, in which case they are
+    /// marked as stale until they are [`reset`](Self::reset).
+    #[inline]
+    pub fn open(
+        &self,
+        path: PathBuf,
+        key: Arc<FileKey>,
+        synced: bool,
+    ) -> Result<bool, cipher::Error> {
+        let balances = cipher::load_or_quarantine(&path, &key)?;
+        let stale = balances.is_none() && synced;
+        *self.balances.lock() = balances.unwrap_or_default();
+        self.stale.store(stale, Ordering::Relaxed);
+        *self.storage.lock() = Some((path, key));
+        Ok(!stale)
+    }
+
+    /// Sets the address and public key of the public account whose balance is read from the
+    /// node, or clears it if the active wallet has none.
+    #[inline]
+    pub fn set_account(&self, account: Option<(String, Vec<u8>)>) {
+        *self.account.lock() = account;
+    }
+
+    /// Applies the balance `update` of a synchronization response and saves the balances.
+    #[inline]
+    pub fn apply(&self, update: &BalanceUpdate) -> Result<(), cipher::Error> {
+        let storage = self.storage.lock();
+        let mut balances = self.balances.lock();
+        balances.apply(update);
+        match storage.as_ref() {
+            Some((path, key)) => cipher::save(path, key, &*balances, 0, &mut OsRng),
+            _ => Ok(()),
+        }
+    }
+
+    /// Clears the shielded balances and saves them, so that they are rebuilt from the balance
+    /// updates of the next synchronizations.
+    #[inline]
+    pub fn reset(&self) -> Result<(), cipher::Error> {
+        let storage = self.storage.lock();
+        let mut balances = self.balances.lock();
+        *balances = Balances::default();
+        self.stale.store(false, Ordering::Relaxed);
+        match storage.as_ref() {
+            Some((path, key)) => cipher::save(path, key, &*balances, 0, &mut OsRng),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lists the assets with a nonzero shielded balance.
+    #[inline]
+    pub fn shielded(&self) -> Vec<AssetBalance> {
+        self.balances.lock().list()
+    }
+
+    /// Builds the balance report, reading the public balance from the node at `node_url` if
+    /// there is one.
+    ///
+    /// This method blocks on the network and should be run on a blocking task.
+    #[inline]
+    pub fn report(&self, node_url: Option<String>) -> BalanceReport {
+        let account = self.account.lock().clone();
+        BalanceReport {
+            shielded: self.shielded(),
+            stale: self.stale.load(Ordering::Relaxed),
+            public: match (node_url, account) {
+                (Some(url), Some((address, public_key))) => {
+                    query_public(&NodeClient::new(url), address, &public_key).ok()
+                }
+                _ => None,
+            },
+        }
+    }
+}
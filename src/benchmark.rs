@@ -0,0 +1,239 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Proving Benchmark
+//!
+//! The benchmark proves sample transfers of every circuit with the installed proving keys and
+//! reports the timings and the peak memory of the process, so that users can evaluate their
+//! machine and support can tell slow hardware apart from bugs.
+
+use core::{fmt, time::Duration};
+use manta_crypto::rand::OsRng;
+use manta_pay::{
+    config::{Mint, PrivateTransfer, Reclaim},
+    signer::base::UtxoAccumulator,
+};
+use manta_util::serde::{Deserialize, Serialize};
+use std::{path::Path, time::Instant};
+
+/// Default Number of Proofs per Circuit
+pub const DEFAULT_ROUNDS: usize = 3;
+
+/// Maximum Number of Proofs per Circuit
+pub const MAX_ROUNDS: usize = 20;
+
+/// Benchmark Error
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Error {
+    /// Parameter Loading Error
+    ParameterLoadingError,
+
+    /// Proving Error
+    ///
+    /// The sample transfer of the circuit could not be proven.
+    ProvingError(&'static str),
+}
+
+impl fmt::Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ParameterLoadingError => write!(f, "unable to load the signer parameters"),
+            Self::ProvingError(circuit) => write!(f, "unable to prove a sample {}", circuit),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Circuit Timing
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct CircuitTiming {
+    /// Circuit Name
+    pub circuit: String,
+
+    /// Number of Proofs
+    pub proofs: usize,
+
+    /// Mean Proving Time in Milliseconds
+    pub mean_ms: u64,
+
+    /// Minimum Proving Time in Milliseconds
+    pub min_ms: u64,
+
+    /// Maximum Proving Time in Milliseconds
+    pub max_ms: u64,
+}
+
+impl CircuitTiming {
+    /// Builds the timing of `circuit` from the `durations` of its proofs.
+    #[inline]
+    fn new(circuit: &str, durations: &[Duration]) -> Self {
+        let millis = durations
+            .iter()
+            .map(|duration| duration.as_millis() as u64)
+            .collect::<Vec<_>>();
+        Self {
+            circuit: circuit.into(),
+            proofs: millis.len(),
+            mean_ms: millis.iter().sum::<u64>() / (millis.len().max(1) as u64),
+            min_ms: millis.iter().copied().min().unwrap_or_default(),
+            max_ms: millis.iter().copied().max().unwrap_or_default(),
+        }
+    }
+}
+
+/// Benchmark Report
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct BenchmarkReport {
+    /// Signer Version
+    pub version: String,
+
+    /// Number of Available CPUs
+    pub cpus: Option<usize>,
+
+    /// Parameter Loading Time in Milliseconds
+    pub parameter_loading_ms: u64,
+
+    /// Proving Timings of every Circuit
+    pub circuits: Vec<CircuitTiming>,
+
+    /// Peak Resident Memory of the Process in Bytes
+    ///
+    /// This is the high-water mark of the whole process, not only of the benchmark, and is only
+    /// available on Linux and Windows.
+    pub peak_memory_bytes: Option<u64>,
+}
+
+/// Runs `rounds` proofs of `prove`, returning their durations.
+#[inline]
+fn time<F>(circuit: &'static str, rounds: usize, mut prove: F) -> Result<Vec<Duration>, Error>
+where
+    F: FnMut() -> bool,
+{
+    (0..rounds)
+        .map(|_| {
+            let start = Instant::now();
+            if prove() {
+                Ok(start.elapsed())
+            } else {
+                Err(Error::ProvingError(circuit))
+            }
+        })
+        .collect()
+}
+
+/// Proves `rounds` sample transfers of every circuit with the parameters in `directory`,
+/// capped at [`MAX_ROUNDS`].
+///
+/// This is CPU-bound and blocks for a long time, so it should run on a blocking thread.
+#[inline]
+pub fn run<P>(directory: P, rounds: usize) -> Result<BenchmarkReport, Error>
+where
+    P: AsRef<Path>,
+{
+    let rounds = rounds.clamp(1, MAX_ROUNDS);
+    let start = Instant::now();
+    let signer_parameters =
+        crate::parameters::load(directory).ok_or(Error::ParameterLoadingError)?;
+    let utxo_accumulator_model =
+        crate::parameters::load_utxo_accumulator_model().ok_or(Error::ParameterLoadingError)?;
+    let parameter_loading_ms = start.elapsed().as_millis() as u64;
+    let proving_context = &signer_parameters.proving_context;
+    let parameters = &signer_parameters.parameters;
+    let mut utxo_accumulator = UtxoAccumulator::new(utxo_accumulator_model);
+    let mint = time("mint", rounds, || {
+        Mint::sample_post(
+            &proving_context.mint,
+            parameters,
+            &mut utxo_accumulator,
+            &mut OsRng,
+        )
+        .is_ok()
+    })?;
+    let private_transfer = time("private transfer", rounds, || {
+        PrivateTransfer::sample_post(
+            &proving_context.private_transfer,
+            parameters,
+            &mut utxo_accumulator,
+            &mut OsRng,
+        )
+        .is_ok()
+    })?;
+    let reclaim = time("reclaim", rounds, || {
+        Reclaim::sample_post(
+            &proving_context.reclaim,
+            parameters,
+            &mut utxo_accumulator,
+            &mut OsRng,
+        )
+        .is_ok()
+    })?;
+    Ok(BenchmarkReport {
+        version: crate::VERSION.into(),
+        cpus: std::thread::available_parallelism()
+            .ok()
+            .map(|cpus| cpus.get()),
+        parameter_loading_ms,
+        circuits: vec![
+            CircuitTiming::new("mint", &mint),
+            CircuitTiming::new("private transfer", &private_transfer),
+            CircuitTiming::new("reclaim", &reclaim),
+        ],
+        peak_memory_bytes: peak_memory(),
+    })
+}
+
+/// Returns the peak resident memory of the process in bytes.
+#[cfg(target_os = "linux")]
+#[inline]
+pub(crate) fn peak_memory() -> Option<u64> {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|kilobytes| kilobytes * 1024)
+}
+
+/// Returns the peak resident memory of the process in bytes.
+#[cfg(target_os = "windows")]
+#[inline]
+pub(crate) fn peak_memory() -> Option<u64> {
+    use windows::Win32::System::{
+        ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+        Threading::GetCurrentProcess,
+    };
+    let mut counters = PROCESS_MEMORY_COUNTERS::default();
+    let size = core::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+    // SAFETY: The counters are written to a buffer of the given size which we own.
+    unsafe { GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, size) }
+        .as_bool()
+        .then(|| counters.PeakWorkingSetSize as u64)
+}
+
+/// Returns the peak resident memory of the process in bytes.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+#[inline]
+pub(crate) fn peak_memory() -> Option<u64> {
+    None
+}
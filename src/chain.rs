@@ -0,0 +1,139 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Chain Specification
+//!
+//! The constants which differ between networks, such as the SS58 address prefix, the genesis
+//! hash and the asset registry, are described by a [`ChainSpec`] in the `chain` section of the
+//! [`Config`](crate::config::Config), so that the signer can be used with other networks and
+//! local development chains without recompiling it. The default specification is the one of the
+//! Dolphin testnet.
+
+use crate::substrate::DEFAULT_SS58_PREFIX;
+use manta_accounting::asset::AssetMetadata;
+use manta_util::serde::{Deserialize, Serialize};
+
+/// Asset Specification
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct AssetSpec {
+    /// Asset Identifier
+    pub id: u32,
+
+    /// Asset Symbol
+    pub symbol: String,
+
+    /// Asset Decimals
+    pub decimals: u32,
+}
+
+impl AssetSpec {
+    /// Returns the asset metadata of `self`.
+    #[inline]
+    pub fn metadata(&self) -> AssetMetadata {
+        AssetMetadata {
+            decimals: self.decimals,
+            symbol: self.symbol.clone(),
+        }
+    }
+}
+
+/// Chain Specification
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ChainSpec {
+    /// Chain Name
+    pub name: String,
+
+    /// SS58 Address Prefix
+    #[serde(default = "ChainSpec::default_ss58_prefix")]
+    pub ss58_prefix: u16,
+
+    /// Hex-Encoded Genesis Hash
+    ///
+    /// When it is set, the signer refuses to sign extrinsics for other chains.
+    #[serde(default)]
+    pub genesis_hash: Option<String>,
+
+    /// Native Asset Symbol
+    pub native_symbol: String,
+
+    /// Native Asset Decimals
+    pub native_decimals: u32,
+
+    /// Asset Registry
+    ///
+    /// The metadata of these assets is shown in the authorization prompt of requests which do not
+    /// carry their own.
+    #[serde(default)]
+    pub assets: Vec<AssetSpec>,
+}
+
+impl ChainSpec {
+    /// Returns the default value of [`ss58_prefix`](Self::ss58_prefix) for deserialization.
+    #[inline]
+    fn default_ss58_prefix() -> u16 {
+        DEFAULT_SS58_PREFIX
+    }
+
+    /// Returns the asset with the given `id` from the registry, if there is one.
+    #[inline]
+    pub fn asset(&self, id: u32) -> Option<&AssetSpec> {
+        self.assets.iter().find(|asset| asset.id == id)
+    }
+
+    /// Returns the metadata of the asset with the given `id` from the registry, if there is one.
+    #[inline]
+    pub fn metadata(&self, id: u32) -> Option<AssetMetadata> {
+        self.asset(id).map(AssetSpec::metadata)
+    }
+
+    /// Returns `true` if `genesis_hash` is the [`genesis_hash`](Self::genesis_hash) of the chain,
+    /// or if the chain has none.
+    #[inline]
+    pub fn matches_genesis(&self, genesis_hash: &str) -> bool {
+        match &self.genesis_hash {
+            Some(expected) => {
+                normalize_hash(expected).eq_ignore_ascii_case(normalize_hash(genesis_hash))
+            }
+            _ => true,
+        }
+    }
+}
+
+impl Default for ChainSpec {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            name: "Dolphin".into(),
+            ss58_prefix: DEFAULT_SS58_PREFIX,
+            genesis_hash: None,
+            native_symbol: "DOL".into(),
+            native_decimals: 18,
+            assets: vec![AssetSpec {
+                id: 1,
+                symbol: "DOL".into(),
+                decimals: 18,
+            }],
+        }
+    }
+}
+
+/// Removes the `0x` prefix from the hex-encoded `hash`.
+#[inline]
+fn normalize_hash(hash: &str) -> &str {
+    hash.trim().strip_prefix("0x").unwrap_or(hash.trim())
+}
@@ -0,0 +1,208 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Request Compatibility
+//!
+//! Service requests are deserialized according to the configured [`Compatibility`] mode. In
+//! [`Relaxed`](Compatibility::Relaxed) mode, fields which the signer does not know about are
+//! dropped from the object where they were found, and fields which were added after a request type was
+//! first released are filled in with their [`FIELD_DEFAULTS`], so that older and newer clients
+//! keep working across signer upgrades. In [`Strict`](Compatibility::Strict) mode, requests are
+//! deserialized as they are.
+
+use manta_util::serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use serde_path_to_error::{Path, Segment};
+
+/// Maximum Number of Request Fixups
+///
+/// This bounds the number of unknown fields dropped and missing fields filled in per request.
+pub const MAX_FIXUPS: usize = 32;
+
+/// Versioned Field Defaults
+///
+/// Each entry names a request field which was added after the first release of its request type
+/// together with the JSON value used when older clients omit it.
+pub const FIELD_DEFAULTS: &[(&str, &str)] = &[("metadata", "null")];
+
+/// Request Compatibility Mode
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum Compatibility {
+    /// Strict Mode
+    ///
+    /// Requests with unknown or missing fields are rejected. This is meant for development.
+    Strict,
+
+    /// Relaxed Mode
+    Relaxed,
+}
+
+impl Default for Compatibility {
+    #[inline]
+    fn default() -> Self {
+        Self::Relaxed
+    }
+}
+
+/// Extracts the name of the field from a serde error message of the form
+/// ``<prefix> `<field>`...``.
+#[inline]
+fn field_name<'m>(message: &'m str, prefix: &str) -> Option<&'m str> {
+    message.strip_prefix(prefix)?.split('`').nth(1)
+}
+
+/// Removes the field called `name` from the object of `value` at `path`, which is the path of the
+/// field itself as reported by the deserializer, returning `false` if there is no such field.
+///
+/// Fields with the same name in other objects of `value` are kept, since they can be part of
+/// what the user signs.
+#[inline]
+fn remove_field(value: &mut Value, path: &Path, name: &str) -> bool {
+    let mut segments = path.iter().collect::<Vec<_>>();
+    if matches!(segments.last(), Some(Segment::Map { key }) if key == name) {
+        segments.pop();
+    }
+    let mut object = value;
+    for segment in segments {
+        object = match (segment, object) {
+            (Segment::Map { key }, Value::Object(fields)) => match fields.get_mut(key) {
+                Some(value) => value,
+                _ => return false,
+            },
+            (Segment::Seq { index }, Value::Array(values)) => match values.get_mut(*index) {
+                Some(value) => value,
+                _ => return false,
+            },
+            (Segment::Enum { variant }, Value::Object(fields)) if fields.contains_key(variant) => {
+                fields
+                    .get_mut(variant)
+                    .expect("The variant was checked above.")
+            }
+            (Segment::Enum { .. }, object) => object,
+            _ => return false,
+        };
+    }
+    match object {
+        Value::Object(fields) => fields.remove(name).is_some(),
+        _ => false,
+    }
+}
+
+/// Request Fixup
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Fixup {
+    /// Dropped Unknown Field
+    ///
+    /// This is the path of the field in the request.
+    Dropped(String),
+
+    /// Filled-In Missing Field
+    Defaulted(String),
+}
+
+/// Deserializes a request of type `T` from `bytes` in the given compatibility `mode`, returning
+/// the request and the fixups which were applied to it.
+#[inline]
+pub fn deserialize<T>(bytes: &[u8], mode: Compatibility) -> serde_json::Result<(T, Vec<Fixup>)>
+where
+    T: DeserializeOwned,
+{
+    if mode == Compatibility::Strict {
+        return Ok((serde_json::from_slice(bytes)?, Vec::new()));
+    }
+    let mut value = serde_json::from_slice::<Value>(bytes)?;
+    let mut fixups = Vec::new();
+    loop {
+        let (path, err) = match serde_path_to_error::deserialize(value.clone()) {
+            Ok(request) => return Ok((request, fixups)),
+            Err(err) if fixups.len() >= MAX_FIXUPS => return Err(err.into_inner()),
+            Err(err) => (err.path().clone(), err.into_inner()),
+        };
+        let message = err.to_string();
+        if let Some(name) = field_name(&message, "unknown field") {
+            if !remove_field(&mut value, &path, name) {
+                return Err(err);
+            }
+            fixups.push(Fixup::Dropped(path.to_string()));
+        } else if let Some((name, default)) = field_name(&message, "missing field")
+            .and_then(|name| FIELD_DEFAULTS.iter().find(|(field, _)| *field == name))
+        {
+            match &mut value {
+                Value::Object(fields) if !fields.contains_key(*name) => {
+                    fields.insert(name.to_string(), serde_json::from_str(default)?);
+                }
+                _ => return Err(err),
+            }
+            fixups.push(Fixup::Defaulted(name.to_string()));
+        } else {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Inner Test Request
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "manta_util::serde", deny_unknown_fields)]
+    struct Inner {
+        /// Value
+        value: u64,
+    }
+
+    /// Outer Test Request
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "manta_util::serde", deny_unknown_fields)]
+    struct Outer {
+        /// Inner Requests
+        inner: Vec<Inner>,
+    }
+
+    /// Checks that relaxed mode only drops the unknown field where it was found, keeping fields
+    /// with the same name in other objects.
+    #[test]
+    fn drops_only_the_reported_field() {
+        let (request, fixups) = deserialize::<Outer>(
+            br#"{"value": 9, "inner": [{"value": 1}, {"value": 2, "extra": {"value": 3}}]}"#,
+            Compatibility::Relaxed,
+        )
+        .expect("The unknown fields are dropped.");
+        assert_eq!(
+            request,
+            Outer {
+                inner: vec![Inner { value: 1 }, Inner { value: 2 }]
+            }
+        );
+        assert_eq!(
+            fixups,
+            vec![
+                Fixup::Dropped("inner[1].extra".into()),
+                Fixup::Dropped("value".into())
+            ]
+        );
+    }
+
+    /// Checks that strict mode rejects unknown fields.
+    #[test]
+    fn strict_mode_rejects_unknown_fields() {
+        assert!(
+            deserialize::<Inner>(br#"{"value": 1, "extra": 2}"#, Compatibility::Strict).is_err()
+        );
+    }
+}
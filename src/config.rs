@@ -16,15 +16,38 @@
 
 //! Manta Signer Configuration
 
+use crate::{
+    chain::ChainSpec,
+    compat::Compatibility,
+    log::{self, Level, LogRotation},
+    schedule::SyncSchedule,
+    storage::{
+        self, cipher, rotation::DEFAULT_ROTATION_DAYS, Manifest, DEFAULT_BACKUP_GENERATIONS,
+    },
+    telemetry::TelemetryConfig,
+    wallet::DEFAULT_WALLET,
+};
+use http_types::Url;
 use manta_crypto::rand::{OsRng, Sample};
 use manta_pay::key::Mnemonic;
 use manta_util::serde::{Deserialize, Serialize};
 use std::{
     io,
+    net::{AddrParseError, SocketAddr},
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tokio::fs;
 
+/// Default Authorization Prompt Expiry in Minutes
+pub const DEFAULT_PROMPT_EXPIRY_MINUTES: u64 = 10;
+
+/// Default Number of Consecutive Failed Password Attempts before a Lockout
+pub const DEFAULT_LOCKOUT_THRESHOLD: u32 = 10;
+
+/// Default Lockout Duration in Minutes
+pub const DEFAULT_LOCKOUT_MINUTES: u64 = 15;
+
 /// Manta Path Identifier
 pub const PATH_IDENTIFIER: &str = "manta-signer";
 
@@ -42,6 +65,22 @@ where
     })
 }
 
+/// Returns `url` without the credentials, query and fragment it may carry, or the
+/// [`REDACTED`](log::REDACTED) placeholder if it is not a valid URL.
+#[inline]
+fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.set_query(None);
+            url.set_fragment(None);
+            url.into()
+        }
+        _ => log::REDACTED.into(),
+    }
+}
+
 /// Configuration
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 #[serde(crate = "manta_util::serde", deny_unknown_fields)]
@@ -50,10 +89,151 @@ pub struct Config {
     pub data_path: PathBuf,
 
     /// Service URL
+    ///
+    /// This is a comma-separated list of socket addresses the service listens at, such as
+    /// `127.0.0.1:29987` or `[::1]:29987`. The `localhost` host stands for both the IPv4 and the
+    /// IPv6 loopback address, so that browsers which resolve `localhost` to either of them reach
+    /// the signer. When a port is taken, the service falls back to the next free port and
+    /// records it in the file at [`discovery_path`](Self::discovery_path).
     pub service_url: String,
 
     /// Origin URL
     pub origin_url: Option<String>,
+
+    /// Remote Access Flag
+    ///
+    /// The service refuses to bind to a [`service_url`](Self::service_url) outside of the
+    /// loopback interface unless this flag is set. Remote clients always need an API key.
+    #[serde(default)]
+    pub allow_remote: bool,
+
+    /// Dapp Authentication Flag
+    ///
+    /// When enabled, requests without an API key are rejected unless they are authenticated
+    /// with the shared secret of a paired dapp, see the [`dapp`](crate::dapp) module. Approval
+    /// sessions only ever apply to authenticated dapps, whether or not this is enabled.
+    #[serde(default)]
+    pub require_dapp_auth: bool,
+
+    /// TOTP Approval Flag
+    ///
+    /// When enabled, signing requests are held until a valid TOTP code is submitted to the
+    /// `/approve` endpoint.
+    #[serde(default)]
+    pub totp_approval: bool,
+
+    /// Trusted Parameter Bundle Signing Keys
+    ///
+    /// Hex-encoded Ed25519 public keys which are accepted as signers of parameter bundles.
+    #[serde(default)]
+    pub parameter_keys: Vec<String>,
+
+    /// Number of Backup Generations Kept for the Wallet Files
+    #[serde(default = "Config::default_backup_generations")]
+    pub backup_generations: usize,
+
+    /// Storage Key Rotation Interval in Days
+    ///
+    /// The wallet files are re-encrypted under a fresh key at the first unlock after this many
+    /// days, see the [`rotation`](crate::storage::rotation) module. A value of zero disables the
+    /// rotation.
+    #[serde(default = "Config::default_key_rotation_days")]
+    pub key_rotation_days: u64,
+
+    /// OTLP Trace Export Endpoint
+    ///
+    /// Traces are only exported when the `otlp` feature is enabled.
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+
+    /// Node HTTP RPC Endpoint
+    ///
+    /// When set, the signer keeps its ledger state up to date by pulling diffs from the node.
+    #[serde(default)]
+    pub node_url: Option<String>,
+
+    /// Fallback Node HTTP RPC Endpoints
+    ///
+    /// When the node at [`node_url`](Self::node_url) is unreachable, the ledger synchronization
+    /// fails over to these endpoints in order, see [`node_endpoints`](Self::node_endpoints).
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+
+    /// Background Synchronization Schedule
+    #[serde(default)]
+    pub sync_schedule: SyncSchedule,
+
+    /// Alternative Node HTTP RPC Endpoints
+    ///
+    /// These endpoints are measured by the network probe next to the [`node_url`](Self::node_url)
+    /// and can be selected in its place.
+    #[serde(default)]
+    pub probe_urls: Vec<String>,
+
+    /// Request Compatibility Mode
+    #[serde(default)]
+    pub compatibility: Compatibility,
+
+    /// Unlock Duration
+    #[serde(default)]
+    pub unlock_duration: UnlockDuration,
+
+    /// Authorization Prompt Expiry in Minutes
+    ///
+    /// Prompts which are left unanswered for this long are dismissed and their request is
+    /// rejected. A value of zero disables the expiry.
+    #[serde(default = "Config::default_prompt_expiry_minutes")]
+    pub prompt_expiry_minutes: u64,
+
+    /// Hardware Key Wrapping Flag
+    ///
+    /// When enabled, new accounts mix a device secret which is wrapped by a Secure Enclave key on
+    /// macOS or a TPM key on Windows into their storage key, see the
+    /// [`keywrap`](crate::storage::keywrap) module. Existing accounts
+    /// keep the protection they were created with.
+    #[serde(default)]
+    pub hardware_key_wrapping: bool,
+
+    /// Lockout Threshold
+    ///
+    /// Failed password attempts are followed by an exponentially growing delay, and every this
+    /// many consecutive failures by a lockout of [`lockout_minutes`](Self::lockout_minutes). A
+    /// value of zero disables the lockout.
+    #[serde(default = "Config::default_lockout_threshold")]
+    pub lockout_threshold: u32,
+
+    /// Lockout Duration in Minutes
+    #[serde(default = "Config::default_lockout_minutes")]
+    pub lockout_minutes: u64,
+
+    /// Telemetry Configuration
+    ///
+    /// Frontends load the choices saved at [`telemetry_path`](Self::telemetry_path) into this
+    /// section before starting the service.
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// Chain Specification
+    #[serde(default)]
+    pub chain: ChainSpec,
+
+    /// SS58 Address Prefix of the Polkadot.js Compatible Account
+    ///
+    /// This overrides the prefix of the [`chain`](Self::chain) and is kept for configuration
+    /// files written before the chain specification existed, see
+    /// [`address_prefix`](Self::address_prefix).
+    #[serde(default)]
+    pub ss58_prefix: Option<u16>,
+
+    /// Minimum Log Level
+    #[serde(default)]
+    pub log_level: Level,
+
+    /// Log File Rotation Policy
+    ///
+    /// The log files are written to the [`log_directory`](Self::log_directory).
+    #[serde(default)]
+    pub log_rotation: LogRotation,
 }
 
 impl Config {
@@ -62,14 +242,265 @@ impl Config {
     pub fn try_default() -> Option<Self> {
         Some(Self {
             data_path: file(dirs_next::config_dir(), "storage.dat")?,
-            service_url: "127.0.0.1:29987".into(),
+            service_url: "localhost:29987".into(),
             #[cfg(feature = "unsafe-disable-cors")]
             origin_url: None,
             #[cfg(not(feature = "unsafe-disable-cors"))]
             origin_url: Some("https://app.dolphin.manta.network".into()),
+            allow_remote: false,
+            require_dapp_auth: false,
+            totp_approval: false,
+            parameter_keys: Vec::new(),
+            backup_generations: DEFAULT_BACKUP_GENERATIONS,
+            key_rotation_days: DEFAULT_ROTATION_DAYS,
+            otlp_endpoint: None,
+            node_url: None,
+            fallback_urls: Vec::new(),
+            sync_schedule: SyncSchedule::default(),
+            probe_urls: Vec::new(),
+            compatibility: Compatibility::default(),
+            unlock_duration: UnlockDuration::default(),
+            prompt_expiry_minutes: DEFAULT_PROMPT_EXPIRY_MINUTES,
+            hardware_key_wrapping: false,
+            lockout_threshold: DEFAULT_LOCKOUT_THRESHOLD,
+            lockout_minutes: DEFAULT_LOCKOUT_MINUTES,
+            telemetry: TelemetryConfig::default(),
+            chain: ChainSpec::default(),
+            ss58_prefix: None,
+            log_level: Level::default(),
+            log_rotation: LogRotation::default(),
         })
     }
 
+    /// Builds the default [`Config`] and applies the settings of the configuration file at
+    /// [`config_path`](Self::config_path), if it exists.
+    #[inline]
+    pub fn load_default() -> io::Result<Self> {
+        let config = Self::try_default().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "unable to find the configuration directory",
+            )
+        })?;
+        config.with_file(config.config_path())
+    }
+
+    /// Returns a copy of `self` with the settings of the configuration file at `path` applied,
+    /// or `self` if the file does not exist.
+    ///
+    /// The file is a JSON object with any subset of the fields of [`Config`].
+    #[inline]
+    pub fn with_file<P>(&self, path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match Self::read_settings(path)? {
+            Some(settings) => self.with_settings(settings),
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /// Reads the settings of the configuration file at `path`, returning `None` if it does not
+    /// exist.
+    #[inline]
+    pub fn read_settings<P>(
+        path: P,
+    ) -> io::Result<Option<serde_json::Map<String, serde_json::Value>>>
+    where
+        P: AsRef<Path>,
+    {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns a copy of `self` with the `settings` applied, which are any subset of the fields
+    /// of [`Config`].
+    #[inline]
+    pub fn with_settings(
+        &self,
+        settings: serde_json::Map<String, serde_json::Value>,
+    ) -> io::Result<Self> {
+        let mut config =
+            serde_json::to_value(self).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        if let Some(fields) = config.as_object_mut() {
+            fields.extend(settings);
+        }
+        serde_json::from_value(config)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Applies the reloadable settings which changed from `previous` to `next` to `self`,
+    /// returning the names of the other changed settings, which only take effect after a
+    /// restart.
+    ///
+    /// The reloadable settings are the [`log_level`](Self::log_level), the
+    /// [`log_rotation`](Self::log_rotation) policy, the [`origin_url`](Self::origin_url), the
+    /// [`require_dapp_auth`](Self::require_dapp_auth) flag, the node endpoints, the
+    /// [`sync_schedule`](Self::sync_schedule), the [`unlock_duration`](Self::unlock_duration), the
+    /// [`prompt_expiry_minutes`](Self::prompt_expiry_minutes), the password lockout limits, the
+    /// [`backup_generations`](Self::backup_generations), and the request
+    /// [`compatibility`](Self::compatibility) mode.
+    #[inline]
+    pub fn reload(&mut self, previous: &Self, next: &Self) -> Vec<&'static str> {
+        let mut restart = Vec::new();
+        macro_rules! reload {
+            ($($field:ident),*) => {$(
+                if previous.$field != next.$field {
+                    self.$field = next.$field.clone();
+                }
+            )*};
+        }
+        macro_rules! restart {
+            ($($field:ident),*) => {$(
+                if previous.$field != next.$field {
+                    restart.push(stringify!($field));
+                }
+            )*};
+        }
+        reload!(
+            log_level,
+            log_rotation,
+            origin_url,
+            require_dapp_auth,
+            node_url,
+            fallback_urls,
+            sync_schedule,
+            probe_urls,
+            unlock_duration,
+            prompt_expiry_minutes,
+            lockout_threshold,
+            lockout_minutes,
+            backup_generations,
+            compatibility
+        );
+        restart!(
+            data_path,
+            service_url,
+            allow_remote,
+            totp_approval,
+            parameter_keys,
+            otlp_endpoint,
+            hardware_key_wrapping,
+            key_rotation_days,
+            telemetry,
+            chain,
+            ss58_prefix
+        );
+        restart
+    }
+
+    /// Returns the default value of [`backup_generations`](Self::backup_generations) for
+    /// deserialization.
+    #[inline]
+    fn default_backup_generations() -> usize {
+        DEFAULT_BACKUP_GENERATIONS
+    }
+
+    /// Returns the default value of [`key_rotation_days`](Self::key_rotation_days) for
+    /// deserialization.
+    #[inline]
+    fn default_key_rotation_days() -> u64 {
+        DEFAULT_ROTATION_DAYS
+    }
+
+    /// Returns the default value of [`prompt_expiry_minutes`](Self::prompt_expiry_minutes) for
+    /// deserialization.
+    #[inline]
+    fn default_prompt_expiry_minutes() -> u64 {
+        DEFAULT_PROMPT_EXPIRY_MINUTES
+    }
+
+    /// Returns the default value of [`lockout_threshold`](Self::lockout_threshold) for
+    /// deserialization.
+    #[inline]
+    fn default_lockout_threshold() -> u32 {
+        DEFAULT_LOCKOUT_THRESHOLD
+    }
+
+    /// Returns the default value of [`lockout_minutes`](Self::lockout_minutes) for
+    /// deserialization.
+    #[inline]
+    fn default_lockout_minutes() -> u64 {
+        DEFAULT_LOCKOUT_MINUTES
+    }
+
+    /// Returns the SS58 address prefix of the polkadot.js compatible account, which is the
+    /// [`ss58_prefix`](Self::ss58_prefix) if it is set or the prefix of the [`chain`](Self::chain)
+    /// otherwise.
+    #[inline]
+    pub fn address_prefix(&self) -> u16 {
+        self.ss58_prefix.unwrap_or(self.chain.ss58_prefix)
+    }
+
+    /// Returns a copy of the configuration which can be logged or exported. The
+    /// [`data_path`](Self::data_path) is replaced by the [`REDACTED`](log::REDACTED) placeholder
+    /// since it usually contains the user name, and the endpoint URLs lose the credentials and
+    /// API keys they may carry.
+    #[inline]
+    pub fn redacted(&self) -> Self {
+        Self {
+            data_path: log::REDACTED.into(),
+            otlp_endpoint: self.otlp_endpoint.as_deref().map(redact_url),
+            node_url: self.node_url.as_deref().map(redact_url),
+            fallback_urls: self
+                .fallback_urls
+                .iter()
+                .map(|url| redact_url(url))
+                .collect(),
+            probe_urls: self.probe_urls.iter().map(|url| redact_url(url)).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the endpoints which the ledger synchronization uses, starting with the
+    /// [`node_url`](Self::node_url) and followed by the [`fallback_urls`](Self::fallback_urls).
+    /// There are none if the [`node_url`](Self::node_url) is not set.
+    #[inline]
+    pub fn node_endpoints(&self) -> Vec<String> {
+        let mut endpoints = Vec::new();
+        if let Some(url) = &self.node_url {
+            endpoints.push(url.clone());
+            for url in &self.fallback_urls {
+                if !endpoints.contains(url) {
+                    endpoints.push(url.clone());
+                }
+            }
+        }
+        endpoints
+    }
+
+    /// Returns the socket addresses in the [`service_url`](Self::service_url).
+    #[inline]
+    pub fn service_addresses(&self) -> Result<Vec<SocketAddr>, AddrParseError> {
+        let mut addresses = Vec::new();
+        for entry in self.service_url.split(',').map(str::trim) {
+            match entry.strip_prefix("localhost:") {
+                Some(port) => {
+                    addresses.push(format!("127.0.0.1:{}", port).parse()?);
+                    addresses.push(format!("[::1]:{}", port).parse()?);
+                }
+                _ => addresses.push(entry.parse()?),
+            }
+        }
+        Ok(addresses)
+    }
+
+    /// Returns the first entry of the [`service_url`](Self::service_url), which clients use to
+    /// reach the service.
+    #[inline]
+    pub fn client_address(&self) -> &str {
+        self.service_url
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+
     /// Returns the data directory path.
     #[inline]
     pub fn data_directory(&self) -> &Path {
@@ -78,21 +509,331 @@ impl Config {
             .expect("The data path file must always have a parent.")
     }
 
+    /// Returns the path to the decoy wallet state which is unlocked by the duress password, see
+    /// the [`duress`](crate::duress) module.
+    ///
+    /// The decoy slot has its own data directory, so every path derived from the data path of
+    /// the decoy slot is separate from the real one.
+    #[inline]
+    pub fn decoy_data_path(&self) -> PathBuf {
+        self.data_directory().join("decoy").join(
+            self.data_path
+                .file_name()
+                .expect("The data path must always have a file name."),
+        )
+    }
+
+    /// Returns the path to the wrapped device secret.
+    #[inline]
+    pub fn device_key_path(&self) -> PathBuf {
+        self.data_directory().join("device-key.json")
+    }
+
+    /// Returns the path to the encrypted TOTP secret file.
+    #[inline]
+    pub fn totp_path(&self) -> PathBuf {
+        self.data_directory().join("totp.dat")
+    }
+
+    /// Returns the path to the encrypted signer identity key.
+    #[inline]
+    pub fn identity_path(&self) -> PathBuf {
+        self.data_directory().join("identity.dat")
+    }
+
+    /// Returns the path to the encrypted paired dapp store.
+    #[inline]
+    pub fn dapps_path(&self) -> PathBuf {
+        self.data_directory().join("dapps.dat")
+    }
+
+    /// Returns the path to the file called `file_name` of the wallet called `name`.
+    ///
+    /// The files of the default wallet are stored next to the wallet file, so that the decoy
+    /// wallet has its own, and the files of the other wallets are prefixed with their name.
+    #[inline]
+    fn wallet_file_path(&self, name: &str, file_name: &str) -> PathBuf {
+        if name == DEFAULT_WALLET {
+            self.data_path.with_file_name(file_name)
+        } else {
+            self.data_directory()
+                .join(format!("wallet-{}.{}", name, file_name))
+        }
+    }
+
+    /// Returns the path to the encrypted account and asset labels of the wallet called `name`.
+    #[inline]
+    pub fn labels_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "labels.dat")
+    }
+
+    /// Returns the path to the encrypted approval sessions of the wallet called `name`.
+    #[inline]
+    pub fn sessions_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "sessions.dat")
+    }
+
+    /// Returns the path to the encrypted remembered approval decisions of the wallet called
+    /// `name`.
+    #[inline]
+    pub fn exemptions_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "exemptions.dat")
+    }
+
+    /// Returns the path to the encrypted transaction memos of the wallet called `name`.
+    #[inline]
+    pub fn memos_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "memos.dat")
+    }
+
+    /// Returns the path to the encrypted transaction proofs of the wallet called `name`.
+    #[inline]
+    pub fn proofs_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "proofs.dat")
+    }
+
+    /// Returns the path to the encrypted paired device store.
+    #[inline]
+    pub fn devices_path(&self) -> PathBuf {
+        self.data_directory().join("devices.dat")
+    }
+
+    /// Returns the path to the plaintext paired device store written by earlier versions, which
+    /// is moved to the [`devices_path`](Self::devices_path) at the next unlock.
+    #[inline]
+    pub fn legacy_devices_path(&self) -> PathBuf {
+        self.data_directory().join("devices.json")
+    }
+
+    /// Returns the path to the encrypted polkadot.js compatible account key derived from the
+    /// recovery phrase of the wallet called `name`.
+    #[inline]
+    pub fn polkadot_account_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "polkadot.dat")
+    }
+
+    /// Returns the path to the encrypted polkadot.js compatible account key sampled at random by
+    /// earlier versions, which cannot be recovered from the recovery phrase. Only the default
+    /// wallet can have one.
+    #[inline]
+    pub fn substrate_account_path(&self) -> PathBuf {
+        self.data_directory().join("substrate.dat")
+    }
+
+    /// Returns the path to the ledger synchronization checkpoint.
+    #[inline]
+    pub fn sync_checkpoint_path(&self) -> PathBuf {
+        self.data_directory().join("sync.json")
+    }
+
+    /// Returns the path to the wallet registry.
+    #[inline]
+    pub fn wallets_path(&self) -> PathBuf {
+        self.data_directory().join("wallets.json")
+    }
+
+    /// Returns the path to the state file of the wallet called `name`.
+    #[inline]
+    pub fn wallet_path(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_WALLET {
+            self.data_path.clone()
+        } else {
+            self.data_directory().join(format!("wallet-{}.dat", name))
+        }
+    }
+
+    /// Returns the path to the ledger synchronization checkpoint of the wallet called `name`.
+    #[inline]
+    pub fn wallet_sync_path(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_WALLET {
+            self.sync_checkpoint_path()
+        } else {
+            self.data_directory()
+                .join(format!("wallet-{}.sync.json", name))
+        }
+    }
+
+    /// Returns the path to the encrypted shielded balances of the wallet called `name`.
+    #[inline]
+    pub fn wallet_balances_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "balances.dat")
+    }
+
+    /// Returns the path to the encrypted API key store.
+    #[inline]
+    pub fn api_keys_path(&self) -> PathBuf {
+        self.data_directory().join("api_keys.dat")
+    }
+
+    /// Returns the path to the plaintext API key store written by earlier versions, which is
+    /// moved to the [`api_keys_path`](Self::api_keys_path) at the next unlock.
+    #[inline]
+    pub fn legacy_api_keys_path(&self) -> PathBuf {
+        self.data_directory().join("api_keys.json")
+    }
+
+    /// Returns the path to the audit log of the wallet called `name`.
+    #[inline]
+    pub fn audit_log_path(&self, name: &str) -> PathBuf {
+        self.wallet_file_path(name, "audit.log")
+    }
+
+    /// Returns the path to the saved telemetry configuration.
+    #[inline]
+    pub fn telemetry_path(&self) -> PathBuf {
+        self.data_directory().join("telemetry.json")
+    }
+
+    /// Returns the path to the recovery phrase [`BackupStatus`](crate::backup::BackupStatus).
+    #[inline]
+    pub fn backup_status_path(&self) -> PathBuf {
+        self.data_directory().join("recovery-phrase-backup.json")
+    }
+
+    /// Returns the path to the record of failed password attempts.
+    #[inline]
+    pub fn password_attempts_path(&self) -> PathBuf {
+        self.data_directory().join("password-attempts.json")
+    }
+
+    /// Returns the path to the storage key rotation status, see the
+    /// [`rotation`](crate::storage::rotation) module.
+    #[inline]
+    pub fn key_rotation_path(&self) -> PathBuf {
+        self.data_directory().join("key-rotation.json")
+    }
+
+    /// Returns the path to the service discovery file.
+    #[inline]
+    pub fn discovery_path(&self) -> PathBuf {
+        self.data_directory().join("discovery.json")
+    }
+
+    /// Returns the path to the inbox directory of the [deep links](crate::deeplink) which are
+    /// forwarded to the running signer.
+    #[inline]
+    pub fn deep_link_inbox_path(&self) -> PathBuf {
+        self.data_directory().join("deeplinks")
+    }
+
+    /// Returns the path to the control socket of the signer daemon.
+    ///
+    /// On Windows, this is a named pipe.
+    #[inline]
+    pub fn control_socket_path(&self) -> PathBuf {
+        if cfg!(windows) {
+            PathBuf::from(r"\\.\pipe\manta-signer-control")
+        } else {
+            self.data_directory().join("signerd.sock")
+        }
+    }
+
+    /// Returns the path to the directory of the log files.
+    #[inline]
+    pub fn log_directory(&self) -> PathBuf {
+        self.data_directory().join("logs")
+    }
+
+    /// Returns the path to the configuration file, see [`with_file`](Self::with_file).
+    #[inline]
+    pub fn config_path(&self) -> PathBuf {
+        self.data_directory().join("config.json")
+    }
+
+    /// Returns the path to the storage [`Manifest`].
+    #[inline]
+    pub fn manifest_path(&self) -> PathBuf {
+        self.data_directory().join("manifest.json")
+    }
+
     /// Builds the [`Setup`] for the given configuration depending on the filesystem resources.
     #[inline]
     pub async fn setup(&self) -> io::Result<Setup> {
         fs::create_dir_all(self.data_directory()).await?;
         match fs::metadata(&self.data_path).await {
-            Ok(metadata) if metadata.is_file() => Ok(Setup::Login),
+            Ok(metadata) if metadata.is_file() => {
+                storage::migrate(self).await?;
+                match storage::check_integrity(&self.data_path).await {
+                    Ok(()) => Ok(Setup::Login),
+                    Err(cipher::Error::Io(err)) => Err(err),
+                    Err(err) => Ok(Setup::Recover(Recovery {
+                        reason: format!("{:?}", err),
+                        backups: storage::valid_backups(&self.data_path, self.backup_generations)
+                            .await,
+                    })),
+                }
+            }
             Ok(metadata) => Err(io::Error::new(
                 io::ErrorKind::Other,
                 format!("Invalid file format: {:?}.", metadata),
             )),
-            _ => Ok(Setup::CreateAccount(Mnemonic::gen(&mut OsRng))),
+            _ => {
+                Manifest::current().save(self.manifest_path()).await?;
+                Ok(Setup::CreateAccount(Mnemonic::gen(&mut OsRng)))
+            }
+        }
+    }
+}
+
+/// Unlock Duration
+///
+/// After the password was entered, the signer stays unlocked for this long. While it is
+/// unlocked, authorization prompts only ask the user to confirm the request instead of asking for
+/// the password again.
+///
+/// # Expiry
+///
+/// When a [`Minutes`](Self::Minutes) duration elapses, the signer locks itself: the decrypted
+/// signer state is dropped from memory and the user has to log in again before the signer
+/// answers requests or synchronizes. With [`EveryTransaction`](Self::EveryTransaction) and
+/// [`UntilQuit`](Self::UntilQuit) the signer only locks when the user locks it or quits.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    content = "content",
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum UnlockDuration {
+    /// Ask for the Password on Every Request
+    EveryTransaction,
+
+    /// Stay Unlocked for the Given Number of Minutes
+    Minutes(u64),
+
+    /// Stay Unlocked until the Signer Quits
+    UntilQuit,
+}
+
+impl UnlockDuration {
+    /// Returns `true` if the signer which was unlocked `elapsed` ago is still unlocked.
+    #[inline]
+    pub fn is_unlocked(&self, elapsed: Duration) -> bool {
+        match self {
+            Self::EveryTransaction => false,
+            Self::Minutes(minutes) => elapsed < Duration::from_secs(60 * minutes),
+            Self::UntilQuit => true,
+        }
+    }
+
+    /// Returns the time after the password entry at which the signer locks itself, or `None` if
+    /// it stays unlocked until the user locks it or quits.
+    #[inline]
+    pub fn expiry(&self) -> Option<Duration> {
+        match self {
+            Self::Minutes(minutes) => Some(Duration::from_secs(60 * minutes)),
+            _ => None,
         }
     }
 }
 
+impl Default for UnlockDuration {
+    #[inline]
+    fn default() -> Self {
+        Self::EveryTransaction
+    }
+}
+
 /// Setup Phase
 #[derive(Clone, Deserialize, Serialize)]
 #[serde(
@@ -107,4 +848,39 @@ pub enum Setup {
 
     /// Login
     Login,
+
+    /// Recover from Corrupted Storage
+    Recover(Recovery),
+}
+
+/// Recovery Information
+///
+/// This is sent to the authorizer when the wallet file fails its integrity check at startup.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Recovery {
+    /// Description of the Integrity Failure
+    pub reason: String,
+
+    /// Backup Generations which Passed the Integrity Check
+    pub backups: Vec<usize>,
+}
+
+/// Recovery Choice
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(
+    content = "content",
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum RecoveryChoice {
+    /// Restore the Given Backup Generation
+    RestoreBackup(usize),
+
+    /// Re-Import the Account from its Mnemonic
+    ImportMnemonic(Mnemonic),
+
+    /// Abort Startup
+    Abort,
 }
@@ -0,0 +1,83 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Confirmation Codes
+//!
+//! A confirmation code is a short fingerprint of the canonicalized transaction which is shown in
+//! the authorization prompt and returned to the client, so that users can check that the client
+//! and the signer agree on what is being signed.
+//!
+//! The code is computed by hashing [`DOMAIN_TAG`] followed by the JSON serialization of the
+//! transaction with SHA-256 and mapping each of the first [`CODE_LENGTH`] bytes of the digest to a
+//! word of [`WORDS`].
+
+use manta_util::serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Confirmation Code Domain Tag
+pub const DOMAIN_TAG: &[u8] = b"manta-signer/confirmation-code/v1";
+
+/// Confirmation Code Length in Words
+pub const CODE_LENGTH: usize = 6;
+
+/// Confirmation Code Word List
+///
+/// Each byte of the digest selects one of these 256 words.
+pub const WORDS: [&str; 256] = [
+    "acid", "acorn", "actor", "agent", "alarm", "album", "alley", "amber", "anchor", "angle",
+    "apple", "april", "arena", "armor", "arrow", "atlas", "attic", "audio", "autumn", "badge",
+    "baker", "bamboo", "banjo", "barn", "basil", "basket", "beach", "beacon", "bean", "bear",
+    "berry", "binder", "birch", "bison", "blade", "blanket", "bloom", "blue", "bonus", "book",
+    "border", "bottle", "bread", "brick", "bridge", "bronze", "brush", "bucket", "bugle", "button",
+    "cabin", "cactus", "camel", "camera", "candle", "canoe", "canvas", "canyon", "carbon", "cargo",
+    "carpet", "castle", "cedar", "cello", "chalk", "cherry", "chess", "circle", "citrus", "clay",
+    "cliff", "clock", "cloud", "clover", "cobalt", "comet", "copper", "coral", "cotton", "cougar",
+    "crane", "crater", "crystal", "cube", "dagger", "daisy", "dawn", "delta", "desert", "diamond",
+    "dolphin", "donkey", "dragon", "drum", "eagle", "echo", "elbow", "ember", "engine", "falcon",
+    "feather", "fern", "fiddle", "finch", "flame", "flint", "forest", "fossil", "fox", "galaxy",
+    "garden", "garlic", "gecko", "geyser", "ginger", "glacier", "globe", "goat", "granite",
+    "grape", "gravel", "guitar", "hammer", "harbor", "harvest", "hazel", "helmet", "heron",
+    "hollow", "honey", "hornet", "iceberg", "igloo", "indigo", "island", "ivory", "jacket",
+    "jaguar", "jelly", "jungle", "kayak", "kettle", "kiwi", "koala", "ladder", "lagoon", "lantern",
+    "lava", "lemon", "lilac", "lion", "lizard", "lobster", "locket", "lotus", "magnet", "mango",
+    "maple", "marble", "meadow", "melon", "meteor", "mint", "mirror", "mole", "monkey", "moose",
+    "mosaic", "mustard", "napkin", "nectar", "needle", "nest", "nickel", "nutmeg", "oasis",
+    "ocean", "olive", "onion", "orange", "orbit", "orchid", "otter", "owl", "oyster", "paddle",
+    "palm", "panda", "paper", "parrot", "peach", "pebble", "pepper", "piano", "pigeon", "pillow",
+    "pine", "planet", "plum", "pocket", "pond", "poppy", "puzzle", "quartz", "quill", "rabbit",
+    "radar", "radish", "raven", "reef", "ribbon", "river", "robin", "rocket", "rose", "ruby",
+    "saddle", "salmon", "sand", "saturn", "scarf", "shadow", "shell", "silver", "sketch",
+    "sparrow", "spider", "spruce", "squid", "star", "stone", "sugar", "summit", "swan", "tango",
+    "temple", "tiger", "timber", "tomato", "topaz", "torch", "tulip", "tundra", "turtle", "valley",
+    "velvet", "violet", "volcano", "walnut", "walrus", "willow", "window", "wizard", "wolf",
+    "yacht", "zebra",
+];
+
+/// Computes the confirmation code of `transaction`, returning the words separated by spaces.
+#[inline]
+pub fn confirmation_code<T>(transaction: &T) -> serde_json::Result<String>
+where
+    T: Serialize,
+{
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN_TAG);
+    hasher.update(serde_json::to_vec(transaction)?);
+    Ok(hasher.finalize()[..CODE_LENGTH]
+        .iter()
+        .map(|byte| WORDS[*byte as usize])
+        .collect::<Vec<_>>()
+        .join(" "))
+}
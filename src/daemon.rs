@@ -0,0 +1,730 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Signer Daemon
+//!
+//! The daemon runs the signer service in the background, for example under an init system or as
+//! a Windows service, where there is no window or terminal to show prompts in. Instead, it is
+//! controlled with [`ControlRequest`]s sent to the control socket in the data directory, or the
+//! control pipe on Windows, which only the local user can access:
+//!
+//! - [`Status`](ControlRequest::Status) reports whether the daemon is locked and how many
+//!   requests are pending,
+//! - [`Unlock`](ControlRequest::Unlock) hands the password to the daemon, which then answers
+//!   the authorization prompts of the approved requests with it until it is locked again,
+//! - [`Lock`](ControlRequest::Lock) forgets the password, rejects the pending requests and
+//!   revokes the approval sessions,
+//! - [`Pending`](ControlRequest::Pending) lists the pending requests,
+//! - [`Approve`](ControlRequest::Approve) and [`Reject`](ControlRequest::Reject) answer a
+//!   pending request.
+//!
+//! The daemon starts locked. Every request which needs the user's authorization or consent stays
+//! pending until it is approved or rejected with the control client, or its prompt expires. An
+//! approved request is only answered once the daemon is unlocked.
+//!
+//! # Socket Activation
+//!
+//! On Unix, when started by systemd with inherited sockets, the daemon serves the API at those
+//! sockets instead of binding its own, so that it only starts once a dapp connects and never
+//! needs to bind a port itself.
+
+use crate::{
+    config::{Config, Recovery, RecoveryChoice, Setup},
+    error::SignerError,
+    log::{info, warn},
+    queue::{PendingRequest, RequestQueue},
+    secret::{
+        Authorizer, ConfirmationFuture, ExposeSecret, Password, PasswordFuture, RecoveryFuture,
+        SecretString, UnitFuture,
+    },
+    service::{self, Handle},
+};
+use async_std::task;
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, Write},
+    path::Path,
+    sync::Arc,
+};
+use tokio::sync::Notify;
+
+#[cfg(unix)]
+use {
+    crate::transport::{HttpTransport, Transport},
+    async_std::{
+        io::{prelude::BufReadExt, BufReader, WriteExt},
+        os::unix::net::{UnixListener, UnixStream},
+        stream::StreamExt,
+    },
+    std::{
+        env,
+        net::{SocketAddr, TcpListener},
+        os::unix::{fs::PermissionsExt, io::FromRawFd, net::UnixListener as StdUnixListener},
+    },
+};
+
+#[cfg(windows)]
+use {
+    std::path::PathBuf,
+    tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::windows::named_pipe::{NamedPipeServer, ServerOptions},
+    },
+};
+
+/// First File Descriptor Passed by Socket Activation
+#[cfg(unix)]
+#[cfg_attr(doc_cfg, doc(cfg(unix)))]
+pub const LISTEN_FDS_START: i32 = 3;
+
+/// Name of the Activated Socket Used as the Control Socket
+#[cfg(unix)]
+#[cfg_attr(doc_cfg, doc(cfg(unix)))]
+pub const CONTROL_SOCKET_NAME: &str = "control";
+
+/// Control Request
+#[derive(Deserialize, Serialize)]
+#[serde(
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    rename_all = "snake_case",
+    tag = "command"
+)]
+pub enum ControlRequest {
+    /// Reports the daemon status.
+    Status,
+
+    /// Locks the daemon.
+    Lock,
+
+    /// Unlocks the daemon with the `password`.
+    Unlock {
+        /// Password
+        password: String,
+    },
+
+    /// Lists the pending requests.
+    Pending,
+
+    /// Approves the pending request with the given `id`.
+    Approve {
+        /// Request Identifier
+        id: u64,
+    },
+
+    /// Rejects the pending request with the given `id`.
+    Reject {
+        /// Request Identifier
+        id: u64,
+    },
+}
+
+/// Daemon Status
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DaemonStatus {
+    /// Signer Version
+    pub version: String,
+
+    /// Locked Flag
+    pub locked: bool,
+
+    /// Number of Pending Requests
+    pub pending: usize,
+
+    /// Number of Active Approval Sessions
+    pub sessions: usize,
+}
+
+/// Control Response
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    rename_all = "snake_case"
+)]
+pub enum ControlResponse {
+    /// Daemon Status
+    Status(DaemonStatus),
+
+    /// Request Completed
+    Done,
+
+    /// Pending Requests
+    Pending(Vec<PendingRequest>),
+
+    /// Unknown Request
+    ///
+    /// There is no pending request with the identifier of an approval or rejection.
+    Unknown,
+}
+
+/// Daemon Keyring
+///
+/// The keyring holds the password while the daemon is unlocked, and the identifiers of the
+/// pending requests which were approved with the control client.
+#[derive(Default)]
+pub struct Keyring {
+    /// Password
+    password: Mutex<Option<SecretString>>,
+
+    /// Unlock Signal
+    unlocked: Notify,
+
+    /// Approved Request Identifiers
+    approved: Mutex<HashSet<u64>>,
+
+    /// Approval Signal
+    approval: Notify,
+}
+
+impl Keyring {
+    /// Unlocks the keyring with `password`.
+    #[inline]
+    pub fn unlock(&self, password: SecretString) {
+        *self.password.lock() = Some(password);
+        self.unlocked.notify_waiters();
+    }
+
+    /// Locks the keyring, returning `false` if it was already locked.
+    #[inline]
+    pub fn lock(&self) -> bool {
+        self.password.lock().take().is_some()
+    }
+
+    /// Returns `true` if the keyring is locked.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.password.lock().is_none()
+    }
+
+    /// Waits until the keyring is unlocked, returning the password.
+    #[inline]
+    pub async fn password(&self) -> SecretString {
+        loop {
+            let unlocked = self.unlocked.notified();
+            if let Some(password) = &*self.password.lock() {
+                return SecretString::new(password.expose_secret().clone());
+            }
+            unlocked.await;
+        }
+    }
+
+    /// Approves the request with the given `id`, forgetting the approvals of the requests which
+    /// are no longer in the `queue`, and returns `false` if the request is not pending.
+    #[inline]
+    pub fn approve(&self, queue: &RequestQueue, id: u64) -> bool {
+        let pending = queue.list();
+        if !pending.iter().any(|request| request.id == id) {
+            return false;
+        }
+        let mut approved = self.approved.lock();
+        approved.retain(|approved| pending.iter().any(|request| request.id == *approved));
+        approved.insert(id);
+        drop(approved);
+        self.approval.notify_waiters();
+        true
+    }
+
+    /// Waits until the request with the given `id` is approved.
+    #[inline]
+    pub async fn approval(&self, id: u64) {
+        loop {
+            let approval = self.approval.notified();
+            if self.approved.lock().contains(&id) {
+                return;
+            }
+            approval.await;
+        }
+    }
+}
+
+/// Daemon Authorizer
+///
+/// The authorizer answers the prompts of the requests approved with the control client, with the
+/// password of the [`Keyring`] once it is unlocked, and logs them, so that init systems collect
+/// them.
+pub struct Daemon {
+    /// Keyring
+    keyring: Arc<Keyring>,
+
+    /// Authorization Request Queue
+    queue: Arc<RequestQueue>,
+
+    /// Prompted Request Identifier
+    ///
+    /// This is the pending request whose authorization prompt was sent with the last call to
+    /// [`wake`](Authorizer::wake), which needs an approval before the password is handed to the
+    /// service. Password requests without a prompt, like the login, need no approval.
+    prompted: Option<u64>,
+
+    /// Answered Flag
+    ///
+    /// This is set once the password was handed to the service, so that a new password request
+    /// means that it did not match.
+    answered: Arc<AtomicBool>,
+}
+
+impl Daemon {
+    /// Builds a new [`Daemon`] authorizer which answers the prompts of the requests in `queue`
+    /// once they are approved, with the password of `keyring`.
+    #[inline]
+    pub fn new(keyring: Arc<Keyring>, queue: Arc<RequestQueue>) -> Self {
+        Self {
+            keyring,
+            queue,
+            prompted: None,
+            answered: Default::default(),
+        }
+    }
+
+    /// Returns the identifier of the request which is currently prompted, which is the first
+    /// pending request.
+    #[inline]
+    fn current_request(&self) -> Option<u64> {
+        self.queue.list().first().map(|request| request.id)
+    }
+}
+
+impl Authorizer for Daemon {
+    #[inline]
+    fn password(&mut self) -> PasswordFuture {
+        let mismatch = self.answered.swap(false, Ordering::Relaxed) && self.keyring.lock();
+        let keyring = self.keyring.clone();
+        let answered = self.answered.clone();
+        let prompted = self.prompted;
+        Box::pin(async move {
+            if mismatch {
+                let _ = warn!("[DAEMON] the unlock password does not match, locking the daemon");
+            }
+            if let Some(id) = prompted {
+                keyring.approval(id).await;
+            }
+            let password = keyring.password().await;
+            answered.store(true, Ordering::Relaxed);
+            Password::from_known(password)
+        })
+    }
+
+    #[inline]
+    fn setup<'s>(&'s mut self, setup: &'s Setup) -> UnitFuture<'s> {
+        self.prompted = None;
+        Box::pin(async move {
+            if let Setup::Login = setup {
+                let _ = info!("[DAEMON] locked, run `manta-signer ctl unlock` to unlock it");
+            }
+        })
+    }
+
+    #[inline]
+    fn wake<T>(&mut self, prompt: &T) -> UnitFuture
+    where
+        T: Serialize,
+    {
+        self.prompted = self.current_request();
+        let prompt = serde_json::to_string(prompt).unwrap_or_default();
+        let prompted = self.prompted;
+        Box::pin(async move {
+            match prompted {
+                Some(id) => {
+                    let _ = info!(
+                        "[DAEMON] authorization requested for request {}, run `manta-signer ctl \
+                         approve {}` to approve it: {}",
+                        id, id, prompt
+                    );
+                }
+                _ => {
+                    let _ = info!("[DAEMON] authorization requested: {}", prompt);
+                }
+            }
+        })
+    }
+
+    #[inline]
+    fn confirm<T>(&mut self, prompt: &T) -> ConfirmationFuture
+    where
+        T: Serialize,
+    {
+        let prompt = serde_json::to_string(prompt).unwrap_or_default();
+        let current = self.current_request();
+        let keyring = self.keyring.clone();
+        Box::pin(async move {
+            let id = match current {
+                Some(id) => id,
+                _ => {
+                    let _ = warn!(
+                        "[DAEMON] declining confirmation without a pending request: {}",
+                        prompt
+                    );
+                    return false;
+                }
+            };
+            let _ = info!(
+                "[DAEMON] confirmation requested for request {}, run `manta-signer ctl approve \
+                 {}` to approve it: {}",
+                id, id, prompt
+            );
+            keyring.approval(id).await;
+            keyring.password().await;
+            true
+        })
+    }
+
+    #[inline]
+    fn recover<'s>(&'s mut self, recovery: &'s Recovery) -> RecoveryFuture<'s> {
+        Box::pin(async move {
+            let _ = warn!(
+                "[DAEMON] the signer storage is corrupted: {}, use the signer app to recover it",
+                recovery.reason
+            );
+            RecoveryChoice::Abort
+        })
+    }
+
+    #[inline]
+    fn cooldown(&mut self, remaining: Duration) -> UnitFuture {
+        Box::pin(async move {
+            let _ = warn!(
+                "[DAEMON] too many failed attempts, password attempts are blocked for {} seconds",
+                (remaining.as_millis() as u64 + 999) / 1000
+            );
+        })
+    }
+
+    #[inline]
+    fn dismiss(&mut self) -> UnitFuture {
+        self.answered.store(false, Ordering::Relaxed);
+        self.prompted = None;
+        Box::pin(async move {
+            let _ = warn!("[DAEMON] the prompt expired");
+        })
+    }
+
+    #[inline]
+    fn sleep(&mut self) -> UnitFuture {
+        self.answered.store(false, Ordering::Relaxed);
+        self.prompted = None;
+        Box::pin(async move {})
+    }
+}
+
+/// Runs `request` against the daemon state in `keyring` and `handle`.
+#[inline]
+pub fn execute(keyring: &Keyring, handle: &Handle, request: ControlRequest) -> ControlResponse {
+    match request {
+        ControlRequest::Status => ControlResponse::Status(DaemonStatus {
+            version: crate::VERSION.into(),
+            locked: keyring.is_locked(),
+            pending: handle.queue.len(),
+            sessions: handle.sessions.list().len(),
+        }),
+        ControlRequest::Lock => {
+            keyring.lock();
+            for request in handle.queue.list() {
+                handle.queue.reject(request.id);
+            }
+            for session in handle.sessions.list() {
+                let _ = handle.sessions.revoke(&session.dapp);
+            }
+            ControlResponse::Done
+        }
+        ControlRequest::Unlock { password } => {
+            keyring.unlock(SecretString::new(password));
+            ControlResponse::Done
+        }
+        ControlRequest::Pending => ControlResponse::Pending(handle.queue.list()),
+        ControlRequest::Approve { id } => {
+            if keyring.approve(&handle.queue, id) {
+                ControlResponse::Done
+            } else {
+                ControlResponse::Unknown
+            }
+        }
+        ControlRequest::Reject { id } => {
+            if handle.queue.reject(id) {
+                ControlResponse::Done
+            } else {
+                ControlResponse::Unknown
+            }
+        }
+    }
+}
+
+/// Answers the control request in `line`, returning the JSON response line.
+#[inline]
+fn respond(keyring: &Keyring, handle: &Handle, line: &str) -> io::Result<Vec<u8>> {
+    let response = serde_json::from_str::<ControlRequest>(line)
+        .map(|request| execute(keyring, handle, request))
+        .map_err(SignerError::from);
+    let mut bytes =
+        serde_json::to_vec(&response).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    bytes.push(b'\n');
+    Ok(bytes)
+}
+
+/// Answers the control requests sent to `stream`, one JSON request per line.
+#[cfg(unix)]
+#[inline]
+async fn answer(stream: UnixStream, keyring: Arc<Keyring>, handle: Handle) -> io::Result<()> {
+    let mut lines = BufReader::new(stream.clone()).lines();
+    let mut stream = stream;
+    while let Some(line) = lines.next().await {
+        stream
+            .write_all(&respond(&keyring, &handle, &line?)?)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Answers the control requests sent to `pipe`, one JSON request per line.
+#[cfg(windows)]
+#[inline]
+async fn answer(pipe: NamedPipeServer, keyring: Arc<Keyring>, handle: Handle) -> io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(pipe);
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        writer
+            .write_all(&respond(&keyring, &handle, &line)?)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Binds the control socket at `path`, replacing a stale socket left by a previous daemon.
+///
+/// Only the owner of the socket can connect to it.
+#[cfg(unix)]
+#[cfg_attr(doc_cfg, doc(cfg(unix)))]
+#[inline]
+pub async fn bind_control<P>(path: P) -> io::Result<UnixListener>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path).await?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Answers the control requests of the connections to `listener` against the daemon state in
+/// `keyring` and `handle`.
+#[cfg(unix)]
+#[cfg_attr(doc_cfg, doc(cfg(unix)))]
+#[inline]
+pub async fn serve_control(listener: UnixListener, keyring: Arc<Keyring>, handle: Handle) {
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let keyring = keyring.clone();
+        let handle = handle.clone();
+        task::spawn(async move {
+            if let Err(err) = answer(stream?, keyring, handle).await {
+                warn!("daemon control connection failed: {}", err)?;
+            }
+            Ok::<_, io::Error>(())
+        });
+    }
+}
+
+/// Creates the first instance of the control pipe at `path`, failing if another daemon already
+/// serves it.
+///
+/// The pipe keeps the default security descriptor, so only the account of the daemon, the
+/// administrators and the local system can send requests to it.
+#[cfg(windows)]
+#[cfg_attr(doc_cfg, doc(cfg(windows)))]
+#[inline]
+pub fn bind_control<P>(path: P) -> io::Result<NamedPipeServer>
+where
+    P: AsRef<Path>,
+{
+    ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(path.as_ref())
+}
+
+/// Answers the control requests of the connections to the control `pipe` at `path` against the
+/// daemon state in `keyring` and `handle`.
+#[cfg(windows)]
+#[cfg_attr(doc_cfg, doc(cfg(windows)))]
+#[inline]
+pub async fn serve_control(
+    mut pipe: NamedPipeServer,
+    path: PathBuf,
+    keyring: Arc<Keyring>,
+    handle: Handle,
+) -> io::Result<()> {
+    loop {
+        pipe.connect().await?;
+        let connected = core::mem::replace(&mut pipe, ServerOptions::new().create(&path)?);
+        let keyring = keyring.clone();
+        let handle = handle.clone();
+        task::spawn(async move {
+            if let Err(err) = answer(connected, keyring, handle).await {
+                warn!("daemon control connection failed: {}", err)?;
+            }
+            Ok::<_, io::Error>(())
+        });
+    }
+}
+
+/// Activated Sockets
+#[cfg(unix)]
+#[cfg_attr(doc_cfg, doc(cfg(unix)))]
+#[derive(Debug, Default)]
+pub struct ActivatedSockets {
+    /// API Listeners with their Addresses
+    pub listeners: Vec<(SocketAddr, TcpListener)>,
+
+    /// Control Socket
+    pub control: Option<StdUnixListener>,
+}
+
+/// Takes the sockets passed to the daemon with the systemd socket activation protocol, returning
+/// `None` if there are none.
+///
+/// The socket named [`CONTROL_SOCKET_NAME`] in `LISTEN_FDNAMES` is the control socket and every
+/// other socket must be a listening TCP socket for the API. The activation variables are
+/// removed from the environment, so that the sockets are only taken once.
+#[cfg(unix)]
+#[cfg_attr(doc_cfg, doc(cfg(unix)))]
+#[inline]
+pub fn activated_sockets() -> io::Result<Option<ActivatedSockets>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let count = env::var("LISTEN_FDS").ok();
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    if pid.and_then(|pid| pid.parse::<u32>().ok()) != Some(std::process::id()) {
+        return Ok(None);
+    }
+    let count = match count.and_then(|count| count.parse::<i32>().ok()) {
+        Some(count) if count > 0 => count,
+        _ => return Ok(None),
+    };
+    let mut names = names.split(':');
+    let mut sockets = ActivatedSockets::default();
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+        // SAFETY: The activation protocol passes the file descriptors starting at
+        //         `LISTEN_FDS_START` to this process, which takes ownership of them once.
+        if names.next() == Some(CONTROL_SOCKET_NAME) {
+            sockets.control = Some(unsafe { StdUnixListener::from_raw_fd(fd) });
+        } else {
+            let listener = unsafe { TcpListener::from_raw_fd(fd) };
+            let address = listener.local_addr().map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("activated socket {} is not a TCP socket: {}", fd, err),
+                )
+            })?;
+            sockets.listeners.push((address, listener));
+        }
+    }
+    Ok(Some(sockets))
+}
+
+/// Runs the signer service with `config` as a daemon, serving the control socket next to it.
+///
+/// On Unix, the daemon serves the sockets passed with systemd socket activation if it has any,
+/// and otherwise binds the sockets like the service does.
+///
+/// The account must already exist, since the daemon cannot show the recovery phrase of a new
+/// one.
+#[inline]
+pub async fn run(config: Config) -> service::Result<()> {
+    if !config.data_path.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "there is no account yet, create one with the signer app or `manta-signer serve`",
+        )
+        .into());
+    }
+    let keyring = Arc::<Keyring>::default();
+    let handle = Handle::default();
+    #[cfg(unix)]
+    {
+        let activated = activated_sockets()?.unwrap_or_default();
+        let listener = match activated.control {
+            Some(listener) => {
+                info!("serving the activated daemon control socket")?;
+                UnixListener::from(listener)
+            }
+            _ => {
+                let path = config.control_socket_path();
+                let listener = bind_control(&path).await?;
+                info!("serving the daemon control socket at {}", path.display())?;
+                listener
+            }
+        };
+        task::spawn(serve_control(listener, keyring.clone(), handle.clone()));
+        if !activated.listeners.is_empty() {
+            let transport = HttpTransport::new(activated.listeners);
+            service::check_bind_addresses(&config, &transport.addresses()).await?;
+            info!("serving the API at the activated sockets")?;
+            return service::start_with_transport(
+                config,
+                Daemon::new(keyring, handle.queue.clone()),
+                handle,
+                transport,
+            )
+            .await;
+        }
+    }
+    #[cfg(windows)]
+    {
+        let path = config.control_socket_path();
+        let pipe = bind_control(&path)?;
+        info!("serving the daemon control pipe at {}", path.display())?;
+        task::spawn(serve_control(pipe, path, keyring.clone(), handle.clone()));
+    }
+    service::start_with_handle(config, Daemon::new(keyring, handle.queue.clone()), handle).await
+}
+
+/// Sends `request` to the daemon listening at the control socket or pipe `path`, returning its response
+/// or the reason it refused the request.
+#[inline]
+pub fn control<P>(
+    path: P,
+    request: &ControlRequest,
+) -> io::Result<Result<ControlResponse, SignerError>>
+where
+    P: AsRef<Path>,
+{
+    #[cfg(unix)]
+    let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+    #[cfg(windows)]
+    let mut stream = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+    let mut bytes =
+        serde_json::to_vec(request).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    bytes.push(b'\n');
+    stream.write_all(&bytes)?;
+    let mut line = String::new();
+    io::BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
@@ -0,0 +1,254 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Dapp Pairing and Request Authentication
+//!
+//! A dapp pairs with the signer once, after the user authorizes it, and receives a shared
+//! secret. When [`require_dapp_auth`](crate::config::Config::require_dapp_auth) is set, every
+//! request which does not use an API key must then carry the [`DAPP_HEADER`] with the pairing
+//! identifier, the [`TIMESTAMP_HEADER`] with the current UNIX timestamp and the [`MAC_HEADER`]
+//! with the [`request_mac`] of the request, so that other local webpages cannot use the signer
+//! API. Every MAC is accepted once, see the [`ReplayCache`], so a dapp which repeats a request
+//! must send it with a later timestamp. The signer authenticates itself to the dapp with its
+//! signed responses, see the [`identity`](crate::identity) module.
+//!
+//! The shared secrets are stored encrypted next to the wallet file.
+
+use crate::secret::ConstantTimeEq;
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac};
+use manta_crypto::rand::{CryptoRng, RngCore};
+use manta_util::serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
+
+/// Request Header holding the Pairing Identifier
+pub const DAPP_HEADER: &str = "X-Signer-Dapp";
+
+/// Request Header holding the Request UNIX Timestamp
+pub const TIMESTAMP_HEADER: &str = "X-Signer-Timestamp";
+
+/// Request Header holding the Hex-Encoded Request MAC
+pub const MAC_HEADER: &str = "X-Signer-Mac";
+
+/// Maximum Difference in Seconds between the Request Timestamp and the Signer Clock
+pub const MAX_CLOCK_SKEW: i64 = 60;
+
+/// Shared Secret Length in Bytes
+pub const SECRET_LENGTH: usize = 32;
+
+/// Pairing Identifier Length in Bytes
+pub const ID_LENGTH: usize = 8;
+
+/// Maximum Number of Requests Remembered by the [`ReplayCache`]
+pub const MAX_REMEMBERED_REQUESTS: usize = 16384;
+
+/// Returns the hex-encoded HMAC-SHA256 of a request with the given `method`, `path`, `timestamp`
+/// and `body` under the shared `secret`.
+///
+/// The MAC covers the method, the path, and the timestamp, each followed by a line break, and
+/// then the body.
+#[inline]
+pub fn request_mac(secret: &[u8], method: &str, path: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take keys of any size.");
+    mac.update(format!("{}\n{}\n{}\n", method, path, timestamp).as_bytes());
+    mac.update(body);
+    HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+/// Dapp Pairing Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DappPairRequest {
+    /// Dapp Name
+    pub name: String,
+}
+
+/// Dapp Pairing
+///
+/// This is returned to the dapp once, when it pairs.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DappPairing {
+    /// Pairing Identifier
+    pub id: String,
+
+    /// Hex-Encoded Shared Secret
+    pub secret: String,
+}
+
+/// Paired Dapp Information
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DappInfo {
+    /// Pairing Identifier
+    pub id: String,
+
+    /// Dapp Name
+    pub name: String,
+
+    /// Dapp Origin
+    pub origin: Option<String>,
+
+    /// Pairing Time as a UNIX Timestamp
+    pub paired_at: i64,
+}
+
+/// Paired Dapp
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+struct PairedDapp {
+    /// Public Information
+    info: DappInfo,
+
+    /// Shared Secret
+    secret: Vec<u8>,
+}
+
+/// Paired Dapp Store
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DappStore {
+    /// Paired Dapps
+    dapps: Vec<PairedDapp>,
+}
+
+impl DappStore {
+    /// Pairs the dapp called `name` from `origin` with a fresh shared secret sampled from `rng`.
+    #[inline]
+    pub fn pair<R>(&mut self, name: String, origin: Option<String>, rng: &mut R) -> DappPairing
+    where
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        let mut id = [0; ID_LENGTH];
+        rng.fill_bytes(&mut id);
+        let mut secret = vec![0; SECRET_LENGTH];
+        rng.fill_bytes(&mut secret);
+        let pairing = DappPairing {
+            id: HEXLOWER.encode(&id),
+            secret: HEXLOWER.encode(&secret),
+        };
+        self.dapps.push(PairedDapp {
+            info: DappInfo {
+                id: pairing.id.clone(),
+                name,
+                origin,
+                paired_at: chrono::offset::Utc::now().timestamp(),
+            },
+            secret,
+        });
+        pairing
+    }
+
+    /// Unpairs the dapp with the given `id`, returning `false` if it is not paired.
+    #[inline]
+    pub fn unpair(&mut self, id: &str) -> bool {
+        let count = self.dapps.len();
+        self.dapps.retain(|dapp| dapp.info.id != id);
+        self.dapps.len() != count
+    }
+
+    /// Lists the paired dapps.
+    #[inline]
+    pub fn list(&self) -> Vec<DappInfo> {
+        self.dapps.iter().map(|dapp| dapp.info.clone()).collect()
+    }
+
+    /// Checks the `mac` of a request from the dapp with the given `id`, returning `true` if it is
+    /// paired, the `timestamp` is within [`MAX_CLOCK_SKEW`] of the current time, and the MAC
+    /// matches.
+    #[inline]
+    pub fn authenticate(
+        &self,
+        id: &str,
+        method: &str,
+        path: &str,
+        timestamp: i64,
+        body: &[u8],
+        mac: &str,
+    ) -> bool {
+        if (chrono::offset::Utc::now().timestamp() - timestamp).abs() > MAX_CLOCK_SKEW {
+            return false;
+        }
+        match self.dapps.iter().find(|dapp| dapp.info.id == id) {
+            Some(dapp) => {
+                let expected = request_mac(&dapp.secret, method, path, timestamp, body);
+                bool::from(
+                    expected
+                        .as_bytes()
+                        .ct_eq(mac.to_ascii_lowercase().as_bytes()),
+                )
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Replay Cache
+///
+/// A captured request would authenticate again until its timestamp leaves the
+/// [`MAX_CLOCK_SKEW`] window, so the cache remembers the pairing identifier, timestamp and MAC of
+/// every authenticated request for that long and rejects the requests it has seen before. Once it
+/// holds [`MAX_REMEMBERED_REQUESTS`] requests which are still in the window, new requests are
+/// rejected until older ones expire.
+#[derive(Debug, Default)]
+pub struct ReplayCache {
+    /// Remembered Requests
+    seen: HashSet<(String, i64, String)>,
+}
+
+impl ReplayCache {
+    /// Remembers the request from the dapp with the given `id` authenticated by `mac` at
+    /// `timestamp`, returning `false` if it was seen before or the cache is full.
+    #[inline]
+    pub fn insert(&mut self, id: &str, timestamp: i64, mac: &str) -> bool {
+        let request = (id.to_owned(), timestamp, mac.to_ascii_lowercase());
+        if self.seen.contains(&request) {
+            return false;
+        }
+        if self.seen.len() >= MAX_REMEMBERED_REQUESTS {
+            let now = chrono::offset::Utc::now().timestamp();
+            self.seen
+                .retain(|(_, timestamp, _)| (now - timestamp).abs() <= MAX_CLOCK_SKEW);
+            if self.seen.len() >= MAX_REMEMBERED_REQUESTS {
+                return false;
+            }
+        }
+        self.seen.insert(request)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Checks that every request MAC is only accepted once, whatever the case of its hex digits.
+    #[test]
+    fn replayed_requests_are_rejected() {
+        let mut cache = ReplayCache::default();
+        let timestamp = chrono::offset::Utc::now().timestamp();
+        let mac = request_mac(b"secret", "POST", "/sign", timestamp, b"{}");
+        assert!(cache.insert("dapp", timestamp, &mac));
+        assert!(!cache.insert("dapp", timestamp, &mac));
+        assert!(!cache.insert("dapp", timestamp, &mac.to_ascii_uppercase()));
+        assert!(cache.insert("other", timestamp, &mac));
+        assert!(cache.insert(
+            "dapp",
+            timestamp,
+            &request_mac(b"secret", "POST", "/sync", timestamp, b"{}")
+        ));
+    }
+}
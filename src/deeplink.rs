@@ -0,0 +1,320 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Deep Links
+//!
+//! Browsers and other apps reach the signer without its HTTP port by opening a link with the
+//! [`SCHEME`] scheme:
+//!
+//! - `manta-signer://pair?name=<name>&code=<code>&callback=<url>` pairs the dapp called `name`,
+//!   see the [`dapp`](crate::dapp) module,
+//! - `manta-signer://sign?payload=<payload>&callback=<url>` signs the sign request in
+//!   `payload`, which is base64url-encoded JSON.
+//!
+//! Both links go through the same authorization prompts as the signer API. Once the user
+//! answered, the signer opens the `callback` URL with the base64url-encoded JSON response in the
+//! `response` parameter of its fragment, or the error report in the `error` parameter. The
+//! optional `code` of a pairing link is passed back in the `code` parameter, so that the dapp
+//! can match the response with its link. Since the fragment is not sent to servers, the response
+//! only reaches the page which handles the callback.
+//!
+//! On Linux and Windows, the signer app [`register`]s itself as the handler of the links, and
+//! the operating system starts it with the link as its argument. The link is [`forward`]ed to the
+//! running signer through the inbox in the data directory, which the service checks every
+//! [`INBOX_INTERVAL`]. Links which wait in the inbox for longer than [`MAX_LINK_AGE`], for
+//! example because no signer was running, are discarded.
+
+use core::time::Duration;
+use data_encoding::BASE64URL_NOPAD;
+use http_types::{url::form_urlencoded, Url};
+use manta_util::serde::{Deserialize, Serialize};
+use std::{io, path::Path, process::Command};
+
+/// Deep Link URI Scheme
+pub const SCHEME: &str = "manta-signer";
+
+/// Interval between the Checks of the Deep Link Inbox
+pub const INBOX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum Time a Link Waits in the Inbox
+pub const MAX_LINK_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// File Extension of the Links in the Inbox
+const LINK_EXTENSION: &str = "link";
+
+/// Name of the Desktop Entry which Handles the Deep Links on Linux
+#[cfg(target_os = "linux")]
+const DESKTOP_ENTRY: &str = "manta-signer-handler.desktop";
+
+/// Deep Link Error
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum Error {
+    /// Invalid Link
+    ///
+    /// The link is not a URL with the [`SCHEME`] scheme.
+    InvalidLink,
+
+    /// Unknown Action
+    UnknownAction,
+
+    /// Missing Parameter
+    MissingParameter,
+
+    /// Invalid Payload
+    ///
+    /// The payload is not base64url-encoded.
+    InvalidPayload,
+
+    /// Invalid Callback
+    ///
+    /// The callback is not an HTTP or HTTPS URL.
+    InvalidCallback,
+}
+
+/// Deep Link
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DeepLink {
+    /// Dapp Pairing Link
+    Pair {
+        /// Dapp Name
+        name: String,
+
+        /// Pairing Code
+        code: Option<String>,
+
+        /// Callback URL
+        callback: Url,
+    },
+
+    /// Transaction Signing Link
+    Sign {
+        /// JSON-Serialized Sign Request
+        payload: Vec<u8>,
+
+        /// Callback URL
+        callback: Url,
+    },
+}
+
+impl DeepLink {
+    /// Parses a deep link from `link`.
+    #[inline]
+    pub fn parse(link: &str) -> Result<Self, Error> {
+        let url = Url::parse(link.trim()).map_err(|_| Error::InvalidLink)?;
+        if url.scheme() != SCHEME {
+            return Err(Error::InvalidLink);
+        }
+        let parameter = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        let callback = parameter("callback").ok_or(Error::MissingParameter)?;
+        let callback = Url::parse(&callback).map_err(|_| Error::InvalidCallback)?;
+        if !matches!(callback.scheme(), "http" | "https") {
+            return Err(Error::InvalidCallback);
+        }
+        match url.host_str() {
+            Some("pair") => Ok(Self::Pair {
+                name: parameter("name").ok_or(Error::MissingParameter)?,
+                code: parameter("code"),
+                callback,
+            }),
+            Some("sign") => Ok(Self::Sign {
+                payload: BASE64URL_NOPAD
+                    .decode(
+                        parameter("payload")
+                            .ok_or(Error::MissingParameter)?
+                            .trim_end_matches('=')
+                            .as_bytes(),
+                    )
+                    .map_err(|_| Error::InvalidPayload)?,
+                callback,
+            }),
+            _ => Err(Error::UnknownAction),
+        }
+    }
+
+    /// Returns the callback URL of `self`.
+    #[inline]
+    pub fn callback(&self) -> &Url {
+        match self {
+            Self::Pair { callback, .. } | Self::Sign { callback, .. } => callback,
+        }
+    }
+}
+
+/// Returns the base64url-encoded JSON serialization of `value`, as it is passed to the callback
+/// URL.
+#[inline]
+pub fn encode<T>(value: &T) -> serde_json::Result<String>
+where
+    T: Serialize,
+{
+    Ok(BASE64URL_NOPAD.encode(&serde_json::to_vec(value)?))
+}
+
+/// Returns `callback` with its fragment set to the form-encoded `parameters`.
+#[inline]
+pub fn callback_url(callback: &Url, parameters: &[(&str, &str)]) -> Url {
+    let mut url = callback.clone();
+    let fragment = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(parameters)
+        .finish();
+    url.set_fragment(Some(&fragment));
+    url
+}
+
+/// Opens `url` with the default browser.
+#[inline]
+pub fn open(url: &Url) -> io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+    command.arg(url.as_str()).spawn().map(drop)
+}
+
+/// Forwards `link` to the running signer through the `inbox` directory.
+#[inline]
+pub fn forward(inbox: &Path, link: &str) -> io::Result<()> {
+    std::fs::create_dir_all(inbox)?;
+    let name = format!(
+        "{}-{}.{}",
+        chrono::offset::Utc::now().timestamp_nanos(),
+        std::process::id(),
+        LINK_EXTENSION
+    );
+    crate::storage::write_atomic(&inbox.join(name), link.as_bytes(), 0)
+}
+
+/// Removes the links which were forwarded to the `inbox` directory, returning the ones younger
+/// than [`MAX_LINK_AGE`] in the order they were forwarded in.
+#[inline]
+pub fn take(inbox: &Path) -> io::Result<Vec<String>> {
+    let entries = match std::fs::read_dir(inbox) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension().map_or(false, |ext| ext == LINK_EXTENSION));
+    paths.sort();
+    let oldest = chrono::offset::Utc::now().timestamp_nanos() - MAX_LINK_AGE.as_nanos() as i64;
+    let mut links = Vec::new();
+    for path in paths {
+        let forwarded_at = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.split('-').next())
+            .and_then(|timestamp| timestamp.parse::<i64>().ok())
+            .unwrap_or_default();
+        let link = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        if forwarded_at >= oldest {
+            links.push(link);
+        }
+    }
+    Ok(links)
+}
+
+/// Returns the path of the signer executable which the operating system starts with the links.
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+#[inline]
+fn executable() -> io::Result<std::path::PathBuf> {
+    match std::env::var_os("APPIMAGE") {
+        Some(path) => Ok(path.into()),
+        _ => std::env::current_exe(),
+    }
+}
+
+/// Runs `command`, failing if it exits unsuccessfully.
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+#[inline]
+fn run(command: &mut Command) -> io::Result<()> {
+    if command.status()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "unable to register the deep link handler",
+        ))
+    }
+}
+
+/// Registers the running signer executable as the handler of the [`SCHEME`] links for the
+/// current user.
+#[cfg(target_os = "linux")]
+#[inline]
+pub fn register() -> io::Result<()> {
+    let applications = dirs_next::data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?
+        .join("applications");
+    std::fs::create_dir_all(&applications)?;
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=Manta Signer\nExec=\"{}\" %u\nTerminal=false\n\
+         NoDisplay=true\nMimeType=x-scheme-handler/{};\n",
+        executable()?.display(),
+        SCHEME
+    );
+    crate::storage::write_atomic(&applications.join(DESKTOP_ENTRY), entry.as_bytes(), 0)?;
+    run(Command::new("xdg-mime").args([
+        "default",
+        DESKTOP_ENTRY,
+        &format!("x-scheme-handler/{}", SCHEME),
+    ]))
+}
+
+/// Registers the running signer executable as the handler of the [`SCHEME`] links for the
+/// current user.
+#[cfg(target_os = "windows")]
+#[inline]
+pub fn register() -> io::Result<()> {
+    let key = format!("HKCU\\Software\\Classes\\{}", SCHEME);
+    let command = format!("\"{}\" \"%1\"", executable()?.display());
+    run(Command::new("reg").args(["add", &key, "/ve", "/d", "URL:Manta Signer", "/f"]))?;
+    run(Command::new("reg").args(["add", &key, "/v", "URL Protocol", "/d", "", "/f"]))?;
+    run(Command::new("reg").args([
+        "add",
+        &format!("{}\\shell\\open\\command", key),
+        "/ve",
+        "/d",
+        &command,
+        "/f",
+    ]))
+}
+
+/// Registers the running signer executable as the handler of the [`SCHEME`] links for the
+/// current user.
+///
+/// Deep links are only supported on Linux and Windows.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+#[inline]
+pub fn register() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "deep links are not supported on this platform",
+    ))
+}
@@ -0,0 +1,82 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Address Derivation Reports
+//!
+//! A derivation report lists every receiving key the wallet has derived so far together with the
+//! derivation algorithm that produced them. Reports only contain public materials and are signed
+//! with the signer [`Identity`](crate::identity::Identity) so that they can be used to verify
+//! recoveries and to test interoperability with other wallets.
+
+use manta_util::serde::{Deserialize, Serialize};
+
+/// Derivation Algorithm Identifier
+///
+/// This names the key derivation used by the signer: a BIP-39 mnemonic expanded into the
+/// `manta-pay` testnet key secret and derived with its hierarchical key derivation function.
+pub const DERIVATION_ALGORITHM: &str = "manta-pay/testnet/bip39-hkdf/v1";
+
+/// Derived Address
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DerivedAddress {
+    /// Derivation Index
+    pub index: usize,
+
+    /// Base58-Encoded Receiving Key
+    pub receiving_key: String,
+}
+
+/// Derivation Report
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DerivationReport {
+    /// Signer Version
+    pub signer_version: String,
+
+    /// Derivation Algorithm Identifier
+    pub derivation_algorithm: String,
+
+    /// Creation Time as a UNIX Timestamp
+    pub created_at: i64,
+
+    /// Derived Addresses
+    pub addresses: Vec<DerivedAddress>,
+}
+
+impl DerivationReport {
+    /// Builds a new [`DerivationReport`] for the base58-encoded `receiving_keys`, listed in
+    /// derivation order.
+    #[inline]
+    pub fn new<I>(receiving_keys: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        Self {
+            signer_version: crate::VERSION.into(),
+            derivation_algorithm: DERIVATION_ALGORITHM.into(),
+            created_at: chrono::offset::Utc::now().timestamp(),
+            addresses: receiving_keys
+                .into_iter()
+                .enumerate()
+                .map(|(index, receiving_key)| DerivedAddress {
+                    index,
+                    receiving_key,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Diagnostics Bundle
+//!
+//! The diagnostics bundle is a zip archive which users can attach to bug reports. It only
+//! contains public information: redacted logs and configuration, parameter hashes, and
+//! information about the host. The [`StateDigest`] complements it with the ledger coordinates of
+//! the wallet state.
+
+use crate::{
+    config::Config,
+    log,
+    parameters::{self, bundle::Bundles},
+    storage::{cipher, Manifest},
+    sync::SyncStatus,
+};
+use data_encoding::HEXLOWER;
+use manta_util::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+use zip::{write::FileOptions, ZipWriter};
+
+/// Host Information
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct HostInfo {
+    /// Operating System
+    pub os: String,
+
+    /// Operating System Family
+    pub family: String,
+
+    /// CPU Architecture
+    pub arch: String,
+
+    /// Number of Available CPUs
+    pub cpus: Option<usize>,
+}
+
+impl HostInfo {
+    /// Collects the information about the current host.
+    #[inline]
+    pub fn current() -> Self {
+        Self {
+            os: std::env::consts::OS.into(),
+            family: std::env::consts::FAMILY.into(),
+            arch: std::env::consts::ARCH.into(),
+            cpus: std::thread::available_parallelism()
+                .ok()
+                .map(|cpus| cpus.get()),
+        }
+    }
+}
+
+/// Diagnostics Report
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Report {
+    /// Signer Version
+    pub version: String,
+
+    /// Host Information
+    pub host: HostInfo,
+
+    /// Signer Configuration
+    ///
+    /// This is the [`redacted`](Config::redacted) configuration of the signer.
+    pub config: Config,
+
+    /// Storage Manifest
+    pub manifest: Option<Manifest>,
+
+    /// Wallet File Integrity Check Result
+    pub integrity: String,
+
+    /// Active Parameter Bundle
+    pub parameter_bundle: Option<String>,
+
+    /// Hex-Encoded SHA-256 Hashes of the Proving Keys
+    pub parameter_hashes: BTreeMap<String, String>,
+
+    /// Recent Warnings and Errors
+    pub recent_errors: Vec<String>,
+}
+
+/// Returns the active parameter bundle of `config` and the hex-encoded SHA-256 hashes of its
+/// proving keys.
+#[inline]
+fn parameter_info(config: &Config) -> (Option<String>, BTreeMap<String, String>) {
+    let bundles = Bundles::new(config.data_directory());
+    let parameter_bundle = bundles.current().ok().flatten();
+    let proving_directory = match bundles.current_directory() {
+        Ok(Some(directory)) => directory,
+        _ => parameters::sdk_proving_directory(config.data_directory()),
+    };
+    let parameter_hashes = parameters::bundle::PROVING_FILES
+        .iter()
+        .map(|name| {
+            let hash = fs::read(proving_directory.join(name))
+                .map(|bytes| HEXLOWER.encode(&Sha256::digest(&bytes)))
+                .unwrap_or_else(|err| format!("unavailable: {}", err));
+            (name.to_string(), hash)
+        })
+        .collect();
+    (parameter_bundle, parameter_hashes)
+}
+
+/// Loads the storage manifest of `config` if it exists.
+#[inline]
+fn manifest(config: &Config) -> Option<Manifest> {
+    fs::read(config.manifest_path())
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+}
+
+impl Report {
+    /// Collects the diagnostics report for `config`.
+    #[inline]
+    pub fn collect(config: &Config) -> Self {
+        let (parameter_bundle, parameter_hashes) = parameter_info(config);
+        let integrity = match fs::read(&config.data_path) {
+            Ok(bytes) if cipher::has_header(&bytes) => match cipher::verify_integrity(&bytes) {
+                Ok(()) => "ok".into(),
+                Err(err) => format!("{:?}", err),
+            },
+            Ok(bytes) if bytes.is_empty() => "empty".into(),
+            Ok(_) => "legacy format".into(),
+            Err(err) => format!("unavailable: {}", err),
+        };
+        Self {
+            version: crate::VERSION.into(),
+            host: HostInfo::current(),
+            config: config.redacted(),
+            manifest: manifest(config),
+            integrity,
+            parameter_bundle,
+            parameter_hashes,
+            recent_errors: log::history()
+                .into_iter()
+                .filter(|line| line.starts_with("WARN") || line.starts_with("ERROR"))
+                .collect(),
+        }
+    }
+}
+
+/// State Digest
+///
+/// The digest describes the ledger coordinates and versions of the wallet state without any
+/// amounts, keys, or note positions, so that users can share it and maintainers can reproduce
+/// synchronization bugs against the same ledger state.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct StateDigest {
+    /// Signer Version
+    pub version: String,
+
+    /// Storage Manifest
+    pub manifest: Option<Manifest>,
+
+    /// Active Parameter Bundle
+    pub parameter_bundle: Option<String>,
+
+    /// Hex-Encoded SHA-256 Hashes of the Proving Keys
+    pub parameter_hashes: BTreeMap<String, String>,
+
+    /// Ledger Synchronization Status
+    pub sync: SyncStatus,
+
+    /// Number of Derived Receiving Keys
+    pub receiving_keys: usize,
+}
+
+impl StateDigest {
+    /// Collects the state digest for `config` with the current `sync` status and the number of
+    /// derived `receiving_keys`.
+    #[inline]
+    pub fn collect(config: &Config, sync: SyncStatus, receiving_keys: usize) -> Self {
+        let (parameter_bundle, parameter_hashes) = parameter_info(config);
+        Self {
+            version: crate::VERSION.into(),
+            manifest: manifest(config),
+            parameter_bundle,
+            parameter_hashes,
+            sync,
+            receiving_keys,
+        }
+    }
+}
+
+/// Converts `err` into an [`io::Error`].
+#[inline]
+fn into_io_error<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+/// Writes the diagnostics bundle for `config` as a zip archive to `path`.
+#[inline]
+pub fn write_bundle<P>(config: &Config, path: P) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let report = Report::collect(config);
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default();
+    zip.start_file("report.json", options)
+        .map_err(into_io_error)?;
+    zip.write_all(&serde_json::to_vec_pretty(&report).map_err(into_io_error)?)?;
+    zip.start_file("signer.log", options)
+        .map_err(into_io_error)?;
+    for line in log::history() {
+        writeln!(zip, "{}", line)?;
+    }
+    zip.finish().map_err(into_io_error)?;
+    Ok(())
+}
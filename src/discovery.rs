@@ -0,0 +1,188 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Service Discovery
+//!
+//! When the configured port is taken, the service falls back to one of the next
+//! [`MAX_PORT_FALLBACKS`] ports. Once it listens, it writes a [`Discovery`] file to the data
+//! directory so that dapps and the UI can find the port it chose. The file is
+//! [`Signed`] with the signer [`Identity`] and also holds a random nonce which only the local user
+//! can read: clients confirm that they reached the signer which wrote the file, and not another
+//! process which took its port, by sending the nonce to the `/discovery/verify` endpoint.
+
+use crate::{
+    identity::{Identity, Signed},
+    secret::ConstantTimeEq,
+};
+use core::time::Duration;
+use data_encoding::HEXLOWER;
+use manta_crypto::rand::{OsRng, RngCore};
+use manta_util::serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+};
+
+/// Maximum Number of Ports Tried after the Configured Port
+pub const MAX_PORT_FALLBACKS: u16 = 16;
+
+/// Nonce Length in Bytes
+pub const NONCE_LENGTH: usize = 16;
+
+/// Timeout of the Nonce Verification in [`is_running`]
+pub const VERIFY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Discovery File
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Discovery {
+    /// Service Port
+    pub port: u16,
+
+    /// Socket Addresses the Service Listens at
+    pub addresses: Vec<String>,
+
+    /// Hex-Encoded Authentication Nonce
+    pub nonce: String,
+
+    /// Service Process Identifier
+    pub pid: u32,
+}
+
+impl Discovery {
+    /// Builds a new discovery file with a fresh nonce for the service listening at `addresses`.
+    #[inline]
+    pub fn new(addresses: &[SocketAddr]) -> Self {
+        let mut nonce = [0; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        Self {
+            port: addresses.first().map(SocketAddr::port).unwrap_or_default(),
+            addresses: addresses.iter().map(ToString::to_string).collect(),
+            nonce: HEXLOWER.encode(&nonce),
+            pid: std::process::id(),
+        }
+    }
+
+    /// Loads the discovery file from `path`, returning `None` if it does not exist.
+    ///
+    /// The signature is not checked, see [`Signed::verify`].
+    #[inline]
+    pub fn load<P>(path: P) -> io::Result<Option<Signed<Self>>>
+    where
+        P: AsRef<Path>,
+    {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Signs the discovery file with `identity` and saves it to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, identity: &Identity, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let signed =
+            Signed::new(identity, self).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        crate::storage::write_atomic(
+            path.as_ref(),
+            &serde_json::to_vec_pretty(&signed)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )
+    }
+
+    /// Returns the first address of the service, which clients use to reach it.
+    #[inline]
+    pub fn client_address(&self) -> Option<&str> {
+        self.addresses.first().map(String::as_str)
+    }
+
+    /// Returns `true` if `nonce` matches the nonce of `self`.
+    #[inline]
+    pub fn verify(&self, nonce: &str) -> bool {
+        bool::from(self.nonce.as_bytes().ct_eq(nonce.as_bytes()))
+    }
+}
+
+/// Discovery Nonce Verification Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct VerifyRequest {
+    /// Hex-Encoded Authentication Nonce
+    pub nonce: String,
+}
+
+/// Returns `true` if the signer which wrote the discovery file at `path` is still serving, that
+/// is, if the file has a valid signature and the service at its address accepts its nonce.
+#[inline]
+pub fn is_running<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    let discovery = match Discovery::load(path) {
+        Ok(Some(discovery)) if discovery.verify() => discovery.payload,
+        _ => return false,
+    };
+    let address = match discovery.client_address() {
+        Some(address) => address,
+        _ => return false,
+    };
+    let request = VerifyRequest {
+        nonce: discovery.nonce.clone(),
+    };
+    let body = match serde_json::to_value(&request) {
+        Ok(body) => body,
+        _ => return false,
+    };
+    ureq::post(&format!("http://{}/discovery/verify", address))
+        .timeout(VERIFY_TIMEOUT)
+        .send_json(body)
+        .ok()
+        .and_then(|response| response.into_json::<bool>().ok())
+        .unwrap_or(false)
+}
+
+/// Binds listeners to `addresses`, moving every address to the next port while any of the ports
+/// is taken, for at most [`MAX_PORT_FALLBACKS`] ports. Returns the listeners with the addresses
+/// they are bound to.
+#[inline]
+pub fn bind(addresses: &[SocketAddr]) -> io::Result<Vec<(SocketAddr, TcpListener)>> {
+    let mut offset = 0;
+    loop {
+        let result = addresses
+            .iter()
+            .map(|address| {
+                let mut address = *address;
+                address.set_port(address.port().checked_add(offset).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::AddrNotAvailable, "no free port left")
+                })?);
+                TcpListener::bind(address).map(|listener| (address, listener))
+            })
+            .collect::<io::Result<Vec<_>>>();
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::AddrInUse && offset < MAX_PORT_FALLBACKS => {
+                offset += 1
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Duress Password
+//!
+//! Next to the real wallet, the signer can keep a decoy wallet with its own recovery phrase in
+//! an alternate encrypted slot which is unlocked by a second, duress, password. Logging in with
+//! the duress password opens the decoy wallet as if it were the only one, with its own identity,
+//! wallets, pairings, and audit log, while the real wallet and its recovery phrase stay
+//! encrypted under the real password. The decoy wallet starts empty, so users who want it to
+//! look used should fund it with a small balance.
+//!
+//! The decoy slot lives in its own directory inside the data directory, so someone who can read
+//! the data directory can tell that a decoy slot exists, but not unlock either slot without the
+//! matching password.
+
+use manta_util::serde::{Deserialize, Serialize};
+
+/// Duress Password Setup Request
+#[derive(Clone, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DuressRequest {
+    /// Duress Password
+    pub password: String,
+}
+
+impl core::fmt::Debug for DuressRequest {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("DuressRequest").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,451 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Signer Errors
+//!
+//! Every failure which reaches a client of the signer, through the service API, the UI, or the
+//! daemon control socket, is described by a [`SignerError`]. Unlike the error types of the
+//! individual modules, it can be serialized, so clients learn why a request failed instead of
+//! only learning that it did.
+//!
+//! # Error Codes
+//!
+//! Every [`SignerError`] is serialized as an [`ErrorReport`] with a stable numeric
+//! [`code`](SignerError::code), its [`ErrorCategory`], and whether the request can be retried, so
+//! that clients can handle failures without matching on the English messages. The thousands of
+//! the code are the category, and codes are never reused for another error:
+//!
+//! | Code | Error | Category | Retriable |
+//! |------|-------|----------|-----------|
+//! | 1001 | [`Io`](SignerError::Io) | [`Io`](ErrorCategory::Io) | yes |
+//! | 2001 | [`Crypto`](SignerError::Crypto) | [`Crypto`](ErrorCategory::Crypto) | no |
+//! | 3001 | [`Decode`](SignerError::Decode) | [`Request`](ErrorCategory::Request) | no |
+//! | 3002 | [`NotFound`](SignerError::NotFound) | [`Request`](ErrorCategory::Request) | no |
+//! | 4001 | [`Unauthorized`](SignerError::Unauthorized) | [`Authorization`](ErrorCategory::Authorization) | no |
+//! | 4002 | [`Rejected`](SignerError::Rejected) | [`Authorization`](ErrorCategory::Authorization) | no |
+//! | 4003 | [`Timeout`](SignerError::Timeout) | [`Authorization`](ErrorCategory::Authorization) | yes |
+//! | 5001 | [`Busy`](SignerError::Busy) | [`Availability`](ErrorCategory::Availability) | yes |
+//! | 5002 | [`Locked`](SignerError::Locked) | [`Availability`](ErrorCategory::Availability) | yes |
+//! | 6001 | [`Corrupted`](SignerError::Corrupted) | [`Storage`](ErrorCategory::Storage) | no |
+//! | 7001 | [`Config`](SignerError::Config) | [`Configuration`](ErrorCategory::Configuration) | no |
+//! | 7002 | [`Unsupported`](SignerError::Unsupported) | [`Configuration`](ErrorCategory::Configuration) | no |
+//! | 9001 | [`Internal`](SignerError::Internal) | [`Internal`](ErrorCategory::Internal) | no |
+
+use crate::{
+    label, service,
+    storage::{cipher, keywrap},
+};
+use core::fmt;
+use manta_util::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io;
+use tokio::task::JoinError;
+
+/// Error Category
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum ErrorCategory {
+    /// File System and Network Errors
+    Io,
+
+    /// Encryption, Key Wrapping and Secret Errors
+    Crypto,
+
+    /// Malformed or Unknown Requests
+    Request,
+
+    /// Requests which the Caller or the User did not Authorize
+    Authorization,
+
+    /// Requests which the Signer cannot take now
+    Availability,
+
+    /// Storage Integrity Errors
+    Storage,
+
+    /// Configuration and Platform Errors
+    Configuration,
+
+    /// Internal Errors
+    Internal,
+}
+
+/// Signer Error
+///
+/// This is serialized as an [`ErrorReport`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SignerError {
+    /// I/O Error
+    Io(String),
+
+    /// Cryptographic Error
+    ///
+    /// Encrypting or decrypting the storage, wrapping the device secret, or loading another
+    /// secret failed.
+    Crypto(String),
+
+    /// Decoding Error
+    ///
+    /// A request or a stored file is malformed.
+    Decode(String),
+
+    /// Missing Item Error
+    NotFound(String),
+
+    /// Unauthorized Error
+    ///
+    /// The request was not authorized, either because the caller is not allowed to send it or
+    /// because the user did not authorize it.
+    Unauthorized,
+
+    /// Rejected Error
+    ///
+    /// The user explicitly rejected the request.
+    Rejected,
+
+    /// Timeout Error
+    ///
+    /// The authorization prompt was left unanswered until it expired.
+    Timeout,
+
+    /// Busy Error
+    ///
+    /// The signer cannot take the request now, but may take it later.
+    Busy(String),
+
+    /// Locked Error
+    ///
+    /// The request needs the signer to be unlocked.
+    Locked,
+
+    /// Corrupted Storage Error
+    Corrupted,
+
+    /// Configuration Error
+    ///
+    /// The signer configuration does not allow the request or the service to start.
+    Config(String),
+
+    /// Unsupported Error
+    ///
+    /// The request is not supported on this platform.
+    Unsupported(String),
+
+    /// Internal Error
+    Internal(String),
+}
+
+impl SignerError {
+    /// Builds an [`Internal`](Self::Internal) error from `err`.
+    #[inline]
+    pub fn internal<E>(err: E) -> Self
+    where
+        E: fmt::Display,
+    {
+        Self::Internal(err.to_string())
+    }
+
+    /// Returns the stable error code of `self`, see the [module documentation](self).
+    #[inline]
+    pub const fn code(&self) -> u16 {
+        match self {
+            Self::Io(_) => 1001,
+            Self::Crypto(_) => 2001,
+            Self::Decode(_) => 3001,
+            Self::NotFound(_) => 3002,
+            Self::Unauthorized => 4001,
+            Self::Rejected => 4002,
+            Self::Timeout => 4003,
+            Self::Busy(_) => 5001,
+            Self::Locked => 5002,
+            Self::Corrupted => 6001,
+            Self::Config(_) => 7001,
+            Self::Unsupported(_) => 7002,
+            Self::Internal(_) => 9001,
+        }
+    }
+
+    /// Returns the category of `self`.
+    #[inline]
+    pub const fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Io(_) => ErrorCategory::Io,
+            Self::Crypto(_) => ErrorCategory::Crypto,
+            Self::Decode(_) | Self::NotFound(_) => ErrorCategory::Request,
+            Self::Unauthorized | Self::Rejected | Self::Timeout => ErrorCategory::Authorization,
+            Self::Busy(_) | Self::Locked => ErrorCategory::Availability,
+            Self::Corrupted => ErrorCategory::Storage,
+            Self::Config(_) | Self::Unsupported(_) => ErrorCategory::Configuration,
+            Self::Internal(_) => ErrorCategory::Internal,
+        }
+    }
+
+    /// Returns `true` if the same request may succeed when it is sent again later.
+    #[inline]
+    pub const fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            Self::Io(_) | Self::Timeout | Self::Busy(_) | Self::Locked
+        )
+    }
+
+    /// Returns the variant name of `self`.
+    #[inline]
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "Io",
+            Self::Crypto(_) => "Crypto",
+            Self::Decode(_) => "Decode",
+            Self::NotFound(_) => "NotFound",
+            Self::Unauthorized => "Unauthorized",
+            Self::Rejected => "Rejected",
+            Self::Timeout => "Timeout",
+            Self::Busy(_) => "Busy",
+            Self::Locked => "Locked",
+            Self::Corrupted => "Corrupted",
+            Self::Config(_) => "Config",
+            Self::Unsupported(_) => "Unsupported",
+            Self::Internal(_) => "Internal",
+        }
+    }
+
+    /// Returns the detail message of `self`, if it has one.
+    #[inline]
+    fn detail(&self) -> Option<&str> {
+        match self {
+            Self::Io(detail)
+            | Self::Crypto(detail)
+            | Self::Decode(detail)
+            | Self::NotFound(detail)
+            | Self::Busy(detail)
+            | Self::Config(detail)
+            | Self::Unsupported(detail)
+            | Self::Internal(detail) => Some(detail),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`ErrorReport`] of `self`.
+    #[inline]
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            code: self.code(),
+            category: self.category(),
+            retriable: self.is_retriable(),
+            kind: self.kind().into(),
+            detail: self.detail().map(Into::into),
+            message: self.to_string(),
+        }
+    }
+
+    /// Rebuilds the error from its `report`, falling back to an [`Internal`](Self::Internal)
+    /// error with the report message for unknown codes.
+    #[inline]
+    pub fn from_report(report: ErrorReport) -> Self {
+        let detail = report.detail.unwrap_or_default();
+        match report.code {
+            1001 => Self::Io(detail),
+            2001 => Self::Crypto(detail),
+            3001 => Self::Decode(detail),
+            3002 => Self::NotFound(detail),
+            4001 => Self::Unauthorized,
+            4002 => Self::Rejected,
+            4003 => Self::Timeout,
+            5001 => Self::Busy(detail),
+            5002 => Self::Locked,
+            6001 => Self::Corrupted,
+            7001 => Self::Config(detail),
+            7002 => Self::Unsupported(detail),
+            _ => Self::Internal(report.message),
+        }
+    }
+}
+
+impl Serialize for SignerError {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.report().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SignerError {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ErrorReport::deserialize(deserializer).map(Self::from_report)
+    }
+}
+
+/// Error Report
+///
+/// This is the serialized form of a [`SignerError`] which clients receive in API error responses,
+/// UI command errors and daemon control responses.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ErrorReport {
+    /// Stable Error Code
+    pub code: u16,
+
+    /// Error Category
+    pub category: ErrorCategory,
+
+    /// Retriable Flag
+    ///
+    /// This is `true` if the same request may succeed when it is sent again later.
+    pub retriable: bool,
+
+    /// Error Variant Name
+    pub kind: String,
+
+    /// Detail Message
+    pub detail: Option<String>,
+
+    /// Human-Readable Message
+    ///
+    /// Clients should not match on this message, since it can change between versions.
+    pub message: String,
+}
+
+impl fmt::Display for SignerError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(message) => write!(f, "I/O error: {}", message),
+            Self::Crypto(message) => write!(f, "cryptographic error: {}", message),
+            Self::Decode(message) => write!(f, "malformed data: {}", message),
+            Self::NotFound(message) => write!(f, "not found: {}", message),
+            Self::Unauthorized => write!(f, "request was not authorized"),
+            Self::Rejected => write!(f, "request was rejected by the user"),
+            Self::Timeout => write!(f, "authorization prompt expired"),
+            Self::Busy(message) => write!(f, "signer is busy: {}", message),
+            Self::Locked => write!(f, "signer is locked"),
+            Self::Corrupted => write!(f, "storage is corrupted"),
+            Self::Config(message) => write!(f, "configuration error: {}", message),
+            Self::Unsupported(message) => write!(f, "unsupported: {}", message),
+            Self::Internal(message) => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SignerError {}
+
+impl From<io::Error> for SignerError {
+    #[inline]
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::InvalidData => Self::Decode(err.to_string()),
+            io::ErrorKind::NotFound => Self::NotFound(err.to_string()),
+            _ => Self::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SignerError {
+    #[inline]
+    fn from(err: serde_json::Error) -> Self {
+        Self::Decode(err.to_string())
+    }
+}
+
+impl From<JoinError> for SignerError {
+    #[inline]
+    fn from(err: JoinError) -> Self {
+        Self::internal(err)
+    }
+}
+
+impl From<cipher::Error> for SignerError {
+    #[inline]
+    fn from(err: cipher::Error) -> Self {
+        match err {
+            cipher::Error::Io(err) => err.into(),
+            cipher::Error::InvalidHeader
+            | cipher::Error::UnsupportedVersion(_)
+            | cipher::Error::Truncated
+            | cipher::Error::Serialization => Self::Decode(format!("{:?}", err)),
+            cipher::Error::ChecksumMismatch => Self::Corrupted,
+            err => Self::Crypto(format!("{:?}", err)),
+        }
+    }
+}
+
+impl From<keywrap::Error> for SignerError {
+    #[inline]
+    fn from(err: keywrap::Error) -> Self {
+        match err {
+            keywrap::Error::Io(err) => err.into(),
+            keywrap::Error::Unsupported => {
+                Self::Unsupported("there is no hardware key store on this platform".into())
+            }
+            err => Self::Crypto(format!("{:?}", err)),
+        }
+    }
+}
+
+impl From<label::Error> for SignerError {
+    #[inline]
+    fn from(err: label::Error) -> Self {
+        match err {
+            label::Error::Locked => Self::Locked,
+            label::Error::Cipher(err) => err.into(),
+            err => Self::Decode(err.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "benchmark")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "benchmark")))]
+impl From<crate::benchmark::Error> for SignerError {
+    #[inline]
+    fn from(err: crate::benchmark::Error) -> Self {
+        Self::internal(err)
+    }
+}
+
+impl From<service::Error> for SignerError {
+    #[inline]
+    fn from(err: service::Error) -> Self {
+        match err {
+            service::Error::AddrParseError(err) => Self::Config(err.to_string()),
+            service::Error::JoinError(err) => err.into(),
+            service::Error::ParameterLoadingError => {
+                Self::Io("unable to load the SDK parameters".into())
+            }
+            service::Error::TotpLoadingError => {
+                Self::Crypto("unable to load the TOTP secret".into())
+            }
+            service::Error::SaveError(err) => Self::Io(format!("{:?}", err)),
+            service::Error::Cipher(err) => err.into(),
+            service::Error::KeyWrap(err) => err.into(),
+            service::Error::Io(err) => err.into(),
+            service::Error::AuthorizationError => Self::Unauthorized,
+            service::Error::Expired => Self::Timeout,
+            service::Error::Rejected => Self::Rejected,
+            service::Error::StorageCorrupted => Self::Corrupted,
+            service::Error::RemoteAccessNotAllowed => Self::Config(
+                "remote access is not allowed, set `allow_remote` to serve remote clients".into(),
+            ),
+            service::Error::RemoteAccessWithoutApiKeys => {
+                Self::Config("remote clients need an API key to authenticate with".into())
+            }
+            service::Error::Locked => Self::Locked,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Signer Events
+//!
+//! The service publishes [`SignerEvent`]s to an [`EventBus`] when the signer locks or unlocks,
+//! when an authorization request completes, and when the ledger synchronization receives new
+//! notes, so that connected dapps can be notified instead of polling the signer API. Events
+//! which a subscriber does not receive before [`EVENT_CAPACITY`] newer events were published are
+//! dropped for it.
+//!
+//! Dapps receive the events from the `/events` endpoint of the service as server-sent events,
+//! named after the event type and holding the JSON-encoded event as their data.
+
+use crate::audit::Outcome;
+use manta_util::serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Number of Events Kept for Slow Subscribers
+pub const EVENT_CAPACITY: usize = 64;
+
+/// Signer Event
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    crate = "manta_util::serde",
+    content = "content",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum SignerEvent {
+    /// The Signer was Locked
+    Locked,
+
+    /// The Signer was Unlocked
+    Unlocked,
+
+    /// An Authorization Request Completed
+    RequestCompleted {
+        /// Request Kind
+        kind: String,
+
+        /// Request Origin
+        origin: Option<String>,
+
+        /// Authorization Outcome
+        outcome: Outcome,
+    },
+
+    /// The Ledger Synchronization Received New Notes
+    NotesReceived {
+        /// Number of Received Notes
+        count: usize,
+    },
+}
+
+impl SignerEvent {
+    /// Returns the name of the event type, which is also the `type` field of its JSON encoding.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Locked => "Locked",
+            Self::Unlocked => "Unlocked",
+            Self::RequestCompleted { .. } => "RequestCompleted",
+            Self::NotesReceived { .. } => "NotesReceived",
+        }
+    }
+
+    /// Returns `true` if the event may be sent to a subscriber from `origin`. Completed requests
+    /// are only sent to the origin which made them.
+    #[inline]
+    pub fn is_visible_to(&self, origin: Option<&str>) -> bool {
+        match self {
+            Self::RequestCompleted {
+                origin: request_origin,
+                ..
+            } => request_origin.is_some() && request_origin.as_deref() == origin,
+            _ => true,
+        }
+    }
+}
+
+/// Event Bus
+#[derive(Debug)]
+pub struct EventBus {
+    /// Event Sender
+    sender: broadcast::Sender<SignerEvent>,
+}
+
+impl Default for EventBus {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl EventBus {
+    /// Publishes `event` to the current subscribers.
+    #[inline]
+    pub fn publish(&self, event: SignerEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to the events published from now on.
+    #[inline]
+    pub fn subscribe(&self) -> broadcast::Receiver<SignerEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Remembered Approval Decisions
+//!
+//! When the user authorizes a transaction from a paired dapp, they can ask the signer to remember
+//! the decision for the dapp, or for the dapp and the asset of the transaction, for a bounded time
+//! and up to a bounded value per transaction. Matching transactions are then signed without asking
+//! again until the exemption expires or is revoked. Like the [`session`](crate::session) grants,
+//! exemptions are bound to the pairing identifier of the dapp rather than to the forgeable
+//! `Origin` header, and are stored encrypted next to the wallet file once the signer is unlocked.
+
+use crate::storage::cipher::{self, FileKey};
+use core::sync::atomic::{AtomicU64, Ordering};
+use manta_crypto::rand::OsRng;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use std::{path::PathBuf, sync::Arc};
+
+/// Maximum Exemption Duration in Minutes
+pub const MAX_DURATION_MINUTES: u64 = 24 * 60;
+
+/// Returns the current UNIX timestamp.
+#[inline]
+fn now() -> i64 {
+    chrono::offset::Utc::now().timestamp()
+}
+
+/// Exemption Scope
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    content = "content",
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum ExemptionScope {
+    /// Every Transaction of the Dapp
+    Origin,
+
+    /// Transactions of the Dapp in the Asset of the Authorized Transaction
+    Asset,
+}
+
+/// Remember Choice
+///
+/// This is picked by the user while authorizing a transaction.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct RememberChoice {
+    /// Exemption Scope
+    pub scope: ExemptionScope,
+
+    /// Exemption Duration in Minutes
+    ///
+    /// This is capped at [`MAX_DURATION_MINUTES`].
+    pub duration_minutes: u64,
+
+    /// Maximum Value of every Covered Transaction
+    pub value_limit: u128,
+}
+
+/// Approval Exemption
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Exemption {
+    /// Exemption Identifier
+    pub id: u64,
+
+    /// Pairing Identifier of the Dapp
+    pub dapp: String,
+
+    /// Origin
+    ///
+    /// This is the `Origin` header of the authorized request, and is only shown to the user.
+    pub origin: Option<String>,
+
+    /// Asset Identifier
+    ///
+    /// The exemption covers every asset when this is `None`.
+    pub asset_id: Option<u32>,
+
+    /// Maximum Value of every Covered Transaction
+    pub value_limit: u128,
+
+    /// Expiry Time as a UNIX Timestamp
+    pub expires_at: i64,
+}
+
+impl Exemption {
+    /// Returns `true` if the exemption has not expired yet.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        now() < self.expires_at
+    }
+
+    /// Returns `true` if the exemption covers a transaction of `value` in `asset_id` from the
+    /// paired dapp with the given `dapp` identifier.
+    #[inline]
+    pub fn covers(&self, dapp: &str, asset_id: u32, value: u128) -> bool {
+        self.dapp == dapp
+            && self.asset_id.map_or(true, |id| id == asset_id)
+            && value <= self.value_limit
+    }
+}
+
+/// Exemption Store
+///
+/// The store is shared with the frontend through the service [`Handle`](crate::service::Handle)
+/// and is opened by the service once the signer is unlocked. Until then, exemptions only live in
+/// memory.
+#[derive(Debug, Default)]
+pub struct ExemptionStore {
+    /// Next Exemption Identifier
+    next_id: AtomicU64,
+
+    /// Exemptions
+    exemptions: Mutex<Vec<Exemption>>,
+
+    /// Encrypted Exemption File Path and Storage Key
+    storage: Mutex<Option<(PathBuf, Arc<FileKey>)>>,
+}
+
+impl ExemptionStore {
+    /// Loads the active exemptions stored encrypted under `key` at `path`, starting without
+    /// exemptions if it does not exist or holds exemptions of an earlier format, and saves later
+    /// changes there.
+    #[inline]
+    pub fn open(&self, path: PathBuf, key: Arc<FileKey>) -> Result<(), cipher::Error> {
+        let mut exemptions = match cipher::load_or_quarantine::<_, Vec<Exemption>>(&path, &key) {
+            Ok(exemptions) => exemptions.unwrap_or_default(),
+            Err(cipher::Error::Serialization) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        exemptions.retain(Exemption::is_active);
+        self.next_id.fetch_max(
+            exemptions
+                .iter()
+                .map(|exemption| exemption.id + 1)
+                .max()
+                .unwrap_or_default(),
+            Ordering::Relaxed,
+        );
+        *self.exemptions.lock() = exemptions;
+        *self.storage.lock() = Some((path, key));
+        Ok(())
+    }
+
+    /// Saves `exemptions` if the store is open.
+    #[inline]
+    fn save(&self, exemptions: &[Exemption]) -> Result<(), cipher::Error> {
+        match &*self.storage.lock() {
+            Some((path, key)) => cipher::save(path, key, &exemptions, 0, &mut OsRng),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remembers the decision to authorize a transaction in `asset_id` from the paired dapp with
+    /// the given `dapp` identifier and `origin` as described by `choice`, returning the new
+    /// exemption.
+    #[inline]
+    pub fn remember(
+        &self,
+        dapp: String,
+        origin: Option<String>,
+        asset_id: u32,
+        choice: RememberChoice,
+    ) -> Result<Exemption, cipher::Error> {
+        let exemption = Exemption {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            dapp,
+            origin,
+            asset_id: match choice.scope {
+                ExemptionScope::Origin => None,
+                ExemptionScope::Asset => Some(asset_id),
+            },
+            value_limit: choice.value_limit,
+            expires_at: now() + 60 * choice.duration_minutes.min(MAX_DURATION_MINUTES) as i64,
+        };
+        let mut exemptions = self.exemptions.lock();
+        let mut updated = exemptions.clone();
+        updated.push(exemption.clone());
+        self.save(&updated)?;
+        *exemptions = updated;
+        Ok(exemption)
+    }
+
+    /// Adds the active exemptions of `imported` which do not outlast [`MAX_DURATION_MINUTES`]
+    /// under new identifiers, and returns how many were added.
+    #[inline]
+    pub fn import(&self, imported: Vec<Exemption>) -> Result<usize, cipher::Error> {
+        let latest_expiry = now() + 60 * MAX_DURATION_MINUTES as i64;
+        let mut exemptions = self.exemptions.lock();
+        let mut updated = exemptions.clone();
+        let previous = updated.len();
+        updated.extend(
+            imported
+                .into_iter()
+                .filter(|exemption| exemption.is_active() && exemption.expires_at <= latest_expiry)
+                .map(|exemption| Exemption {
+                    id: self.next_id.fetch_add(1, Ordering::Relaxed),
+                    ..exemption
+                }),
+        );
+        let count = updated.len() - previous;
+        self.save(&updated)?;
+        *exemptions = updated;
+        Ok(count)
+    }
+
+    /// Revokes the exemption with the given `id`, returning `false` if there is none.
+    #[inline]
+    pub fn revoke(&self, id: u64) -> Result<bool, cipher::Error> {
+        let mut exemptions = self.exemptions.lock();
+        let count = exemptions.len();
+        exemptions.retain(|exemption| exemption.id != id);
+        if exemptions.len() == count {
+            return Ok(false);
+        }
+        self.save(&exemptions)?;
+        Ok(true)
+    }
+
+    /// Revokes every exemption.
+    #[inline]
+    pub fn revoke_all(&self) -> Result<(), cipher::Error> {
+        let mut exemptions = self.exemptions.lock();
+        exemptions.clear();
+        self.save(&exemptions)
+    }
+
+    /// Lists the active exemptions, dropping the expired ones.
+    #[inline]
+    pub fn list(&self) -> Vec<Exemption> {
+        let mut exemptions = self.exemptions.lock();
+        exemptions.retain(Exemption::is_active);
+        exemptions.clone()
+    }
+
+    /// Returns `true` if an active exemption covers a transaction of `value` in `asset_id` from
+    /// the paired dapp with the given `dapp` identifier. The caller must have checked that the
+    /// request was authenticated by the pairing of the dapp.
+    #[inline]
+    pub fn authorize(&self, dapp: &str, asset_id: u32, value: u128) -> bool {
+        let mut exemptions = self.exemptions.lock();
+        exemptions.retain(Exemption::is_active);
+        exemptions
+            .iter()
+            .any(|exemption| exemption.covers(dapp, asset_id, value))
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Network Fees
+//!
+//! Dapps can send a [`FeeQuote`] with a signing request, listing the network fee of each
+//! [`FeeTier`]. The signer shows the fees in the authorization prompt and lets the user pick a
+//! tier before the transfer is proven, and returns the chosen fee so that the dapp submits the
+//! transaction with it.
+
+use crate::{audit::decode_value, chain::ChainSpec};
+use core::fmt;
+use manta_pay::signer::{SignError, SignRequest, SignResponse};
+use manta_util::serde::{Deserialize, Serialize};
+
+/// Fee Tier
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum FeeTier {
+    /// Slow Inclusion at the Lowest Fee
+    Low,
+
+    /// Default Inclusion Speed
+    Standard,
+
+    /// Fast Inclusion at the Highest Fee
+    High,
+}
+
+impl fmt::Display for FeeTier {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Fee Option
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct FeeOption {
+    /// Fee Tier
+    pub tier: FeeTier,
+
+    /// Fee in Base Units of the Native Asset
+    pub fee: u128,
+}
+
+/// Fee Quote
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct FeeQuote {
+    /// Fee Options
+    pub options: Vec<FeeOption>,
+
+    /// Native Asset Decimals
+    #[serde(default)]
+    pub decimals: Option<u32>,
+
+    /// Native Asset Symbol
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+impl FeeQuote {
+    /// Returns the option of the given `tier`, if the quote has one.
+    #[inline]
+    pub fn option(&self, tier: FeeTier) -> Option<FeeOption> {
+        self.options
+            .iter()
+            .find(|option| option.tier == tier)
+            .copied()
+    }
+
+    /// Returns the option which is used if the user does not pick a tier, which is the
+    /// [`Standard`](FeeTier::Standard) option or the first option if there is none.
+    #[inline]
+    pub fn default_option(&self) -> Option<FeeOption> {
+        self.option(FeeTier::Standard)
+            .or_else(|| self.options.first().copied())
+    }
+
+    /// Returns the option of `tier` if the user picked one which the quote has, or the
+    /// [default option](Self::default_option) otherwise.
+    #[inline]
+    pub fn select(&self, tier: Option<FeeTier>) -> Option<FeeOption> {
+        tier.and_then(|tier| self.option(tier))
+            .or_else(|| self.default_option())
+    }
+
+    /// Fills in the decimals and symbol of the native asset of `chain` if the quote does not
+    /// have them.
+    #[inline]
+    pub fn with_native_asset(mut self, chain: &ChainSpec) -> Self {
+        if self.decimals.is_none() {
+            self.decimals = Some(chain.native_decimals);
+        }
+        if self.symbol.is_none() {
+            self.symbol = Some(chain.native_symbol.clone());
+        }
+        self
+    }
+
+    /// Formats `fee` with the decimals and symbol of the native asset if they are known.
+    #[inline]
+    pub fn display(&self, fee: u128) -> String {
+        let value = match self.decimals {
+            Some(decimals) => decode_value(fee, decimals),
+            _ => fee.to_string(),
+        };
+        match &self.symbol {
+            Some(symbol) => format!("{} {}", value, symbol),
+            _ => value,
+        }
+    }
+
+    /// Appends the fee options to the authorization `prompt`.
+    #[inline]
+    pub fn annotate(&self, mut prompt: String) -> String {
+        for option in &self.options {
+            prompt.push_str(&format!(
+                "\nNetwork fee ({}): {}",
+                option.tier,
+                self.display(option.fee)
+            ));
+        }
+        prompt
+    }
+}
+
+/// Signing Request with a Fee Quote
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct FeeSignRequest {
+    /// Signing Request
+    pub request: SignRequest,
+
+    /// Fee Quote
+    pub fee: FeeQuote,
+
+    /// Memo Supplied by the Dapp
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// Signing Response with the Chosen Fee
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct FeeSignResponse {
+    /// Signing Response
+    pub response: Result<SignResponse, SignError>,
+
+    /// Chosen Fee
+    ///
+    /// This is `None` if the quote had no options.
+    pub fee: Option<FeeOption>,
+}
@@ -0,0 +1,285 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Frontend Building Blocks
+//!
+//! Frontends run the [`service`](crate::service) with their own [`Authorizer`] and only take care
+//! of showing prompts and collecting the user's answers. The types in this module hold the state
+//! which every interactive frontend shares between its authorizer and its user interface: the
+//! [`PasswordStore`] relays passwords from the user to the [`PasswordRelay`] of the authorizer,
+//! the [`ApprovalChoices`] hold the memo, fee tier, and remember choice picked while authorizing a
+//! transaction, and the [`BackupQuizStore`] checks the backup of a new recovery phrase.
+//!
+//! [`Authorizer`]: crate::secret::Authorizer
+
+use crate::{
+    backup::{BackupQuiz, BackupStatus},
+    exemption::RememberChoice,
+    fee::FeeTier,
+    memo,
+    secret::{ExposeSecret, Password, SecretString, SecretVec},
+};
+use manta_crypto::rand::OsRng;
+use manta_pay::key::Mnemonic;
+use std::{io, path::Path, sync::Arc};
+use tokio::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+
+/// Password Relay
+///
+/// The relay is the authorizer end of a [`PasswordStore`].
+pub struct PasswordRelay {
+    /// Password Receiver
+    password: Receiver<Password>,
+
+    /// Password Retry Sender
+    retry: Sender<bool>,
+
+    /// Waiting Flag
+    waiting: bool,
+}
+
+impl PasswordRelay {
+    /// Sends the `retry` message to have the user retry the password.
+    #[inline]
+    async fn should_retry(&mut self, retry: bool) {
+        self.retry
+            .send(retry)
+            .await
+            .expect("Failed to send retry message.");
+    }
+
+    /// Requests the password from the user, sending a retry message if the previous password did
+    /// not match.
+    #[inline]
+    pub async fn request(&mut self) -> Password {
+        if self.waiting {
+            self.should_retry(true).await;
+        }
+        let password = self
+            .password
+            .recv()
+            .await
+            .expect("Failed to receive retry message.");
+        self.waiting = password.is_known();
+        password
+    }
+
+    /// Sends the validation message once the password matched.
+    #[inline]
+    pub async fn validate(&mut self) {
+        self.waiting = false;
+        self.should_retry(false).await;
+    }
+
+    /// Stops waiting for the answer to the last password request, for example because its prompt
+    /// was dismissed.
+    #[inline]
+    pub fn dismiss(&mut self) {
+        self.waiting = false;
+    }
+}
+
+/// Password Storage Channel
+struct PasswordStoreChannel {
+    /// Password Sender
+    password: Sender<Password>,
+
+    /// Retry Receiver
+    retry: Receiver<bool>,
+}
+
+/// Password Storage Type
+type PasswordStoreType = Arc<Mutex<Option<PasswordStoreChannel>>>;
+
+/// Password Storage Handle
+pub struct PasswordStoreHandle(PasswordStoreType);
+
+impl PasswordStoreHandle {
+    /// Constructs the [`PasswordRelay`] at the opposite end of `self`.
+    #[inline]
+    pub async fn into_relay(self) -> PasswordRelay {
+        let (password, receiver) = channel(1);
+        let (sender, retry) = channel(1);
+        *self.0.lock().await = Some(PasswordStoreChannel { password, retry });
+        PasswordRelay {
+            password: receiver,
+            retry: sender,
+            waiting: false,
+        }
+    }
+}
+
+/// Password Storage
+#[derive(Default)]
+pub struct PasswordStore(PasswordStoreType);
+
+impl PasswordStore {
+    /// Returns a handle for setting up a [`PasswordStore`].
+    #[inline]
+    pub fn handle(&self) -> PasswordStoreHandle {
+        PasswordStoreHandle(self.0.clone())
+    }
+
+    /// Loads the password store with `password` and the optional `keyfile` contents, returning
+    /// `true` if the password should be retried.
+    #[inline]
+    pub async fn load(&self, password: SecretString, keyfile: Option<SecretVec<u8>>) -> bool {
+        if let Some(store) = &mut *self.0.lock().await {
+            let password = match keyfile {
+                Some(keyfile) => {
+                    Password::from_known(password).with_keyfile(keyfile.expose_secret())
+                }
+                _ => Password::from_known(password),
+            };
+            let _ = store.password.send(password).await;
+            store.retry.recv().await.unwrap()
+        } else {
+            false
+        }
+    }
+
+    /// Loads the password with `password`, not requesting a retry.
+    #[inline]
+    pub async fn load_exact(&self, password: SecretString) {
+        if let Some(store) = &mut *self.0.lock().await {
+            let _ = store.password.send(Password::from_known(password)).await;
+        }
+    }
+
+    /// Clears the password from the store.
+    #[inline]
+    pub async fn clear(&self) {
+        if let Some(store) = &mut *self.0.lock().await {
+            let _ = store.password.send(Password::from_unknown()).await;
+        }
+    }
+}
+
+/// Approval Choices
+///
+/// Holds the memo, the network fee tier, and the remember choice which the user picked for the
+/// transaction they are authorizing. The choices are cleared whenever a new prompt is shown.
+#[derive(Debug, Default)]
+pub struct ApprovalChoices {
+    /// Transaction Memo
+    memo: std::sync::Mutex<Option<String>>,
+
+    /// Network Fee Tier
+    fee_tier: std::sync::Mutex<Option<FeeTier>>,
+
+    /// Remember Choice
+    remember: std::sync::Mutex<Option<RememberChoice>>,
+}
+
+impl ApprovalChoices {
+    /// Sets the `memo` of the transaction, normalizing it with [`memo::normalize`].
+    #[inline]
+    pub fn set_memo(&self, memo: Option<&str>) {
+        *self.memo.lock().expect("Memo lock is poisoned.") = memo.and_then(memo::normalize);
+    }
+
+    /// Sets the network fee `tier` of the transaction.
+    #[inline]
+    pub fn set_fee_tier(&self, tier: Option<FeeTier>) {
+        *self.fee_tier.lock().expect("Fee tier lock is poisoned.") = tier;
+    }
+
+    /// Sets the `choice` to remember the decision on the transaction.
+    #[inline]
+    pub fn set_remember(&self, choice: Option<RememberChoice>) {
+        *self.remember.lock().expect("Remember lock is poisoned.") = choice;
+    }
+
+    /// Takes the memo of the transaction.
+    #[inline]
+    pub fn take_memo(&self) -> Option<String> {
+        self.memo.lock().expect("Memo lock is poisoned.").take()
+    }
+
+    /// Takes the network fee tier of the transaction.
+    #[inline]
+    pub fn take_fee_tier(&self) -> Option<FeeTier> {
+        self.fee_tier
+            .lock()
+            .expect("Fee tier lock is poisoned.")
+            .take()
+    }
+
+    /// Takes the choice to remember the decision on the transaction.
+    #[inline]
+    pub fn take_remember(&self) -> Option<RememberChoice> {
+        self.remember
+            .lock()
+            .expect("Remember lock is poisoned.")
+            .take()
+    }
+
+    /// Clears the choices for a new prompt.
+    #[inline]
+    pub fn clear(&self) {
+        self.take_memo();
+        self.take_fee_tier();
+        self.take_remember();
+    }
+}
+
+/// Recovery Phrase Quiz Storage
+///
+/// Holds the recovery phrase of the newly created account until the user has verified it.
+#[derive(Default)]
+pub struct BackupQuizStore(Mutex<Option<BackupQuiz>>);
+
+impl BackupQuizStore {
+    /// Starts the quiz for the new recovery phrase `mnemonic`.
+    #[inline]
+    pub async fn start(&self, mnemonic: &Mnemonic) {
+        *self.0.lock().await = Some(BackupQuiz::new(mnemonic, &mut OsRng));
+    }
+
+    /// Returns the positions of the words of the new recovery phrase which the user is asked
+    /// for, or `None` if there is no new recovery phrase to verify.
+    #[inline]
+    pub async fn indices(&self) -> Option<Vec<usize>> {
+        self.0
+            .lock()
+            .await
+            .as_ref()
+            .map(|quiz| quiz.indices().to_vec())
+    }
+
+    /// Checks the `words` entered by the user against the asked words of the new recovery
+    /// phrase, saving the [`BackupStatus`] to `path` if they match. Returns `None` if there is no
+    /// new recovery phrase to verify.
+    #[inline]
+    pub async fn verify<P>(&self, path: P, words: &[String]) -> io::Result<Option<bool>>
+    where
+        P: AsRef<Path>,
+    {
+        let mut quiz = self.0.lock().await;
+        match quiz.as_ref() {
+            Some(pending) if pending.verify(words) => {
+                BackupStatus::now().save(path)?;
+                *quiz = None;
+                Ok(Some(true))
+            }
+            Some(_) => Ok(Some(false)),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Hidden Wallets
+//!
+//! A hidden wallet is only reachable with the signer password and an extra passphrase. It is
+//! derived from its own recovery phrase with the passphrase as the BIP39 password, and its state
+//! file is encrypted under a key derived from both the password and the passphrase. Hidden
+//! wallets are never added to the [`WalletRegistry`](crate::wallet::WalletRegistry), so the
+//! wallet list and the wallet history of a normal unlock show no trace of them. Their state file
+//! is named after a MAC of the passphrase, so the file name does not reveal the passphrase.
+//!
+//! The audit log, memos and proofs are shared by every wallet, so the signer neither audits nor
+//! logs the requests about hidden wallets and the transactions signed while one is open. Only the
+//! balance cache of a hidden wallet is kept, encrypted under its own key.
+
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac};
+use manta_util::serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Hidden Wallet Request
+#[derive(Clone, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct HiddenRequest {
+    /// Passphrase
+    pub passphrase: String,
+}
+
+impl core::fmt::Debug for HiddenRequest {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("HiddenRequest").finish_non_exhaustive()
+    }
+}
+
+/// Returns the HMAC-SHA256 of `passphrase` under the `password_hash` with the given `label`.
+#[inline]
+fn derive(password_hash: &[u8], label: &str, passphrase: &str) -> Vec<u8> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(password_hash).expect("HMAC can take keys of any size.");
+    mac.update(label.as_bytes());
+    mac.update(b"\n");
+    mac.update(passphrase.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Returns the wallet name under which the hidden wallet of `passphrase` is stored.
+#[inline]
+pub fn wallet_name(password_hash: &[u8], passphrase: &str) -> String {
+    format!(
+        "h{}",
+        &HEXLOWER.encode(&derive(password_hash, "name", passphrase))[..16]
+    )
+}
+
+/// Returns the secret from which the storage key of the hidden wallet of `passphrase` is
+/// derived.
+#[inline]
+pub fn storage_secret(password_hash: &[u8], passphrase: &str) -> Vec<u8> {
+    derive(password_hash, "key", passphrase)
+}
@@ -0,0 +1,167 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Signer Identity
+//!
+//! Every signer owns an Ed25519 identity key which it uses to sign the documents it produces so
+//! that third parties can check where they came from. The secret key is stored encrypted next to
+//! the wallet file.
+//!
+//! Every API response is signed as well. The service adds the [`PUBLIC_KEY_HEADER`] and the
+//! [`SIGNATURE_HEADER`] to each response, where the signature covers the
+//! [`response_message`] built from the response body and the value of the
+//! [`CHALLENGE_HEADER`] of the request. A dapp which remembers the public key of the signer it
+//! paired with and sends a fresh challenge with its requests can tell the real signer apart from
+//! another local process listening on its port.
+
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer as _, Verifier};
+use manta_crypto::rand::{CryptoRng, RngCore};
+use manta_util::serde::{Deserialize, Serialize};
+
+/// Identity Secret Key Length in Bytes
+pub const SECRET_KEY_LENGTH: usize = ed25519_dalek::SECRET_KEY_LENGTH;
+
+/// Request Header holding the Client Challenge
+pub const CHALLENGE_HEADER: &str = "X-Signer-Challenge";
+
+/// Response Header holding the Hex-Encoded Public Key of the Signer
+pub const PUBLIC_KEY_HEADER: &str = "X-Signer-Public-Key";
+
+/// Response Header holding the Hex-Encoded Response Signature
+pub const SIGNATURE_HEADER: &str = "X-Signer-Signature";
+
+/// Returns the message which is signed for a response with the given `body` to a request with
+/// the given `challenge`, which is empty if the request had none.
+#[inline]
+pub fn response_message(challenge: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(challenge.len() + 1 + body.len());
+    message.extend_from_slice(challenge.as_bytes());
+    message.push(b'\n');
+    message.extend_from_slice(body);
+    message
+}
+
+/// Verifies that the hex-encoded `signature` of `message` was made by the identity with the
+/// hex-encoded `public_key`.
+#[inline]
+pub fn verify(public_key: &str, message: &[u8], signature: &str) -> bool {
+    let public_key = match HEXLOWER_PERMISSIVE
+        .decode(public_key.as_bytes())
+        .ok()
+        .and_then(|bytes| PublicKey::from_bytes(&bytes).ok())
+    {
+        Some(public_key) => public_key,
+        _ => return false,
+    };
+    match HEXLOWER_PERMISSIVE
+        .decode(signature.as_bytes())
+        .ok()
+        .and_then(|bytes| Signature::from_bytes(&bytes).ok())
+    {
+        Some(signature) => public_key.verify(message, &signature).is_ok(),
+        _ => false,
+    }
+}
+
+/// Signer Identity
+pub struct Identity {
+    /// Identity Keypair
+    keypair: Keypair,
+}
+
+impl Identity {
+    /// Builds an [`Identity`] from the secret key `bytes`, returning `None` if they are not a
+    /// valid secret key.
+    #[inline]
+    pub fn from_secret_bytes(bytes: &[u8]) -> Option<Self> {
+        let secret = SecretKey::from_bytes(bytes).ok()?;
+        let public = PublicKey::from(&secret);
+        Some(Self {
+            keypair: Keypair { secret, public },
+        })
+    }
+
+    /// Samples a new [`Identity`] from `rng`.
+    #[inline]
+    pub fn gen<R>(rng: &mut R) -> Self
+    where
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        let mut bytes = [0; SECRET_KEY_LENGTH];
+        rng.fill_bytes(&mut bytes);
+        Self::from_secret_bytes(&bytes).expect("Every 32-byte string is a valid secret key.")
+    }
+
+    /// Returns the secret key bytes for storage.
+    #[inline]
+    pub fn secret_bytes(&self) -> Vec<u8> {
+        self.keypair.secret.as_bytes().to_vec()
+    }
+
+    /// Returns the hex-encoded public key.
+    #[inline]
+    pub fn public_key(&self) -> String {
+        HEXLOWER.encode(self.keypair.public.as_bytes())
+    }
+
+    /// Signs `message`, returning the hex-encoded signature.
+    #[inline]
+    pub fn sign(&self, message: &[u8]) -> String {
+        HEXLOWER.encode(&self.keypair.sign(message).to_bytes())
+    }
+}
+
+/// Signed Document
+///
+/// The signature covers the JSON serialization of the `payload`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Signed<T> {
+    /// Signed Payload
+    pub payload: T,
+
+    /// Hex-Encoded Ed25519 Public Key of the Signer
+    pub public_key: String,
+
+    /// Hex-Encoded Ed25519 Signature
+    pub signature: String,
+}
+
+impl<T> Signed<T>
+where
+    T: Serialize,
+{
+    /// Signs `payload` with `identity`.
+    #[inline]
+    pub fn new(identity: &Identity, payload: T) -> serde_json::Result<Self> {
+        let signature = identity.sign(&serde_json::to_vec(&payload)?);
+        Ok(Self {
+            payload,
+            public_key: identity.public_key(),
+            signature,
+        })
+    }
+
+    /// Returns `true` if the signature of `self` is valid for its public key.
+    #[inline]
+    pub fn verify(&self) -> bool {
+        match serde_json::to_vec(&self.payload) {
+            Ok(message) => verify(&self.public_key, &message, &self.signature),
+            _ => false,
+        }
+    }
+}
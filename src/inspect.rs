@@ -0,0 +1,107 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction Inspection
+//!
+//! Dapps and auditors can decode a signing request into a [`TransactionBreakdown`] before
+//! submitting it, to check exactly what the signer will show in its authorization prompt.
+
+use crate::confirmation::confirmation_code;
+use manta_accounting::{
+    asset::AssetMetadata,
+    transfer::canonical::{Transaction, TransferShape},
+};
+use manta_pay::{
+    config::{receiving_key_to_base58, Config},
+    signer::SignRequest,
+};
+use manta_util::serde::{Deserialize, Serialize};
+
+/// Transaction Breakdown
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct TransactionBreakdown {
+    /// Transfer Shape
+    pub shape: String,
+
+    /// Asset Identifier
+    pub asset_id: u32,
+
+    /// Asset Value in Base Units
+    pub value: u128,
+
+    /// Asset Symbol
+    ///
+    /// This is only known if the request carries asset metadata.
+    pub symbol: Option<String>,
+
+    /// Base58-Encoded Receiving Key of Private Transfers
+    pub receiver: Option<String>,
+
+    /// Confirmation Code
+    pub confirmation_code: String,
+
+    /// Authorization Prompt
+    ///
+    /// This is the text the signer shows when it asks the user to authorize the transaction.
+    /// Mints are not authorized and have no prompt.
+    pub prompt: Option<String>,
+}
+
+impl TransactionBreakdown {
+    /// Decodes the signing `request`.
+    #[inline]
+    pub fn new(request: &SignRequest) -> serde_json::Result<Self> {
+        let transaction = &request.transaction;
+        let (asset, receiver) = match transaction {
+            Transaction::Mint(asset) | Transaction::Reclaim(asset) => (asset, None),
+            Transaction::PrivateTransfer(asset, receiving_key) => {
+                (asset, Some(receiving_key_to_base58(receiving_key)))
+            }
+        };
+        let shape = transaction.shape();
+        Ok(Self {
+            shape: format!("{:?}", shape),
+            asset_id: asset.id.0,
+            value: asset.value.0,
+            symbol: request
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.symbol.clone()),
+            receiver,
+            confirmation_code: confirmation_code(transaction)?,
+            prompt: match shape {
+                TransferShape::Mint => None,
+                _ => Some(prompt(transaction, request.metadata.as_ref())?),
+            },
+        })
+    }
+}
+
+/// Returns the authorization prompt of `transaction` with the asset `metadata` if it is known.
+#[inline]
+pub fn prompt(
+    transaction: &Transaction<Config>,
+    metadata: Option<&AssetMetadata>,
+) -> serde_json::Result<String> {
+    Ok(format!(
+        "{}\nConfirmation code: {}",
+        metadata
+            .map(|metadata| transaction.display(metadata, receiving_key_to_base58))
+            .unwrap_or_default(),
+        confirmation_code(transaction)?
+    ))
+}
@@ -0,0 +1,241 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Account and Asset Labels
+//!
+//! Users can name their accounts, by derivation index, and their assets, by asset identifier, so
+//! that authorization prompts and the exported history show "Savings" instead of raw indices.
+//! Labels are stored encrypted next to the wallet file and are only readable once the signer is
+//! unlocked.
+
+use crate::storage::cipher::{self, FileKey};
+use core::fmt;
+use manta_crypto::rand::OsRng;
+use manta_util::{
+    from_variant_impl,
+    serde::{Deserialize, Serialize},
+};
+use parking_lot::{Mutex, RwLock};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+/// Maximum Label Length in Characters
+pub const MAX_LABEL_LENGTH: usize = 64;
+
+/// Label Target
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(
+    content = "content",
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum LabelTarget {
+    /// Account at the given Derivation Index
+    Account(usize),
+
+    /// Asset with the given Identifier
+    Asset(u32),
+}
+
+/// Label Update Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SetLabelRequest {
+    /// Label Target
+    pub target: LabelTarget,
+
+    /// New Label
+    ///
+    /// The label of the target is removed if this is `None` or empty.
+    pub label: Option<String>,
+}
+
+/// Labels
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Labels {
+    /// Account Labels by Derivation Index
+    pub accounts: BTreeMap<usize, String>,
+
+    /// Asset Labels by Asset Identifier
+    pub assets: BTreeMap<u32, String>,
+}
+
+impl Labels {
+    /// Returns the label of the account at `index`, if any.
+    #[inline]
+    pub fn account(&self, index: usize) -> Option<&str> {
+        self.accounts.get(&index).map(String::as_str)
+    }
+
+    /// Returns the label of the asset with the given `id`, if any.
+    #[inline]
+    pub fn asset(&self, id: u32) -> Option<&str> {
+        self.assets.get(&id).map(String::as_str)
+    }
+
+    /// Sets the label of `target` to `label`, removing it if `label` is `None` or empty.
+    #[inline]
+    pub fn set(&mut self, target: LabelTarget, label: Option<String>) -> Result<(), Error> {
+        let label = label
+            .map(|label| label.trim().to_owned())
+            .filter(|label| !label.is_empty());
+        if let Some(label) = &label {
+            if label.chars().count() > MAX_LABEL_LENGTH {
+                return Err(Error::TooLong);
+            }
+            if label.chars().any(char::is_control) {
+                return Err(Error::InvalidCharacter);
+            }
+        }
+        match (target, label) {
+            (LabelTarget::Account(index), Some(label)) => {
+                self.accounts.insert(index, label);
+            }
+            (LabelTarget::Account(index), _) => {
+                self.accounts.remove(&index);
+            }
+            (LabelTarget::Asset(id), Some(label)) => {
+                self.assets.insert(id, label);
+            }
+            (LabelTarget::Asset(id), _) => {
+                self.assets.remove(&id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends the labels of the asset with the given `asset_id` and of the receiving `account`
+    /// to the authorization `prompt`, if they are known.
+    #[inline]
+    pub fn annotate(&self, mut prompt: String, asset_id: u32, account: Option<usize>) -> String {
+        if let Some(label) = self.asset(asset_id) {
+            prompt.push_str(&format!("\nAsset: {}", label));
+        }
+        if let Some((index, label)) =
+            account.and_then(|index| self.account(index).map(|label| (index, label)))
+        {
+            prompt.push_str(&format!("\nTo your account: {} (#{})", label, index));
+        }
+        prompt
+    }
+}
+
+/// Label Error
+#[derive(Debug)]
+pub enum Error {
+    /// The signer is locked, so the labels cannot be read or saved.
+    Locked,
+
+    /// The label is longer than [`MAX_LABEL_LENGTH`].
+    TooLong,
+
+    /// The label contains control characters.
+    InvalidCharacter,
+
+    /// Encryption Error
+    Cipher(cipher::Error),
+}
+
+from_variant_impl!(Error, Cipher, cipher::Error);
+
+impl fmt::Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Locked => write!(f, "The signer is locked."),
+            Self::TooLong => write!(
+                f,
+                "Labels cannot be longer than {} characters.",
+                MAX_LABEL_LENGTH
+            ),
+            Self::InvalidCharacter => write!(f, "Labels cannot contain control characters."),
+            Self::Cipher(err) => write!(f, "Unable to save the labels: {:?}", err),
+        }
+    }
+}
+
+/// Label Storage
+#[derive(Debug)]
+struct Storage {
+    /// Encrypted Label File Path
+    path: PathBuf,
+
+    /// Storage Key
+    key: Arc<FileKey>,
+}
+
+/// Label Store
+///
+/// The store is shared with the frontend through the service [`Handle`](crate::service::Handle)
+/// and is opened by the service once the signer is unlocked.
+#[derive(Debug, Default)]
+pub struct LabelStore {
+    /// Labels
+    labels: RwLock<Labels>,
+
+    /// Label Storage
+    storage: Mutex<Option<Storage>>,
+}
+
+impl LabelStore {
+    /// Loads the labels stored encrypted under `key` at `path`, starting without labels if it
+    /// does not exist, and saves later updates there.
+    #[inline]
+    pub fn open(&self, path: PathBuf, key: Arc<FileKey>) -> Result<(), cipher::Error> {
+        let labels = cipher::load_or_quarantine(&path, &key)?.unwrap_or_default();
+        *self.labels.write() = labels;
+        *self.storage.lock() = Some(Storage { path, key });
+        Ok(())
+    }
+
+    /// Returns a copy of the labels.
+    #[inline]
+    pub fn get(&self) -> Labels {
+        self.labels.read().clone()
+    }
+
+    /// Sets the label of `target` to `label` and saves the labels, removing the label if it is
+    /// `None` or empty.
+    #[inline]
+    pub fn set(&self, target: LabelTarget, label: Option<String>) -> Result<Labels, Error> {
+        let storage = self.storage.lock();
+        let storage = storage.as_ref().ok_or(Error::Locked)?;
+        let mut labels = self.get();
+        labels.set(target, label)?;
+        cipher::save(&storage.path, &storage.key, &labels, 0, &mut OsRng)?;
+        *self.labels.write() = labels.clone();
+        Ok(labels)
+    }
+
+    /// Sets the label of every target in `labels`, keeping the labels of the other targets, and
+    /// saves the labels.
+    #[inline]
+    pub fn merge(&self, labels: Labels) -> Result<Labels, Error> {
+        let storage = self.storage.lock();
+        let storage = storage.as_ref().ok_or(Error::Locked)?;
+        let mut merged = self.get();
+        for (index, label) in labels.accounts {
+            merged.set(LabelTarget::Account(index), Some(label))?;
+        }
+        for (id, label) in labels.assets {
+            merged.set(LabelTarget::Asset(id), Some(label))?;
+        }
+        cipher::save(&storage.path, &storage.key, &merged, 0, &mut OsRng)?;
+        *self.labels.write() = merged.clone();
+        Ok(merged)
+    }
+}
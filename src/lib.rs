@@ -20,11 +20,69 @@
 #![forbid(rustdoc::broken_intra_doc_links)]
 #![forbid(missing_docs)]
 
+pub mod airgap;
+pub mod api_key;
+pub mod audit;
+pub mod backup;
+pub mod balance;
+#[cfg(feature = "benchmark")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "benchmark")))]
+pub mod benchmark;
+pub mod chain;
+pub mod compat;
 pub mod config;
+pub mod confirmation;
+#[cfg(any(unix, windows))]
+#[cfg_attr(doc_cfg, doc(cfg(any(unix, windows))))]
+pub mod daemon;
+pub mod dapp;
+pub mod deeplink;
+pub mod derivation;
+pub mod diagnostics;
+pub mod discovery;
+pub mod duress;
+pub mod error;
+pub mod events;
+pub mod exemption;
+pub mod fee;
+pub mod frontend;
+pub mod hidden;
+pub mod identity;
+pub mod inspect;
+pub mod label;
+#[cfg(feature = "load-test")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "load-test")))]
+pub mod load_test;
 pub mod log;
+pub mod memo;
+pub mod mnemonic;
+pub mod pairing;
+pub mod palette;
 pub mod parameters;
+pub mod password;
+pub mod probe;
+pub mod prompt;
+pub mod proof;
+pub mod queue;
+pub mod schedule;
 pub mod secret;
 pub mod service;
+pub mod session;
+pub mod settings;
+pub mod status;
+pub mod storage;
+pub mod substrate;
+pub mod sync;
+pub mod telemetry;
+#[cfg(feature = "testing")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "testing")))]
+pub mod testing;
+pub mod totp;
+pub mod transport;
+pub mod wallet;
+#[cfg(windows)]
+#[cfg_attr(doc_cfg, doc(cfg(windows)))]
+pub mod winservice;
 
 #[doc(inline)]
 pub use manta_util::serde;
@@ -0,0 +1,201 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Load Testing
+//!
+//! The load test starts a [`TestService`] against a throwaway seed and sends it synthetic signing
+//! requests from concurrent clients, measuring the latency, the length of the authorization
+//! [`RequestQueue`](crate::queue::RequestQueue), and the memory growth under sustained load.
+//! Every prompt is approved by a [`MockAuthorizer`]. The throwaway wallet has no balance, so the
+//! requests go through the queue, the authorization, and the audit log, and are then rejected by
+//! the signer without proving.
+//!
+//! This module is only available with the `load-test` feature.
+
+use crate::{
+    benchmark, service,
+    testing::{MockAuthorizer, TestService},
+};
+use async_std::task;
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+use manta_accounting::{
+    asset::{Asset, AssetId, AssetValue},
+    transfer::canonical::Transaction,
+};
+use manta_pay::signer::SignRequest;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use std::{io, sync::Arc, time::Instant};
+
+/// Interval between two Samples of the Queue Length and the Memory
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Load Test Options
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct LoadTestOptions {
+    /// Total Number of Requests
+    pub requests: usize,
+
+    /// Number of Concurrent Clients
+    pub concurrency: usize,
+
+    /// Simulated User Delay in Milliseconds
+    pub approval_delay_ms: u64,
+}
+
+impl Default for LoadTestOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            requests: 1000,
+            concurrency: 8,
+            approval_delay_ms: 0,
+        }
+    }
+}
+
+/// Load Test Report
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct LoadTestReport {
+    /// Number of Requests which Received a Response
+    pub responded: usize,
+
+    /// Number of Requests which Failed at the Transport or HTTP Level
+    pub failed: usize,
+
+    /// Total Duration in Milliseconds
+    pub elapsed_ms: u64,
+
+    /// Median Latency in Milliseconds
+    pub latency_p50_ms: u64,
+
+    /// 95th Percentile Latency in Milliseconds
+    pub latency_p95_ms: u64,
+
+    /// Maximum Latency in Milliseconds
+    pub latency_max_ms: u64,
+
+    /// Maximum Number of Pending Authorization Requests
+    pub max_pending: usize,
+
+    /// Peak Memory of the Process in Bytes before the Load
+    pub memory_before_bytes: Option<u64>,
+
+    /// Peak Memory of the Process in Bytes after the Load
+    pub memory_after_bytes: Option<u64>,
+}
+
+/// Returns the synthetic signing request which the load test sends.
+#[inline]
+pub fn synthetic_request() -> SignRequest {
+    SignRequest {
+        transaction: Transaction::Reclaim(Asset::new(AssetId(1), AssetValue(1))),
+        metadata: None,
+    }
+}
+
+/// Returns the latency at `percentile` of the sorted `latencies` in milliseconds.
+#[inline]
+fn percentile(latencies: &[u64], percentile: usize) -> u64 {
+    match latencies.len() {
+        0 => 0,
+        len => latencies[((len - 1) * percentile) / 100],
+    }
+}
+
+/// Runs the load test described by `options` against a fresh [`TestService`].
+///
+/// The service is stopped once the load test is over.
+#[inline]
+pub async fn run(options: LoadTestOptions) -> service::Result<LoadTestReport> {
+    let service = TestService::start(
+        MockAuthorizer::random().with_delay(Duration::from_millis(options.approval_delay_ms)),
+    )
+    .await?;
+    let result = run_against(&service, options).await;
+    service.stop().await;
+    result
+}
+
+/// Runs the load test described by `options` against `service`.
+#[inline]
+async fn run_against(
+    service: &TestService,
+    options: LoadTestOptions,
+) -> service::Result<LoadTestReport> {
+    let url = service.url();
+    let handle = service.handle();
+    let body = serde_json::to_value(synthetic_request())
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    let memory_before_bytes = benchmark::peak_memory();
+    let done = Arc::new(AtomicBool::new(false));
+    let sampler = task::spawn({
+        let (done, handle) = (done.clone(), handle.clone());
+        async move {
+            let mut max_pending = 0;
+            while !done.load(Ordering::Relaxed) {
+                max_pending = max_pending.max(handle.queue.len());
+                task::sleep(SAMPLE_INTERVAL).await;
+            }
+            max_pending
+        }
+    });
+    let next = Arc::new(AtomicUsize::new(0));
+    let latencies = Arc::new(Mutex::new(Vec::with_capacity(options.requests)));
+    let failed = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+    let workers = (0..options.concurrency.max(1))
+        .map(|_| {
+            let (next, latencies, failed) = (next.clone(), latencies.clone(), failed.clone());
+            let (sign_url, body) = (format!("{}/sign", url), body.clone());
+            task::spawn_blocking(move || {
+                while next.fetch_add(1, Ordering::Relaxed) < options.requests {
+                    let sent = Instant::now();
+                    match ureq::post(&sign_url).send_json(body.clone()) {
+                        Ok(_) => latencies.lock().push(sent.elapsed().as_millis() as u64),
+                        _ => {
+                            failed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker.await;
+    }
+    let elapsed_ms = start.elapsed().as_millis() as u64;
+    done.store(true, Ordering::Relaxed);
+    let max_pending = sampler.await;
+    let mut latencies = core::mem::take(&mut *latencies.lock());
+    latencies.sort_unstable();
+    Ok(LoadTestReport {
+        responded: latencies.len(),
+        failed: failed.load(Ordering::Relaxed),
+        elapsed_ms,
+        latency_p50_ms: percentile(&latencies, 50),
+        latency_p95_ms: percentile(&latencies, 95),
+        latency_max_ms: latencies.last().copied().unwrap_or_default(),
+        max_pending,
+        memory_before_bytes,
+        memory_after_bytes: benchmark::peak_memory(),
+    })
+}
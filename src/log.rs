@@ -15,21 +15,41 @@
 // along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
 
 //! Logging Utilities
+//!
+//! Log lines go to the default writer and, once [`set_file`] is called, to the `signer.log` file
+//! in the log directory. The log file is rotated to `signer.log.1`, shifting the older files up
+//! by one, once it grows larger or older than its [`LogRotation`] policy allows, and only the
+//! newest [`retention`](LogRotation::retention) rotated files are kept.
 
 #![allow(unused_imports, unused_macros)] // NOTE: We are exposing them as a library for this crate.
 
 use core::fmt;
 use core::marker::Unpin;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::{const_mutex, Mutex};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::SystemTime,
+};
 use tokio::io::{self, AsyncWrite, AsyncWriteExt};
 
 pub use tokio::io::stdout;
 
 /// Log Level
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
 pub enum Level {
     /// Trace
     Trace,
 
+    /// Debugging Information
+    Debug,
+
     /// Information
     Info,
 
@@ -41,11 +61,21 @@ pub enum Level {
 }
 
 impl Level {
+    /// Log Levels from the Most to the Least Verbose
+    pub const ALL: [Self; 5] = [
+        Self::Trace,
+        Self::Debug,
+        Self::Info,
+        Self::Warn,
+        Self::Error,
+    ];
+
     /// Returns the loggging prefix for `self` as a static string.
     #[inline]
     const fn as_prefix(&self) -> &'static str {
         match self {
             Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
             Self::Info => "INFO ",
             Self::Warn => "WARN ",
             Self::Error => "ERROR",
@@ -53,24 +83,396 @@ impl Level {
     }
 }
 
-/// Prints the `display` as a log line to the `writer` with the given logging `level`.
+impl Default for Level {
+    #[inline]
+    fn default() -> Self {
+        Self::Trace
+    }
+}
+
+/// Minimum Log Level
+static LEVEL: Mutex<Level> = const_mutex(Level::Trace);
+
+/// Returns the minimum level of the log lines which are logged.
+#[inline]
+pub fn level() -> Level {
+    *LEVEL.lock()
+}
+
+/// Sets the minimum `level` of the log lines which are logged. Lines below it are dropped.
+///
+/// The level applies from the next log line on, so frontends can change it while the service is
+/// running.
+#[inline]
+pub fn set_level(level: Level) {
+    *LEVEL.lock() = level;
+}
+
+/// Log History Capacity
+pub const HISTORY_CAPACITY: usize = 1024;
+
+/// Log History
+static HISTORY: Mutex<VecDeque<String>> = const_mutex(VecDeque::new());
+
+/// Log Sink
+static SINK: Mutex<Option<fn(Level, &str)>> = const_mutex(None);
+
+/// Sets the `sink` which receives every log line next to the default writer, for example to
+/// forward the log to the system log.
+#[inline]
+pub fn set_sink(sink: fn(Level, &str)) {
+    *SINK.lock() = Some(sink);
+}
+
+/// Log File Name
+pub const LOG_FILE_NAME: &str = "signer.log";
+
+/// Default Maximum Log File Size in Bytes
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default Maximum Log File Age in Hours
+pub const DEFAULT_MAX_FILE_AGE_HOURS: u64 = 24 * 7;
+
+/// Default Number of Rotated Log Files which are Kept
+pub const DEFAULT_RETENTION: usize = 5;
+
+/// Log Rotation Policy
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct LogRotation {
+    /// Maximum Log File Size in Bytes
+    #[serde(default = "LogRotation::default_max_bytes")]
+    pub max_bytes: u64,
+
+    /// Maximum Log File Age in Hours
+    #[serde(default = "LogRotation::default_max_age_hours")]
+    pub max_age_hours: u64,
+
+    /// Number of Rotated Log Files which are Kept
+    ///
+    /// With no retention, the log file is deleted instead of rotated.
+    #[serde(default = "LogRotation::default_retention")]
+    pub retention: usize,
+}
+
+impl LogRotation {
+    /// Returns the default value of [`max_bytes`](Self::max_bytes) for deserialization.
+    #[inline]
+    fn default_max_bytes() -> u64 {
+        DEFAULT_MAX_FILE_BYTES
+    }
+
+    /// Returns the default value of [`max_age_hours`](Self::max_age_hours) for deserialization.
+    #[inline]
+    fn default_max_age_hours() -> u64 {
+        DEFAULT_MAX_FILE_AGE_HOURS
+    }
+
+    /// Returns the default value of [`retention`](Self::retention) for deserialization.
+    #[inline]
+    fn default_retention() -> usize {
+        DEFAULT_RETENTION
+    }
+}
+
+impl Default for LogRotation {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_age_hours: DEFAULT_MAX_FILE_AGE_HOURS,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+}
+
+/// Open Log File
+#[derive(Debug)]
+struct OpenFile {
+    /// File Handle
+    file: File,
+
+    /// File Size in Bytes
+    size: u64,
+
+    /// Creation Time of the File
+    created_at: SystemTime,
+}
+
+/// Log File
+#[derive(Debug)]
+struct LogFile {
+    /// Log Directory
+    directory: PathBuf,
+
+    /// Rotation Policy
+    rotation: LogRotation,
+
+    /// Open File
+    ///
+    /// The file is opened again by the next line after a rotation, a purge, or a failed write.
+    open: Option<OpenFile>,
+}
+
+impl LogFile {
+    /// Returns `true` if `file` has outgrown the rotation policy.
+    #[inline]
+    fn is_expired(&self, file: &OpenFile) -> bool {
+        file.size >= self.rotation.max_bytes
+            || file.created_at.elapsed().map_or(false, |age| {
+                age.as_secs() >= self.rotation.max_age_hours * 3600
+            })
+    }
+
+    /// Opens the log file, creating the log directory if it does not exist.
+    #[inline]
+    fn open(&self) -> io::Result<OpenFile> {
+        fs::create_dir_all(&self.directory)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.directory.join(LOG_FILE_NAME))?;
+        let metadata = file.metadata()?;
+        Ok(OpenFile {
+            size: metadata.len(),
+            created_at: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+            file,
+        })
+    }
+
+    /// Appends `line` to the log file, rotating it first if it has outgrown the rotation policy.
+    #[inline]
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let mut open = match self.open.take() {
+            Some(open) => open,
+            _ => self.open()?,
+        };
+        if self.is_expired(&open) {
+            drop(open);
+            rotate(&self.directory, self.rotation.retention)?;
+            open = self.open()?;
+        }
+        open.file.write_all(line.as_bytes())?;
+        open.size += line.len() as u64;
+        self.open = Some(open);
+        Ok(())
+    }
+}
+
+/// Log File
+static FILE: Mutex<Option<LogFile>> = const_mutex(None);
+
+/// Log File Writer
+///
+/// Log lines are sent to a dedicated thread which appends them to the log file, so that [`log`]
+/// never blocks the async task it is called from on file I/O or rotation.
+static WRITER: Mutex<Option<Sender<String>>> = const_mutex(None);
+
+/// Spawns the thread which appends the log lines it receives to the log file, returning the
+/// sender half of its channel.
+#[inline]
+fn spawn_writer() -> io::Result<Sender<String>> {
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::Builder::new()
+        .name("signer-log".into())
+        .spawn(move || {
+            for line in receiver {
+                if let Some(file) = FILE.lock().as_mut() {
+                    let _ = file.write(&line);
+                }
+            }
+        })?;
+    Ok(sender)
+}
+
+/// Starts writing every log line to the log file in `directory`, rotating it with the given
+/// `rotation` policy.
+///
+/// Writing to the log file is best effort: a line which cannot be written is only printed to the
+/// default writer, and the file is opened again for the next line. Lines are written in order by
+/// a background thread, so the newest lines can still be in flight when the process exits.
+#[inline]
+pub fn set_file(directory: PathBuf, rotation: LogRotation) {
+    *FILE.lock() = Some(LogFile {
+        directory,
+        rotation,
+        open: None,
+    });
+    let mut writer = WRITER.lock();
+    if writer.is_none() {
+        *writer = spawn_writer().ok();
+    }
+}
+
+/// Sets the `rotation` policy of the log file, if [`set_file`] was called. The new policy applies
+/// from the next log line on.
+#[inline]
+pub fn set_rotation(rotation: LogRotation) {
+    if let Some(file) = FILE.lock().as_mut() {
+        file.rotation = rotation;
+    }
+}
+
+/// Returns the path of the rotated log file with the given `index` in `directory`.
+#[inline]
+fn rotated_path(directory: &Path, index: usize) -> PathBuf {
+    directory.join(format!("{}.{}", LOG_FILE_NAME, index))
+}
+
+/// Returns the indices of the rotated log files in `directory`, in no particular order.
+#[inline]
+fn rotated_indices(directory: &Path) -> io::Result<Vec<usize>> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut indices = Vec::new();
+    for entry in entries {
+        if let Some(index) = entry?.file_name().to_str().and_then(|name| {
+            name.strip_prefix(LOG_FILE_NAME)?
+                .strip_prefix('.')?
+                .parse()
+                .ok()
+        }) {
+            indices.push(index);
+        }
+    }
+    Ok(indices)
+}
+
+/// Rotates the log file in `directory` to the first rotated file, shifting the rotated files up
+/// by one and deleting the ones past the `retention` count.
+#[inline]
+fn rotate(directory: &Path, retention: usize) -> io::Result<()> {
+    let mut indices = rotated_indices(directory)?;
+    indices.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+    for index in indices {
+        if index >= retention {
+            fs::remove_file(rotated_path(directory, index))?;
+        } else {
+            fs::rename(
+                rotated_path(directory, index),
+                rotated_path(directory, index + 1),
+            )?;
+        }
+    }
+    let current = directory.join(LOG_FILE_NAME);
+    let result = if retention == 0 {
+        fs::remove_file(current)
+    } else {
+        fs::rename(current, rotated_path(directory, 1))
+    };
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Deletes every rotated log file in `directory` and empties the current one, returning the
+/// number of bytes which were freed.
+#[inline]
+pub fn purge<P>(directory: P) -> io::Result<u64>
+where
+    P: AsRef<Path>,
+{
+    let directory = directory.as_ref();
+    let mut file = FILE.lock();
+    if let Some(file) = file.as_mut() {
+        if file.directory == directory {
+            file.open = None;
+        }
+    }
+    let mut freed = 0;
+    for index in rotated_indices(directory)? {
+        let path = rotated_path(directory, index);
+        freed += fs::metadata(&path)?.len();
+        fs::remove_file(path)?;
+    }
+    match OpenOptions::new()
+        .write(true)
+        .open(directory.join(LOG_FILE_NAME))
+    {
+        Ok(current) => {
+            freed += current.metadata()?.len();
+            current.set_len(0)?;
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    Ok(freed)
+}
+
+/// Returns the most recent log lines, oldest first, keeping at most [`HISTORY_CAPACITY`] lines.
+#[inline]
+pub fn history() -> Vec<String> {
+    HISTORY.lock().iter().cloned().collect()
+}
+
+/// Redaction Placeholder
+pub const REDACTED: &str = "<redacted>";
+
+/// Redacted Log Value
+///
+/// Request and response payloads are wrapped in this type at the call sites which log them, so
+/// that only the [`REDACTED`] placeholder reaches the log file, the log history and the exports
+/// built from it.
+pub struct Redacted<'t, T>(pub &'t T)
+where
+    T: ?Sized;
+
+impl<T> fmt::Debug for Redacted<'_, T>
+where
+    T: ?Sized,
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Redacted<'_, T>
+where
+    T: ?Sized,
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Prints the `display` as a log line to the `writer` with the given logging `level`, unless the
+/// `level` is below the minimum [`level()`].
 #[inline]
 pub async fn log<W, D>(writer: &mut W, level: Level, display: D) -> io::Result<()>
 where
     W: AsyncWrite + Unpin,
     D: fmt::Display,
 {
-    writer
-        .write_all(
-            format!(
-                "{} {}: {}\n",
-                level.as_prefix(),
-                chrono::offset::Utc::now(),
-                display
-            )
-            .as_bytes(),
-        )
-        .await
+    if level < self::level() {
+        return Ok(());
+    }
+    let line = format!(
+        "{} {}: {}\n",
+        level.as_prefix(),
+        chrono::offset::Utc::now(),
+        display
+    );
+    {
+        let mut history = HISTORY.lock();
+        if history.len() >= HISTORY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back(line.trim_end().to_owned());
+    }
+    if let Some(writer) = WRITER.lock().as_ref() {
+        let _ = writer.send(line.clone());
+    }
+    let sink = *SINK.lock();
+    if let Some(sink) = sink {
+        sink(level, line.trim_end());
+    }
+    writer.write_all(line.as_bytes()).await
 }
 
 /// Logs a single log line to the default writer of the given `$level`.
@@ -91,6 +493,15 @@ macro_rules! trace_macro {
 
 pub(crate) use trace_macro as trace;
 
+/// Logs some debugging information to the default writer.
+macro_rules! debug_macro {
+    ($($expr:expr),*) => {{
+        $crate::log::log!($crate::log::Level::Debug, $($expr),*)
+    }}
+}
+
+pub(crate) use debug_macro as debug;
+
 /// Logs some basic information to the default writer.
 macro_rules! info_macro {
     ($($expr:expr),*) => {{
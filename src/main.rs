@@ -0,0 +1,509 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Manta Signer Command Line Interface
+
+#![forbid(missing_docs)]
+
+use async_std::task;
+use core::time::Duration;
+use manta_signer::{
+    airgap::SignFileRequest,
+    audit::{self, ExportFormat, ExportRange},
+    config::{Config, Recovery, RecoveryChoice, Setup},
+    discovery::Discovery,
+    label::Labels,
+    log,
+    pairing::PairingOffer,
+    parameters::bundle::{parse_public_key, BundleError, Bundles, BUILTIN_VERSION},
+    secret::{
+        Authorizer, ConfirmationFuture, Password, PasswordFuture, RecoveryFuture, SecretString,
+        UnitFuture,
+    },
+    serde::Serialize,
+    service,
+    wallet::WalletRegistry,
+};
+use std::{env, fs, path::Path, process};
+
+#[cfg(any(unix, windows))]
+use manta_signer::daemon::{self, ControlRequest, ControlResponse};
+
+#[cfg(feature = "benchmark")]
+use manta_signer::benchmark::{self, BenchmarkReport};
+
+/// Usage String
+const USAGE: &str = "\
+Usage:
+    manta-signer serve
+    manta-signer daemon
+    manta-signer ctl <status|lock|unlock|pending>
+    manta-signer ctl <approve|reject> <ID>
+    manta-signer service
+    manta-signer params list
+    manta-signer params install <BUNDLE_DIRECTORY>
+    manta-signer params rollback
+    manta-signer sign-file <INPUT> <OUTPUT>
+    manta-signer audit verify
+    manta-signer audit export <csv|json> <OUTPUT> [FROM] [TO]
+    manta-signer benchmark [ROUNDS]
+    manta-signer logs purge
+
+The `serve` subcommand runs the signer service in the foreground and asks for the password on the
+terminal. The `daemon` subcommand runs it without a terminal, for example under an init system. The
+daemon starts locked, `ctl unlock` hands it the password until `ctl lock`. Every request waits
+until `ctl approve` or `ctl reject` answers it, with the identifier listed by `ctl pending`, and an
+approved request is only signed while the daemon is unlocked. On Windows, the `service` subcommand
+runs the daemon as a Windows service and must be started by the service control manager. The
+`sign-file` subcommand sends the request to the running signer, authenticating with the API key in
+the `MANTA_SIGNER_API_KEY` environment variable if it is set. The `audit` subcommands work on the
+audit log of the active wallet, and `audit export` exports the audit records decided between the
+optional `FROM` and `TO` UNIX timestamps. The `benchmark` subcommand proves `ROUNDS` sample
+transfers of every circuit, three by default, and is only available in builds with the `benchmark`
+feature. The `logs purge` subcommand deletes the rotated log files and empties the current one.";
+
+/// Prints `message` and the usage string to standard error and exits with a failure code.
+#[inline]
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(1)
+}
+
+/// Terminal Authorizer
+///
+/// The authorizer shows the prompts on standard error and reads the password from the terminal.
+/// An empty password rejects the prompt.
+#[derive(Default)]
+struct Terminal {
+    /// Retry Flag
+    ///
+    /// This is set while a password is requested, so that a new request means that the last
+    /// password did not match.
+    retrying: bool,
+}
+
+impl Authorizer for Terminal {
+    #[inline]
+    fn password(&mut self) -> PasswordFuture {
+        if self.retrying {
+            eprintln!("The password does not match.");
+        }
+        self.retrying = true;
+        Box::pin(async move {
+            match task::spawn_blocking(|| rpassword::prompt_password("Password: ")).await {
+                Ok(password) if !password.is_empty() => {
+                    Password::from_known(SecretString::new(password))
+                }
+                _ => Password::from_unknown(),
+            }
+        })
+    }
+
+    #[inline]
+    fn setup<'s>(&'s mut self, setup: &'s Setup) -> UnitFuture<'s> {
+        match setup {
+            Setup::CreateAccount(mnemonic) => {
+                let phrase = serde_json::to_value(mnemonic).unwrap_or_default();
+                eprintln!(
+                    "Write down the recovery phrase of the new account and keep it safe:\
+                     \n\n    {}\n\nThen choose the password of the account.",
+                    phrase.as_str().unwrap_or_default()
+                );
+            }
+            Setup::Login => eprintln!("Enter the password to unlock the signer."),
+            Setup::Recover(_) => {}
+        }
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn wake<T>(&mut self, prompt: &T) -> UnitFuture
+    where
+        T: Serialize,
+    {
+        if let Ok(prompt) = serde_json::to_string_pretty(prompt) {
+            eprintln!(
+                "Authorization requested:\n{}\nEnter the password to approve it or nothing to \
+                 reject it.",
+                prompt
+            );
+        }
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn confirm<T>(&mut self, prompt: &T) -> ConfirmationFuture
+    where
+        T: Serialize,
+    {
+        if let Ok(prompt) = serde_json::to_string_pretty(prompt) {
+            eprintln!("Confirmation requested:\n{}", prompt);
+        }
+        Box::pin(async move {
+            task::spawn_blocking(|| {
+                eprint!("Approve it? [y/N] ");
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer).is_ok()
+                    && answer.trim().eq_ignore_ascii_case("y")
+            })
+            .await
+        })
+    }
+
+    #[inline]
+    fn recover<'s>(&'s mut self, recovery: &'s Recovery) -> RecoveryFuture<'s> {
+        eprintln!(
+            "The signer storage is corrupted: {}. Use the signer app to recover it.",
+            recovery.reason
+        );
+        Box::pin(async move { RecoveryChoice::Abort })
+    }
+
+    #[inline]
+    fn enroll_totp<'s>(&'s mut self, uri: &'s str) -> UnitFuture<'s> {
+        eprintln!(
+            "Enroll this TOTP secret into your authenticator app: {}",
+            uri
+        );
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn show_pairing<'s>(&'s mut self, offer: &'s PairingOffer) -> UnitFuture<'s> {
+        eprintln!(
+            "Open this link on the companion device before it expires: {}",
+            offer.uri
+        );
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn cooldown(&mut self, remaining: Duration) -> UnitFuture {
+        eprintln!(
+            "Too many failed attempts. Try again in {} seconds.",
+            (remaining.as_millis() as u64 + 999) / 1000
+        );
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn dismiss(&mut self) -> UnitFuture {
+        self.retrying = false;
+        eprintln!("The prompt expired.");
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn sleep(&mut self) -> UnitFuture {
+        self.retrying = false;
+        Box::pin(async move {})
+    }
+}
+
+/// Runs the `params` subcommand with `args` against the parameter bundles of `config`.
+#[inline]
+fn params(config: &Config, args: &[String]) -> Result<(), BundleError> {
+    let bundles = Bundles::new(config.data_directory());
+    match args {
+        [command] if command == "list" => {
+            let installed = bundles.list()?;
+            if installed.is_empty() {
+                println!("No parameter bundles installed. Using the Manta SDK parameters.");
+            }
+            for bundle in installed {
+                println!(
+                    "{} {}",
+                    if bundle.active { "*" } else { " " },
+                    bundle.version
+                );
+            }
+        }
+        [command, source] if command == "install" => {
+            let trusted_keys = config
+                .parameter_keys
+                .iter()
+                .map(|key| {
+                    parse_public_key(key)
+                        .unwrap_or_else(|| fail(&format!("invalid parameter key: {}", key)))
+                })
+                .collect::<Vec<_>>();
+            if trusted_keys.is_empty() {
+                fail("no trusted parameter keys are configured");
+            }
+            let version = bundles.install(Path::new(source), &trusted_keys)?;
+            println!("Installed and activated parameter bundle {}.", version);
+        }
+        [command] if command == "rollback" => match bundles.rollback()?.as_str() {
+            BUILTIN_VERSION => println!("Rolled back to the Manta SDK parameters."),
+            version => println!("Rolled back to parameter bundle {}.", version),
+        },
+        _ => fail("invalid `params` subcommand"),
+    }
+    Ok(())
+}
+
+/// Asks the running signer of `config` to sign the request in the `input` file, writing the
+/// response to the `output` file.
+#[inline]
+fn sign_file(config: &Config, input: &str, output: &str) -> Result<String, String> {
+    let request = SignFileRequest {
+        input: fs::canonicalize(input).map_err(|err| format!("{}: {}", input, err))?,
+        output: env::current_dir()
+            .map_err(|err| err.to_string())?
+            .join(output),
+    };
+    let discovery = Discovery::load(config.discovery_path()).map_err(|err| err.to_string())?;
+    if matches!(&discovery, Some(discovery) if !discovery.verify()) {
+        return Err("the discovery file has an invalid signature".into());
+    }
+    let address = discovery
+        .as_ref()
+        .and_then(|discovery| discovery.payload.client_address())
+        .unwrap_or_else(|| config.client_address());
+    let mut call = ureq::post(&format!("http://{}/signFile", address));
+    if let Ok(key) = env::var("MANTA_SIGNER_API_KEY") {
+        call = call.set("Authorization", &format!("Bearer {}", key));
+    }
+    let response = call
+        .send_json(serde_json::to_value(&request).map_err(|err| err.to_string())?)
+        .map_err(|err| err.to_string())?
+        .into_json::<Result<bool, serde_json::Value>>()
+        .map_err(|err| err.to_string())?;
+    match response {
+        Ok(true) => Ok(format!("Signed response written to {}.", output)),
+        Ok(false) => Ok(format!("The signer rejected the request, see {}.", output)),
+        Err(err) => Err(format!("invalid request file: {}", err)),
+    }
+}
+
+/// Parses the optional UNIX timestamp `bound` of an audit export range.
+#[inline]
+fn parse_bound(bound: Option<&String>) -> Option<i64> {
+    bound.map(|bound| {
+        bound
+            .parse()
+            .unwrap_or_else(|_| fail(&format!("invalid timestamp: {}", bound)))
+    })
+}
+
+/// Runs the `audit` subcommand with `args` against the audit log of the active wallet of
+/// `config`, returning `false` if the hash chain of the log is broken.
+#[inline]
+fn audit(config: &Config, args: &[String]) -> std::io::Result<bool> {
+    let audit_log_path =
+        config.audit_log_path(&WalletRegistry::load(config.wallets_path())?.active);
+    match args {
+        [command] if command == "verify" => {
+            let verification = audit::verify(&audit_log_path)?;
+            match verification.broken_at {
+                Some(line) => println!(
+                    "The audit log was tampered with: the chain breaks at line {} after {} intact \
+                     records.",
+                    line, verification.records
+                ),
+                _ => println!(
+                    "The audit log is intact with {} records. Head: {}",
+                    verification.records, verification.head
+                ),
+            }
+            Ok(verification.is_intact())
+        }
+        [command, format, output, bounds @ ..] if command == "export" && bounds.len() <= 2 => {
+            let format = match format.as_str() {
+                "csv" => ExportFormat::Csv,
+                "json" => ExportFormat::Json,
+                _ => fail(&format!("invalid export format: {}", format)),
+            };
+            let range = ExportRange {
+                from: parse_bound(bounds.first()),
+                to: parse_bound(bounds.get(1)),
+            };
+            fs::write(
+                output,
+                audit::export(&audit_log_path, format, range, &Labels::default())?,
+            )?;
+            println!("Audit log exported to {}.", output);
+            Ok(true)
+        }
+        _ => fail("invalid `audit` subcommand"),
+    }
+}
+
+/// Parses the request identifier of the `ctl approve` and `ctl reject` subcommands.
+#[cfg(any(unix, windows))]
+#[inline]
+fn request_id(id: &str) -> u64 {
+    id.trim_start_matches('#')
+        .parse()
+        .unwrap_or_else(|_| fail("invalid request identifier"))
+}
+
+/// Runs the `ctl` subcommand `command` with the arguments in `rest` against the daemon of
+/// `config`.
+#[cfg(any(unix, windows))]
+#[inline]
+fn ctl(config: &Config, command: &str, rest: &[String]) -> Result<(), String> {
+    let request = match (command, rest) {
+        ("status", []) => ControlRequest::Status,
+        ("lock", []) => ControlRequest::Lock,
+        ("unlock", []) => ControlRequest::Unlock {
+            password: rpassword::prompt_password("Password: ").map_err(|err| err.to_string())?,
+        },
+        ("pending", []) => ControlRequest::Pending,
+        ("approve", [id]) => ControlRequest::Approve { id: request_id(id) },
+        ("reject", [id]) => ControlRequest::Reject { id: request_id(id) },
+        _ => fail("invalid `ctl` subcommand"),
+    };
+    let response = daemon::control(config.control_socket_path(), &request)
+        .map_err(|err| format!("unable to reach the daemon: {}", err))?
+        .map_err(|err| err.to_string())?;
+    match response {
+        ControlResponse::Status(status) => {
+            println!("manta-signer {}", status.version);
+            println!("{}", if status.locked { "Locked" } else { "Unlocked" });
+            println!("Pending requests: {}", status.pending);
+            println!("Approval sessions: {}", status.sessions);
+        }
+        ControlResponse::Done if command == "lock" => println!("The daemon is locked."),
+        ControlResponse::Done if command == "approve" => println!("The request is approved."),
+        ControlResponse::Done if command == "reject" => println!("The request is rejected."),
+        ControlResponse::Done => println!("The daemon is unlocked."),
+        ControlResponse::Pending(requests) if requests.is_empty() => {
+            println!("There are no pending requests.")
+        }
+        ControlResponse::Pending(requests) => {
+            for request in requests {
+                println!(
+                    "#{} {} (received at {}): {}",
+                    request.id, request.kind, request.received_at, request.preview
+                );
+            }
+        }
+        ControlResponse::Unknown => {
+            return Err("there is no pending request with this identifier".into())
+        }
+    }
+    Ok(())
+}
+
+/// Prints the benchmark `report`.
+#[cfg(feature = "benchmark")]
+#[inline]
+fn print_benchmark(report: &BenchmarkReport) {
+    println!("manta-signer {}", report.version);
+    if let Some(cpus) = report.cpus {
+        println!("CPUs: {}", cpus);
+    }
+    println!("Parameter loading: {} ms", report.parameter_loading_ms);
+    for timing in &report.circuits {
+        println!(
+            "{}: {} proofs, mean {} ms, min {} ms, max {} ms",
+            timing.circuit, timing.proofs, timing.mean_ms, timing.min_ms, timing.max_ms
+        );
+    }
+    match report.peak_memory_bytes {
+        Some(bytes) => println!("Peak memory: {} MiB", bytes / (1024 * 1024)),
+        _ => println!("Peak memory: unavailable on this platform"),
+    }
+}
+
+/// Runs the command line interface.
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let config = Config::load_default().expect("Unable to load the server configuration.");
+    match args.split_first() {
+        Some((command, [])) if command == "serve" => {
+            if let Err(err) = task::block_on(service::start(config, Terminal::default())) {
+                eprintln!("error: {:?}", err);
+                process::exit(1);
+            }
+        }
+        #[cfg(any(unix, windows))]
+        Some((command, [])) if command == "daemon" => {
+            if let Err(err) = task::block_on(daemon::run(config)) {
+                eprintln!("error: {:?}", err);
+                process::exit(1);
+            }
+        }
+        #[cfg(any(unix, windows))]
+        Some((command, [subcommand, rest @ ..])) if command == "ctl" => {
+            if let Err(err) = ctl(&config, subcommand, rest) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        #[cfg(windows)]
+        Some((command, [])) if command == "service" => {
+            if let Err(err) = manta_signer::winservice::run() {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        #[cfg(not(any(unix, windows)))]
+        Some((command, _)) if command == "daemon" || command == "ctl" => {
+            fail("the daemon is not supported on this platform")
+        }
+        Some((command, rest)) if command == "params" => {
+            if let Err(err) = params(&config, rest) {
+                eprintln!("error: {:?}", err);
+                process::exit(1);
+            }
+        }
+        Some((command, rest)) if command == "sign-file" => match rest {
+            [input, output] => match sign_file(&config, input, output) {
+                Ok(message) => println!("{}", message),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            },
+            _ => fail("invalid `sign-file` arguments"),
+        },
+        Some((command, rest)) if command == "audit" => match audit(&config, rest) {
+            Ok(true) => {}
+            Ok(false) => process::exit(2),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        },
+        #[cfg(feature = "benchmark")]
+        Some((command, rest)) if command == "benchmark" && rest.len() <= 1 => {
+            let rounds = rest.first().map_or(benchmark::DEFAULT_ROUNDS, |rounds| {
+                rounds
+                    .parse()
+                    .unwrap_or_else(|_| fail(&format!("invalid number of rounds: {}", rounds)))
+            });
+            match benchmark::run(config.data_directory(), rounds) {
+                Ok(report) => print_benchmark(&report),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        Some((command, [subcommand])) if command == "logs" && subcommand == "purge" => {
+            match log::purge(config.log_directory()) {
+                Ok(freed) => println!("Purged {} KiB of logs.", freed / 1024),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        _ => fail("missing or unknown subcommand"),
+    }
+}
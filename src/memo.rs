@@ -0,0 +1,202 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction Memos
+//!
+//! Users can attach a memo to every signed transaction, either supplied by the dapp with the
+//! signing request or entered when approving it. Memos are stored encrypted next to the wallet
+//! file and keyed by the hash of the audit record of the approval, so that the history in
+//! [`history`] shows each transaction with its memo.
+
+use crate::{
+    audit::{self, AuditRecord, ExportRange},
+    label::Labels,
+    storage::cipher::{self, FileKey},
+};
+use manta_crypto::rand::OsRng;
+use manta_pay::signer::SignRequest;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::{Mutex, RwLock};
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Maximum Memo Length in Characters
+pub const MAX_MEMO_LENGTH: usize = 256;
+
+/// Normalizes `memo`, trimming it and truncating it to [`MAX_MEMO_LENGTH`] characters, returning
+/// `None` if it is empty.
+#[inline]
+pub fn normalize(memo: &str) -> Option<String> {
+    let memo = memo
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n')
+        .take(MAX_MEMO_LENGTH)
+        .collect::<String>();
+    (!memo.is_empty()).then(|| memo)
+}
+
+/// Signing Request with a Memo
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct MemoSignRequest {
+    /// Signing Request
+    pub request: SignRequest,
+
+    /// Memo Supplied by the Dapp
+    pub memo: Option<String>,
+}
+
+/// Memo Store
+///
+/// The store is shared with the frontend through the service [`Handle`](crate::service::Handle)
+/// and is opened by the service once the signer is unlocked.
+#[derive(Debug, Default)]
+pub struct MemoStore {
+    /// Memos by Audit Record Hash
+    memos: RwLock<BTreeMap<String, String>>,
+
+    /// Encrypted Memo File Path and Storage Key
+    storage: Mutex<Option<(PathBuf, Arc<FileKey>)>>,
+}
+
+impl MemoStore {
+    /// Loads the memos stored encrypted under `key` at `path`, starting without memos if it does
+    /// not exist, and saves later memos there.
+    #[inline]
+    pub fn open(&self, path: PathBuf, key: Arc<FileKey>) -> Result<(), cipher::Error> {
+        let memos = cipher::load_or_quarantine(&path, &key)?.unwrap_or_default();
+        *self.memos.write() = memos;
+        *self.storage.lock() = Some((path, key));
+        Ok(())
+    }
+
+    /// Returns the memo of the transaction approved in the audit record with the given `hash`.
+    #[inline]
+    pub fn get(&self, hash: &str) -> Option<String> {
+        self.memos.read().get(hash).cloned()
+    }
+
+    /// Attaches `memo` to the transaction approved in the audit record with the given `hash` and
+    /// saves the memos.
+    #[inline]
+    pub fn insert(&self, hash: String, memo: String) -> Result<(), cipher::Error> {
+        let storage = self.storage.lock();
+        let (path, key) = storage
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "the signer is locked"))?;
+        let mut memos = self.memos.read().clone();
+        memos.insert(hash, memo);
+        cipher::save(path, key, &memos, 0, &mut OsRng)?;
+        *self.memos.write() = memos;
+        Ok(())
+    }
+}
+
+/// History Query
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct HistoryQuery {
+    /// Search Text
+    ///
+    /// Only entries whose memo, kind, origin, asset symbol or asset label contain this text,
+    /// ignoring case, are returned.
+    #[serde(default)]
+    pub search: Option<String>,
+
+    /// Time Range
+    #[serde(default)]
+    pub range: ExportRange,
+}
+
+/// History Entry
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct HistoryEntry {
+    /// Audit Record
+    pub record: AuditRecord,
+
+    /// Asset Label
+    pub asset_label: Option<String>,
+
+    /// Transaction Memo
+    pub memo: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Returns `true` if any of the searchable fields of `self` contains the lowercase `search`
+    /// text.
+    #[inline]
+    fn matches(&self, search: &str) -> bool {
+        [
+            self.memo.as_deref(),
+            Some(self.record.kind.as_str()),
+            self.record.origin.as_deref(),
+            self.record
+                .amount
+                .as_ref()
+                .and_then(|amount| amount.symbol.as_deref()),
+            self.asset_label.as_deref(),
+        ]
+        .iter()
+        .flatten()
+        .any(|field| field.to_lowercase().contains(search))
+    }
+}
+
+/// Returns the history of the audit log at `path` which matches `query`, newest first, with the
+/// asset `labels` and transaction `memos`.
+#[inline]
+pub fn history<P>(
+    path: P,
+    query: &HistoryQuery,
+    labels: &Labels,
+    memos: &MemoStore,
+) -> io::Result<Vec<HistoryEntry>>
+where
+    P: AsRef<Path>,
+{
+    let search = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|search| !search.is_empty())
+        .map(str::to_lowercase);
+    let mut entries = audit::read(path)?
+        .into_iter()
+        .filter(|record| query.range.contains(record.timestamp))
+        .map(|record| HistoryEntry {
+            asset_label: record
+                .amount
+                .as_ref()
+                .and_then(|amount| labels.asset(amount.asset_id))
+                .map(Into::into),
+            memo: memos.get(&record.hash),
+            record,
+        })
+        .filter(|entry| {
+            search
+                .as_deref()
+                .map_or(true, |search| entry.matches(search))
+        })
+        .collect::<Vec<_>>();
+    entries.reverse();
+    Ok(entries)
+}
@@ -0,0 +1,154 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Recovery Phrase Validation
+//!
+//! Before a recovery phrase is imported, [`validate`] checks its word count, that every word is
+//! in the English BIP-39 wordlist, and its checksum, and reports every issue it finds with the
+//! position of the offending word, so that the recovery UI can point the user at the exact typo.
+
+use bip39::Language;
+use manta_util::serde::{Deserialize, Serialize};
+
+/// Valid Recovery Phrase Word Counts
+pub const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// Maximum Number of Suggestions for an Unknown Word
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Maximum Edit Distance between an Unknown Word and its Suggestions
+pub const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Recovery Phrase Issue
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    crate = "manta_util::serde",
+    content = "content",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum MnemonicIssue {
+    /// Word Count is not one of the [`WORD_COUNTS`]
+    WordCount {
+        /// Number of Words in the Phrase
+        count: usize,
+    },
+
+    /// Word is not in the Wordlist
+    UnknownWord {
+        /// Word Position, Starting at Zero
+        index: usize,
+
+        /// Wordlist Words Closest to the Unknown Word
+        suggestions: Vec<String>,
+    },
+
+    /// Checksum does not Match
+    ///
+    /// The checksum is encoded in the last word, but any word of the phrase can be wrong.
+    InvalidChecksum,
+}
+
+/// Recovery Phrase Validation Report
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct MnemonicReport {
+    /// Number of Words in the Phrase
+    pub word_count: usize,
+
+    /// Issues Found in the Phrase
+    ///
+    /// The phrase is valid if there are none. The checksum is only checked once the word count
+    /// is valid and every word is known.
+    pub issues: Vec<MnemonicIssue>,
+}
+
+impl MnemonicReport {
+    /// Returns `true` if the phrase has no issues.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Validates the recovery `phrase`, ignoring case and the whitespace between words.
+#[inline]
+pub fn validate(phrase: &str) -> MnemonicReport {
+    let words = phrase
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let mut report = MnemonicReport {
+        word_count: words.len(),
+        issues: Vec::new(),
+    };
+    if !WORD_COUNTS.contains(&words.len()) {
+        report
+            .issues
+            .push(MnemonicIssue::WordCount { count: words.len() });
+    }
+    for (index, word) in words.iter().enumerate() {
+        if Language::English.find_word(word).is_none() {
+            report.issues.push(MnemonicIssue::UnknownWord {
+                index,
+                suggestions: suggestions(word),
+            });
+        }
+    }
+    if report.is_valid()
+        && bip39::Mnemonic::parse_in_normalized(Language::English, &words.join(" ")).is_err()
+    {
+        report.issues.push(MnemonicIssue::InvalidChecksum);
+    }
+    report
+}
+
+/// Returns at most [`MAX_SUGGESTIONS`] wordlist words within [`MAX_SUGGESTION_DISTANCE`] edits
+/// of `word`, closest first.
+#[inline]
+fn suggestions(word: &str) -> Vec<String> {
+    let mut candidates = Language::English
+        .word_list()
+        .iter()
+        .filter_map(|candidate| {
+            let distance = edit_distance(word, candidate);
+            (distance <= MAX_SUGGESTION_DISTANCE).then(|| (distance, *candidate))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.into())
+        .collect()
+}
+
+/// Returns the Levenshtein distance between `lhs` and `rhs`.
+#[inline]
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let rhs = rhs.chars().collect::<Vec<_>>();
+    let mut previous = (0..=rhs.len()).collect::<Vec<_>>();
+    for (i, left) in lhs.chars().enumerate() {
+        let mut current = vec![i + 1; rhs.len() + 1];
+        for (j, right) in rhs.iter().enumerate() {
+            current[j + 1] = (previous[j] + usize::from(left != *right))
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[rhs.len()]
+}
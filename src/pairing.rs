@@ -0,0 +1,410 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Mobile Companion Pairing
+//!
+//! A companion device pairs with the signer by scanning a [`PairingOffer`], usually rendered as a
+//! QR code, and registering its Ed25519 public key with the one-time token it contains. Once a
+//! device is paired, signing requests are held until one of the paired devices signs its decision
+//! for the challenge of the [`PendingApproval`]. Pending approvals are signed with the signer
+//! [`Identity`](crate::identity::Identity) so that the device can check that they come from the
+//! signer it paired with. The paired devices are stored encrypted under the storage key, so that
+//! no device can be added while the signer is locked.
+
+use crate::{
+    secret::ConstantTimeEq,
+    storage::cipher::{self, FileKey},
+};
+use core::time::Duration;
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use http_types::url::form_urlencoded;
+use manta_crypto::rand::{OsRng, RngCore};
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::oneshot;
+
+/// Pairing Offer Lifetime
+pub const PAIRING_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Device Approval Timeout
+///
+/// Signing requests which are not approved by a paired device within this interval are
+/// rejected.
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Pairing Token and Challenge Length in Bytes
+pub const TOKEN_LENGTH: usize = 32;
+
+/// Samples a random hex-encoded token.
+#[inline]
+fn random_token() -> String {
+    let mut bytes = [0; TOKEN_LENGTH];
+    OsRng.fill_bytes(&mut bytes);
+    HEXLOWER.encode(&bytes)
+}
+
+/// Returns the current time as a UNIX timestamp.
+#[inline]
+fn now() -> i64 {
+    chrono::offset::Utc::now().timestamp()
+}
+
+/// Paired Device
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PairedDevice {
+    /// Device Name
+    pub name: String,
+
+    /// Hex-Encoded Ed25519 Public Key of the Device
+    pub public_key: String,
+
+    /// Pairing Time as a UNIX Timestamp
+    pub paired_at: i64,
+}
+
+/// Pairing Offer
+///
+/// The offer is only shown to the user by the signer UI, see
+/// [`Authorizer::show_pairing`](crate::secret::Authorizer::show_pairing), since its one-time token
+/// lets any device which learns it pair with the signer.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PairingOffer {
+    /// Pairing URI
+    ///
+    /// The URI carries the signer URL, the signer identity key, and the one-time pairing token.
+    pub uri: String,
+
+    /// Expiration Time as a UNIX Timestamp
+    pub expires_at: i64,
+}
+
+/// Pairing Session
+///
+/// This is the answer to a request which starts pairing a companion device. It leaves out the
+/// [`PairingOffer`] itself, which is only shown by the signer UI.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PairingSession {
+    /// Expiration Time of the Pairing Offer as a UNIX Timestamp
+    pub expires_at: i64,
+}
+
+/// Pairing Request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PairRequest {
+    /// One-Time Pairing Token
+    pub token: String,
+
+    /// Device Name
+    pub name: String,
+
+    /// Hex-Encoded Ed25519 Public Key of the Device
+    pub public_key: String,
+}
+
+/// Pending Approval
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PendingApproval {
+    /// Hex-Encoded Challenge which the Device Signs
+    pub challenge: String,
+
+    /// Summary of the Request
+    pub summary: String,
+}
+
+/// Device Approval Request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DeviceApprovalRequest {
+    /// Hex-Encoded Ed25519 Public Key of the Device
+    pub public_key: String,
+
+    /// Hex-Encoded Challenge of the [`PendingApproval`]
+    pub challenge: String,
+
+    /// Approval Decision
+    pub approve: bool,
+
+    /// Hex-Encoded Ed25519 Signature over the [`decision message`](decision_message)
+    pub signature: String,
+}
+
+/// Returns the message which a device signs to approve or deny the request with `challenge`.
+///
+/// The message is the challenge bytes followed by a single byte which is `1` for approval and `0`
+/// for denial.
+#[inline]
+pub fn decision_message(challenge: &[u8], approve: bool) -> Vec<u8> {
+    let mut message = challenge.to_vec();
+    message.push(approve as u8);
+    message
+}
+
+/// Paired Device Store
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct DeviceStore {
+    /// Paired Devices
+    devices: Vec<PairedDevice>,
+}
+
+impl DeviceStore {
+    /// Loads the store encrypted under `key` at `path`, returning an empty store if it does not
+    /// exist.
+    #[inline]
+    pub fn load<P>(path: P, key: &FileKey) -> Result<Self, cipher::Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(cipher::load_or_quarantine(path, key)?.unwrap_or_default())
+    }
+
+    /// Loads the plaintext store written by earlier versions at `path`, returning `None` if it
+    /// does not exist.
+    #[inline]
+    pub fn load_legacy<P>(path: P) -> io::Result<Option<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the store encrypted under `key` to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P, key: &FileKey) -> Result<(), cipher::Error>
+    where
+        P: AsRef<Path>,
+    {
+        cipher::save(path, key, self, 0, &mut OsRng)
+    }
+
+    /// Returns the paired devices.
+    #[inline]
+    pub fn devices(&self) -> &[PairedDevice] {
+        &self.devices
+    }
+}
+
+/// Device Approval Gate
+pub struct DeviceApproval {
+    /// Device Store Path
+    path: PathBuf,
+
+    /// Storage Key
+    key: Arc<FileKey>,
+
+    /// Paired Devices
+    store: Mutex<DeviceStore>,
+
+    /// Active Pairing Token and its Expiration Time
+    offer: Mutex<Option<(String, i64)>>,
+
+    /// Pending Approval and its Sender
+    pending: Mutex<Option<(PendingApproval, oneshot::Sender<bool>)>>,
+}
+
+impl DeviceApproval {
+    /// Builds the device approval gate with the paired devices of `store`, saving later changes
+    /// encrypted under `key` at `path`.
+    #[inline]
+    pub fn new(path: PathBuf, key: Arc<FileKey>, store: DeviceStore) -> Self {
+        Self {
+            path,
+            key,
+            store: Mutex::new(store),
+            offer: Default::default(),
+            pending: Default::default(),
+        }
+    }
+
+    /// Returns `true` if at least one device is paired.
+    #[inline]
+    pub fn has_devices(&self) -> bool {
+        !self.store.lock().devices.is_empty()
+    }
+
+    /// Returns the paired devices.
+    #[inline]
+    pub fn devices(&self) -> Vec<PairedDevice> {
+        self.store.lock().devices.clone()
+    }
+
+    /// Starts a new pairing session, replacing any previous offer, for the signer reachable at
+    /// `service_url` with the given hex-encoded `signer_key`.
+    #[inline]
+    pub fn start_pairing(&self, service_url: &str, signer_key: &str) -> PairingOffer {
+        let token = random_token();
+        let expires_at = now() + PAIRING_TIMEOUT.as_secs() as i64;
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("url", service_url)
+            .append_pair("key", signer_key)
+            .append_pair("token", &token)
+            .finish();
+        let uri = format!("manta-signer://pair?{}", query);
+        *self.offer.lock() = Some((token, expires_at));
+        PairingOffer { uri, expires_at }
+    }
+
+    /// Completes the pairing session with `request`, returning `true` if the device was paired.
+    /// The pairing token can only be used once.
+    #[inline]
+    pub fn complete_pairing(&self, request: PairRequest) -> Result<bool, cipher::Error> {
+        let valid_token = match self.offer.lock().take() {
+            Some((token, expires_at)) => {
+                expires_at >= now() && bool::from(token.as_bytes().ct_eq(request.token.as_bytes()))
+            }
+            _ => false,
+        };
+        if !valid_token || parse_public_key(&request.public_key).is_none() {
+            return Ok(false);
+        }
+        let mut store = self.store.lock().clone();
+        if store
+            .devices
+            .iter()
+            .any(|device| device.public_key == request.public_key)
+        {
+            return Ok(false);
+        }
+        store.devices.push(PairedDevice {
+            name: request.name,
+            public_key: request.public_key.to_lowercase(),
+            paired_at: now(),
+        });
+        store.save(&self.path, &self.key)?;
+        *self.store.lock() = store;
+        Ok(true)
+    }
+
+    /// Unpairs the device with the hex-encoded `public_key`, returning `true` if it was paired.
+    #[inline]
+    pub fn unpair(&self, public_key: &str) -> Result<bool, cipher::Error> {
+        let mut store = self.store.lock().clone();
+        let count = store.devices.len();
+        store
+            .devices
+            .retain(|device| !device.public_key.eq_ignore_ascii_case(public_key));
+        if store.devices.len() == count {
+            return Ok(false);
+        }
+        store.save(&self.path, &self.key)?;
+        *self.store.lock() = store;
+        Ok(true)
+    }
+
+    /// Returns the approval currently waiting for a device.
+    #[inline]
+    pub fn pending(&self) -> Option<PendingApproval> {
+        self.pending
+            .lock()
+            .as_ref()
+            .map(|(pending, _)| pending.clone())
+    }
+
+    /// Waits for a paired device to decide on the request described by `summary`, returning
+    /// `false` if the request was denied or timed out.
+    #[inline]
+    pub async fn wait(&self, summary: String) -> bool {
+        let (sender, receiver) = oneshot::channel();
+        let challenge = random_token();
+        *self.pending.lock() = Some((
+            PendingApproval {
+                challenge: challenge.clone(),
+                summary,
+            },
+            sender,
+        ));
+        let approved = matches!(
+            tokio::time::timeout(APPROVAL_TIMEOUT, receiver).await,
+            Ok(Ok(true))
+        );
+        {
+            let mut pending = self.pending.lock();
+            if matches!(&*pending, Some((current, _)) if current.challenge == challenge) {
+                pending.take();
+            }
+        }
+        approved
+    }
+
+    /// Forwards the decision in `request` to the pending request if it carries a valid signature
+    /// from a paired device, returning `true` if the decision was accepted.
+    #[inline]
+    pub fn decide(&self, request: DeviceApprovalRequest) -> bool {
+        let is_paired = self
+            .store
+            .lock()
+            .devices
+            .iter()
+            .any(|device| device.public_key.eq_ignore_ascii_case(&request.public_key));
+        if !is_paired {
+            return false;
+        }
+        let verified = (|| {
+            let public_key = parse_public_key(&request.public_key)?;
+            let challenge = HEXLOWER_PERMISSIVE
+                .decode(request.challenge.as_bytes())
+                .ok()?;
+            let signature = Signature::try_from(
+                HEXLOWER_PERMISSIVE
+                    .decode(request.signature.as_bytes())
+                    .ok()?
+                    .as_slice(),
+            )
+            .ok()?;
+            public_key
+                .verify(&decision_message(&challenge, request.approve), &signature)
+                .ok()
+        })();
+        if verified.is_none() {
+            return false;
+        }
+        let mut pending = self.pending.lock();
+        match pending.take() {
+            Some((current, sender))
+                if current.challenge.eq_ignore_ascii_case(&request.challenge) =>
+            {
+                sender.send(request.approve).is_ok()
+            }
+            other => {
+                *pending = other;
+                false
+            }
+        }
+    }
+}
+
+/// Parses the hex-encoded Ed25519 public `key`.
+#[inline]
+fn parse_public_key(key: &str) -> Option<PublicKey> {
+    PublicKey::from_bytes(&HEXLOWER_PERMISSIVE.decode(key.as_bytes()).ok()?).ok()
+}
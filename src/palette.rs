@@ -0,0 +1,205 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Command Palette
+//!
+//! The command palette lets users run signer maintenance actions from the keyboard. The registry
+//! and the fuzzy matching live here so that every frontend ranks commands the same way, while the
+//! frontend decides how each [`CommandId`] is executed.
+
+use crate::{
+    config::Config,
+    error::SignerError,
+    log,
+    parameters::{
+        self,
+        bundle::{parse_public_key, Bundles},
+    },
+};
+use manta_util::serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// Command Identifier
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum CommandId {
+    /// Declines any pending prompt and hides the signer windows
+    Lock,
+
+    /// Opens the signer data directory
+    OpenDataDirectory,
+
+    /// Exports the redacted log history
+    ExportHistory,
+
+    /// Verifies the active proving parameters
+    VerifyParameters,
+
+    /// Generates a diagnostics bundle
+    GenerateDiagnostics,
+}
+
+/// Command Description
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Command {
+    /// Command Identifier
+    pub id: CommandId,
+
+    /// Command Title
+    pub title: &'static str,
+
+    /// Additional Search Keywords
+    pub keywords: &'static [&'static str],
+}
+
+/// Command Registry
+pub const COMMANDS: &[Command] = &[
+    Command {
+        id: CommandId::Lock,
+        title: "Lock",
+        keywords: &["decline", "hide", "cancel"],
+    },
+    Command {
+        id: CommandId::OpenDataDirectory,
+        title: "Open Data Directory",
+        keywords: &["folder", "files", "storage"],
+    },
+    Command {
+        id: CommandId::ExportHistory,
+        title: "Export History",
+        keywords: &["log", "save"],
+    },
+    Command {
+        id: CommandId::VerifyParameters,
+        title: "Verify Parameters",
+        keywords: &["proving", "keys", "check", "bundle"],
+    },
+    Command {
+        id: CommandId::GenerateDiagnostics,
+        title: "Generate Diagnostics",
+        keywords: &["report", "bug", "support"],
+    },
+];
+
+/// Command Match
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct CommandMatch {
+    /// Command Identifier
+    pub id: CommandId,
+
+    /// Command Title
+    pub title: String,
+
+    /// Match Score
+    ///
+    /// Higher scores are better matches.
+    pub score: u32,
+}
+
+/// Scores how well `query` matches `text`, returning `None` if the characters of `query` do not
+/// appear in order in `text`. Consecutive characters and characters at the start of words score
+/// higher. Matching is case-insensitive.
+#[inline]
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let mut score = 0;
+    let mut previous_matched = false;
+    let mut previous = ' ';
+    let mut query = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .peekable();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match query.peek() {
+            Some(next) if *next == c => {
+                score += 1;
+                if previous_matched {
+                    score += 2;
+                }
+                if !previous.is_alphanumeric() {
+                    score += 3;
+                }
+                previous_matched = true;
+                query.next();
+            }
+            Some(_) => previous_matched = false,
+            None => break,
+        }
+        previous = c;
+    }
+    query.peek().is_none().then(|| score)
+}
+
+/// Searches the [`COMMANDS`] for `query`, returning the matches from best to worst. An empty
+/// query matches every command in registry order.
+#[inline]
+pub fn search(query: &str) -> Vec<CommandMatch> {
+    let mut matches = COMMANDS
+        .iter()
+        .filter_map(|command| {
+            let score = core::iter::once(command.title)
+                .chain(command.keywords.iter().copied())
+                .filter_map(|text| fuzzy_score(query, text))
+                .max()?;
+            Some(CommandMatch {
+                id: command.id,
+                title: command.title.into(),
+                score,
+            })
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by(|lhs, rhs| rhs.score.cmp(&lhs.score));
+    matches
+}
+
+/// Writes the redacted log history to `path`.
+#[inline]
+pub fn export_history<P>(path: P) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let mut contents = String::new();
+    for line in log::history() {
+        contents.push_str(&line);
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+/// Verifies the active proving parameters of `config`, returning a description of the result.
+#[inline]
+pub fn verify_parameters(config: &Config) -> Result<String, SignerError> {
+    let bundles = Bundles::new(config.data_directory());
+    match bundles.current_directory()? {
+        Some(directory) => {
+            let trusted_keys = config
+                .parameter_keys
+                .iter()
+                .filter_map(|key| parse_public_key(key))
+                .collect::<Vec<_>>();
+            let manifest = Bundles::verify(&directory, &trusted_keys)
+                .map_err(|err| SignerError::Crypto(format!("{:?}", err)))?;
+            Ok(format!("Parameter bundle {} is valid.", manifest.version))
+        }
+        _ => match parameters::load(config.data_directory()) {
+            Some(_) => Ok("Manta SDK parameters are valid.".into()),
+            _ => Err(SignerError::Io(
+                "unable to load the Manta SDK parameters".into(),
+            )),
+        },
+    }
+}
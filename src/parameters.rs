@@ -28,29 +28,27 @@ use manta_pay::{
 use manta_util::codec::{Decode, IoReader};
 use std::{
     fs::{self, File},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-/// Loads the [`SignerParameters`] from the Manta SDK.
+pub mod bundle;
+
+/// Loads the [`SignerParameters`] from the active parameter bundle if one is installed, or from
+/// the Manta SDK otherwise.
 #[inline]
 pub fn load<P>(directory: P) -> Option<SignerParameters>
 where
     P: AsRef<Path>,
 {
-    let mut directory = directory.as_ref().to_owned();
-    directory.push("sdk");
-    directory.push("data");
-    directory.push("pay");
-    directory.push("testnet");
-    directory.push("proving");
-    fs::create_dir_all(&directory).ok()?;
-    let mint = directory.join("mint.dat");
-    manta_sdk::pay::testnet::proving::Mint::download_if_invalid(&mint).ok()?;
-    let private_transfer = directory.join("private-transfer.dat");
-    manta_sdk::pay::testnet::proving::PrivateTransfer::download_if_invalid(&private_transfer)
-        .ok()?;
-    let reclaim = directory.join("reclaim.dat");
-    manta_sdk::pay::testnet::proving::Reclaim::download_if_invalid(&reclaim).ok()?;
+    let (mint, private_transfer, reclaim) =
+        match bundle::Bundles::new(&directory).current_directory().ok()? {
+            Some(bundle) => (
+                bundle.join("mint.dat"),
+                bundle.join("private-transfer.dat"),
+                bundle.join("reclaim.dat"),
+            ),
+            _ => download_proving_keys(directory)?,
+        };
     Some(SignerParameters {
         proving_context: MultiProvingContext {
             mint: ProvingContext::decode(IoReader(File::open(mint).ok()?)).ok()?,
@@ -75,6 +73,41 @@ where
     })
 }
 
+/// Returns the directory inside of `directory` where the proving keys from the Manta SDK are
+/// stored.
+#[inline]
+pub fn sdk_proving_directory<P>(directory: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    let mut directory = directory.as_ref().to_owned();
+    directory.push("sdk");
+    directory.push("data");
+    directory.push("pay");
+    directory.push("testnet");
+    directory.push("proving");
+    directory
+}
+
+/// Downloads the proving keys from the Manta SDK into `directory` if they are missing or invalid,
+/// returning the paths to the mint, private transfer, and reclaim proving keys.
+#[inline]
+fn download_proving_keys<P>(directory: P) -> Option<(PathBuf, PathBuf, PathBuf)>
+where
+    P: AsRef<Path>,
+{
+    let directory = sdk_proving_directory(directory);
+    fs::create_dir_all(&directory).ok()?;
+    let mint = directory.join("mint.dat");
+    manta_sdk::pay::testnet::proving::Mint::download_if_invalid(&mint).ok()?;
+    let private_transfer = directory.join("private-transfer.dat");
+    manta_sdk::pay::testnet::proving::PrivateTransfer::download_if_invalid(&private_transfer)
+        .ok()?;
+    let reclaim = directory.join("reclaim.dat");
+    manta_sdk::pay::testnet::proving::Reclaim::download_if_invalid(&reclaim).ok()?;
+    Some((mint, private_transfer, reclaim))
+}
+
 /// Loads the [`UtxoAccumulatorModel`] from the Manta SDK.
 #[inline]
 pub fn load_utxo_accumulator_model() -> Option<UtxoAccumulatorModel> {
@@ -0,0 +1,319 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioned Parameter Bundles
+//!
+//! A parameter bundle is a directory containing the proving keys listed in [`PROVING_FILES`],
+//! a [`BundleManifest`] with their checksums, and an Ed25519 signature over the manifest. Bundles
+//! are installed side-by-side under [`BUNDLE_DIRECTORY`] and the active one is selected by a
+//! pointer file which is switched atomically. While no bundle is active, the signer uses the
+//! parameters of the Manta SDK, which can be rolled back to as the [`BUILTIN_VERSION`].
+
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use manta_util::{
+    from_variant_impl,
+    serde::{Deserialize, Serialize},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Bundle Directory Name
+pub const BUNDLE_DIRECTORY: &str = "parameters";
+
+/// Active Bundle Pointer File Name
+pub const CURRENT_FILE: &str = "current";
+
+/// Previously Active Bundle Pointer File Name
+pub const PREVIOUS_FILE: &str = "previous";
+
+/// Built-in Parameters Version
+///
+/// This names the Manta SDK parameters which are used while no bundle is active. It can be
+/// activated and rolled back to like a bundle, but is never installed as one.
+pub const BUILTIN_VERSION: &str = "builtin";
+
+/// Bundle Manifest File Name
+pub const MANIFEST_FILE: &str = "bundle.json";
+
+/// Bundle Signature File Name
+pub const SIGNATURE_FILE: &str = "bundle.sig";
+
+/// Proving Key Files Required in every Bundle
+pub const PROVING_FILES: [&str; 3] = ["mint.dat", "private-transfer.dat", "reclaim.dat"];
+
+/// Bundle Manifest
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct BundleManifest {
+    /// Bundle Version
+    pub version: String,
+
+    /// Hex-Encoded SHA-256 Checksums of the Bundle Files
+    pub files: BTreeMap<String, String>,
+}
+
+/// Bundle Error
+#[derive(Debug)]
+pub enum BundleError {
+    /// Generic I/O Error
+    Io(io::Error),
+
+    /// Invalid Manifest
+    InvalidManifest,
+
+    /// Invalid Version Name
+    InvalidVersion(String),
+
+    /// Missing or Malformed Signature
+    InvalidSignature,
+
+    /// Signature not Produced by a Trusted Key
+    UntrustedSignature,
+
+    /// Missing File in Bundle
+    MissingFile(String),
+
+    /// File Checksum Mismatch
+    ChecksumMismatch(String),
+
+    /// Bundle Version Already Installed
+    AlreadyInstalled(String),
+
+    /// Bundle Version not Installed
+    NotInstalled(String),
+
+    /// No Previous Bundle to Roll Back To
+    NoPreviousBundle,
+}
+
+from_variant_impl!(BundleError, Io, io::Error);
+
+/// Parses the hex-encoded Ed25519 public `key`.
+#[inline]
+pub fn parse_public_key(key: &str) -> Option<PublicKey> {
+    PublicKey::from_bytes(&HEXLOWER_PERMISSIVE.decode(key.as_bytes()).ok()?).ok()
+}
+
+/// Returns `true` if `version` is a valid bundle version name.
+///
+/// Version names are used as directory names next to the pointer files, so they are restricted
+/// to ASCII alphanumerics, dots, dashes and underscores, and cannot start with a dot or name a
+/// pointer file, its temporary file, or the [`BUILTIN_VERSION`]. The names are compared without
+/// case since some filesystems ignore it.
+#[inline]
+pub fn is_valid_version(version: &str) -> bool {
+    let pointer = version.strip_suffix(".tmp").unwrap_or(version);
+    !version.is_empty()
+        && !version.starts_with('.')
+        && !version.contains(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')))
+        && ![CURRENT_FILE, PREVIOUS_FILE]
+            .iter()
+            .any(|name| pointer.eq_ignore_ascii_case(name))
+        && !version.eq_ignore_ascii_case(BUILTIN_VERSION)
+}
+
+/// Returns the hex-encoded SHA-256 checksum of the file at `path`.
+#[inline]
+fn checksum<P>(path: P) -> io::Result<String>
+where
+    P: AsRef<Path>,
+{
+    Ok(HEXLOWER.encode(&Sha256::digest(&fs::read(path)?)))
+}
+
+/// Writes `contents` to `path` atomically by writing to a temporary file and renaming it.
+#[inline]
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(temporary, path)
+}
+
+/// Installed Bundle Information
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct BundleInfo {
+    /// Bundle Version
+    pub version: String,
+
+    /// Active Flag
+    pub active: bool,
+}
+
+/// Parameter Bundle Store
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Bundles {
+    /// Bundle Root Directory
+    root: PathBuf,
+}
+
+impl Bundles {
+    /// Builds a new [`Bundles`] store inside of the `data_directory`.
+    #[inline]
+    pub fn new<P>(data_directory: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            root: data_directory.as_ref().join(BUNDLE_DIRECTORY),
+        }
+    }
+
+    /// Reads the version stored in the pointer file `name`.
+    #[inline]
+    fn read_pointer(&self, name: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.root.join(name)) {
+            Ok(version) => Ok(Some(version.trim().to_owned())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the active bundle version if one is installed.
+    #[inline]
+    pub fn current(&self) -> io::Result<Option<String>> {
+        self.read_pointer(CURRENT_FILE)
+    }
+
+    /// Returns the directory of the active bundle if one is installed.
+    #[inline]
+    pub fn current_directory(&self) -> io::Result<Option<PathBuf>> {
+        Ok(self.current()?.map(|version| self.root.join(version)))
+    }
+
+    /// Lists all installed bundles.
+    #[inline]
+    pub fn list(&self) -> io::Result<Vec<BundleInfo>> {
+        let current = self.current()?;
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut bundles = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(version) = entry.file_name().to_str() {
+                if !is_valid_version(version) {
+                    continue;
+                }
+                bundles.push(BundleInfo {
+                    version: version.to_owned(),
+                    active: current.as_deref() == Some(version),
+                });
+            }
+        }
+        bundles.sort_by(|lhs, rhs| lhs.version.cmp(&rhs.version));
+        Ok(bundles)
+    }
+
+    /// Verifies the bundle at `source` against the `trusted_keys`, returning its manifest.
+    #[inline]
+    pub fn verify(source: &Path, trusted_keys: &[PublicKey]) -> Result<BundleManifest, BundleError> {
+        let manifest_bytes = fs::read(source.join(MANIFEST_FILE))?;
+        let signature = Signature::try_from(fs::read(source.join(SIGNATURE_FILE))?.as_slice())
+            .map_err(|_| BundleError::InvalidSignature)?;
+        if !trusted_keys
+            .iter()
+            .any(|key| key.verify(&manifest_bytes, &signature).is_ok())
+        {
+            return Err(BundleError::UntrustedSignature);
+        }
+        let manifest = serde_json::from_slice::<BundleManifest>(&manifest_bytes)
+            .map_err(|_| BundleError::InvalidManifest)?;
+        if !is_valid_version(&manifest.version) {
+            return Err(BundleError::InvalidVersion(manifest.version));
+        }
+        for name in PROVING_FILES {
+            let expected = manifest
+                .files
+                .get(name)
+                .ok_or_else(|| BundleError::MissingFile(name.into()))?;
+            if !checksum(source.join(name))?.eq_ignore_ascii_case(expected) {
+                return Err(BundleError::ChecksumMismatch(name.into()));
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Installs the bundle at `source` after verifying it against the `trusted_keys`, and then
+    /// atomically switches over to it, returning the installed version.
+    #[inline]
+    pub fn install(&self, source: &Path, trusted_keys: &[PublicKey]) -> Result<String, BundleError> {
+        let manifest = Self::verify(source, trusted_keys)?;
+        let target = self.root.join(&manifest.version);
+        if target.exists() {
+            return Err(BundleError::AlreadyInstalled(manifest.version));
+        }
+        fs::create_dir_all(&self.root)?;
+        let staging = self.root.join(format!(".{}.staging", manifest.version));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir_all(&staging)?;
+        for name in PROVING_FILES.iter().chain(&[MANIFEST_FILE, SIGNATURE_FILE]) {
+            fs::copy(source.join(name), staging.join(name))?;
+        }
+        Self::verify(&staging, trusted_keys)?;
+        fs::rename(&staging, &target)?;
+        self.activate(&manifest.version)?;
+        Ok(manifest.version)
+    }
+
+    /// Atomically switches the active bundle to `version`, remembering the currently active one
+    /// for rollback. Activating the [`BUILTIN_VERSION`] switches back to the Manta SDK
+    /// parameters.
+    #[inline]
+    pub fn activate(&self, version: &str) -> Result<(), BundleError> {
+        let is_builtin = version == BUILTIN_VERSION;
+        if !is_builtin && (!is_valid_version(version) || !self.root.join(version).is_dir()) {
+            return Err(BundleError::NotInstalled(version.into()));
+        }
+        let current = self
+            .current()?
+            .unwrap_or_else(|| BUILTIN_VERSION.to_owned());
+        if current == version {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.root)?;
+        write_atomic(&self.root.join(PREVIOUS_FILE), current.as_bytes())?;
+        if is_builtin {
+            fs::remove_file(self.root.join(CURRENT_FILE))?;
+        } else {
+            write_atomic(&self.root.join(CURRENT_FILE), version.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Rolls back to the previously active bundle, returning its version. After the first bundle
+    /// was installed, this rolls back to the [`BUILTIN_VERSION`].
+    #[inline]
+    pub fn rollback(&self) -> Result<String, BundleError> {
+        let previous = self
+            .read_pointer(PREVIOUS_FILE)?
+            .ok_or(BundleError::NoPreviousBundle)?;
+        self.activate(&previous)?;
+        Ok(previous)
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Password Strength Evaluation
+//!
+//! Passwords are rated with the [`zxcvbn`] estimator so that every frontend applies the same
+//! password policy when a new account is created.
+
+use manta_util::serde::{Deserialize, Serialize};
+use zxcvbn::time_estimates::CrackTimeSeconds;
+
+/// Minimum Password Length
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Minimum Password Score
+///
+/// Scores range from `0` for very guessable passwords to `4` for very unguessable ones.
+pub const MIN_PASSWORD_SCORE: u8 = 2;
+
+/// Password Strength
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PasswordStrength {
+    /// Score from `0` to `4`
+    pub score: u8,
+
+    /// Estimated Offline Crack Time in Seconds
+    ///
+    /// The estimate assumes an attacker who has the wallet file and runs ten thousand guesses
+    /// per second against the slow password hash protecting it.
+    pub crack_time_seconds: f64,
+
+    /// Human-Readable Crack Time Estimate
+    pub crack_time_display: String,
+
+    /// Warning about the Password
+    pub warning: Option<String>,
+
+    /// Suggestions for a Stronger Password
+    pub suggestions: Vec<String>,
+
+    /// Acceptance Flag
+    ///
+    /// This is `true` if the password has at least [`MIN_PASSWORD_LENGTH`] characters and a score
+    /// of at least [`MIN_PASSWORD_SCORE`].
+    pub acceptable: bool,
+}
+
+impl PasswordStrength {
+    /// Evaluates the strength of `password`, penalizing passwords which are built from any of
+    /// the `user_inputs`.
+    #[inline]
+    pub fn evaluate(password: &str, user_inputs: &[&str]) -> Self {
+        let long_enough = password.chars().count() >= MIN_PASSWORD_LENGTH;
+        match zxcvbn::zxcvbn(password, user_inputs) {
+            Ok(entropy) => {
+                let crack_time = entropy.crack_times().offline_slow_hashing_1e4_per_second();
+                let (warning, suggestions) = match entropy.feedback() {
+                    Some(feedback) => (
+                        feedback.warning().map(|warning| warning.to_string()),
+                        feedback
+                            .suggestions()
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect(),
+                    ),
+                    _ => (None, Vec::new()),
+                };
+                Self {
+                    score: entropy.score(),
+                    crack_time_seconds: match crack_time {
+                        CrackTimeSeconds::Integer(seconds) => seconds as f64,
+                        CrackTimeSeconds::Float(seconds) => seconds,
+                    },
+                    crack_time_display: crack_time.to_string(),
+                    warning,
+                    suggestions,
+                    acceptable: long_enough && entropy.score() >= MIN_PASSWORD_SCORE,
+                }
+            }
+            _ => Self {
+                score: 0,
+                crack_time_seconds: 0.0,
+                crack_time_display: "instant".into(),
+                warning: Some("Password is empty.".into()),
+                suggestions: Vec::new(),
+                acceptable: false,
+            },
+        }
+    }
+}
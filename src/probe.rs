@@ -0,0 +1,166 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Network Probe
+//!
+//! The network probe measures the latency and throughput of every configured node endpoint so
+//! that users on poor connections can pick the fastest one. Latency is the median round trip of a
+//! few `system_health` calls and throughput is measured on a ledger diff pulled from the default
+//! checkpoint, which is the largest response the signer regularly downloads.
+
+use crate::{
+    config::Config,
+    sync::{NodeClient, MAX_RECEIVERS, MAX_SENDERS, PULL_METHOD},
+};
+use manta_pay::signer::Checkpoint;
+use manta_util::serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{io, time::Instant};
+
+/// Number of Latency Samples per Endpoint
+pub const LATENCY_SAMPLES: usize = 3;
+
+/// Health Check Method
+pub const HEALTH_METHOD: &str = "system_health";
+
+/// Probe Request
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ProbeRequest {
+    /// Auto-Select Flag
+    ///
+    /// When set, the signer switches its ledger synchronization to the fastest reachable endpoint.
+    pub auto_select: bool,
+}
+
+/// Endpoint Measurement
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct EndpointReport {
+    /// Endpoint URL
+    pub url: String,
+
+    /// Median Round Trip Time in Milliseconds
+    pub latency_ms: Option<u64>,
+
+    /// Download Throughput in Bytes per Second
+    pub throughput: Option<u64>,
+
+    /// Error Description if the Endpoint is Unreachable
+    pub error: Option<String>,
+}
+
+impl EndpointReport {
+    /// Returns `true` if the endpoint answered every probe.
+    #[inline]
+    pub fn is_reachable(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Network Probe Report
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ProbeReport {
+    /// Endpoint Measurements Ranked from Fastest to Slowest
+    ///
+    /// Unreachable endpoints are ranked last.
+    pub endpoints: Vec<EndpointReport>,
+
+    /// Endpoint Selected for Ledger Synchronization
+    ///
+    /// This is only set when the probe was run with [`auto_select`](ProbeRequest::auto_select)
+    /// and some endpoint was reachable.
+    pub selected: Option<String>,
+}
+
+/// Returns the node endpoints of `config` which are probed, starting with the current node and
+/// its fallbacks.
+#[inline]
+pub fn endpoints(config: &Config) -> Vec<String> {
+    let mut endpoints = config.node_endpoints();
+    for url in config.fallback_urls.iter().chain(&config.probe_urls) {
+        if !endpoints.contains(url) {
+            endpoints.push(url.clone());
+        }
+    }
+    endpoints
+}
+
+/// Measures the latency and throughput of the node endpoint behind `client`.
+///
+/// This function blocks on the network and should be run on a blocking task.
+#[inline]
+fn measure(client: &NodeClient) -> io::Result<(u64, u64)> {
+    let mut samples = Vec::with_capacity(LATENCY_SAMPLES);
+    for _ in 0..LATENCY_SAMPLES {
+        let start = Instant::now();
+        client.call::<serde_json::Value>(HEALTH_METHOD, json!([]))?;
+        samples.push(start.elapsed().as_millis() as u64);
+    }
+    samples.sort_unstable();
+    let start = Instant::now();
+    let bytes = client.send(
+        PULL_METHOD,
+        json!([Checkpoint::default(), MAX_RECEIVERS, MAX_SENDERS]),
+    )?;
+    let elapsed = start.elapsed().as_secs_f64().max(f64::EPSILON);
+    Ok((
+        samples[samples.len() / 2],
+        (bytes.len() as f64 / elapsed) as u64,
+    ))
+}
+
+/// Probes the node endpoint at `url`.
+///
+/// This function blocks on the network and should be run on a blocking task.
+#[inline]
+pub fn probe_endpoint(url: String) -> EndpointReport {
+    match measure(&NodeClient::new(url.clone())) {
+        Ok((latency_ms, throughput)) => EndpointReport {
+            url,
+            latency_ms: Some(latency_ms),
+            throughput: Some(throughput),
+            error: None,
+        },
+        Err(err) => EndpointReport {
+            url,
+            latency_ms: None,
+            throughput: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+/// Probes every endpoint in `urls`, returning the measurements ranked from fastest to slowest.
+/// Endpoints are ranked by latency first and by throughput between equal latencies.
+///
+/// This function blocks on the network and should be run on a blocking task.
+#[inline]
+pub fn run_network_probe<I>(urls: I) -> Vec<EndpointReport>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut endpoints = urls.into_iter().map(probe_endpoint).collect::<Vec<_>>();
+    endpoints.sort_by_key(|endpoint| {
+        (
+            !endpoint.is_reachable(),
+            endpoint.latency_ms,
+            endpoint.throughput.map(core::cmp::Reverse),
+        )
+    });
+    endpoints
+}
@@ -0,0 +1,295 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Authorization Prompts
+//!
+//! Every request which needs the user's authorization or consent is described by a [`Prompt`],
+//! which carries the structured data of the request so that the authorizer can render a screen
+//! for its kind. The prompt is serialized with a `type` tag, and authorizers should fall back to
+//! a generic screen for types they do not know, since new types are added over time.
+//!
+//! Prompts are rendered by the authorizer, so their size is bounded to keep a malicious client
+//! from freezing the UI with an enormous request. Oversized prompts are replaced by a
+//! [`PromptSummary`] and the full payload is kept in a [`PayloadStore`] where it can be retrieved
+//! by its hash on demand.
+
+use crate::{audit::Amount, fee::FeeQuote};
+use core::fmt;
+use data_encoding::HEXLOWER;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Maximum Prompt Size in Bytes of its JSON Serialization
+pub const MAX_PROMPT_SIZE: usize = 16 * 1024;
+
+/// Preview Length in Characters
+pub const PREVIEW_LENGTH: usize = 256;
+
+/// Authorization Prompt
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    content = "content",
+    crate = "manta_util::serde",
+    deny_unknown_fields,
+    tag = "type"
+)]
+#[non_exhaustive]
+pub enum Prompt {
+    /// Sign Transaction Prompt
+    SignTransaction {
+        /// Transaction Summary
+        ///
+        /// This holds the labeled transaction, its memo, and the network fees of the quote.
+        summary: String,
+
+        /// Transaction Amount
+        amount: Amount,
+
+        /// Fee Quote
+        ///
+        /// When it is set, the authorizer can offer the fee tiers of the quote, see
+        /// [`Authorizer::fee_tier`](crate::secret::Authorizer::fee_tier).
+        fee: Option<FeeQuote>,
+    },
+
+    /// Sign Message Prompt
+    SignMessage {
+        /// Signing Address
+        address: String,
+
+        /// Message
+        message: String,
+    },
+
+    /// Sign Extrinsic Prompt
+    SignExtrinsic {
+        /// Signing Address
+        address: String,
+
+        /// Genesis Hash of the Chain
+        genesis_hash: String,
+
+        /// Hex-Encoded Call
+        call: String,
+    },
+
+    /// Export Viewing Key Prompt
+    ExportViewingKey {
+        /// Origin which Receives the Viewing Key
+        origin: Option<String>,
+    },
+
+    /// Pair Dapp Prompt
+    PairDapp {
+        /// Dapp Name
+        name: String,
+
+        /// Dapp Origin
+        origin: Option<String>,
+    },
+
+    /// Change Settings Prompt
+    ChangeSettings {
+        /// Setting
+        ///
+        /// This is the name of the service endpoint which changes the setting.
+        setting: String,
+
+        /// Description of the Change
+        description: String,
+    },
+
+    /// Oversized Prompt
+    ///
+    /// This replaces prompts larger than [`MAX_PROMPT_SIZE`].
+    Oversized(PromptSummary),
+}
+
+impl Prompt {
+    /// Builds a new [`ChangeSettings`](Self::ChangeSettings) prompt for `setting` with the given
+    /// `description`.
+    #[inline]
+    pub fn change_settings<D>(setting: &str, description: D) -> Self
+    where
+        D: Into<String>,
+    {
+        Self::ChangeSettings {
+            setting: setting.into(),
+            description: description.into(),
+        }
+    }
+}
+
+impl fmt::Display for Prompt {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SignTransaction { summary, .. } => write!(f, "{}", summary),
+            Self::SignMessage { address, message } => {
+                write!(f, "Sign message from {}\nMessage:\n{}", address, message)
+            }
+            Self::SignExtrinsic {
+                address,
+                genesis_hash,
+                call,
+            } => write!(
+                f,
+                "Sign extrinsic from {}\nGenesis hash: {}\nCall: {}",
+                address, genesis_hash, call
+            ),
+            Self::ExportViewingKey { origin } => write!(
+                f,
+                "Export the viewing key to {}",
+                origin.as_deref().unwrap_or("an unknown origin")
+            ),
+            Self::PairDapp { name, origin } => write!(
+                f,
+                "Pair dapp `{}` from {}",
+                name,
+                origin.as_deref().unwrap_or("an unknown origin")
+            ),
+            Self::ChangeSettings { description, .. } => write!(f, "{}", description),
+            Self::Oversized(summary) => write!(f, "{}", summary),
+        }
+    }
+}
+
+/// Prompt Summary
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PromptSummary {
+    /// Payload Size in Bytes
+    pub size: usize,
+
+    /// Number of Items in the Payload
+    ///
+    /// This counts the elements of arrays, the fields of objects, and the lines of strings.
+    pub items: usize,
+
+    /// Sum of the Numeric `value` Fields in the Payload
+    pub total_value: u128,
+
+    /// Hex-Encoded SHA-256 Hash of the Payload
+    pub hash: String,
+
+    /// Truncated Preview of the Payload
+    pub preview: String,
+}
+
+impl PromptSummary {
+    /// Summarizes the JSON-serialized prompt `payload`.
+    #[inline]
+    pub fn new(payload: &[u8]) -> Self {
+        let value = serde_json::from_slice::<Value>(payload).unwrap_or(Value::Null);
+        let items = match &value {
+            Value::Array(values) => values.len(),
+            Value::Object(fields) => fields.len(),
+            Value::String(string) => string.lines().count(),
+            _ => 1,
+        };
+        let preview = match &value {
+            Value::String(string) => string.chars().take(PREVIEW_LENGTH).collect(),
+            _ => String::from_utf8_lossy(payload)
+                .chars()
+                .take(PREVIEW_LENGTH)
+                .collect(),
+        };
+        Self {
+            size: payload.len(),
+            items,
+            total_value: total_value(&value),
+            hash: HEXLOWER.encode(&Sha256::digest(payload)),
+            preview,
+        }
+    }
+}
+
+impl fmt::Display for PromptSummary {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Oversized request of {} bytes with {} items and a total value of {}.\nPayload hash: {}\n{}...",
+            self.size, self.items, self.total_value, self.hash, self.preview
+        )
+    }
+}
+
+/// Sums the numeric `value` fields in `value`, saturating on overflow.
+#[inline]
+fn total_value(value: &Value) -> u128 {
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .fold(0, |total, value| total.saturating_add(total_value(value))),
+        Value::Object(fields) => fields.iter().fold(0, |total, (key, value)| {
+            let value = match value {
+                Value::Number(number) if key == "value" => number.as_u64().map(u128::from),
+                Value::String(string) if key == "value" => string.parse().ok(),
+                _ => None,
+            }
+            .unwrap_or_else(|| total_value(value));
+            total.saturating_add(value)
+        }),
+        _ => 0,
+    }
+}
+
+/// Oversized Payload
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Payload {
+    /// Hex-Encoded SHA-256 Hash of the Payload
+    pub hash: String,
+
+    /// JSON-Serialized Payload
+    pub payload: String,
+}
+
+/// Oversized Payload Store
+///
+/// Only the payload of the most recent oversized prompt is kept.
+#[derive(Debug, Default)]
+pub struct PayloadStore(Mutex<Option<Payload>>);
+
+impl PayloadStore {
+    /// Bounds the JSON-serialized prompt `payload`, returning `None` if it fits into
+    /// [`MAX_PROMPT_SIZE`] or its summary otherwise, in which case the full payload is stored.
+    #[inline]
+    pub fn bound(&self, payload: &[u8]) -> Option<PromptSummary> {
+        if payload.len() <= MAX_PROMPT_SIZE {
+            return None;
+        }
+        let summary = PromptSummary::new(payload);
+        *self.0.lock() = Some(Payload {
+            hash: summary.hash.clone(),
+            payload: String::from_utf8_lossy(payload).into_owned(),
+        });
+        Some(summary)
+    }
+
+    /// Returns the stored payload if its hash matches `hash`.
+    #[inline]
+    pub fn get(&self, hash: &str) -> Option<Payload> {
+        self.0
+            .lock()
+            .as_ref()
+            .filter(|payload| payload.hash.eq_ignore_ascii_case(hash))
+            .cloned()
+    }
+}
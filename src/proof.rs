@@ -0,0 +1,153 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction Proofs
+//!
+//! The signer keeps the signed transfer posts, with their proofs and public inputs, of every
+//! approved transaction so that a single transaction can later be exported as a self-contained
+//! [`TransactionProof`] package, for example as evidence in a dispute or for compliance. The
+//! posts are stored encrypted next to the wallet file and keyed by the hash of the audit record
+//! of the approval, like the [`memo`](crate::memo) of the transaction.
+
+use crate::{
+    audit::{self, AuditRecord},
+    label::Labels,
+    memo::MemoStore,
+    storage::cipher::{self, FileKey},
+};
+use manta_crypto::rand::OsRng;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::{Mutex, RwLock};
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Proof Store
+///
+/// The store is shared with the frontend through the service [`Handle`](crate::service::Handle)
+/// and is opened by the service once the signer is unlocked.
+#[derive(Debug, Default)]
+pub struct ProofStore {
+    /// JSON-Encoded Signing Responses by Audit Record Hash
+    responses: RwLock<BTreeMap<String, String>>,
+
+    /// Encrypted Proof File Path and Storage Key
+    storage: Mutex<Option<(PathBuf, Arc<FileKey>)>>,
+}
+
+impl ProofStore {
+    /// Loads the proofs stored encrypted under `key` at `path`, starting without proofs if it
+    /// does not exist, and saves later proofs there.
+    #[inline]
+    pub fn open(&self, path: PathBuf, key: Arc<FileKey>) -> Result<(), cipher::Error> {
+        let responses = cipher::load_or_quarantine(&path, &key)?.unwrap_or_default();
+        *self.responses.write() = responses;
+        *self.storage.lock() = Some((path, key));
+        Ok(())
+    }
+
+    /// Returns the signing response of the transaction approved in the audit record with the
+    /// given `hash`.
+    #[inline]
+    pub fn get(&self, hash: &str) -> Option<serde_json::Value> {
+        serde_json::from_str(self.responses.read().get(hash)?).ok()
+    }
+
+    /// Stores the signing `response` of the transaction approved in the audit record with the
+    /// given `hash` and saves the proofs.
+    #[inline]
+    pub fn insert<T>(&self, hash: String, response: &T) -> Result<(), cipher::Error>
+    where
+        T: Serialize,
+    {
+        let response = serde_json::to_string(response)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let storage = self.storage.lock();
+        let (path, key) = storage
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "the signer is locked"))?;
+        let mut responses = self.responses.read().clone();
+        responses.insert(hash, response);
+        cipher::save(path, key, &responses, 0, &mut OsRng)?;
+        *self.responses.write() = responses;
+        Ok(())
+    }
+}
+
+/// Transaction Proof Package
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct TransactionProof {
+    /// Signer Version
+    pub signer_version: String,
+
+    /// Export Time as a UNIX Timestamp
+    pub exported_at: i64,
+
+    /// Audit Record of the Approval
+    ///
+    /// The record hash can be checked against the audit log with [`AuditRecord::compute_hash`].
+    pub record: AuditRecord,
+
+    /// Asset Label
+    pub asset_label: Option<String>,
+
+    /// Transaction Memo
+    pub memo: Option<String>,
+
+    /// Signing Response
+    ///
+    /// This holds the signed transfer posts with their proofs and public inputs.
+    pub response: serde_json::Value,
+}
+
+/// Builds the [`TransactionProof`] of the transaction approved in the record with hash `tx_id`
+/// of the audit log at `path`, returning `None` if there is no such record or no proof was
+/// stored for it.
+#[inline]
+pub fn export<P>(
+    path: P,
+    tx_id: &str,
+    labels: &Labels,
+    memos: &MemoStore,
+    proofs: &ProofStore,
+) -> io::Result<Option<TransactionProof>>
+where
+    P: AsRef<Path>,
+{
+    let response = match proofs.get(tx_id) {
+        Some(response) => response,
+        _ => return Ok(None),
+    };
+    Ok(audit::read(path)?
+        .into_iter()
+        .find(|record| record.hash == tx_id)
+        .map(|record| TransactionProof {
+            signer_version: crate::VERSION.into(),
+            exported_at: chrono::offset::Utc::now().timestamp(),
+            asset_label: record
+                .amount
+                .as_ref()
+                .and_then(|amount| labels.asset(amount.asset_id))
+                .map(Into::into),
+            memo: memos.get(&record.hash),
+            record,
+            response,
+        }))
+}
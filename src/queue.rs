@@ -0,0 +1,209 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Authorization Request Queue
+//!
+//! Every request which needs the user's authorization is given an identifier and queued until
+//! it is answered. The authorizer handles one request at a time in arrival order, and the queue
+//! lets frontends show which requests are still waiting and reject them.
+
+use crate::prompt::PREVIEW_LENGTH;
+use core::sync::atomic::{AtomicU64, Ordering};
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tokio::sync::{watch, Notify};
+
+/// Pending Authorization Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PendingRequest {
+    /// Request Identifier
+    pub id: u64,
+
+    /// Request Kind
+    ///
+    /// This is the name of the service endpoint which received the request.
+    pub kind: String,
+
+    /// Truncated Preview of the Prompt
+    pub preview: String,
+
+    /// Arrival Time as a UNIX Timestamp
+    pub received_at: i64,
+}
+
+/// Queue Entry
+#[derive(Debug)]
+struct Entry {
+    /// Pending Request
+    request: PendingRequest,
+
+    /// Rejection Signal
+    rejected: Arc<Notify>,
+}
+
+/// Authorization Request Queue
+#[derive(Debug)]
+pub struct RequestQueue {
+    /// Next Request Identifier
+    next_id: AtomicU64,
+
+    /// Pending Requests in Arrival Order
+    pending: Mutex<Vec<Entry>>,
+
+    /// Pending Request Count Sender
+    count: watch::Sender<usize>,
+
+    /// Pending Request Count Receiver
+    ///
+    /// This receiver keeps the channel open while there are no subscribers.
+    count_receiver: watch::Receiver<usize>,
+}
+
+impl Default for RequestQueue {
+    #[inline]
+    fn default() -> Self {
+        let (count, count_receiver) = watch::channel(0);
+        Self {
+            next_id: Default::default(),
+            pending: Default::default(),
+            count,
+            count_receiver,
+        }
+    }
+}
+
+impl RequestQueue {
+    /// Queues a request of `kind` with the given `prompt`, returning the [`Ticket`] which keeps it
+    /// queued until it is dropped.
+    #[inline]
+    pub fn push(self: &Arc<Self>, kind: &str, prompt: &str) -> Ticket {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let rejected = Arc::new(Notify::new());
+        let mut pending = self.pending.lock();
+        pending.push(Entry {
+            request: PendingRequest {
+                id,
+                kind: kind.into(),
+                preview: prompt.chars().take(PREVIEW_LENGTH).collect(),
+                received_at: chrono::offset::Utc::now().timestamp(),
+            },
+            rejected: rejected.clone(),
+        });
+        let _ = self.count.send(pending.len());
+        drop(pending);
+        Ticket {
+            queue: self.clone(),
+            id,
+            rejected,
+        }
+    }
+
+    /// Lists the pending requests in arrival order. The first request is the one the user is
+    /// currently asked to authorize.
+    #[inline]
+    pub fn list(&self) -> Vec<PendingRequest> {
+        self.pending
+            .lock()
+            .iter()
+            .map(|entry| entry.request.clone())
+            .collect()
+    }
+
+    /// Rejects the pending request with the given `id`, returning `false` if there is none.
+    #[inline]
+    pub fn reject(&self, id: u64) -> bool {
+        match self
+            .pending
+            .lock()
+            .iter()
+            .find(|entry| entry.request.id == id)
+        {
+            Some(entry) => {
+                entry.rejected.notify_one();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Rejects every pending request, returning how many were rejected.
+    #[inline]
+    pub fn reject_all(&self) -> usize {
+        let pending = self.pending.lock();
+        for entry in pending.iter() {
+            entry.rejected.notify_one();
+        }
+        pending.len()
+    }
+
+    /// Returns the number of pending requests.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Returns `true` if there are no pending requests.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pending.lock().is_empty()
+    }
+
+    /// Subscribes to the number of pending requests.
+    #[inline]
+    pub fn subscribe(&self) -> watch::Receiver<usize> {
+        self.count_receiver.clone()
+    }
+}
+
+/// Queue Ticket
+///
+/// The request is removed from the queue when its ticket is dropped.
+#[derive(Debug)]
+pub struct Ticket {
+    /// Request Queue
+    queue: Arc<RequestQueue>,
+
+    /// Request Identifier
+    id: u64,
+
+    /// Rejection Signal
+    rejected: Arc<Notify>,
+}
+
+impl Ticket {
+    /// Returns the request identifier.
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Waits until the user rejects the request.
+    #[inline]
+    pub async fn rejected(&self) {
+        self.rejected.notified().await
+    }
+}
+
+impl Drop for Ticket {
+    #[inline]
+    fn drop(&mut self) {
+        let mut pending = self.queue.pending.lock();
+        pending.retain(|entry| entry.request.id != self.id);
+        let _ = self.queue.count.send(pending.len());
+    }
+}
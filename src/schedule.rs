@@ -0,0 +1,272 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Background Synchronization Schedule
+//!
+//! The signer synchronizes with the node in the background once per
+//! [`interval`](SyncSchedule::interval) after it has caught up with the ledger. The
+//! synchronization is held while a transaction is proven, while the user paused it with the
+//! [`SyncControl`], and, if the [`SyncSchedule`] asks for it, while the computer runs on battery
+//! or uses a metered network, since catching up with the ledger can keep the CPU busy for a long
+//! time. A power source or network cost which the platform does not report never holds the
+//! synchronization.
+
+use crate::sync::SYNC_INTERVAL;
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use manta_util::serde::{Deserialize, Serialize};
+use tokio::sync::{futures::Notified, Notify};
+
+/// Interval between the Checks of a Held Synchronization
+pub const HOLD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Synchronization Schedule
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SyncSchedule {
+    /// Synchronization Interval in Seconds after Catching Up
+    #[serde(default = "SyncSchedule::default_interval_seconds")]
+    pub interval_seconds: u64,
+
+    /// AC Power Only Flag
+    ///
+    /// When set, the signer only synchronizes while the computer is plugged in.
+    #[serde(default)]
+    pub ac_power_only: bool,
+
+    /// Unmetered Network Only Flag
+    ///
+    /// When set, the signer only synchronizes while the network connection is not metered.
+    #[serde(default)]
+    pub unmetered_only: bool,
+}
+
+impl SyncSchedule {
+    /// Returns the default value of [`interval_seconds`](Self::interval_seconds) for
+    /// deserialization.
+    #[inline]
+    fn default_interval_seconds() -> u64 {
+        SYNC_INTERVAL.as_secs()
+    }
+
+    /// Returns the synchronization interval after catching up.
+    #[inline]
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_seconds.max(1))
+    }
+
+    /// Returns the reason the schedule holds the synchronization because of the power source or
+    /// the network, if any.
+    ///
+    /// This function may run platform tools and should be run on a blocking task.
+    #[inline]
+    pub fn hold(&self) -> Option<SyncHold> {
+        if self.ac_power_only && on_ac_power() == Some(false) {
+            Some(SyncHold::OnBattery)
+        } else if self.unmetered_only && on_metered_network() == Some(true) {
+            Some(SyncHold::MeteredNetwork)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for SyncSchedule {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            interval_seconds: Self::default_interval_seconds(),
+            ac_power_only: false,
+            unmetered_only: false,
+        }
+    }
+}
+
+/// Synchronization Hold Reason
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum SyncHold {
+    /// The Signer is Locked
+    Locked,
+
+    /// The User Paused the Synchronization
+    Paused,
+
+    /// A Transaction is being Proven
+    Proving,
+
+    /// The Computer Runs on Battery
+    OnBattery,
+
+    /// The Network Connection is Metered
+    MeteredNetwork,
+}
+
+/// Synchronization Controls
+#[derive(Debug, Default)]
+pub struct SyncControl {
+    /// Paused Flag
+    paused: AtomicBool,
+
+    /// Change Signal
+    changed: Notify,
+}
+
+impl SyncControl {
+    /// Pauses the background synchronization until it is [resumed](Self::resume).
+    #[inline]
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        self.changed.notify_waiters();
+    }
+
+    /// Resumes the background synchronization.
+    #[inline]
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.changed.notify_waiters();
+    }
+
+    /// Returns `true` if the background synchronization is paused.
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future which completes once the synchronization is paused or resumed after
+    /// this call.
+    #[inline]
+    pub fn changed(&self) -> Notified<'_> {
+        self.changed.notified()
+    }
+}
+
+/// Returns `true` if the computer runs on AC power, or `None` if it is not known.
+#[cfg(target_os = "linux")]
+#[inline]
+pub fn on_ac_power() -> Option<bool> {
+    let mut online = None;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        match std::fs::read_to_string(path.join("type")) {
+            Ok(kind) if kind.trim() == "Mains" => {
+                let mains = std::fs::read_to_string(path.join("online"))
+                    .map(|value| value.trim() == "1")
+                    .unwrap_or_default();
+                online = Some(online.unwrap_or_default() || mains);
+            }
+            _ => {}
+        }
+    }
+    online
+}
+
+/// Returns `true` if the computer runs on AC power, or `None` if it is not known.
+#[cfg(target_os = "macos")]
+#[inline]
+pub fn on_ac_power() -> Option<bool> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "ps"])
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    if output.contains("'AC Power'") {
+        Some(true)
+    } else if output.contains("'Battery Power'") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if the computer runs on AC power, or `None` if it is not known.
+#[cfg(target_os = "windows")]
+#[inline]
+pub fn on_ac_power() -> Option<bool> {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+    let mut status = SYSTEM_POWER_STATUS::default();
+    // SAFETY: The status is written to a structure which we own.
+    if !unsafe { GetSystemPowerStatus(&mut status) }.as_bool() {
+        return None;
+    }
+    match status.ACLineStatus {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the computer runs on AC power, or `None` if it is not known.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+#[inline]
+pub fn on_ac_power() -> Option<bool> {
+    None
+}
+
+/// Returns `true` if the network connection is metered, or `None` if it is not known.
+///
+/// The metered state is read from NetworkManager.
+#[cfg(target_os = "linux")]
+#[inline]
+pub fn on_metered_network() -> Option<bool> {
+    let output = std::process::Command::new("busctl")
+        .args([
+            "--system",
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .strip_prefix("u ")?
+    {
+        "1" | "3" => Some(true),
+        "2" | "4" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the network connection is metered, or `None` if it is not known.
+#[cfg(target_os = "windows")]
+#[inline]
+pub fn on_metered_network() -> Option<bool> {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+    let cost = NetworkInformation::GetInternetConnectionProfile()
+        .ok()?
+        .GetConnectionCost()
+        .ok()?;
+    match cost.NetworkCostType().ok()? {
+        NetworkCostType::Unrestricted => Some(false),
+        NetworkCostType::Fixed | NetworkCostType::Variable => Some(true),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the network connection is metered, or `None` if it is not known.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+#[inline]
+pub fn on_metered_network() -> Option<bool> {
+    None
+}
@@ -18,23 +18,63 @@
 
 // TODO: Use password hashing abstractions from `manta-rs`.
 
-use crate::config::Setup;
+use crate::{
+    config::{Recovery, RecoveryChoice, Setup},
+    exemption::RememberChoice,
+    fee::FeeTier,
+    pairing::PairingOffer,
+};
+use core::time::Duration;
 use futures::future::BoxFuture;
-use manta_util::serde::Serialize;
+use manta_util::serde::{Deserialize, Serialize};
 use password_hash::{PasswordHashString, SaltString};
+use sha2::{Digest, Sha256};
+use std::{io, path::Path};
 
 pub use password_hash::{Error as PasswordHashError, PasswordHasher, PasswordVerifier};
-pub use secrecy::{ExposeSecret, Secret, SecretString};
+pub use secrecy::{ExposeSecret, Secret, SecretString, SecretVec};
 pub use subtle::{Choice, ConstantTimeEq, CtOption};
 
+/// Maximum Keyfile Size in Bytes
+pub const MAX_KEYFILE_SIZE: u64 = 1 << 20;
+
+/// Reads the keyfile at `path`, rejecting keyfiles which are empty or larger than
+/// [`MAX_KEYFILE_SIZE`].
+#[inline]
+pub fn read_keyfile<P>(path: P) -> io::Result<SecretVec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let size = std::fs::metadata(&path)?.len();
+    if size == 0 || size > MAX_KEYFILE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("keyfiles must be between 1 and {} bytes", MAX_KEYFILE_SIZE),
+        ));
+    }
+    Ok(Secret::new(std::fs::read(path)?))
+}
+
 /// Password Secret Wrapper
-pub struct Password(CtOption<SecretString>);
+///
+/// The password can come with the contents of a keyfile, which is an additional unlock factor
+/// mixed into the storage key.
+pub struct Password {
+    /// Password
+    password: CtOption<SecretString>,
+
+    /// Keyfile Digest
+    keyfile: Option<SecretVec<u8>>,
+}
 
 impl Password {
     /// Builds a new [`Password`] from `password` if `is_known` evaluates to `true`.
     #[inline]
     pub fn new(password: SecretString, is_known: Choice) -> Self {
-        Self(CtOption::new(password, is_known))
+        Self {
+            password: CtOption::new(password, is_known),
+            keyfile: None,
+        }
     }
 
     /// Builds a new [`Password`] from `password`.
@@ -49,16 +89,29 @@ impl Password {
         Self::new(Secret::new(String::with_capacity(64)), 0.into())
     }
 
+    /// Adds the `contents` of a keyfile to `self`. Only their SHA-256 digest is kept.
+    #[inline]
+    pub fn with_keyfile(mut self, contents: &[u8]) -> Self {
+        self.keyfile = Some(Secret::new(Sha256::digest(contents).to_vec()));
+        self
+    }
+
     /// Returns [`Some`] if `self` represents a known password.
     #[inline]
     pub fn known(self) -> Option<SecretString> {
-        self.0.into()
+        self.password.into()
+    }
+
+    /// Returns the known password, if any, and the keyfile digest, if any.
+    #[inline]
+    pub fn into_parts(self) -> (Option<SecretString>, Option<SecretVec<u8>>) {
+        (self.password.into(), self.keyfile)
     }
 
     /// Returns `true` if `self` represents a known password.
     #[inline]
     pub fn is_known(&self) -> bool {
-        self.0.is_some().into()
+        self.password.is_some().into()
     }
 }
 
@@ -69,6 +122,101 @@ impl Default for Password {
     }
 }
 
+/// Delay after the First Failed Password Attempt
+///
+/// The delay doubles with every further consecutive failure, up to [`MAX_RETRY_DELAY`].
+pub const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum Delay after a Failed Password Attempt
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Failed Password Attempts
+///
+/// The attempts are saved to disk so that restarting the signer does not reset the cooldown.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PasswordAttempts {
+    /// Consecutive Failed Attempts
+    pub failures: u32,
+
+    /// End of the Cooldown as a UNIX Timestamp in Milliseconds
+    pub blocked_until: Option<i64>,
+}
+
+impl PasswordAttempts {
+    /// Loads the failed attempts from `path`, returning no failed attempts if the file is missing
+    /// or malformed.
+    #[inline]
+    pub fn load<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the failed attempts to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        crate::storage::write_atomic(
+            path.as_ref(),
+            &serde_json::to_vec_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )
+    }
+
+    /// Records a failed attempt and starts its cooldown, returning the cooldown duration.
+    ///
+    /// Every `lockout_threshold` consecutive failures, the cooldown is the `lockout` duration
+    /// instead of the exponential delay. A threshold of zero disables the lockout.
+    #[inline]
+    pub fn record_failure(&mut self, lockout_threshold: u32, lockout: Duration) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        let cooldown = if lockout_threshold != 0 && self.failures % lockout_threshold == 0 {
+            lockout
+        } else {
+            MIN_RETRY_DELAY
+                .checked_mul(1u32 << (self.failures - 1).min(16))
+                .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+        };
+        self.blocked_until = Some(
+            chrono::offset::Utc::now().timestamp_millis()
+                + i64::try_from(cooldown.as_millis()).unwrap_or(i64::MAX / 2),
+        );
+        cooldown
+    }
+
+    /// Returns `true` if there is any failed attempt since the last successful one.
+    #[inline]
+    pub fn has_failures(&self) -> bool {
+        self.failures != 0 || self.blocked_until.is_some()
+    }
+
+    /// Forgets the failed attempts after a successful attempt.
+    #[inline]
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Returns the time left until the next password attempt is accepted.
+    #[inline]
+    pub fn cooldown(&self) -> Duration {
+        match self.blocked_until {
+            Some(blocked_until) => Duration::from_millis(
+                u64::try_from(blocked_until - chrono::offset::Utc::now().timestamp_millis())
+                    .unwrap_or_default(),
+            ),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
 /// Unit Future
 ///
 /// This `type` is used by the [`setup`], [`wake`], and [`sleep`] methods of [`Authorizer`].
@@ -85,6 +233,36 @@ pub type UnitFuture<'t> = BoxFuture<'t, ()>;
 /// See its documentation for more.
 pub type PasswordFuture<'t> = BoxFuture<'t, Password>;
 
+/// Recovery Future
+///
+/// This `type` is used by the [`recover`](Authorizer::recover) method of [`Authorizer`].
+/// See its documentation for more.
+pub type RecoveryFuture<'t> = BoxFuture<'t, RecoveryChoice>;
+
+/// Memo Future
+///
+/// This `type` is used by the [`memo`](Authorizer::memo) method of [`Authorizer`].
+/// See its documentation for more.
+pub type MemoFuture<'t> = BoxFuture<'t, Option<String>>;
+
+/// Fee Tier Future
+///
+/// This `type` is used by the [`fee_tier`](Authorizer::fee_tier) method of [`Authorizer`].
+/// See its documentation for more.
+pub type FeeTierFuture<'t> = BoxFuture<'t, Option<FeeTier>>;
+
+/// Remember Future
+///
+/// This `type` is used by the [`remember`](Authorizer::remember) method of [`Authorizer`].
+/// See its documentation for more.
+pub type RememberFuture<'t> = BoxFuture<'t, Option<RememberChoice>>;
+
+/// Confirmation Future
+///
+/// This `type` is used by the [`confirm`](Authorizer::confirm) method of [`Authorizer`].
+/// See its documentation for more.
+pub type ConfirmationFuture<'t> = BoxFuture<'t, bool>;
+
 /// Authorizer
 pub trait Authorizer: 'static + Send {
     /// Retrieves the password from the authorizer.
@@ -111,7 +289,8 @@ pub trait Authorizer: 'static + Send {
     ///
     /// After [`wake`] is called, [`password`] should be called to retrieve the password. These are
     /// implemented as two separate methods so that [`password`] can be called multiple times for
-    /// password retries. By default, [`wake`] does nothing.
+    /// password retries. The service prompts with a [`Prompt`](crate::prompt::Prompt). By
+    /// default, [`wake`] does nothing.
     ///
     /// [`wake`]: Self::wake
     /// [`password`]: Self::password
@@ -124,6 +303,131 @@ pub trait Authorizer: 'static + Send {
         Box::pin(async move {})
     }
 
+    /// Asks the authorizer to consent to `prompt` without entering the password, returning `true`
+    /// if they approve it.
+    ///
+    /// # Implementation Note
+    ///
+    /// This method is used for requests which grant or revoke permissions or change settings
+    /// without using the account secrets, like pairing a dapp or revoking an API key. Neither
+    /// [`wake`](Self::wake) nor [`password`](Self::password) is called for these requests, so the
+    /// password is never asked for in a consent flow. By default, [`confirm`](Self::confirm)
+    /// declines every request.
+    #[inline]
+    fn confirm<T>(&mut self, prompt: &T) -> ConfirmationFuture
+    where
+        T: Serialize,
+    {
+        let _ = prompt;
+        Box::pin(async move { false })
+    }
+
+    /// Asks the authorizer how to recover from the corrupted storage described by `recovery`.
+    ///
+    /// # Implementation Note
+    ///
+    /// This method is called after [`setup`](Self::setup) when the setup is
+    /// [`Setup::Recover`]. By default, [`recover`](Self::recover) aborts startup.
+    #[inline]
+    fn recover<'s>(&'s mut self, recovery: &'s Recovery) -> RecoveryFuture<'s> {
+        let _ = recovery;
+        Box::pin(async move { RecoveryChoice::Abort })
+    }
+
+    /// Sends the TOTP provisioning `uri` to the authorizer so that it can be enrolled into an
+    /// authenticator app, usually by rendering it as a QR code.
+    ///
+    /// # Implementation Note
+    ///
+    /// This method is only called during setup when TOTP approval is enabled in the
+    /// [`Config`](crate::config::Config) and no TOTP secret has been enrolled yet. By default,
+    /// [`enroll_totp`](Self::enroll_totp) does nothing.
+    #[inline]
+    fn enroll_totp<'s>(&'s mut self, uri: &'s str) -> UnitFuture<'s> {
+        let _ = uri;
+        Box::pin(async move {})
+    }
+
+    /// Shows the pairing `offer` for a companion device to the authorizer, usually by rendering
+    /// its URI as a QR code for the device to scan.
+    ///
+    /// # Implementation Note
+    ///
+    /// The offer is never sent back to the client which started the pairing, so it has to be
+    /// shown here for the device to be paired. By default, [`show_pairing`](Self::show_pairing)
+    /// does nothing.
+    #[inline]
+    fn show_pairing<'s>(&'s mut self, offer: &'s PairingOffer) -> UnitFuture<'s> {
+        let _ = offer;
+        Box::pin(async move {})
+    }
+
+    /// Notifies the authorizer that no password is accepted for the `remaining` time because of
+    /// failed password attempts.
+    ///
+    /// # Implementation Note
+    ///
+    /// The password is requested again once the cooldown is over. By default,
+    /// [`cooldown`](Self::cooldown) does nothing.
+    #[inline]
+    fn cooldown(&mut self, remaining: Duration) -> UnitFuture {
+        let _ = remaining;
+        Box::pin(async move {})
+    }
+
+    /// Retrieves the memo which the authorizer attached to the transaction they just authorized,
+    /// if any.
+    ///
+    /// # Implementation Note
+    ///
+    /// This method is called after the password of a transaction signing prompt was accepted. A
+    /// memo returned here replaces the memo supplied by the dapp. By default,
+    /// [`memo`](Self::memo) returns `None`.
+    #[inline]
+    fn memo(&mut self) -> MemoFuture {
+        Box::pin(async move { None })
+    }
+
+    /// Retrieves the network fee tier which the authorizer picked for the transaction they just
+    /// authorized, if any.
+    ///
+    /// # Implementation Note
+    ///
+    /// This method is called after the password of a
+    /// [`SignTransaction`](crate::prompt::Prompt::SignTransaction) prompt with a fee quote was
+    /// accepted, before the transfer is proven. By default, [`fee_tier`](Self::fee_tier) returns
+    /// `None`, which picks the default tier of the quote.
+    #[inline]
+    fn fee_tier(&mut self) -> FeeTierFuture {
+        Box::pin(async move { None })
+    }
+
+    /// Retrieves the choice of the authorizer to remember their decision on the transaction they
+    /// just authorized, if any.
+    ///
+    /// # Implementation Note
+    ///
+    /// This method is called after a transaction signing prompt of a request with an origin was
+    /// accepted. Later transactions which the returned choice covers are signed without a prompt,
+    /// see the [`exemption`](crate::exemption) module. By default, [`remember`](Self::remember)
+    /// returns `None`, which remembers nothing.
+    #[inline]
+    fn remember(&mut self) -> RememberFuture {
+        Box::pin(async move { None })
+    }
+
+    /// Dismisses the prompt of the last call to [`wake`](Self::wake) which was left unanswered,
+    /// for example because it expired.
+    ///
+    /// # Implementation Note
+    ///
+    /// The password is not requested again for the dismissed prompt. By default,
+    /// [`dismiss`](Self::dismiss) does nothing.
+    #[inline]
+    fn dismiss(&mut self) -> UnitFuture {
+        Box::pin(async move {})
+    }
+
     /// Sends a message to the authorizer to end communication.
     ///
     /// # Implementation Note
@@ -16,18 +16,75 @@
 
 //! Manta Signer Service Implementation
 
-use crate::log::{info, trace, warn};
+use crate::log::{error, info, trace, warn, Redacted};
 use crate::{
-    config::{Config, Setup},
-    secret::{Argon2, Authorizer, ExposeSecret, PasswordHash, SecretString},
+    airgap::{self, AirgapRequest, SignFileRequest},
+    api_key::{
+        ApiKey, ApiKeyInfo, ApiKeyStore, CreateRequest, CreateResponse, Permission, RevokeRequest,
+    },
+    audit::{Amount, AuditLog, AuditRecord, Outcome},
+    balance::{BalanceReport, BalanceStore},
+    compat,
+    config::{Config, Recovery, RecoveryChoice, Setup, UnlockDuration},
+    confirmation::confirmation_code,
+    dapp::{self, DappInfo, DappPairRequest, DappPairing, DappStore, ReplayCache},
+    deeplink::{self, DeepLink},
+    derivation::DerivationReport,
+    diagnostics::StateDigest,
+    discovery::{self, Discovery, VerifyRequest},
+    duress::DuressRequest,
+    error::SignerError,
+    events::{EventBus, SignerEvent},
+    exemption::ExemptionStore,
+    fee::{FeeQuote, FeeSignRequest, FeeSignResponse},
+    hidden::{self, HiddenRequest},
+    identity::{self, Identity, Signed},
+    inspect::{self, TransactionBreakdown},
+    label::LabelStore,
+    memo::{self, MemoSignRequest, MemoStore},
+    pairing::{
+        DeviceApproval, DeviceApprovalRequest, DeviceStore, PairRequest, PairedDevice,
+        PairingSession,
+    },
+    password::PasswordStrength,
+    probe::{self, ProbeReport, ProbeRequest},
+    prompt::{Payload, PayloadStore, Prompt},
+    proof::ProofStore,
+    queue::{PendingRequest, RequestQueue, Ticket},
+    schedule::{SyncControl, SyncHold, HOLD_INTERVAL},
+    secret::{
+        Argon2, Authorizer, ExposeSecret, PasswordAttempts, PasswordHash, SecretString, SecretVec,
+    },
+    session::{self, RevokeSessionRequest, Session, SessionRequest, SessionStore},
+    status::{StatusReceiver, StatusStore},
+    storage::{
+        self,
+        cipher::{self, FileKey},
+        keywrap,
+        rotation::{self, RotationStatus},
+        CompactionReport, UnlockSecrets,
+    },
+    substrate::{
+        self, Account, InjectedAccount, MessageSignature, PublicAddress, SignMessageRequest,
+        SignerPayloadJson, SignerPayloadRaw, SignerResult,
+    },
+    sync::{NodeClient, NodeConnection, ProgressTracker, SyncProgress, SyncStatus, SYNC_INTERVAL},
+    telemetry::Span,
+    totp::{Approval, ApprovalRequest, TotpSecret},
+    transport::{HttpTransport, Transport},
+    wallet::{WalletRegistry, WalletRequest, DEFAULT_WALLET},
 };
 use core::{future::Future, time::Duration};
+use futures::future::{self, Either};
 use http_types::headers::HeaderValue;
 use manta_accounting::{
-    fs::{cocoon::File, File as _, SaveError},
+    asset::AssetMetadata,
+    fs::{cocoon::File, SaveError},
     key::HierarchicalKeyDerivationScheme,
-    transfer::canonical::TransferShape,
+    transfer::canonical::{Transaction, TransferShape},
+    wallet::signer::BalanceUpdate,
 };
+use manta_crypto::rand::OsRng;
 use manta_pay::{
     config::{receiving_key_to_base58, ReceivingKey},
     key::{Mnemonic, TestnetKeySecret},
@@ -44,20 +101,22 @@ use manta_util::{
     from_variant_impl,
     serde::{de::DeserializeOwned, Serialize},
 };
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use std::{
     io,
-    net::{AddrParseError, SocketAddr},
-    path::Path,
+    net::{AddrParseError, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Instant,
 };
 use tide::{
     security::{CorsMiddleware, Origin},
-    Body, Request, Response, StatusCode,
+    sse::Sender,
+    Body, Middleware, Next, Request, Response, StatusCode,
 };
 use tokio::{
     fs,
-    sync::Mutex as AsyncMutex,
+    sync::{broadcast::error::RecvError, Mutex as AsyncMutex, Notify},
     task::{self, JoinError},
 };
 
@@ -70,6 +129,21 @@ pub async fn delay_password_retry() {
     tokio::time::sleep(PASSWORD_RETRY_INTERVAL).await;
 }
 
+/// Configuration File Reload Interval
+pub const CONFIG_RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Unlock Expiry Check Interval
+pub const UNLOCK_EXPIRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Returns the modification time of the file at `path`, or `None` if it does not exist.
+#[inline]
+async fn file_modified(path: &Path) -> Option<std::time::SystemTime> {
+    fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 /// Service Error
 #[derive(Debug)]
 pub enum Error {
@@ -82,36 +156,89 @@ pub enum Error {
     /// Failed to Load SDK Parameters
     ParameterLoadingError,
 
+    /// Failed to Load TOTP Secret
+    TotpLoadingError,
+
     /// Save Error
     SaveError(SaveError<File>),
 
+    /// Storage Encryption Error
+    Cipher(cipher::Error),
+
+    /// Hardware Key Wrapping Error
+    KeyWrap(keywrap::Error),
+
     /// Generic I/O Error
     Io(io::Error),
 
     /// Authorization Error
     AuthorizationError,
+
+    /// Expired Prompt Error
+    ///
+    /// The authorization prompt was left unanswered until it expired.
+    Expired,
+
+    /// Rejected Request Error
+    ///
+    /// The user explicitly rejected the request.
+    Rejected,
+
+    /// Corrupted Storage Error
+    ///
+    /// The wallet file failed its integrity check and the authorizer did not choose a way to
+    /// recover it.
+    StorageCorrupted,
+
+    /// Remote Access Not Allowed Error
+    ///
+    /// The service URL is outside of the loopback interface but
+    /// [`allow_remote`](Config::allow_remote) is not set.
+    RemoteAccessNotAllowed,
+
+    /// Remote Access Without API Keys Error
+    ///
+    /// The service URL is outside of the loopback interface but there is no API key which remote
+    /// clients could authenticate with.
+    RemoteAccessWithoutApiKeys,
+
+    /// Locked Signer Error
+    ///
+    /// The signer is locked, so the decrypted signer state is not available until the user logs
+    /// in again.
+    Locked,
 }
 
 from_variant_impl!(Error, AddrParseError, AddrParseError);
 from_variant_impl!(Error, JoinError, JoinError);
 from_variant_impl!(Error, SaveError, SaveError<File>);
+from_variant_impl!(Error, Cipher, cipher::Error);
+from_variant_impl!(Error, KeyWrap, keywrap::Error);
 from_variant_impl!(Error, Io, io::Error);
 
 impl From<Error> for tide::Error {
     #[inline]
     fn from(err: Error) -> tide::Error {
-        match err {
-            Error::AuthorizationError => {
-                Self::from_str(StatusCode::Unauthorized, "request was not authorized")
-            }
-            _ => Self::from_str(
-                StatusCode::InternalServerError,
-                "unable to complete request",
-            ),
-        }
+        signer_error(err.into())
     }
 }
 
+/// Builds the [`tide::Error`] which responds to the request with `err`, see [`ErrorBody`].
+#[inline]
+fn signer_error(err: SignerError) -> tide::Error {
+    let status = match &err {
+        SignerError::Decode(_) => StatusCode::UnprocessableEntity,
+        SignerError::NotFound(_) => StatusCode::NotFound,
+        SignerError::Unauthorized => StatusCode::Unauthorized,
+        SignerError::Rejected => StatusCode::Forbidden,
+        SignerError::Timeout => StatusCode::RequestTimeout,
+        SignerError::Busy(_) | SignerError::Locked => StatusCode::ServiceUnavailable,
+        SignerError::Unsupported(_) => StatusCode::NotImplemented,
+        _ => StatusCode::InternalServerError,
+    };
+    tide::Error::new(status, err)
+}
+
 /// Result Type
 pub type Result<T, E = Error> = core::result::Result<T, E>;
 
@@ -125,6 +252,24 @@ where
 
     /// Authorizer
     authorizer: A,
+
+    /// Oversized Prompt Payloads
+    payloads: Arc<PayloadStore>,
+
+    /// Prompt Expiry
+    expiry: Option<Duration>,
+
+    /// Password Attempt Throttle
+    throttle: Throttle,
+
+    /// Unlock Duration
+    unlock_duration: UnlockDuration,
+
+    /// Time of the Last Password Entry
+    ///
+    /// This is `None` while the signer is locked. It decides whether prompts ask for the password
+    /// or for a confirmation, and when the signer locks itself, see [`UnlockDuration`].
+    unlocked_at: Option<Instant>,
 }
 
 impl<A> CheckedAuthorizer<A>
@@ -132,27 +277,249 @@ where
     A: Authorizer,
 {
     /// Checks that the authorizer's password matches the known password by sending the `prompt`.
+    ///
+    /// Prompts larger than [`MAX_PROMPT_SIZE`](crate::prompt::MAX_PROMPT_SIZE) are replaced by
+    /// their summary. Prompts which are left unanswered until the expiry are dismissed and fail
+    /// with [`Error::Expired`]. While the signer is unlocked, see [`UnlockDuration`], the
+    /// authorizer is only asked to [`confirm`](Self::confirm) the `prompt`.
     #[inline]
-    async fn check<T>(&mut self, prompt: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        self.authorizer.wake(prompt).await;
+    async fn check(&mut self, prompt: &Prompt) -> Result<()> {
+        if self.is_unlocked() {
+            info!("[AUTH] signer is unlocked, asking for confirmation")?;
+            return self.confirm(prompt).await;
+        }
+        let payload =
+            serde_json::to_vec(prompt).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        match self.payloads.bound(&payload) {
+            Some(summary) => {
+                warn!(
+                    "[AUTH] summarizing oversized prompt of {} bytes with hash {}",
+                    summary.size, summary.hash
+                )?;
+                self.authorizer.wake(&Prompt::Oversized(summary)).await;
+            }
+            _ => self.authorizer.wake(prompt).await,
+        }
+        let expiry = match self.expiry {
+            Some(expiry) => expiry,
+            _ => return self.verify_password().await.map(|_| self.unlock()),
+        };
+        let result = tokio::time::timeout(expiry, self.verify_password()).await;
+        match result {
+            Ok(result) => result.map(|_| self.unlock()),
+            _ => {
+                warn!("[AUTH] authorization prompt expired after {:?}", expiry)?;
+                self.authorizer.dismiss().await;
+                Err(Error::Expired)
+            }
+        }
+    }
+
+    /// Returns `true` if the password was entered within the [`UnlockDuration`].
+    #[inline]
+    fn is_unlocked(&self) -> bool {
+        match self.unlocked_at {
+            Some(unlocked_at) => self.unlock_duration.is_unlocked(unlocked_at.elapsed()),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the [`UnlockDuration`] has elapsed since the password was entered, so
+    /// that the signer should lock itself.
+    #[inline]
+    fn is_expired(&self) -> bool {
+        match (self.unlocked_at, self.unlock_duration.expiry()) {
+            (Some(unlocked_at), Some(expiry)) => unlocked_at.elapsed() >= expiry,
+            _ => false,
+        }
+    }
+
+    /// Marks the signer as unlocked after the password was entered.
+    #[inline]
+    fn unlock(&mut self) {
+        self.unlocked_at = Some(Instant::now());
+    }
+
+    /// Asks the authorizer for the password once to log in again after the signer was locked,
+    /// returning the password hash and the keyfile if the password matches the known password.
+    ///
+    /// The login makes one attempt per call, so that the authorizer is released between the
+    /// attempts.
+    #[inline]
+    async fn log_in(&mut self) -> Result<Option<(PasswordHash<Argon2>, Option<SecretVec<u8>>)>> {
+        self.throttle.wait(&mut self.authorizer).await?;
+        let (password, keyfile) = self.authorizer.password().await.into_parts();
+        let password = password.ok_or(Error::AuthorizationError)?;
+        if self
+            .password_hash
+            .verify(password.expose_secret().as_bytes())
+            .is_err()
+        {
+            self.throttle.fail().await?;
+            return Ok(None);
+        }
+        self.throttle.reset().await?;
+        Ok(Some((self.password_hash.clone(), keyfile)))
+    }
+
+    /// Asks the authorizer to consent to `prompt` without the password.
+    ///
+    /// Prompts are summarized and expire like in [`check`](Self::check), and prompts which the
+    /// authorizer declines fail with [`Error::Rejected`].
+    #[inline]
+    async fn confirm(&mut self, prompt: &Prompt) -> Result<()> {
+        let payload =
+            serde_json::to_vec(prompt).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let expiry = self.expiry;
+        let confirmation = match self.payloads.bound(&payload) {
+            Some(summary) => {
+                warn!(
+                    "[AUTH] summarizing oversized prompt of {} bytes with hash {}",
+                    summary.size, summary.hash
+                )?;
+                self.authorizer.confirm(&Prompt::Oversized(summary))
+            }
+            _ => self.authorizer.confirm(prompt),
+        };
+        let approved = match expiry {
+            Some(expiry) => match tokio::time::timeout(expiry, confirmation).await {
+                Ok(approved) => approved,
+                _ => {
+                    warn!("[AUTH] confirmation prompt expired after {:?}", expiry)?;
+                    self.authorizer.dismiss().await;
+                    return Err(Error::Expired);
+                }
+            },
+            _ => confirmation.await,
+        };
+        if approved {
+            Ok(())
+        } else {
+            Err(Error::Rejected)
+        }
+    }
+
+    /// Shows the new `mnemonic` to the user through the account creation flow of the authorizer,
+    /// returning the password once it matches the known password.
+    #[inline]
+    async fn create_account(&mut self, mnemonic: Mnemonic) -> Result<SecretString> {
+        self.authorizer.setup(&Setup::CreateAccount(mnemonic)).await;
+        self.verify_password().await
+    }
+
+    /// Asks the authorizer for the password until it matches the known password, returning it.
+    #[inline]
+    async fn verify_password(&mut self) -> Result<SecretString> {
         loop {
+            self.throttle.wait(&mut self.authorizer).await?;
             if let Some(password) = self.authorizer.password().await.known() {
                 if self
                     .password_hash
                     .verify(password.expose_secret().as_bytes())
                     .is_ok()
                 {
+                    self.throttle.reset().await?;
                     self.authorizer.sleep().await;
-                    return Ok(());
+                    return Ok(password);
                 }
             } else {
                 return Err(Error::AuthorizationError);
             }
-            delay_password_retry().await;
+            self.throttle.fail().await?;
+        }
+    }
+}
+
+/// Password Attempt Throttle
+///
+/// Failed password attempts are followed by a cooldown during which no password is accepted,
+/// see [`PasswordAttempts`].
+struct Throttle {
+    /// Failed Password Attempts
+    attempts: PasswordAttempts,
+
+    /// Failed Password Attempts Path
+    path: PathBuf,
+
+    /// Lockout Threshold
+    lockout_threshold: u32,
+
+    /// Lockout Duration
+    lockout: Duration,
+}
+
+impl Throttle {
+    /// Loads the failed password attempts for the limits in `config`.
+    #[inline]
+    async fn load(config: &Config) -> Result<Self> {
+        let path = config.password_attempts_path();
+        let attempts_path = path.clone();
+        let mut throttle = Self {
+            attempts: task::spawn_blocking(move || PasswordAttempts::load(attempts_path)).await?,
+            path,
+            lockout_threshold: 0,
+            lockout: Duration::ZERO,
+        };
+        throttle.configure(config);
+        Ok(throttle)
+    }
+
+    /// Sets the lockout limits to the ones in `config`.
+    #[inline]
+    fn configure(&mut self, config: &Config) {
+        self.lockout_threshold = config.lockout_threshold;
+        self.lockout = Duration::from_secs(60 * config.lockout_minutes);
+    }
+
+    /// Waits until the cooldown of the last failed attempt is over, notifying the `authorizer`
+    /// of the remaining time.
+    #[inline]
+    async fn wait<A>(&self, authorizer: &mut A) -> Result<()>
+    where
+        A: Authorizer,
+    {
+        let remaining = self.attempts.cooldown();
+        if !remaining.is_zero() {
+            warn!(
+                "[AUTH] password attempts are blocked for {:?} after {} failures",
+                remaining, self.attempts.failures
+            )?;
+            authorizer.cooldown(remaining).await;
+            tokio::time::sleep(remaining).await;
+        }
+        Ok(())
+    }
+
+    /// Records a failed attempt.
+    #[inline]
+    async fn fail(&mut self) -> Result<()> {
+        let cooldown = self
+            .attempts
+            .record_failure(self.lockout_threshold, self.lockout);
+        warn!(
+            "[AUTH] password attempt failed, {} consecutive failures, cooldown of {:?}",
+            self.attempts.failures, cooldown
+        )?;
+        self.save().await
+    }
+
+    /// Forgets the failed attempts after a successful attempt.
+    #[inline]
+    async fn reset(&mut self) -> Result<()> {
+        if !self.attempts.has_failures() {
+            return Ok(());
         }
+        self.attempts.reset();
+        self.save().await
+    }
+
+    /// Saves the failed attempts to disk.
+    #[inline]
+    async fn save(&self) -> Result<()> {
+        let attempts = self.attempts.clone();
+        let path = self.path.clone();
+        task::spawn_blocking(move || attempts.save(path)).await??;
+        Ok(())
     }
 }
 
@@ -161,10 +528,155 @@ struct State {
     /// Configuration
     config: Config,
 
+    /// Active Wallet Name
+    wallet: String,
+
+    /// Unlocked State
+    ///
+    /// This is `None` while the signer is locked, so that the decrypted signer state and the keys
+    /// are dropped until the user logs in again.
+    unlocked: Option<UnlockedState>,
+}
+
+impl State {
+    /// Returns the unlocked state, failing with [`Error::Locked`] while the signer is locked.
+    #[inline]
+    fn unlocked(&self) -> Result<&UnlockedState> {
+        self.unlocked.as_ref().ok_or(Error::Locked)
+    }
+
+    /// Returns the unlocked state mutably, failing with [`Error::Locked`] while the signer is
+    /// locked.
+    #[inline]
+    fn unlocked_mut(&mut self) -> Result<&mut UnlockedState> {
+        self.unlocked.as_mut().ok_or(Error::Locked)
+    }
+
+    /// Returns the storage encryption key, failing with [`Error::Locked`] while the signer is
+    /// locked.
+    #[inline]
+    fn key(&self) -> Result<Arc<FileKey>> {
+        Ok(self.unlocked()?.key.clone())
+    }
+}
+
+/// Unlocked State
+struct UnlockedState {
+    /// Storage Encryption Key
+    key: Arc<FileKey>,
+
+    /// Hidden Wallet Storage Key
+    ///
+    /// This is only set while a hidden wallet is open, see the [`hidden`] module.
+    hidden_key: Option<Arc<FileKey>>,
+
+    /// Polkadot.js Compatible Account of the Active Wallet
+    ///
+    /// This is `None` if the wallet was set up before the account was derived from the recovery
+    /// phrase and never sampled one.
+    substrate: Option<Arc<Account>>,
+
     /// Signer
     signer: Signer,
 }
 
+impl UnlockedState {
+    /// Builds the unlocked state of the default wallet from the storage `key` and the `signer`.
+    #[inline]
+    fn new(key: Arc<FileKey>, signer: Signer) -> Self {
+        Self {
+            key,
+            hidden_key: None,
+            substrate: None,
+            signer,
+        }
+    }
+
+    /// Returns the key which encrypts the state of the active wallet.
+    #[inline]
+    fn wallet_key(&self) -> &FileKey {
+        self.hidden_key.as_deref().unwrap_or(&self.key)
+    }
+}
+
+/// Request Caller
+#[derive(Clone, Debug)]
+enum Caller {
+    /// Allowed Origin
+    ///
+    /// Requests without an API key come from the allowed origin as checked by the CORS policy.
+    /// The value of their `Origin` header is kept for the audit log, but since any local process
+    /// can forge it, it never grants anything by itself.
+    Origin(Option<String>),
+
+    /// Paired Dapp
+    ///
+    /// Requests authenticated with the MAC of a dapp pairing by the [`DappAuthenticator`] carry
+    /// the pairing identifier, which looks up the approval session of the dapp, and the value of
+    /// their `Origin` header.
+    Dapp {
+        /// Pairing Identifier
+        id: String,
+
+        /// Origin
+        origin: Option<String>,
+    },
+
+    /// API Key Holder
+    ApiKey(ApiKey),
+}
+
+impl Caller {
+    /// Returns the origin of `self` which is recorded in the audit log.
+    #[inline]
+    fn audit_origin(&self) -> Option<String> {
+        match self {
+            Self::Origin(origin) | Self::Dapp { origin, .. } => origin.clone(),
+            Self::ApiKey(key) => Some(format!("api-key:{}", key.name)),
+        }
+    }
+}
+
+/// Appends the dapp `memo` to the authorization `prompt`, if any.
+#[inline]
+fn with_memo(prompt: String, memo: Option<&str>) -> String {
+    match memo {
+        Some(memo) => format!("{}\nMemo: {}", prompt, memo),
+        _ => prompt,
+    }
+}
+
+/// Returns the value moved by `transaction`.
+#[inline]
+fn transaction_value<C>(transaction: &Transaction<C>) -> u128
+where
+    C: manta_accounting::transfer::Configuration,
+{
+    match transaction {
+        Transaction::Mint(asset)
+        | Transaction::PrivateTransfer(asset, _)
+        | Transaction::Reclaim(asset) => asset.value.0,
+    }
+}
+
+/// Returns the amount moved by `transaction` with the asset `metadata` if it is known.
+#[inline]
+fn transaction_amount<C>(transaction: &Transaction<C>, metadata: Option<&AssetMetadata>) -> Amount
+where
+    C: manta_accounting::transfer::Configuration,
+{
+    match transaction {
+        Transaction::Mint(asset)
+        | Transaction::PrivateTransfer(asset, _)
+        | Transaction::Reclaim(asset) => Amount {
+            asset_id: asset.id.0,
+            value: asset.value.0,
+            decimals: metadata.map(|metadata| metadata.decimals),
+            symbol: metadata.map(|metadata| metadata.symbol.clone()),
+        },
+    }
+}
+
 /// Signer Server
 #[derive(derivative::Derivative)]
 #[derivative(Clone(bound = ""))]
@@ -177,15 +689,95 @@ where
 
     /// Authorizer
     authorizer: Arc<AsyncMutex<CheckedAuthorizer<A>>>,
+
+    /// TOTP Approval Gate
+    totp: Option<Arc<Approval>>,
+
+    /// API Key Store
+    api_keys: Arc<Mutex<ApiKeyStore>>,
+
+    /// Signer Identity
+    identity: Arc<Identity>,
+
+    /// Paired Device Approval Gate
+    devices: Arc<DeviceApproval>,
+
+    /// Oversized Prompt Payloads
+    payloads: Arc<PayloadStore>,
+
+    /// Ledger Synchronization Status
+    sync_status: Arc<Mutex<SyncStatus>>,
+
+    /// Ledger Synchronization Progress
+    sync_progress: Arc<ProgressTracker>,
+
+    /// Background Synchronization Controls
+    sync_control: Arc<SyncControl>,
+
+    /// Wallet Registry
+    wallets: Arc<Mutex<WalletRegistry>>,
+
+    /// Approval Sessions
+    sessions: Arc<SessionStore>,
+
+    /// Remembered Approval Decisions
+    exemptions: Arc<ExemptionStore>,
+
+    /// Lock Signal
+    lock_signal: Arc<Notify>,
+
+    /// Signer Status
+    status: Arc<StatusStore>,
+
+    /// Signer Events
+    events: Arc<EventBus>,
+
+    /// Authorization Request Queue
+    queue: Arc<RequestQueue>,
+
+    /// Audit Log of the Active Wallet
+    audit: Arc<Mutex<Arc<AuditLog>>>,
+
+    /// Service Discovery File
+    ///
+    /// This is only known once the service is bound to its ports.
+    discovery: Option<Arc<Discovery>>,
+
+    /// Paired Dapp Store
+    dapps: Arc<Mutex<DappStore>>,
+
+    /// Replayed Dapp Request Cache
+    replays: Arc<Mutex<ReplayCache>>,
+
+    /// Account and Asset Labels
+    labels: Arc<LabelStore>,
+
+    /// Transaction Memos
+    memos: Arc<MemoStore>,
+
+    /// Shielded Balances
+    balances: Arc<BalanceStore>,
+
+    /// Public Address
+    public_address: Arc<RwLock<Option<PublicAddress>>>,
+
+    /// Active Wallet Name
+    wallet: Arc<RwLock<Option<String>>>,
+
+    /// Transaction Proofs
+    proofs: Arc<ProofStore>,
+
+    /// CORS Policy
+    cors: Arc<RwLock<CorsMiddleware>>,
 }
 
 impl<A> Server<A>
 where
     A: Authorizer,
 {
-    /// Builds a new [`Server`] from `config` and `authorizer`.
+    /// Builds a new [`Server`] from `config`, `authorizer`, and the shared state in `handle`.
     #[inline]
-    async fn build(config: Config, mut authorizer: A) -> Result<Self> {
+    async fn build(mut config: Config, mut authorizer: A, handle: Handle) -> Result<Self> {
         info!("building signer server")?;
         info!("loading latest parameters from Manta SDK")?;
         let data_path = config.data_directory().to_owned();
@@ -195,193 +787,3107 @@ where
         info!("setting up configuration")?;
         let setup = config.setup().await?;
         authorizer.setup(&setup).await;
-        let (password_hash, signer) = match setup {
-            Setup::CreateAccount(mnemonic) => loop {
-                if let Some((password, password_hash)) = Self::load_password(&mut authorizer).await
-                {
-                    let state = Self::create_state(
-                        &config.data_path,
-                        &password,
-                        &password_hash,
-                        mnemonic,
-                        parameters,
-                    )
-                    .await?;
-                    break (password_hash, state);
+        let setup = match setup {
+            Setup::Recover(recovery) => Self::recover(&config, &mut authorizer, recovery).await?,
+            setup => setup,
+        };
+        let derived_account = match &setup {
+            Setup::CreateAccount(mnemonic) => substrate::derive_secret_bytes(mnemonic),
+            _ => None,
+        };
+        let mut throttle = Throttle::load(&config).await?;
+        let (password_hash, key, signer) = match setup {
+            Setup::CreateAccount(mnemonic) => {
+                let device_secret = Self::create_device_secret(&config).await?;
+                loop {
+                    if let Some((password, password_hash, keyfile)) =
+                        Self::load_password(&mut authorizer).await
+                    {
+                        let factors = unlock_factors(device_secret.as_deref(), keyfile.as_ref());
+                        let (key, state) = Self::create_state(
+                            &config.data_path,
+                            &password,
+                            &password_hash,
+                            &factors,
+                            mnemonic,
+                            parameters,
+                        )
+                        .await?;
+                        let key =
+                            Self::adopt_storage(&config, &password_hash, &factors, key).await?;
+                        break (password_hash, key, state);
+                    }
+                    delay_password_retry().await;
                 }
-                delay_password_retry().await;
-            },
-            Setup::Login => loop {
-                if let Some((_, password_hash)) = Self::load_password(&mut authorizer).await {
-                    if let Some(state) = Self::load_state(&config.data_path, &password_hash).await?
+            }
+            Setup::Login => {
+                let device_secret = Self::load_device_secret(&config).await?;
+                loop {
+                    throttle.wait(&mut authorizer).await?;
+                    if let Some((_, password_hash, keyfile)) =
+                        Self::load_password(&mut authorizer).await
                     {
-                        break (password_hash, Signer::from_parts(parameters, state));
+                        let factors = unlock_factors(device_secret.as_deref(), keyfile.as_ref());
+                        let loaded = if Self::migrate_storage(&config, &password_hash, &factors)
+                            .await?
+                        {
+                            Self::load_state(&config.data_path, &password_hash, &factors).await?
+                        } else {
+                            None
+                        };
+                        if let Some((key, state)) = loaded {
+                            throttle.reset().await?;
+                            let key =
+                                Self::rotate_key(&config, &password_hash, &factors, key).await?;
+                            break (password_hash, key, Signer::from_parts(parameters, state));
+                        }
+                        let decoy_path = config.decoy_data_path();
+                        if fs::metadata(&decoy_path).await.is_ok() {
+                            if let Some((key, state)) =
+                                Self::load_state(&decoy_path, &password_hash, &[]).await?
+                            {
+                                throttle.reset().await?;
+                                config.data_path = decoy_path;
+                                break (password_hash, key, Signer::from_parts(parameters, state));
+                            }
+                        }
+                        throttle.fail().await?;
+                    } else {
+                        delay_password_retry().await;
                     }
                 }
-                delay_password_retry().await;
-            },
+            }
+            Setup::Recover(_) => unreachable!("Recovery is resolved before loading the state."),
         };
+        let key = Arc::new(key);
+        let totp = if config.totp_approval {
+            Some(Arc::new(
+                Self::load_totp(&config.totp_path(), key.clone(), &mut authorizer).await?,
+            ))
+        } else {
+            None
+        };
+        let identity = Self::load_identity(config.identity_path(), key.clone()).await?;
+        let dapps = Self::load_dapps(config.dapps_path(), key.clone()).await?;
+        let sync_status_path = config.sync_checkpoint_path();
+        let sync_status =
+            task::spawn_blocking(move || SyncStatus::load(sync_status_path)).await??;
+        handle.sync_progress.reset(&sync_status);
+        let audit =
+            Self::open_wallet_files(&config, DEFAULT_WALLET, key.clone(), &sync_status, &handle)
+                .await?;
+        let substrate =
+            Self::load_substrate_account(&config, DEFAULT_WALLET, key.clone(), derived_account)
+                .await?;
+        let api_keys = Self::load_api_keys(&config, key.clone()).await?;
+        let devices = Self::load_devices(&config, key.clone()).await?;
+        let expiry = prompt_expiry(&config);
+        let unlock_duration = config.unlock_duration;
+        let cors = cors_policy(&config);
+        let wallets_path = config.wallets_path();
+        let wallets = task::spawn_blocking(move || WalletRegistry::load(wallets_path)).await??;
+        let active_wallet = wallets.active.clone();
         info!("telling authorizer to sleep")?;
         authorizer.sleep().await;
-        Ok(Self {
-            state: Arc::new(Mutex::new(State { config, signer })),
+        let payloads = Arc::new(PayloadStore::default());
+        let server = Self {
+            state: Arc::new(Mutex::new(State {
+                config,
+                wallet: DEFAULT_WALLET.into(),
+                unlocked: Some(UnlockedState::new(key, signer)),
+            })),
             authorizer: Arc::new(AsyncMutex::new(CheckedAuthorizer {
                 password_hash,
                 authorizer,
+                payloads: payloads.clone(),
+                expiry,
+                throttle,
+                unlock_duration,
+                unlocked_at: Some(Instant::now()),
             })),
-        })
+            totp,
+            api_keys: Arc::new(Mutex::new(api_keys)),
+            identity: Arc::new(identity),
+            devices: Arc::new(devices),
+            payloads,
+            sync_status: Arc::new(Mutex::new(sync_status)),
+            sync_progress: handle.sync_progress,
+            sync_control: handle.sync_control,
+            wallets: Arc::new(Mutex::new(wallets)),
+            sessions: handle.sessions,
+            exemptions: handle.exemptions,
+            lock_signal: handle.lock_signal,
+            status: handle.status,
+            events: handle.events,
+            queue: handle.queue,
+            audit: Arc::new(Mutex::new(Arc::new(audit))),
+            discovery: None,
+            dapps: Arc::new(Mutex::new(dapps)),
+            replays: Arc::new(Mutex::new(ReplayCache::default())),
+            labels: handle.labels,
+            memos: handle.memos,
+            balances: handle.balances,
+            public_address: handle.public_address,
+            wallet: handle.wallet,
+            proofs: handle.proofs,
+            cors: Arc::new(RwLock::new(cors)),
+        };
+        *server.wallet.write() = Some(DEFAULT_WALLET.into());
+        server.set_substrate_account(substrate)?;
+        if active_wallet != DEFAULT_WALLET {
+            if let Err(err) = server.clone().open_wallet(active_wallet.clone()).await {
+                warn!(
+                    "unable to open wallet `{}`, staying on the default wallet: {:?}",
+                    active_wallet, err
+                )?;
+                server.wallets.lock().active = DEFAULT_WALLET.into();
+            }
+        }
+        Ok(server)
     }
 
-    /// Loads the password from the `authorizer` and compute the password hash.
+    /// Loads the API key store encrypted under `key`.
     #[inline]
-    async fn load_password(authorizer: &mut A) -> Option<(SecretString, PasswordHash<Argon2>)> {
-        info!("loading password from authorizer").ok()?;
-        let password = authorizer.password().await.known()?;
-        let password_hash = PasswordHash::from_default(password.expose_secret().as_bytes());
-        Some((password, password_hash))
+    async fn load_api_keys(config: &Config, key: Arc<FileKey>) -> Result<ApiKeyStore> {
+        let path = config.api_keys_path();
+        Ok(task::spawn_blocking(move || ApiKeyStore::load(path, &key)).await??)
     }
 
-    /// Creates the initial signer state for a new account.
+    /// Loads the paired devices encrypted under `key`.
     #[inline]
-    async fn create_state(
-        data_path: &Path,
-        password: &SecretString,
-        password_hash: &PasswordHash<Argon2>,
-        mnemonic: Mnemonic,
-        parameters: SignerParameters,
-    ) -> Result<Signer> {
-        info!("creating signer state")?;
-        let state = SignerState::new(
-            TestnetKeySecret::new(mnemonic, password.expose_secret())
-                .map(HierarchicalKeyDerivationFunction::default()),
-            UtxoAccumulator::new(
-                task::spawn_blocking(crate::parameters::load_utxo_accumulator_model)
-                    .await?
-                    .ok_or(Error::ParameterLoadingError)?,
-            ),
-        );
-        info!("saving signer state")?;
-        let data_path = data_path.to_owned();
-        let password_hash_bytes = password_hash.as_bytes();
-        let cloned_state = state.clone();
-        task::spawn_blocking(move || File::save(&data_path, &password_hash_bytes, cloned_state))
-            .await??;
-        Ok(Signer::from_parts(parameters, state))
+    async fn load_devices(config: &Config, key: Arc<FileKey>) -> Result<DeviceApproval> {
+        let path = config.devices_path();
+        task::spawn_blocking(move || {
+            let store = DeviceStore::load(&path, &key)?;
+            Ok(DeviceApproval::new(path, key, store))
+        })
+        .await?
     }
 
-    /// Loads the signer state from the data path.
+    /// Loads the signer identity from `path`, generating and saving a new one if it does not
+    /// exist yet.
     #[inline]
-    async fn load_state(
-        data_path: &Path,
-        password_hash: &PasswordHash<Argon2>,
-    ) -> Result<Option<SignerState>> {
-        info!("loading signer state from disk")?;
-        let data_path = data_path.to_owned();
-        let password_hash_bytes = password_hash.as_bytes();
-        if let Ok(state) =
-            task::spawn_blocking(move || File::load(&data_path, &password_hash_bytes)).await?
-        {
-            Ok(Some(state))
-        } else {
-            Ok(None)
-        }
+    async fn load_identity(path: PathBuf, key: Arc<FileKey>) -> Result<Identity> {
+        task::spawn_blocking(
+            move || match cipher::load_or_quarantine::<_, Vec<u8>>(&path, &key)? {
+                Some(bytes) => {
+                    Identity::from_secret_bytes(&bytes).ok_or(cipher::Error::Serialization)
+                }
+                _ => {
+                    let identity = Identity::gen(&mut OsRng);
+                    cipher::save(&path, &key, &identity.secret_bytes(), 0, &mut OsRng)?;
+                    Ok(identity)
+                }
+            },
+        )
+        .await?
+        .map_err(Error::from)
     }
 
-    /// Executes `f` on the incoming `request`.
+    /// Loads the paired dapp store from `path`, returning an empty store if it does not exist.
     #[inline]
-    async fn execute<T, R, F, Fut>(
-        mut request: Request<Self>,
-        f: F,
-    ) -> Result<Response, tide::Error>
-    where
-        T: DeserializeOwned,
-        R: Serialize,
-        F: FnOnce(Self, T) -> Fut,
-        Fut: Future<Output = Result<R>>,
-    {
-        let args = request.body_json::<T>().await?;
-        into_body(move || async move { f(request.state().clone(), args).await }).await
+    async fn load_dapps(path: PathBuf, key: Arc<FileKey>) -> Result<DappStore> {
+        task::spawn_blocking(move || {
+            cipher::load_or_quarantine(&path, &key).map(Option::unwrap_or_default)
+        })
+        .await?
+        .map_err(Error::from)
     }
 
-    /// Saves the signer state to disk.
+    /// Opens the labels, approval grants, memos, proofs and balances of the wallet called `name`,
+    /// which are encrypted under `key` and synchronized up to `sync_status`, into the stores
+    /// shared through `handle`, and returns the audit log of the wallet.
     #[inline]
-    async fn save(self) -> Result<()> {
-        info!("starting signer state save to disk")?;
-        let path = self.state.lock().config.data_path.clone();
-        let backup = path.with_extension("backup");
-        fs::rename(&path, &backup).await?;
-        let password_hash_bytes = self.authorizer.lock().await.password_hash.as_bytes();
+    async fn open_wallet_files(
+        config: &Config,
+        name: &str,
+        key: Arc<FileKey>,
+        sync_status: &SyncStatus,
+        handle: &Handle,
+    ) -> Result<AuditLog> {
+        let synced = sync_status.batches > 0;
+        let paths = (
+            config.labels_path(name),
+            config.sessions_path(name),
+            config.exemptions_path(name),
+            config.memos_path(name),
+            config.proofs_path(name),
+            config.wallet_balances_path(name),
+            config.audit_log_path(name),
+        );
+        let handle = handle.clone();
         task::spawn_blocking(move || {
-            let lock = self.state.lock();
-            File::save(path, &password_hash_bytes, lock.signer.state())
+            let (labels, sessions, exemptions, memos, proofs, balances, audit) = paths;
+            handle.labels.open(labels, key.clone())?;
+            handle.sessions.open(sessions, key.clone())?;
+            handle.exemptions.open(exemptions, key.clone())?;
+            handle.memos.open(memos, key.clone())?;
+            handle.proofs.open(proofs, key.clone())?;
+            if !handle.balances.open(balances, key, synced)? {
+                let _ = warn!(
+                    "the shielded balance cache is missing, resynchronize the wallet to rebuild it"
+                );
+            }
+            Ok::<_, cipher::Error>(AuditLog::open(audit)?)
         })
-        .await??;
-        fs::remove_file(backup).await?;
-        info!("save complete")?;
-        Ok(())
+        .await?
+        .map_err(Error::from)
     }
 
-    /// Returns the [`crate::VERSION`] string to the client.
+    /// Loads the polkadot.js compatible account of the wallet called `name`, saving the account
+    /// `derived` from the recovery phrase of a new account first. The account sampled at random
+    /// by earlier versions is only loaded for the default wallet if it has no derived account, and
+    /// `None` is returned if there is neither.
+    #[inline]
+    async fn load_substrate_account(
+        config: &Config,
+        name: &str,
+        key: Arc<FileKey>,
+        derived: Option<Vec<u8>>,
+    ) -> Result<Option<Account>> {
+        let mut paths = vec![(config.polkadot_account_path(name), true)];
+        if name == DEFAULT_WALLET {
+            paths.push((config.substrate_account_path(), false));
+        }
+        let prefix = config.address_prefix();
+        task::spawn_blocking(move || {
+            if let Some(bytes) = derived {
+                cipher::save(&paths[0].0, &key, &bytes, 0, &mut OsRng)?;
+                return Account::from_secret_bytes(&bytes, prefix, true)
+                    .map(Some)
+                    .ok_or(cipher::Error::Serialization);
+            }
+            for (path, recoverable) in paths {
+                if let Some(bytes) = cipher::load_or_quarantine::<_, Vec<u8>>(&path, &key)? {
+                    return Account::from_secret_bytes(&bytes, prefix, recoverable)
+                        .map(Some)
+                        .ok_or(cipher::Error::Serialization);
+                }
+            }
+            Ok(None)
+        })
+        .await?
+        .map_err(Error::from)
+    }
+
+    /// Recovers from the corrupted storage described by `recovery` using the choice of the
+    /// `authorizer`, returning the [`Setup`] to continue with.
+    ///
+    /// A restored backup can be older than the last key rotation, so the rotation is marked as in
+    /// progress and the next unlock re-encrypts the newer wallet files under the key of the
+    /// backup, see [`rotation::resume`].
+    #[inline]
+    async fn recover(config: &Config, authorizer: &mut A, recovery: Recovery) -> Result<Setup> {
+        warn!("signer storage is corrupted: {}", recovery.reason)?;
+        match authorizer.recover(&recovery).await {
+            RecoveryChoice::RestoreBackup(generation) if recovery.backups.contains(&generation) => {
+                info!("restoring signer storage from backup {}", generation)?;
+                storage::restore_backup(&config.data_path, generation).await?;
+                let status_path = config.key_rotation_path();
+                task::spawn_blocking(move || {
+                    let mut status = RotationStatus::load(&status_path);
+                    status.in_progress = true;
+                    status.save(&status_path)
+                })
+                .await??;
+                Ok(Setup::Login)
+            }
+            RecoveryChoice::ImportMnemonic(mnemonic) => {
+                info!("re-importing account from mnemonic")?;
+                storage::quarantine(&config.data_path).await?;
+                Ok(Setup::CreateAccount(mnemonic))
+            }
+            _ => Err(Error::StorageCorrupted),
+        }
+    }
+
+    /// Loads the TOTP secret encrypted under `key` from `path`, enrolling a new one with the
+    /// `authorizer` if it does not exist yet or was saved under another key, for example before
+    /// the account was re-imported from its recovery phrase.
+    #[inline]
+    async fn load_totp(path: &Path, key: Arc<FileKey>, authorizer: &mut A) -> Result<Approval> {
+        let path = path.to_owned();
+        let (loaded_path, loaded_key) = (path.clone(), key.clone());
+        if let Some(secret) = task::spawn_blocking(move || {
+            cipher::load_or_quarantine::<_, Vec<u8>>(&loaded_path, &loaded_key)
+        })
+        .await??
+        {
+            info!("loaded TOTP secret from disk")?;
+            return Ok(Approval::new(TotpSecret::new(secret)));
+        }
+        info!("enrolling new TOTP secret")?;
+        let secret = TotpSecret::gen(&mut OsRng);
+        let bytes = secret.as_bytes().to_vec();
+        task::spawn_blocking(move || cipher::save(&path, &key, &bytes, 0, &mut OsRng)).await??;
+        authorizer
+            .enroll_totp(&secret.provisioning_uri("signer"))
+            .await;
+        Ok(Approval::new(secret))
+    }
+
+    /// Loads the password and the optional keyfile digest from the `authorizer` and computes the
+    /// password hash.
+    #[inline]
+    async fn load_password(
+        authorizer: &mut A,
+    ) -> Option<(SecretString, PasswordHash<Argon2>, Option<SecretVec<u8>>)> {
+        info!("loading password from authorizer").ok()?;
+        let (password, keyfile) = authorizer.password().await.into_parts();
+        let password = password?;
+        let password_hash = PasswordHash::from_default(password.expose_secret().as_bytes());
+        Some((password, password_hash, keyfile))
+    }
+
+    /// Creates a new wrapped device secret if hardware key wrapping is enabled in `config`,
+    /// removing the device secret of any previous account otherwise.
+    #[inline]
+    async fn create_device_secret(config: &Config) -> Result<Option<Vec<u8>>> {
+        let path = config.device_key_path();
+        if !config.hardware_key_wrapping {
+            return match fs::remove_file(path).await {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(None),
+            };
+        }
+        info!("wrapping the storage key with a hardware key")?;
+        Ok(Some(
+            task::spawn_blocking(move || keywrap::create(path)).await??,
+        ))
+    }
+
+    /// Re-encrypts the wallet files under a fresh storage key if the last rotation is older
+    /// than [`key_rotation_days`](Config::key_rotation_days), or finishes an interrupted
+    /// rotation, returning the key the files are encrypted under. See the [`rotation`] module.
+    #[inline]
+    async fn rotate_key(
+        config: &Config,
+        password_hash: &PasswordHash<Argon2>,
+        factors: &[&[u8]],
+        key: FileKey,
+    ) -> Result<FileKey> {
+        let status_path = config.key_rotation_path();
+        let loaded_path = status_path.clone();
+        let mut status = task::spawn_blocking(move || RotationStatus::load(loaded_path)).await?;
+        let resume = status.in_progress;
+        if !resume && !status.is_due(config.key_rotation_days) {
+            return Ok(key);
+        }
+        if resume {
+            info!("finishing the interrupted storage key rotation")?;
+        } else {
+            info!("rotating the storage key")?;
+        }
+        let secret = cipher::storage_secret(&password_hash.as_bytes(), factors);
+        let directory = config.data_directory().to_owned();
+        let data_path = config.data_path.clone();
+        let generations = config.backup_generations;
+        let (key, count) = task::spawn_blocking(move || {
+            status.in_progress = true;
+            status.save(&status_path)?;
+            let (key, count) = if resume {
+                let count = rotation::resume(
+                    &directory,
+                    &data_path,
+                    &secret,
+                    &key,
+                    generations,
+                    &mut OsRng,
+                )?;
+                (key, count)
+            } else {
+                rotation::rotate(
+                    &directory,
+                    &data_path,
+                    &secret,
+                    &key,
+                    generations,
+                    &mut OsRng,
+                )?
+            };
+            status.in_progress = false;
+            status.rotated_at = Some(chrono::offset::Utc::now().timestamp());
+            status.save(&status_path)?;
+            Ok::<_, cipher::Error>((key, count))
+        })
+        .await??;
+        info!(
+            "re-encrypted {} wallet files under the new storage key",
+            count
+        )?;
+        Ok(key)
+    }
+
+    /// Re-encrypts the files of the data directory which are encrypted under a previous storage
+    /// key derived from the same password and unlock `factors` under the `key` of a new signer
+    /// state, returning the key. This keeps the wallet files when the account is re-imported from
+    /// its recovery phrase, and the files which cannot be re-encrypted are moved aside when they
+    /// are loaded, see [`cipher::load_or_quarantine`].
+    #[inline]
+    async fn adopt_storage(
+        config: &Config,
+        password_hash: &PasswordHash<Argon2>,
+        factors: &[&[u8]],
+        key: FileKey,
+    ) -> Result<FileKey> {
+        let secret = cipher::storage_secret(&password_hash.as_bytes(), factors);
+        let directory = config.data_directory().to_owned();
+        let data_path = config.data_path.clone();
+        let generations = config.backup_generations;
+        let (key, count) = task::spawn_blocking(move || {
+            let count = rotation::resume(
+                &directory,
+                &data_path,
+                &secret,
+                &key,
+                generations,
+                &mut OsRng,
+            )?;
+            Ok::<_, cipher::Error>((key, count))
+        })
+        .await??;
+        if count > 0 {
+            info!(
+                "re-encrypted {} wallet files of the previous account under the new storage key",
+                count
+            )?;
+        }
+        Ok(key)
+    }
+
+    /// Loads the wrapped device secret of the account, returning `None` if the account was not
+    /// created with hardware key wrapping.
+    #[inline]
+    async fn load_device_secret(config: &Config) -> Result<Option<Vec<u8>>> {
+        let path = config.device_key_path();
+        Ok(task::spawn_blocking(move || keywrap::load(path)).await??)
+    }
+
+    /// Creates the initial signer state for a new account, mixing the unlock `factors` into the
+    /// storage key.
+    #[inline]
+    async fn create_state(
+        data_path: &Path,
+        password: &SecretString,
+        password_hash: &PasswordHash<Argon2>,
+        factors: &[&[u8]],
+        mnemonic: Mnemonic,
+        parameters: SignerParameters,
+    ) -> Result<(FileKey, Signer)> {
+        let state = Self::new_state(password, mnemonic).await?;
+        info!("saving signer state")?;
+        let data_path = data_path.to_owned();
+        let secret = cipher::storage_secret(&password_hash.as_bytes(), factors);
+        let cloned_state = state.clone();
+        let key = task::spawn_blocking(move || {
+            let key = FileKey::generate(&secret, &mut OsRng)?;
+            cipher::save(&data_path, &key, &cloned_state, 0, &mut OsRng)?;
+            Ok::<_, cipher::Error>(key)
+        })
+        .await??;
+        Ok((key, Signer::from_parts(parameters, state)))
+    }
+
+    /// Builds the initial signer state for the account derived from `mnemonic` and `password`.
+    #[inline]
+    async fn new_state(password: &SecretString, mnemonic: Mnemonic) -> Result<SignerState> {
+        info!("creating signer state")?;
+        Ok(SignerState::new(
+            TestnetKeySecret::new(mnemonic, password.expose_secret())
+                .map(HierarchicalKeyDerivationFunction::default()),
+            UtxoAccumulator::new(
+                task::spawn_blocking(crate::parameters::load_utxo_accumulator_model)
+                    .await?
+                    .ok_or(Error::ParameterLoadingError)?,
+            ),
+        ))
+    }
+
+    /// Runs the pending storage migrations which need the password, see
+    /// [`migrate_unlocked`](storage::migrate_unlocked), returning `false` if the password hash
+    /// and the unlock `factors` do not open the signer state.
+    #[inline]
+    async fn migrate_storage(
+        config: &Config,
+        password_hash: &PasswordHash<Argon2>,
+        factors: &[&[u8]],
+    ) -> Result<bool> {
+        let password_hash = password_hash.as_bytes();
+        let secrets = UnlockSecrets {
+            storage_secret: cipher::storage_secret(&password_hash, factors),
+            password_hash,
+        };
+        match storage::migrate_unlocked(config, &secrets).await {
+            Ok(version) => {
+                info!("storage format is at version {}", version)?;
+                Ok(true)
+            }
+            Err(cipher::Error::Decryption) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Loads the signer state from `data_path`, returning `None` if the password hash and the
+    /// unlock `factors` do not open it.
+    ///
+    /// The storage key is derived from the password hash mixed with the unlock `factors` the
+    /// account was created with. Signer states in the legacy format are re-encrypted by the
+    /// [`migrate_storage`](Self::migrate_storage) before.
+    #[inline]
+    async fn load_state(
+        data_path: &Path,
+        password_hash: &PasswordHash<Argon2>,
+        factors: &[&[u8]],
+    ) -> Result<Option<(FileKey, SignerState)>> {
+        info!("loading signer state from disk")?;
+        let data_path = data_path.to_owned();
+        let secret = cipher::storage_secret(&password_hash.as_bytes(), factors);
+        match task::spawn_blocking(move || cipher::load(&data_path, &secret)).await? {
+            Ok(loaded) => Ok(Some(loaded)),
+            Err(cipher::Error::Decryption) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Authenticates the API key attached to `request`, if any, checking that it grants
+    /// `permission`. Requests from other machines must carry an API key.
+    #[inline]
+    async fn authenticate(request: &Request<Self>, permission: Permission) -> Result<Caller> {
+        let header = match request.header("Authorization") {
+            Some(header) => header.last().as_str().to_owned(),
+            _ if !is_loopback_peer(request) => {
+                warn!(
+                    "[API KEY] rejected remote request from {:?} without an API key",
+                    request.peer_addr()
+                )?;
+                return Err(Error::AuthorizationError);
+            }
+            _ => {
+                let origin = request
+                    .header("Origin")
+                    .map(|origin| origin.last().as_str().to_owned());
+                return Ok(match request.ext::<AuthenticatedDapp>() {
+                    Some(AuthenticatedDapp(id)) => Caller::Dapp {
+                        id: id.clone(),
+                        origin,
+                    },
+                    _ => Caller::Origin(origin),
+                });
+            }
+        };
+        let key = header
+            .strip_prefix("Bearer ")
+            .and_then(|key| request.state().api_keys.lock().authenticate(key).cloned());
+        match key {
+            Some(key) if key.allows(permission) => {
+                info!("[API KEY] `{}` authorized for {:?}", key.name, permission)?;
+                Ok(Caller::ApiKey(key))
+            }
+            Some(key) => {
+                warn!("[API KEY] `{}` is not allowed {:?}", key.name, permission)?;
+                Err(Error::AuthorizationError)
+            }
+            _ => {
+                warn!("[API KEY] rejected unknown or revoked API key")?;
+                Err(Error::AuthorizationError)
+            }
+        }
+    }
+
+    /// Executes `f` on the incoming `request` after checking that the caller has `permission`.
+    #[inline]
+    async fn execute<T, R, F, Fut>(
+        request: Request<Self>,
+        permission: Permission,
+        f: F,
+    ) -> Result<Response, tide::Error>
+    where
+        T: DeserializeOwned,
+        R: Serialize,
+        F: FnOnce(Self, T) -> Fut,
+        Fut: Future<Output = Result<R>>,
+    {
+        Self::execute_as(request, permission, move |server, _, args| f(server, args)).await
+    }
+
+    /// Executes `f` on the incoming `request` after checking that the caller has `permission`,
+    /// passing the authenticated [`Caller`] to `f`.
+    #[inline]
+    async fn execute_as<T, R, F, Fut>(
+        mut request: Request<Self>,
+        permission: Permission,
+        f: F,
+    ) -> Result<Response, tide::Error>
+    where
+        T: DeserializeOwned,
+        R: Serialize,
+        F: FnOnce(Self, Caller, T) -> Fut,
+        Fut: Future<Output = Result<R>>,
+    {
+        let caller = Self::authenticate(&request, permission).await?;
+        let mode = request.state().state.lock().config.compatibility;
+        let (args, fixups) = compat::deserialize::<T>(&request.body_bytes().await?, mode)
+            .map_err(|err| signer_error(err.into()))?;
+        if !fixups.is_empty() {
+            info!("[COMPAT] applied request fixups: {:?}", fixups)?;
+        }
+        into_body(move || async move { f(request.state().clone(), caller, args).await }).await
+    }
+
+    /// Queues the request of `kind` and asks the user to authorize it with `prompt` once every
+    /// request which arrived before it was answered.
+    #[inline]
+    async fn authorize(&self, kind: &str, prompt: Prompt) -> Result<()> {
+        self.authorize_as(kind, None, prompt).await
+    }
+
+    /// Queues the request of `kind` from `origin` and asks the user to authorize it with `prompt`
+    /// once every request which arrived before it was answered, recording the decision in the
+    /// audit log.
+    #[inline]
+    async fn authorize_as(&self, kind: &str, origin: Option<String>, prompt: Prompt) -> Result<()> {
+        self.ensure_unlocked()?;
+        let summary = prompt.to_string();
+        let ticket = self.queue.push(kind, &summary);
+        let result = unless_rejected(&ticket, async {
+            self.authorizer.lock().await.check(&prompt).await
+        })
+        .await;
+        drop(ticket);
+        self.audit(kind, origin, &summary, None, &result).await?;
+        result
+    }
+
+    /// Queues the request of `kind` and asks the user to consent to `prompt`, without the
+    /// password, once every request which arrived before it was answered.
+    #[inline]
+    async fn confirm(&self, kind: &str, prompt: Prompt) -> Result<()> {
+        self.confirm_as(kind, None, prompt).await
+    }
+
+    /// Queues the request of `kind` from `origin` and asks the user to consent to `prompt`,
+    /// without the password, once every request which arrived before it was answered, recording
+    /// the decision in the audit log.
+    #[inline]
+    async fn confirm_as(&self, kind: &str, origin: Option<String>, prompt: Prompt) -> Result<()> {
+        self.ensure_unlocked()?;
+        let summary = prompt.to_string();
+        let ticket = self.queue.push(kind, &summary);
+        let result = unless_rejected(&ticket, async {
+            self.authorizer.lock().await.confirm(&prompt).await
+        })
+        .await;
+        drop(ticket);
+        self.audit(kind, origin, &summary, None, &result).await?;
+        result
+    }
+
+    /// Records the `result` of authorizing the request of `kind` from `origin` with `prompt` in
+    /// the audit log, along with the `amount` of the transaction it signs, if any, and returns
+    /// the new record. Failing to write the log is only reported as a warning.
+    ///
+    /// The requests about hidden wallets and the requests made while a hidden wallet is open are
+    /// not recorded, since the audit log, memos and proofs are shared by every wallet and would
+    /// reveal the hidden wallet.
+    #[inline]
+    async fn audit<T>(
+        &self,
+        kind: &str,
+        origin: Option<String>,
+        prompt: &str,
+        amount: Option<Amount>,
+        result: &Result<T>,
+    ) -> Result<Option<AuditRecord>> {
+        let outcome = match result {
+            Ok(_) => Outcome::Approved,
+            Err(Error::Rejected) => Outcome::Rejected,
+            Err(Error::Expired) => Outcome::Expired,
+            Err(_) => Outcome::Failed,
+        };
+        self.events.publish(SignerEvent::RequestCompleted {
+            kind: kind.to_owned(),
+            origin: origin.clone(),
+            outcome,
+        });
+        let hidden = self
+            .state
+            .lock()
+            .unlocked
+            .as_ref()
+            .map_or(false, |unlocked| unlocked.hidden_key.is_some());
+        if kind.starts_with("hidden/") || hidden {
+            return Ok(None);
+        }
+        let audit = self.audit.lock().clone();
+        let (kind, prompt) = (kind.to_owned(), prompt.to_owned());
+        match task::spawn_blocking(move || audit.append(&kind, origin, &prompt, amount, outcome))
+            .await?
+        {
+            Ok(record) => Ok(Some(record)),
+            Err(err) => {
+                warn!("[AUDIT] unable to record {} decision: {}", outcome, err)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Attaches `memo` to the transaction approved in the audit `record`. Failing to save the
+    /// memo is only reported as a warning.
+    #[inline]
+    async fn attach_memo(&self, record: Option<AuditRecord>, memo: Option<String>) -> Result<()> {
+        if let (Some(record), Some(memo)) = (record, memo) {
+            let memos = self.memos.clone();
+            if let Err(err) = task::spawn_blocking(move || memos.insert(record.hash, memo)).await? {
+                warn!("[MEMO] unable to save transaction memo: {:?}", err)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores the signing `response` of the transaction approved in the audit `record`, so that
+    /// its proof can be exported later. Failing to save the proof is only reported as a warning.
+    #[inline]
+    async fn attach_proof(
+        &self,
+        record: Option<AuditRecord>,
+        response: &Result<SignResponse, SignError>,
+    ) -> Result<()> {
+        if let (Some(record), Ok(response)) = (record, response) {
+            let (proofs, response) = (self.proofs.clone(), response.clone());
+            if let Err(err) =
+                task::spawn_blocking(move || proofs.insert(record.hash, &response)).await?
+            {
+                warn!("[PROOF] unable to save transaction proof: {:?}", err)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the signer state to disk.
+    #[inline]
+    async fn save(self) -> Result<()> {
+        info!("starting signer state save to disk")?;
+        let _span = Span::new("save");
+        task::spawn_blocking(move || {
+            let lock = self.state.lock();
+            let unlocked = lock.unlocked()?;
+            cipher::save(
+                lock.config.wallet_path(&lock.wallet),
+                unlocked.wallet_key(),
+                unlocked.signer.state(),
+                lock.config.backup_generations,
+                &mut OsRng,
+            )?;
+            Ok::<_, Error>(())
+        })
+        .await??;
+        info!("save complete")?;
+        Ok(())
+    }
+
+    /// Returns the [`crate::VERSION`] string to the client.
     #[inline]
     async fn version() -> Result<&'static str> {
         trace!("[PING] current signer version: {}", crate::VERSION)?;
         Ok(crate::VERSION)
     }
 
-    /// Runs the synchronization protocol on the signer.
+    /// Runs the synchronization protocol on the signer.
+    #[inline]
+    async fn sync(self, request: SyncRequest) -> Result<Result<SyncResponse, SyncError>> {
+        self.sync_batch(request, true, None).await
+    }
+
+    /// Synchronizes the signer with the ledger diff in `request`, recording the batch in the
+    /// [`SyncStatus`] with the `caught_up` flag and the block `height` it reached if it succeeds.
+    #[inline]
+    async fn sync_batch(
+        self,
+        request: SyncRequest,
+        caught_up: bool,
+        height: Option<u64>,
+    ) -> Result<Result<SyncResponse, SyncError>> {
+        info!("[REQUEST] processing `sync`:  {:?}.", Redacted(&request))?;
+        let span = Span::new("sync");
+        let (receivers, senders) = (request.inserts.len(), request.removes.len());
+        let response = {
+            let _update = span.child("sync.update");
+            self.state.lock().unlocked_mut()?.signer.sync(request)
+        };
+        span.record("sync.success", response.is_ok());
+        if let Ok(SyncResponse {
+            checkpoint,
+            balance_update,
+            ..
+        }) = &response
+        {
+            let notes = match balance_update {
+                BalanceUpdate::Partial { deposit, .. } => deposit.len(),
+                _ => 0,
+            };
+            let status = {
+                let mut status = self.sync_status.lock();
+                status.record(
+                    checkpoint.clone(),
+                    receivers,
+                    senders,
+                    notes,
+                    caught_up,
+                    height,
+                );
+                status.clone()
+            };
+            self.sync_progress.record(&status);
+            if notes > 0 {
+                self.events
+                    .publish(SignerEvent::NotesReceived { count: notes });
+            }
+            let (balances, balance_update) = (self.balances.clone(), balance_update.clone());
+            task::spawn(async move {
+                if !matches!(
+                    task::spawn_blocking(move || balances.apply(&balance_update)).await,
+                    Ok(Ok(()))
+                ) {
+                    let _ = warn!("unable to save shielded balances");
+                }
+            });
+        }
+        task::spawn(async {
+            if self.clone().save().await.is_err() {
+                let _ = warn!("unable to save current signer state");
+                return;
+            }
+            let path = {
+                let lock = self.state.lock();
+                lock.config.wallet_sync_path(&lock.wallet)
+            };
+            let status = self.sync_status.lock().clone();
+            if !matches!(
+                task::spawn_blocking(move || status.save(path)).await,
+                Ok(Ok(()))
+            ) {
+                let _ = warn!("unable to save synchronization status");
+            }
+        });
+        info!(
+            "[RESPONSE] responding to `sync` with: {:?}.",
+            Redacted(&response)
+        )?;
+        Ok(response)
+    }
+
+    /// Wipes the derived wallet state and the shielded balances after the user authorizes it,
+    /// keeping only the account keys, so that they are rebuilt from the ledger by the next
+    /// synchronization.
+    #[inline]
+    async fn resync_wallet(self, _: ()) -> Result<bool> {
+        info!("[REQUEST] processing `resyncWallet`")?;
+        self.authorize(
+            "resyncWallet",
+            Prompt::change_settings(
+                "resyncWallet",
+                "Wipe the wallet state and resynchronize it from the ledger",
+            ),
+        )
+        .await?;
+        let data_directory = self.state.lock().config.data_directory().to_owned();
+        let parameters = task::spawn_blocking(move || crate::parameters::load(data_directory))
+            .await?
+            .ok_or(Error::ParameterLoadingError)?;
+        let utxo_accumulator_model =
+            task::spawn_blocking(crate::parameters::load_utxo_accumulator_model)
+                .await?
+                .ok_or(Error::ParameterLoadingError)?;
+        {
+            let mut state = self.state.lock();
+            let unlocked = state.unlocked_mut()?;
+            let keys = unlocked.signer.state().accounts().keys().clone();
+            unlocked.signer = Signer::from_parts(
+                parameters,
+                SignerState::new(keys, UtxoAccumulator::new(utxo_accumulator_model)),
+            );
+        }
+        *self.sync_status.lock() = SyncStatus::default();
+        self.sync_progress.reset(&SyncStatus::default());
+        self.clone().save().await?;
+        let balances = self.balances.clone();
+        task::spawn_blocking(move || balances.reset()).await??;
+        let path = {
+            let lock = self.state.lock();
+            lock.config.wallet_sync_path(&lock.wallet)
+        };
+        task::spawn_blocking(move || SyncStatus::default().save(path)).await??;
+        warn!("wallet state was wiped and will be rebuilt from the ledger")?;
+        Ok(true)
+    }
+
+    /// Compacts the wallet storage by rewriting the wallet file from the in-memory state and
+    /// pruning stale temporary files and backups.
+    #[inline]
+    async fn compact_storage(self, _: ()) -> Result<CompactionReport> {
+        info!("[REQUEST] processing `compactStorage`")?;
+        let report = task::spawn_blocking(move || {
+            let lock = self.state.lock();
+            let unlocked = lock.unlocked()?;
+            let path = &lock.config.wallet_path(&lock.wallet);
+            let size_before = std::fs::metadata(path)?.len();
+            cipher::save(
+                path,
+                unlocked.wallet_key(),
+                unlocked.signer.state(),
+                0,
+                &mut OsRng,
+            )?;
+            let removed = storage::prune(path, lock.config.backup_generations)?;
+            Ok::<_, Error>(CompactionReport {
+                size_before,
+                size_after: std::fs::metadata(path)?.len(),
+                removed: removed
+                    .into_iter()
+                    .map(|path| path.display().to_string())
+                    .collect(),
+            })
+        })
+        .await??;
+        info!(
+            "[RESPONSE] compacted wallet storage from {} to {} bytes",
+            report.size_before, report.size_after
+        )?;
+        Ok(report)
+    }
+
+    /// Replaces the signer state and synchronization status with those of the wallet called
+    /// `name`.
+    #[inline]
+    async fn open_wallet(self, name: String) -> Result<()> {
+        self.open_wallet_with(name, None).await
+    }
+
+    /// Returns the [`Handle`] which shares the state of the server with the frontend.
+    #[inline]
+    fn handle(&self) -> Handle {
+        Handle {
+            sessions: self.sessions.clone(),
+            exemptions: self.exemptions.clone(),
+            lock_signal: self.lock_signal.clone(),
+            status: self.status.clone(),
+            events: self.events.clone(),
+            queue: self.queue.clone(),
+            labels: self.labels.clone(),
+            memos: self.memos.clone(),
+            balances: self.balances.clone(),
+            public_address: self.public_address.clone(),
+            wallet: self.wallet.clone(),
+            proofs: self.proofs.clone(),
+            sync_progress: self.sync_progress.clone(),
+            sync_control: self.sync_control.clone(),
+        }
+    }
+
+    /// Makes `account` the polkadot.js compatible account of the active wallet, publishing its
+    /// public address and reading its public balance from the node.
+    #[inline]
+    fn set_substrate_account(&self, account: Option<Account>) -> Result<()> {
+        if let Some(account) = &account {
+            if !account.is_recoverable() {
+                warn!(
+                    "the polkadot.js account {} is not derived from the recovery phrase, move its funds to a recoverable account",
+                    account.address()
+                )?;
+            }
+        }
+        self.balances.set_account(
+            account
+                .as_ref()
+                .map(|account| (account.address().into(), account.public_key().to_vec())),
+        );
+        *self.public_address.write() = account.as_ref().and_then(Account::public_address);
+        self.state.lock().unlocked_mut()?.substrate = account.map(Arc::new);
+        Ok(())
+    }
+
+    /// Opens the wallet called `name` whose state is encrypted under `hidden_key`, or under the
+    /// storage key if it is not a hidden wallet, and makes it the active wallet. The names of
+    /// hidden wallets are not logged.
+    #[inline]
+    async fn open_wallet_with(self, name: String, hidden_key: Option<Arc<FileKey>>) -> Result<()> {
+        if hidden_key.is_none() {
+            info!("opening wallet `{}`", name)?;
+        }
+        let (config, key) = {
+            let lock = self.state.lock();
+            let key = match &hidden_key {
+                Some(hidden_key) => hidden_key.clone(),
+                _ => lock.key()?,
+            };
+            (lock.config.clone(), key)
+        };
+        let data_directory = config.data_directory().to_owned();
+        let parameters = task::spawn_blocking(move || crate::parameters::load(data_directory))
+            .await?
+            .ok_or(Error::ParameterLoadingError)?;
+        let (path, sync_path, state_key) = (
+            config.wallet_path(&name),
+            config.wallet_sync_path(&name),
+            key.clone(),
+        );
+        let (state, sync_status) = task::spawn_blocking(move || {
+            let state = cipher::load_with_key::<_, SignerState>(path, &state_key)?;
+            let sync_status = SyncStatus::load(sync_path)?;
+            Ok::<_, cipher::Error>((state, sync_status))
+        })
+        .await??;
+        let audit =
+            Self::open_wallet_files(&config, &name, key.clone(), &sync_status, &self.handle())
+                .await?;
+        let substrate = Self::load_substrate_account(&config, &name, key, None).await?;
+        {
+            let mut lock = self.state.lock();
+            let unlocked = lock.unlocked_mut()?;
+            unlocked.signer = Signer::from_parts(parameters, state);
+            unlocked.hidden_key = hidden_key;
+            lock.wallet = name.clone();
+        }
+        *self.audit.lock() = Arc::new(audit);
+        *self.wallet.write() = Some(name);
+        self.set_substrate_account(substrate)?;
+        self.sync_progress.reset(&sync_status);
+        *self.sync_status.lock() = sync_status;
+        Ok(())
+    }
+
+    /// Grants the calling paired dapp an approval session after the user authorizes it,
+    /// returning `None` if the request was not authenticated by a dapp pairing.
+    #[inline]
+    async fn request_session(
+        self,
+        caller: Caller,
+        request: SessionRequest,
+    ) -> Result<Option<Session>> {
+        info!("[REQUEST] processing `sessions/request`: {:?}", request)?;
+        let (dapp, origin) = match caller {
+            Caller::Dapp { id, origin } => (id, origin),
+            _ => return Ok(None),
+        };
+        self.authorize_as(
+            "sessions/request",
+            origin.clone(),
+            Prompt::change_settings(
+                "sessions/request",
+                format!(
+                    "Allow {} to sign without the password for {} minutes up to a value of {}",
+                    self.dapp_label(&dapp),
+                    request.duration_minutes.min(session::MAX_DURATION_MINUTES),
+                    request.value_limit
+                ),
+            ),
+        )
+        .await?;
+        let session = self.sessions.grant(dapp, origin, request)?;
+        info!("[RESPONSE] granted session to dapp `{}`", session.dapp)?;
+        Ok(Some(session))
+    }
+
+    /// Returns the name and origin of the paired dapp with the given `id` as shown in prompts.
+    #[inline]
+    fn dapp_label(&self, id: &str) -> String {
+        match self
+            .dapps
+            .lock()
+            .list()
+            .into_iter()
+            .find(|dapp| dapp.id == id)
+        {
+            Some(dapp) => format!(
+                "dapp `{}` from {}",
+                dapp.name,
+                dapp.origin.as_deref().unwrap_or("an unknown origin")
+            ),
+            _ => format!("dapp `{}`", id),
+        }
+    }
+
+    /// Revokes the approval session of the dapp in `request`.
+    #[inline]
+    async fn revoke_session(self, request: RevokeSessionRequest) -> Result<bool> {
+        info!("[REQUEST] processing `sessions/revoke`: {:?}", request)?;
+        Ok(self.sessions.revoke(&request.dapp)?)
+    }
+
+    /// Lists the requests waiting for the user's authorization.
+    #[inline]
+    async fn pending_requests(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(move || async move { Ok::<Vec<PendingRequest>, Error>(server.queue.list()) })
+            .await
+    }
+
+    /// Lists the active approval sessions.
+    #[inline]
+    async fn list_sessions(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(move || async move { Ok::<Vec<Session>, Error>(server.sessions.list()) }).await
+    }
+
+    /// Checks the discovery nonce in `request`, returning `true` if it matches the nonce this
+    /// service wrote to its discovery file.
+    #[inline]
+    async fn verify_discovery(self, request: VerifyRequest) -> Result<bool> {
+        Ok(self
+            .discovery
+            .as_ref()
+            .map_or(false, |discovery| discovery.verify(&request.nonce)))
+    }
+
+    /// Lists the wallets and the active wallet.
+    #[inline]
+    async fn list_wallets(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::View).await?;
+        let server = request.state().clone();
+        into_body(move || async move {
+            server.ensure_unlocked()?;
+            Ok::<WalletRegistry, Error>(server.wallets.lock().clone())
+        })
+        .await
+    }
+
+    /// Creates a new wallet from a fresh recovery phrase which is shown to the user, returning
+    /// `false` if the wallet name is invalid or taken. The new wallet does not become active.
+    #[inline]
+    async fn create_wallet(self, request: WalletRequest) -> Result<bool> {
+        info!("[REQUEST] processing `createWallet`: {:?}", request.name)?;
+        self.ensure_unlocked()?;
+        let mut wallets = self.wallets.lock().clone();
+        if !wallets.add(request.name.clone()) {
+            return Ok(false);
+        }
+        let mnemonic = Mnemonic::gen(&mut OsRng);
+        let prompt = format!("Create wallet `{}`", request.name);
+        let ticket = self.queue.push("wallets/create", &prompt);
+        let result = unless_rejected(&ticket, async {
+            self.authorizer
+                .lock()
+                .await
+                .create_account(mnemonic.clone())
+                .await
+        })
+        .await;
+        drop(ticket);
+        self.audit("wallets/create", None, &prompt, None, &result)
+            .await?;
+        let password = result?;
+        let account = substrate::derive_secret_bytes(&mnemonic);
+        let state = Self::new_state(&password, mnemonic).await?;
+        let (key, path, account_path, wallets_path) = {
+            let lock = self.state.lock();
+            (
+                lock.key()?,
+                lock.config.wallet_path(&request.name),
+                lock.config.polkadot_account_path(&request.name),
+                lock.config.wallets_path(),
+            )
+        };
+        let registry = wallets.clone();
+        task::spawn_blocking(move || {
+            cipher::save(path, &key, &state, 0, &mut OsRng)?;
+            if let Some(account) = account {
+                cipher::save(account_path, &key, &account, 0, &mut OsRng)?;
+            }
+            registry.save(wallets_path)?;
+            Ok::<_, cipher::Error>(())
+        })
+        .await??;
+        *self.wallets.lock() = wallets;
+        info!("[RESPONSE] created wallet `{}`", request.name)?;
+        Ok(true)
+    }
+
+    /// Sets up the duress password in `request` after the user authorizes it, replacing the decoy
+    /// wallet with a new one. Returns `false` if the duress password matches the real password or
+    /// is too weak.
+    #[inline]
+    async fn setup_duress(self, request: DuressRequest) -> Result<bool> {
+        info!("[REQUEST] processing `duress/setup`")?;
+        if !PasswordStrength::evaluate(&request.password, &[]).acceptable
+            || self
+                .authorizer
+                .lock()
+                .await
+                .password_hash
+                .verify(request.password.as_bytes())
+                .is_ok()
+        {
+            return Ok(false);
+        }
+        self.authorize(
+            "duress/setup",
+            Prompt::change_settings(
+                "duress/setup",
+                "Set up a duress password which unlocks a new decoy wallet",
+            ),
+        )
+        .await?;
+        let password = SecretString::new(request.password);
+        let mnemonic = Mnemonic::gen(&mut OsRng);
+        let account = substrate::derive_secret_bytes(&mnemonic);
+        let state = Self::new_state(&password, mnemonic).await?;
+        let password_hash_bytes =
+            PasswordHash::<Argon2>::from_default(password.expose_secret().as_bytes()).as_bytes();
+        let path = self.state.lock().config.decoy_data_path();
+        task::spawn_blocking(move || {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let key = FileKey::generate(&password_hash_bytes, &mut OsRng)?;
+            cipher::save(&path, &key, &state, 0, &mut OsRng)?;
+            match account {
+                Some(account) => cipher::save(
+                    path.with_file_name("polkadot.dat"),
+                    &key,
+                    &account,
+                    0,
+                    &mut OsRng,
+                ),
+                _ => Ok(()),
+            }
+        })
+        .await??;
+        info!("[RESPONSE] set up duress password")?;
+        Ok(true)
+    }
+
+    /// Removes the duress password and its decoy wallet after the user authorizes it, returning
+    /// `false` if there is none.
+    #[inline]
+    async fn remove_duress(self, _: ()) -> Result<bool> {
+        info!("[REQUEST] processing `duress/remove`")?;
+        let path = self.state.lock().config.decoy_data_path();
+        if fs::metadata(&path).await.is_err() {
+            return Ok(false);
+        }
+        self.authorize(
+            "duress/remove",
+            Prompt::change_settings(
+                "duress/remove",
+                "Remove the duress password and its decoy wallet",
+            ),
+        )
+        .await?;
+        if let Some(directory) = path.parent() {
+            fs::remove_dir_all(directory).await?;
+        }
+        info!("[RESPONSE] removed duress password")?;
+        Ok(true)
+    }
+
+    /// Creates the hidden wallet of the passphrase in `request` from a new recovery phrase after
+    /// the user authorizes it, returning `false` if it already exists.
+    #[inline]
+    async fn create_hidden_wallet(self, request: HiddenRequest) -> Result<bool> {
+        self.ensure_unlocked()?;
+        let password_hash = self.authorizer.lock().await.password_hash.as_bytes();
+        let name = hidden::wallet_name(&password_hash, &request.passphrase);
+        let path = self.state.lock().config.wallet_path(&name);
+        if fs::metadata(&path).await.is_ok() {
+            return Ok(false);
+        }
+        let mnemonic = Mnemonic::gen(&mut OsRng);
+        let prompt = "Create a hidden wallet".to_owned();
+        let ticket = self.queue.push("hidden/create", &prompt);
+        let result = unless_rejected(&ticket, async {
+            self.authorizer
+                .lock()
+                .await
+                .create_account(mnemonic.clone())
+                .await
+        })
+        .await;
+        drop(ticket);
+        self.audit("hidden/create", None, &prompt, None, &result)
+            .await?;
+        result?;
+        let account = substrate::derive_secret_bytes(&mnemonic);
+        let state =
+            Self::new_state(&SecretString::new(request.passphrase.clone()), mnemonic).await?;
+        let secret = hidden::storage_secret(&password_hash, &request.passphrase);
+        let account_path = self.state.lock().config.polkadot_account_path(&name);
+        task::spawn_blocking(move || {
+            let key = FileKey::generate(&secret, &mut OsRng)?;
+            cipher::save(path, &key, &state, 0, &mut OsRng)?;
+            match account {
+                Some(account) => cipher::save(account_path, &key, &account, 0, &mut OsRng),
+                _ => Ok(()),
+            }
+        })
+        .await??;
+        Ok(true)
+    }
+
+    /// Opens the hidden wallet of the passphrase in `request` after the user authorizes it,
+    /// returning `false` if there is none. The hidden wallet stays open until another wallet is
+    /// opened or the signer is restarted.
+    #[inline]
+    async fn open_hidden_wallet(self, request: HiddenRequest) -> Result<bool> {
+        self.ensure_unlocked()?;
+        let password_hash = self.authorizer.lock().await.password_hash.as_bytes();
+        let name = hidden::wallet_name(&password_hash, &request.passphrase);
+        let path = self.state.lock().config.wallet_path(&name);
+        self.authorize(
+            "hidden/open",
+            Prompt::change_settings("hidden/open", "Open a hidden wallet"),
+        )
+        .await?;
+        if fs::metadata(&path).await.is_err() {
+            return Ok(false);
+        }
+        let secret = hidden::storage_secret(&password_hash, &request.passphrase);
+        let key = match task::spawn_blocking(move || {
+            FileKey::decrypt(&secret, &std::fs::read(path)?).map(|(key, _)| key)
+        })
+        .await?
+        {
+            Ok(key) => key,
+            _ => return Ok(false),
+        };
+        self.clone().save().await?;
+        self.open_wallet_with(name, Some(Arc::new(key))).await?;
+        Ok(true)
+    }
+
+    /// Switches to the wallet called `name` after the user confirms it, returning `false` if it
+    /// does not exist.
+    #[inline]
+    async fn switch_wallet(self, request: WalletRequest) -> Result<bool> {
+        info!("[REQUEST] processing `switchWallet`: {:?}", request.name)?;
+        if !self.wallets.lock().contains(&request.name) {
+            return Ok(false);
+        }
+        self.confirm(
+            "wallets/switch",
+            Prompt::change_settings(
+                "wallets/switch",
+                format!("Switch to the `{}` wallet", request.name),
+            ),
+        )
+        .await?;
+        self.clone().save().await?;
+        self.clone().open_wallet(request.name.clone()).await?;
+        let wallets = {
+            let mut wallets = self.wallets.lock();
+            wallets.set_active(&request.name);
+            wallets.clone()
+        };
+        let path = self.state.lock().config.wallets_path();
+        task::spawn_blocking(move || wallets.save(path)).await??;
+        info!("[RESPONSE] switched to wallet `{}`", request.name)?;
+        Ok(true)
+    }
+
+    /// Returns the ledger synchronization status.
+    #[inline]
+    async fn sync_status(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::View).await?;
+        let server = request.state().clone();
+        into_body(move || async move { Ok::<SyncStatus, Error>(server.sync_status.lock().clone()) })
+            .await
+    }
+
+    /// Returns the ledger synchronization progress.
+    #[inline]
+    async fn sync_progress(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::View).await?;
+        let server = request.state().clone();
+        into_body(move || async move { Ok::<SyncProgress, Error>(server.sync_progress.progress()) })
+            .await
+    }
+
+    /// Streams the [`SignerEvent`]s visible to the caller as server-sent events, see the
+    /// [`events`](crate::events) module. When the stream falls behind, a `Lagged` event holding
+    /// the number of skipped events is sent instead of them.
+    #[inline]
+    async fn event_stream(request: Request<Self>) -> Result<Response, tide::Error> {
+        let origin = Self::authenticate(&request, Permission::View)
+            .await?
+            .audit_origin();
+        info!("[REQUEST] opening event stream for {:?}", origin)?;
+        Ok(tide::sse::upgrade(request, move |request, sender| {
+            Self::send_events(request, sender, origin.clone())
+        }))
+    }
+
+    /// Sends the [`SignerEvent`]s visible to `origin` with `sender` until the client disconnects.
+    #[inline]
+    async fn send_events(
+        request: Request<Self>,
+        sender: Sender,
+        origin: Option<String>,
+    ) -> Result<(), tide::Error> {
+        let mut events = request.state().events.subscribe();
+        loop {
+            match events.recv().await {
+                Ok(event) if event.is_visible_to(origin.as_deref()) => {
+                    sender
+                        .send(event.name(), serde_json::to_string(&event)?, None)
+                        .await?
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    sender.send("Lagged", skipped.to_string(), None).await?
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns the shielded balance of every asset and the public balance of the native asset.
+    #[inline]
+    async fn list_balances(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::View).await?;
+        let server = request.state().clone();
+        into_body(move || async move {
+            info!("[REQUEST] processing `balances`")?;
+            server.ensure_unlocked()?;
+            let node_url = server.state.lock().config.node_url.clone();
+            let balances = server.balances.clone();
+            Ok::<BalanceReport, Error>(
+                task::spawn_blocking(move || balances.report(node_url)).await?,
+            )
+        })
+        .await
+    }
+
+    /// Keeps the signer state synchronized with the ledger of the node behind `connection`,
+    /// failing over to the next endpoint and backing off while the node is unreachable.
+    #[inline]
+    async fn run_ledger_sync(self, mut connection: NodeConnection) {
+        loop {
+            connection.set_endpoints(self.state.lock().config.node_endpoints());
+            let client = match connection.client() {
+                Some(client) => client.clone(),
+                _ => {
+                    self.status.set_network(None);
+                    self.status.set_connection(connection.state());
+                    tokio::time::sleep(SYNC_INTERVAL).await;
+                    continue;
+                }
+            };
+            self.status.set_network(Some(client.url().to_owned()));
+            self.wait_for_schedule().await;
+            let result = self.clone().pull_ledger_diff(&client).await;
+            self.status.set_syncing(matches!(result, Ok(true)));
+            let delay = match result {
+                Ok(should_continue) => {
+                    if connection.succeed() {
+                        let _ = info!("reconnected to node at {}", client.url());
+                    }
+                    self.status.set_connection(connection.state());
+                    if should_continue {
+                        continue;
+                    }
+                    self.state.lock().config.sync_schedule.interval()
+                }
+                Err(err) => {
+                    let backoff = connection.fail();
+                    let _ = warn!(
+                        "unable to synchronize with node at {}, retrying in {:?}: {:?}",
+                        client.url(),
+                        backoff,
+                        err
+                    );
+                    self.status.set_connection(connection.state());
+                    backoff
+                }
+            };
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Waits until the background synchronization may run, publishing the reason it is held in
+    /// the signer status, see the [`schedule`](crate::schedule) module.
+    #[inline]
+    async fn wait_for_schedule(&self) {
+        loop {
+            let changed = self.sync_control.changed();
+            let hold = if self.status.get().locked {
+                Some(SyncHold::Locked)
+            } else if self.sync_control.is_paused() {
+                Some(SyncHold::Paused)
+            } else if self.status.get().proving {
+                Some(SyncHold::Proving)
+            } else {
+                let schedule = self.state.lock().config.sync_schedule.clone();
+                task::spawn_blocking(move || schedule.hold())
+                    .await
+                    .ok()
+                    .flatten()
+            };
+            if hold != self.status.get().sync_hold {
+                let _ = info!("background synchronization hold changed to {:?}", hold);
+            }
+            self.status.set_sync_hold(hold);
+            if hold.is_none() {
+                return;
+            }
+            self.status.set_syncing(false);
+            future::select(
+                Box::pin(changed),
+                Box::pin(tokio::time::sleep(HOLD_INTERVAL)),
+            )
+            .await;
+        }
+    }
+
+    /// Pauses the background synchronization until it is resumed.
+    #[inline]
+    async fn pause_sync(self, _: ()) -> Result<()> {
+        info!("[REQUEST] processing `pauseSync`")?;
+        self.sync_control.pause();
+        Ok(())
+    }
+
+    /// Resumes the background synchronization.
+    #[inline]
+    async fn resume_sync(self, _: ()) -> Result<()> {
+        info!("[REQUEST] processing `resumeSync`")?;
+        self.sync_control.resume();
+        Ok(())
+    }
+
+    /// Watches the configuration file at `path` and applies the changes to the reloadable
+    /// settings, see [`Config::reload`]. The file is checked every [`CONFIG_RELOAD_INTERVAL`],
+    /// and on Unix also reloaded on `SIGHUP`. The `loaded` configuration is the one the service
+    /// was started with, which the file is applied to.
+    #[inline]
+    async fn run_config_watch(self, path: PathBuf, loaded: Config) {
+        let mut previous = loaded.clone();
+        let mut modified = file_modified(&path).await;
+        #[cfg(unix)]
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        loop {
+            let interval = Box::pin(tokio::time::sleep(CONFIG_RELOAD_INTERVAL));
+            #[cfg(unix)]
+            let forced = match &mut hangup {
+                Some(hangup) => matches!(
+                    future::select(Box::pin(hangup.recv()), interval).await,
+                    Either::Left(_)
+                ),
+                _ => {
+                    interval.await;
+                    false
+                }
+            };
+            #[cfg(not(unix))]
+            let forced = {
+                interval.await;
+                false
+            };
+            let current = file_modified(&path).await;
+            if !forced && current == modified {
+                continue;
+            }
+            modified = current;
+            let file_path = path.clone();
+            let base = loaded.clone();
+            let next = match task::spawn_blocking(move || base.with_file(file_path)).await {
+                Ok(Ok(next)) => next,
+                Ok(Err(err)) => {
+                    let _ = warn!(
+                        "unable to reload the configuration file {}: {}",
+                        path.display(),
+                        err
+                    );
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let restart = self.reload_config(&previous, &next).await;
+            let _ = info!("reloaded the configuration file {}", path.display());
+            if !restart.is_empty() {
+                let _ = warn!(
+                    "the changes to {} take effect after a restart",
+                    restart.join(", ")
+                );
+            }
+            previous = next;
+        }
+    }
+
+    /// Locks the signer whenever the [`Handle`] is locked, dropping the decrypted signer state
+    /// until the user logs in again with the password. Requests which need the signer state fail
+    /// with [`Error::Locked`] in the meantime.
+    #[inline]
+    async fn run_lock_watch(self) {
+        loop {
+            self.lock_signal.notified().await;
+            let _ = info!("[AUTH] locking the signer");
+            self.status.set_locked(true);
+            self.events.publish(SignerEvent::Locked);
+            self.drop_state().await;
+            self.log_in().await;
+            self.status.set_locked(false);
+            self.events.publish(SignerEvent::Unlocked);
+            let _ = info!("[AUTH] unlocked the signer");
+        }
+    }
+
+    /// Locks the signer once the [`UnlockDuration`] has elapsed since the last password entry,
+    /// checking every [`UNLOCK_EXPIRY_INTERVAL`]. The check is skipped while a prompt holds the
+    /// authorizer, so that the signer locks after the prompt was answered.
+    #[inline]
+    async fn run_unlock_expiry(self) {
+        loop {
+            tokio::time::sleep(UNLOCK_EXPIRY_INTERVAL).await;
+            if self.status.get().locked {
+                continue;
+            }
+            let expired = match self.authorizer.try_lock() {
+                Ok(authorizer) => authorizer.is_expired(),
+                _ => false,
+            };
+            if expired {
+                let _ = info!("[AUTH] unlock duration elapsed");
+                self.handle().lock();
+            }
+        }
+    }
+
+    /// Fails with [`Error::Locked`] while the signer is locked.
+    #[inline]
+    fn ensure_unlocked(&self) -> Result<()> {
+        if self.status.get().locked {
+            return Err(Error::Locked);
+        }
+        Ok(())
+    }
+
+    /// Saves the signer state and drops it from memory, together with the storage keys and the
+    /// polkadot.js compatible account of the active wallet.
+    #[inline]
+    async fn drop_state(&self) {
+        if let Err(err) = self.clone().save().await {
+            let _ = warn!(
+                "[AUTH] unable to save the signer state before locking: {:?}",
+                err
+            );
+        }
+        let _ = self.set_substrate_account(None);
+        self.state.lock().unlocked = None;
+        self.authorizer.lock().await.unlocked_at = None;
+    }
+
+    /// Asks the authorizer to log in again after the signer was locked, until the password and
+    /// the unlock factors decrypt the signer state, and opens the active wallet again.
+    #[inline]
+    async fn log_in(&self) {
+        self.authorizer
+            .lock()
+            .await
+            .authorizer
+            .setup(&Setup::Login)
+            .await;
+        loop {
+            match self.try_log_in().await {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(err) => {
+                    let _ = warn!("[AUTH] unable to log in after locking: {:?}", err);
+                    delay_password_retry().await;
+                }
+            }
+        }
+    }
+
+    /// Makes one login attempt for [`log_in`](Self::log_in), returning `true` if it restored the
+    /// signer state.
+    #[inline]
+    async fn try_log_in(&self) -> Result<bool> {
+        let (password_hash, keyfile) = match self.authorizer.lock().await.log_in().await? {
+            Some(login) => login,
+            _ => return Ok(false),
+        };
+        let config = self.state.lock().config.clone();
+        let device_secret = Self::load_device_secret(&config).await?;
+        let factors = if config.data_path == config.decoy_data_path() {
+            Vec::new()
+        } else {
+            unlock_factors(device_secret.as_deref(), keyfile.as_ref())
+        };
+        let (key, state) =
+            match Self::load_state(&config.data_path, &password_hash, &factors).await? {
+                Some(loaded) => loaded,
+                _ => {
+                    warn!("[AUTH] the unlock factors do not decrypt the signer state")?;
+                    return Ok(false);
+                }
+            };
+        let data_directory = config.data_directory().to_owned();
+        let parameters = task::spawn_blocking(move || crate::parameters::load(data_directory))
+            .await?
+            .ok_or(Error::ParameterLoadingError)?;
+        {
+            let mut lock = self.state.lock();
+            lock.unlocked = Some(UnlockedState::new(
+                Arc::new(key),
+                Signer::from_parts(parameters, state),
+            ));
+            lock.wallet = DEFAULT_WALLET.into();
+        }
+        let active_wallet = self.wallets.lock().active.clone();
+        if let Err(err) = self.clone().open_wallet(active_wallet.clone()).await {
+            warn!(
+                "unable to open wallet `{}`, staying on the default wallet: {:?}",
+                active_wallet, err
+            )?;
+            self.clone().open_wallet(DEFAULT_WALLET.into()).await?;
+            self.wallets.lock().active = DEFAULT_WALLET.into();
+        }
+        let mut authorizer = self.authorizer.lock().await;
+        authorizer.authorizer.sleep().await;
+        authorizer.unlock();
+        Ok(true)
+    }
+
+    /// Handles the [deep links](deeplink) which are forwarded to the `inbox` directory.
+    #[inline]
+    async fn run_deep_link_inbox(self, inbox: PathBuf) {
+        loop {
+            tokio::time::sleep(deeplink::INBOX_INTERVAL).await;
+            let path = inbox.clone();
+            let links = match task::spawn_blocking(move || deeplink::take(&path)).await {
+                Ok(Ok(links)) => links,
+                Ok(Err(err)) => {
+                    let _ = warn!(
+                        "unable to read the deep link inbox {}: {}",
+                        inbox.display(),
+                        err
+                    );
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            for link in links {
+                task::spawn(self.clone().open_deep_link(link));
+            }
+        }
+    }
+
+    /// Runs the request of the deep `link` and opens its callback URL with the response.
+    #[inline]
+    async fn open_deep_link(self, link: String) {
+        let link = match DeepLink::parse(&link) {
+            Ok(link) => link,
+            Err(err) => {
+                let _ = warn!("[REQUEST] ignoring invalid deep link: {:?}", err);
+                return;
+            }
+        };
+        let callback = link.callback().clone();
+        let code = match &link {
+            DeepLink::Pair { code, .. } => code.clone(),
+            _ => None,
+        };
+        let _ = info!(
+            "[REQUEST] processing deep link with a callback to {}",
+            callback.origin().ascii_serialization()
+        );
+        let (key, value) = match self.deep_link_response(link).await {
+            Ok(response) => ("response", response),
+            Err(err) => match deeplink::encode(&err) {
+                Ok(report) => ("error", report),
+                _ => return,
+            },
+        };
+        let mut parameters = vec![(key, value.as_str())];
+        if let Some(code) = &code {
+            parameters.insert(0, ("code", code.as_str()));
+        }
+        let url = deeplink::callback_url(&callback, &parameters);
+        match task::spawn_blocking(move || deeplink::open(&url)).await {
+            Ok(Ok(())) => {
+                let _ = info!("[RESPONSE] opened the deep link callback with the {}", key);
+            }
+            Ok(Err(err)) => {
+                let _ = warn!("unable to open the deep link callback: {}", err);
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Runs the request of the deep `link`, returning its encoded response.
+    ///
+    /// Pairing links are answered like requests from the origin of their callback, which is
+    /// shown in the prompt. Signing links are answered like requests without an origin, so that
+    /// a link can never use the approval session or the remembered decisions of a dapp.
+    #[inline]
+    async fn deep_link_response(self, link: DeepLink) -> Result<String, SignerError> {
+        match link {
+            DeepLink::Pair { name, callback, .. } => {
+                let caller = Caller::Origin(Some(callback.origin().ascii_serialization()));
+                let pairing = self.pair_dapp(caller, DappPairRequest { name }).await?;
+                Ok(deeplink::encode(&pairing)?)
+            }
+            DeepLink::Sign { payload, .. } => {
+                let mode = self.state.lock().config.compatibility;
+                let (request, _) = compat::deserialize::<SignRequest>(&payload, mode)?;
+                let response = self.sign(Caller::Origin(None), request).await?;
+                Ok(deeplink::encode(&response)?)
+            }
+        }
+    }
+
+    /// Applies the reloadable settings which changed from `previous` to `next`, returning the
+    /// names of the other changed settings.
+    ///
+    /// The prompt expiry and the lockout limits are applied once the current prompt is answered.
+    #[inline]
+    async fn reload_config(&self, previous: &Config, next: &Config) -> Vec<&'static str> {
+        let (restart, config) = {
+            let mut state = self.state.lock();
+            let restart = state.config.reload(previous, next);
+            (restart, state.config.clone())
+        };
+        crate::log::set_level(config.log_level);
+        crate::log::set_rotation(config.log_rotation);
+        *self.cors.write() = cors_policy(&config);
+        let mut authorizer = self.authorizer.lock().await;
+        authorizer.expiry = prompt_expiry(&config);
+        authorizer.unlock_duration = config.unlock_duration;
+        authorizer.throttle.configure(&config);
+        restart
+    }
+
+    /// Pulls the next ledger diff from the node behind `client` and synchronizes the signer with
+    /// it, returning `true` if there are more diffs to pull.
+    #[inline]
+    async fn pull_ledger_diff(self, client: &NodeClient) -> Result<bool> {
+        let checkpoint = self.sync_status.lock().checkpoint.clone();
+        let (height, response) = {
+            let client = client.clone();
+            let checkpoint = checkpoint.clone();
+            task::spawn_blocking(move || {
+                Ok::<_, io::Error>((client.block_height()?, client.pull(&checkpoint)?))
+            })
+            .await??
+        };
+        self.sync_progress.set_block_height(height);
+        let should_continue = response.should_continue;
+        let request = SyncRequest {
+            origin_checkpoint: checkpoint,
+            inserts: response.receivers,
+            removes: response.senders,
+        };
+        match self
+            .sync_batch(request, !should_continue, Some(height))
+            .await?
+        {
+            Ok(_) => Ok(should_continue),
+            Err(err) => {
+                warn!("node ledger diff was rejected by the signer: {:?}", err)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Measures the latency and throughput of the configured node endpoints, switching the ledger
+    /// synchronization to the fastest one if requested.
+    #[inline]
+    async fn network_probe(self, request: ProbeRequest) -> Result<ProbeReport> {
+        info!("[REQUEST] processing `networkProbe`: {:?}", request)?;
+        let urls = probe::endpoints(&self.state.lock().config);
+        let endpoints = task::spawn_blocking(move || probe::run_network_probe(urls)).await?;
+        let selected = endpoints
+            .first()
+            .filter(|endpoint| request.auto_select && endpoint.is_reachable())
+            .map(|endpoint| endpoint.url.clone());
+        if let Some(url) = &selected {
+            info!("[PROBE] selected node endpoint {}", url)?;
+            self.state.lock().config.node_url = Some(url.clone());
+        }
+        info!(
+            "[RESPONSE] responding to `networkProbe` with {} endpoints",
+            endpoints.len()
+        )?;
+        Ok(ProbeReport {
+            endpoints,
+            selected,
+        })
+    }
+
+    /// Returns the authorization prompt of `transaction` with the asset `metadata` if it is known,
+    /// annotated with the labels of its asset and of the receiving account if it is one of ours.
+    #[inline]
+    fn labeled_prompt(
+        &self,
+        transaction: &Transaction<manta_pay::config::Config>,
+        metadata: Option<&AssetMetadata>,
+    ) -> Result<String> {
+        let prompt = inspect::prompt(transaction, metadata)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let account = match transaction {
+            Transaction::PrivateTransfer(_, receiving_key) => {
+                let receiver = receiving_key_to_base58(receiving_key);
+                self.state
+                    .lock()
+                    .unlocked_mut()?
+                    .signer
+                    .receiving_keys(ReceivingKeyRequest::GetAll)
+                    .iter()
+                    .position(|key| receiving_key_to_base58(key) == receiver)
+            }
+            _ => None,
+        };
+        Ok(self.labels.get().annotate(
+            prompt,
+            transaction_amount(transaction, None).asset_id,
+            account,
+        ))
+    }
+
+    /// Returns the authorization prompt of `transaction` with the asset `metadata` if it is known,
+    /// followed by the dapp `memo` and the options of the `fee` quote, if any.
+    #[inline]
+    fn sign_prompt(
+        &self,
+        transaction: &Transaction<manta_pay::config::Config>,
+        metadata: Option<&AssetMetadata>,
+        memo: Option<&str>,
+        fee: Option<&FeeQuote>,
+    ) -> Result<String> {
+        let prompt = with_memo(self.labeled_prompt(transaction, metadata)?, memo);
+        Ok(match fee {
+            Some(fee) => fee.annotate(prompt),
+            _ => prompt,
+        })
+    }
+
+    /// Runs the transaction signing protocol on the signer.
+    #[inline]
+    async fn sign(
+        self,
+        caller: Caller,
+        request: SignRequest,
+    ) -> Result<Result<SignResponse, SignError>> {
+        Ok(self.sign_with(caller, request, None, None).await?.response)
+    }
+
+    /// Runs the transaction signing protocol on the signer, attaching the memo supplied by the
+    /// dapp to the transaction.
+    #[inline]
+    async fn sign_with_memo(
+        self,
+        caller: Caller,
+        request: MemoSignRequest,
+    ) -> Result<Result<SignResponse, SignError>> {
+        let memo = request.memo.as_deref().and_then(memo::normalize);
+        Ok(self
+            .sign_with(caller, request.request, memo, None)
+            .await?
+            .response)
+    }
+
+    /// Runs the transaction signing protocol on the signer, showing the network fees of the quote
+    /// in the request and returning the fee the user chose.
+    #[inline]
+    async fn sign_with_fee(
+        self,
+        caller: Caller,
+        request: FeeSignRequest,
+    ) -> Result<FeeSignResponse> {
+        let memo = request.memo.as_deref().and_then(memo::normalize);
+        self.sign_with(caller, request.request, memo, Some(request.fee))
+            .await
+    }
+
+    /// Waits for the TOTP code and the paired device approval of the transaction described by
+    /// `summary` if they are enabled. Every signing path must pass this check, including the
+    /// transactions authorized by an API key.
+    #[inline]
+    async fn check_second_factors(&self, summary: &str) -> Result<()> {
+        if let Some(totp) = &self.totp {
+            info!("[AUTH] waiting for TOTP approval")?;
+            if !totp.wait().await {
+                return Err(Error::AuthorizationError);
+            }
+        }
+        if self.devices.has_devices() {
+            info!("[AUTH] waiting for paired device approval")?;
+            if !self.devices.wait(summary.to_owned()).await {
+                return Err(Error::AuthorizationError);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the transaction signing protocol on the signer, attaching `memo` to the transaction
+    /// unless the user enters another one when authorizing it. If there is a `fee` quote, the
+    /// user can pick its tier before the transfer is proven.
+    #[inline]
+    async fn sign_with(
+        self,
+        caller: Caller,
+        request: SignRequest,
+        memo: Option<String>,
+        fee: Option<FeeQuote>,
+    ) -> Result<FeeSignResponse> {
+        info!("[REQUEST] processing `sign`: {:?}.", Redacted(&request))?;
+        self.ensure_unlocked()?;
+        let span = Span::new("sign");
+        let SignRequest {
+            transaction,
+            metadata,
+        } = request;
+        let (metadata, fee) = {
+            let chain = &self.state.lock().config.chain;
+            (
+                metadata
+                    .or_else(|| chain.metadata(transaction_amount(&transaction, None).asset_id)),
+                fee.map(|fee| fee.with_native_asset(chain)),
+            )
+        };
+        let shape = transaction.shape();
+        span.record("sign.shape", format!("{:?}", shape));
+        let mut chosen_fee = fee.as_ref().and_then(FeeQuote::default_option);
+        let mut approval = None;
+        match (shape, &caller) {
+            (TransferShape::Mint, _) => {
+                // NOTE: We skip authorization on mint transactions because they are deposits not
+                //       withdrawals from the point of view of the signer. Everything else, by
+                //       default, requests authorization.
+            }
+            (_, Caller::ApiKey(key)) => {
+                let summary = self.sign_prompt(
+                    &transaction,
+                    metadata.as_ref(),
+                    memo.as_deref(),
+                    fee.as_ref(),
+                )?;
+                let value = transaction_value(&transaction);
+                let result = match key.send_limit() {
+                    Some(limit) if value <= limit => {
+                        info!(
+                            "[AUTH] transaction of value {} authorized by API key `{}`",
+                            value, key.name
+                        )?;
+                        let ticket = self.queue.push("sign", &summary);
+                        let result =
+                            unless_rejected(&ticket, self.check_second_factors(&summary)).await;
+                        drop(ticket);
+                        result
+                    }
+                    _ => {
+                        warn!(
+                            "[AUTH] transaction of value {} exceeds the limit of API key `{}`",
+                            value, key.name
+                        )?;
+                        Err(Error::AuthorizationError)
+                    }
+                };
+                let record = self
+                    .audit(
+                        "sign",
+                        caller.audit_origin(),
+                        &summary,
+                        Some(transaction_amount(&transaction, metadata.as_ref())),
+                        &result,
+                    )
+                    .await?;
+                result?;
+                self.attach_memo(record.clone(), memo).await?;
+                approval = record;
+            }
+            _ => {
+                info!("[AUTH] asking for transaction authorization")?;
+                let _authorize = span.child("sign.authorize");
+                let summary = self.sign_prompt(
+                    &transaction,
+                    metadata.as_ref(),
+                    memo.as_deref(),
+                    fee.as_ref(),
+                )?;
+                let value = transaction_value(&transaction);
+                let amount = transaction_amount(&transaction, metadata.as_ref());
+                let asset_id = amount.asset_id;
+                let prompt = Prompt::SignTransaction {
+                    summary: summary.clone(),
+                    amount: amount.clone(),
+                    fee: fee.clone(),
+                };
+                let ticket = self.queue.push("sign", &summary);
+                let result = unless_rejected(&ticket, async {
+                    let mut authorizer = self.authorizer.lock().await;
+                    let choices = match &caller {
+                        Caller::Dapp { id, .. } if self.sessions.authorize(id, value) => {
+                            info!(
+                                "[AUTH] transaction of value {} authorized by the session of dapp `{}`",
+                                value, id
+                            )?;
+                            (None, None, None)
+                        }
+                        Caller::Dapp { id, .. } if self.exemptions.authorize(id, asset_id, value) => {
+                            info!(
+                                "[AUTH] transaction of value {} in asset {} authorized by a remembered decision for dapp `{}`",
+                                value, asset_id, id
+                            )?;
+                            (None, None, None)
+                        }
+                        _ => {
+                            authorizer.check(&prompt).await?;
+                            let memo = authorizer
+                                .authorizer
+                                .memo()
+                                .await
+                                .as_deref()
+                                .and_then(memo::normalize);
+                            let tier = match &fee {
+                                Some(_) => authorizer.authorizer.fee_tier().await,
+                                _ => None,
+                            };
+                            let remember = match &caller {
+                                Caller::Dapp { .. } => authorizer.authorizer.remember().await,
+                                _ => None,
+                            };
+                            (memo, tier, remember)
+                        }
+                    };
+                    drop(authorizer);
+                    self.check_second_factors(&summary).await?;
+                    Ok(choices)
+                })
+                .await;
+                drop(ticket);
+                let record = self
+                    .audit(
+                        "sign",
+                        caller.audit_origin(),
+                        &summary,
+                        Some(amount),
+                        &result,
+                    )
+                    .await?;
+                let (entered_memo, tier, remember) = result?;
+                if let (Caller::Dapp { id, origin }, Some(choice)) = (&caller, remember) {
+                    match self
+                        .exemptions
+                        .remember(id.clone(), origin.clone(), asset_id, choice)
+                    {
+                        Ok(exemption) => info!(
+                            "[AUTH] remembering the decision for dapp `{}` up to a value of {} until {}",
+                            exemption.dapp, exemption.value_limit, exemption.expires_at
+                        )?,
+                        Err(err) => warn!("[AUTH] unable to remember the decision: {:?}", err)?,
+                    }
+                }
+                self.attach_memo(record.clone(), entered_memo.or(memo))
+                    .await?;
+                approval = record;
+                if let (Some(fee), Some(tier)) = (&fee, tier) {
+                    chosen_fee = fee.select(Some(tier));
+                }
+            }
+        }
+        if let Some(chosen_fee) = &chosen_fee {
+            info!(
+                "[FEE] using the {} network fee of {}",
+                chosen_fee.tier, chosen_fee.fee
+            )?;
+        }
+        let response = {
+            let _prove = span.child("sign.prove");
+            self.status.set_proving(true);
+            let response = self
+                .state
+                .lock()
+                .unlocked_mut()
+                .map(|unlocked| unlocked.signer.sign(transaction));
+            self.status.set_proving(false);
+            response?
+        };
+        span.record("sign.success", response.is_ok());
+        self.attach_proof(approval, &response).await?;
+        info!(
+            "[RESPONSE] responding to `sign` with: {:?}.",
+            Redacted(&response)
+        )?;
+        Ok(FeeSignResponse {
+            response,
+            fee: chosen_fee,
+        })
+    }
+
+    /// Runs the transaction signing protocol on the request stored in the input file of
+    /// `request`, writing the response to its output file. Returns `true` if the transaction
+    /// was signed and `false` if the signer rejected it, in which case the error is written to
+    /// the output file.
+    #[inline]
+    async fn sign_file(
+        self,
+        caller: Caller,
+        request: SignFileRequest,
+    ) -> Result<Result<bool, airgap::Error>> {
+        info!("[REQUEST] processing `signFile`: {:?}", Redacted(&request))?;
+        let input = request.input;
+        let payload = task::spawn_blocking(move || std::fs::read(input)).await??;
+        let mode = self.state.lock().config.compatibility;
+        let sign_request = match compat::deserialize::<SignRequest>(&payload, mode) {
+            Ok((sign_request, _)) => sign_request,
+            _ => return Ok(Err(airgap::Error::InvalidPayload)),
+        };
+        let response = self.sign(caller, sign_request).await?;
+        let signed = response.is_ok();
+        let bytes = serde_json::to_vec_pretty(&response)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let output = request.output.clone();
+        task::spawn_blocking(move || storage::write_atomic(&output, &bytes, 0)).await??;
+        info!(
+            "[RESPONSE] wrote `signFile` response to {}",
+            request.output.display()
+        )?;
+        Ok(Ok(signed))
+    }
+
+    /// Runs the transaction signing protocol on a request which was scanned from the frames of
+    /// an air-gapped machine, returning the response split into frames.
+    #[inline]
+    async fn airgap_sign(
+        self,
+        caller: Caller,
+        request: AirgapRequest,
+    ) -> Result<Result<Vec<String>, airgap::Error>> {
+        info!(
+            "[REQUEST] processing `airgapSign` with {} frames",
+            request.frames.len()
+        )?;
+        let payload = match airgap::decode_frames(&request.frames) {
+            Ok(payload) => payload,
+            Err(err) => return Ok(Err(err)),
+        };
+        let mode = self.state.lock().config.compatibility;
+        let sign_request = match compat::deserialize::<SignRequest>(&payload, mode) {
+            Ok((sign_request, _)) => sign_request,
+            _ => return Ok(Err(airgap::Error::InvalidPayload)),
+        };
+        let response = self.sign(caller, sign_request).await?;
+        let frames = airgap::encode_frames(
+            &serde_json::to_vec(&response)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+        );
+        info!(
+            "[RESPONSE] responding to `airgapSign` with {} frames",
+            frames.len()
+        )?;
+        Ok(Ok(frames))
+    }
+
+    /// Lists the polkadot.js compatible accounts.
+    #[inline]
+    async fn polkadot_accounts(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::View).await?;
+        let server = request.state().clone();
+        into_body(move || async move {
+            Ok::<Vec<InjectedAccount>, Error>(
+                server
+                    .state
+                    .lock()
+                    .unlocked()?
+                    .substrate
+                    .iter()
+                    .map(|account| account.injected())
+                    .collect(),
+            )
+        })
+        .await
+    }
+
+    /// Returns the public address of the polkadot.js compatible account, or `None` if the account
+    /// cannot be recovered from the recovery phrase.
+    #[inline]
+    async fn public_address(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::View).await?;
+        let server = request.state().clone();
+        into_body(move || async move {
+            Ok::<Option<PublicAddress>, Error>(
+                server
+                    .state
+                    .lock()
+                    .unlocked()?
+                    .substrate
+                    .as_ref()
+                    .and_then(|account| account.public_address()),
+            )
+        })
+        .await
+    }
+
+    /// Signs the extrinsic payload in `request` with the polkadot.js compatible account after
+    /// the user authorizes it.
+    #[inline]
+    async fn polkadot_sign_payload(
+        self,
+        request: SignerPayloadJson,
+    ) -> Result<Result<SignerResult, substrate::Error>> {
+        info!(
+            "[REQUEST] processing `polkadot/signPayload`: {:?}",
+            Redacted(&request)
+        )?;
+        let account = match &self.state.lock().unlocked()?.substrate {
+            Some(account) if request.address == account.address() => account.clone(),
+            _ => return Ok(Err(substrate::Error::UnknownAddress)),
+        };
+        let known_chain = self
+            .state
+            .lock()
+            .config
+            .chain
+            .matches_genesis(&request.genesis_hash);
+        if !known_chain {
+            warn!(
+                "[AUTH] rejecting `polkadot/signPayload` for unknown genesis hash {}",
+                request.genesis_hash
+            )?;
+            return Ok(Err(substrate::Error::UnknownChain));
+        }
+        self.authorize(
+            "polkadot/signPayload",
+            Prompt::SignExtrinsic {
+                address: request.address.clone(),
+                genesis_hash: request.genesis_hash.clone(),
+                call: request.method.clone(),
+            },
+        )
+        .await?;
+        let response = account.sign_payload(&request);
+        info!(
+            "[RESPONSE] responding to `polkadot/signPayload` with: {:?}",
+            Redacted(&response)
+        )?;
+        Ok(response)
+    }
+
+    /// Signs the raw payload in `request` with the polkadot.js compatible account after the user
+    /// authorizes it.
+    #[inline]
+    async fn polkadot_sign_raw(
+        self,
+        request: SignerPayloadRaw,
+    ) -> Result<Result<SignerResult, substrate::Error>> {
+        info!(
+            "[REQUEST] processing `polkadot/signRaw`: {:?}",
+            Redacted(&request)
+        )?;
+        let account = match &self.state.lock().unlocked()?.substrate {
+            Some(account) if request.address == account.address() => account.clone(),
+            _ => return Ok(Err(substrate::Error::UnknownAddress)),
+        };
+        self.authorize(
+            "polkadot/signRaw",
+            Prompt::SignMessage {
+                address: request.address.clone(),
+                message: request.data.clone(),
+            },
+        )
+        .await?;
+        let response = account.sign_raw(&request);
+        info!(
+            "[RESPONSE] responding to `polkadot/signRaw` with: {:?}",
+            Redacted(&response)
+        )?;
+        Ok(response)
+    }
+
+    /// Signs the text message in `request` with the polkadot.js compatible account after the
+    /// user authorizes it, showing the full message in the prompt.
+    #[inline]
+    async fn sign_message(
+        self,
+        request: SignMessageRequest,
+    ) -> Result<Result<MessageSignature, substrate::Error>> {
+        info!(
+            "[REQUEST] processing `signMessage`: {:?}",
+            Redacted(&request)
+        )?;
+        if request.message.len() > substrate::MAX_MESSAGE_LENGTH {
+            return Ok(Err(substrate::Error::MessageTooLong));
+        }
+        let account = match &self.state.lock().unlocked()?.substrate {
+            Some(account) => account.clone(),
+            _ => return Ok(Err(substrate::Error::UnknownAddress)),
+        };
+        self.authorize(
+            "signMessage",
+            Prompt::SignMessage {
+                address: account.address().to_owned(),
+                message: request.message.clone(),
+            },
+        )
+        .await?;
+        let response = account.sign_message(request.message);
+        info!(
+            "[RESPONSE] responding to `signMessage` with: {:?}",
+            Redacted(&response)
+        )?;
+        Ok(response)
+    }
+
+    /// Decodes the transaction in `request` into the breakdown of what the signer would show
+    /// when signing it, without asking for authorization.
+    #[inline]
+    async fn decode_transaction(self, request: SignRequest) -> Result<TransactionBreakdown> {
+        info!("[REQUEST] processing `decodeTransaction`")?;
+        let breakdown = TransactionBreakdown::new(&request)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        info!(
+            "[RESPONSE] responding to `decodeTransaction` with: {:?}",
+            Redacted(&breakdown)
+        )?;
+        Ok(breakdown)
+    }
+
+    /// Computes the confirmation code of the transaction in `request` without signing it.
     #[inline]
-    async fn sync(self, request: SyncRequest) -> Result<Result<SyncResponse, SyncError>> {
-        info!("[REQUEST] processing `sync`:  {:?}.", request)?;
-        let response = self.state.lock().signer.sync(request);
-        task::spawn(async {
-            if self.save().await.is_err() {
-                let _ = warn!("unable to save current signer state");
-            }
-        });
-        info!("[RESPONSE] responding to `sync` with: {:?}.", response)?;
-        Ok(response)
+    async fn confirmation_code(self, request: SignRequest) -> Result<String> {
+        info!("[REQUEST] processing `confirmationCode`")?;
+        let code = confirmation_code(&request.transaction)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        info!(
+            "[RESPONSE] responding to `confirmationCode` with: {:?}",
+            Redacted(&code)
+        )?;
+        Ok(code)
     }
 
-    /// Runs the transaction signing protocol on the signer.
+    /// Approves the pending signing request with the TOTP code in `request`.
     #[inline]
-    async fn sign(self, request: SignRequest) -> Result<Result<SignResponse, SignError>> {
-        info!("[REQUEST] processing `sign`: {:?}.", request)?;
-        let SignRequest {
-            transaction,
-            metadata,
-        } = request;
-        match transaction.shape() {
-            TransferShape::Mint => {
-                // NOTE: We skip authorization on mint transactions because they are deposits not
-                //       withdrawals from the point of view of the signer. Everything else, by
-                //       default, requests authorization.
-            }
-            _ => {
-                info!("[AUTH] asking for transaction authorization")?;
-                let summary = metadata
-                    .map(|m| transaction.display(&m, receiving_key_to_base58))
-                    .unwrap_or_default();
-                self.authorizer.lock().await.check(&summary).await?
-            }
+    async fn approve(self, request: ApprovalRequest) -> Result<bool> {
+        info!("[REQUEST] processing `approve`")?;
+        let approved = match &self.totp {
+            Some(totp) => totp.approve(&request.code),
+            _ => false,
+        };
+        info!("[RESPONSE] responding to `approve` with: {:?}", approved)?;
+        Ok(approved)
+    }
+
+    /// Starts pairing a companion device after the user authorizes it. The pairing offer is only
+    /// shown to the user by the authorizer, and the client learns when it expires.
+    #[inline]
+    async fn start_pairing(self, _: ()) -> Result<PairingSession> {
+        info!("[REQUEST] processing `pairing/start`")?;
+        self.authorize(
+            "pairing/start",
+            Prompt::change_settings("pairing/start", "Pair a new companion device"),
+        )
+        .await?;
+        let service_url = match self.discovery.as_ref().and_then(|d| d.client_address()) {
+            Some(address) => address.to_owned(),
+            _ => self.state.lock().config.client_address().to_owned(),
+        };
+        let offer = self
+            .devices
+            .start_pairing(&service_url, &self.identity.public_key());
+        self.authorizer
+            .lock()
+            .await
+            .authorizer
+            .show_pairing(&offer)
+            .await;
+        info!("[RESPONSE] pairing offer expires at {}", offer.expires_at)?;
+        Ok(PairingSession {
+            expires_at: offer.expires_at,
+        })
+    }
+
+    /// Completes pairing a companion device with the one-time token of the current offer.
+    #[inline]
+    async fn complete_pairing(self, request: PairRequest) -> Result<bool> {
+        info!(
+            "[REQUEST] processing `pairing/complete` for `{}`",
+            request.name
+        )?;
+        let devices = self.devices.clone();
+        let paired = task::spawn_blocking(move || devices.complete_pairing(request)).await??;
+        info!(
+            "[RESPONSE] responding to `pairing/complete` with: {:?}",
+            paired
+        )?;
+        Ok(paired)
+    }
+
+    /// Unpairs the companion device with the given public key after the user confirms it.
+    #[inline]
+    async fn unpair(self, public_key: String) -> Result<bool> {
+        info!("[REQUEST] processing `pairing/unpair`: {:?}", public_key)?;
+        self.confirm(
+            "pairing/unpair",
+            Prompt::change_settings(
+                "pairing/unpair",
+                format!("Unpair companion device `{}`", public_key),
+            ),
+        )
+        .await?;
+        let devices = self.devices.clone();
+        let unpaired = task::spawn_blocking(move || devices.unpair(&public_key)).await??;
+        info!(
+            "[RESPONSE] responding to `pairing/unpair` with: {:?}",
+            unpaired
+        )?;
+        Ok(unpaired)
+    }
+
+    /// Forwards the decision of a paired device to the pending signing request.
+    #[inline]
+    async fn device_decision(self, request: DeviceApprovalRequest) -> Result<bool> {
+        info!("[REQUEST] processing `pairing/decide`")?;
+        let accepted = self.devices.decide(request);
+        info!(
+            "[RESPONSE] responding to `pairing/decide` with: {:?}",
+            accepted
+        )?;
+        Ok(accepted)
+    }
+
+    /// Pairs the calling dapp after the user confirms it, returning the shared secret it
+    /// authenticates its requests with.
+    #[inline]
+    async fn pair_dapp(self, caller: Caller, request: DappPairRequest) -> Result<DappPairing> {
+        info!("[REQUEST] processing `dapps/pair`: {:?}", request)?;
+        let origin = match &caller {
+            Caller::Origin(origin) | Caller::Dapp { origin, .. } => origin.clone(),
+            Caller::ApiKey(_) => None,
+        };
+        self.confirm_as(
+            "dapps/pair",
+            caller.audit_origin(),
+            Prompt::PairDapp {
+                name: request.name.clone(),
+                origin: origin.clone(),
+            },
+        )
+        .await?;
+        let mut store = self.dapps.lock().clone();
+        let pairing = store.pair(request.name, origin, &mut OsRng);
+        self.save_dapps(store).await?;
+        info!("[RESPONSE] paired dapp `{}`", pairing.id)?;
+        Ok(pairing)
+    }
+
+    /// Unpairs the dapp with the given pairing `id` after the user confirms it.
+    #[inline]
+    async fn unpair_dapp(self, id: String) -> Result<bool> {
+        info!("[REQUEST] processing `dapps/unpair`: {:?}", id)?;
+        self.confirm(
+            "dapps/unpair",
+            Prompt::change_settings("dapps/unpair", format!("Unpair dapp `{}`", id)),
+        )
+        .await?;
+        let mut store = self.dapps.lock().clone();
+        if !store.unpair(&id) {
+            return Ok(false);
         }
-        let response = self.state.lock().signer.sign(transaction);
-        info!("[RESPONSE] responding to `sign` with: {:?}.", response)?;
-        Ok(response)
+        self.save_dapps(store).await?;
+        info!("[RESPONSE] unpaired dapp `{}`", id)?;
+        Ok(true)
+    }
+
+    /// Saves the paired dapp `store` to disk and makes it current.
+    #[inline]
+    async fn save_dapps(&self, store: DappStore) -> Result<()> {
+        let (path, key) = {
+            let lock = self.state.lock();
+            (lock.config.dapps_path(), lock.key()?)
+        };
+        let saved_store = store.clone();
+        task::spawn_blocking(move || cipher::save(path, &key, &saved_store, 0, &mut OsRng))
+            .await??;
+        *self.dapps.lock() = store;
+        Ok(())
+    }
+
+    /// Lists the paired dapps.
+    #[inline]
+    async fn list_dapps(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(move || async move { Ok::<Vec<DappInfo>, Error>(server.dapps.lock().list()) })
+            .await
+    }
+
+    /// Lists the paired companion devices.
+    #[inline]
+    async fn list_devices(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(move || async move { Ok::<Vec<PairedDevice>, Error>(server.devices.devices()) })
+            .await
+    }
+
+    /// Returns the signing request waiting for a paired device, signed with the signer identity.
+    #[inline]
+    async fn pending_approval(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(move || async move {
+            server
+                .devices
+                .pending()
+                .map(|pending| Signed::new(&server.identity, pending))
+                .transpose()
+                .map_err(|err| Error::from(io::Error::new(io::ErrorKind::Other, err)))
+        })
+        .await
+    }
+
+    /// Returns the full payload of the most recent oversized prompt with the hash given in the
+    /// request path.
+    #[inline]
+    async fn prompt_payload(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        let hash = request.param("hash")?.to_owned();
+        into_body(move || async move { Ok::<Option<Payload>, Error>(server.payloads.get(&hash)) })
+            .await
+    }
+
+    /// Creates a new API key after the user authorizes it.
+    #[inline]
+    async fn create_api_key(self, request: CreateRequest) -> Result<CreateResponse> {
+        info!("[REQUEST] processing `apiKeys/create`: {:?}", request)?;
+        self.authorize(
+            "apiKeys/create",
+            Prompt::change_settings(
+                "apiKeys/create",
+                format!(
+                    "Create API key `{}` with scopes {:?}",
+                    request.name, request.scopes
+                ),
+            ),
+        )
+        .await?;
+        let (path, file_key) = {
+            let state = self.state.lock();
+            (state.config.api_keys_path(), state.key()?)
+        };
+        let mut store = self.api_keys.lock().clone();
+        let key = store
+            .create(request.name.clone(), request.scopes, &mut OsRng)
+            .ok_or(Error::AuthorizationError)?;
+        let saved_store = store.clone();
+        task::spawn_blocking(move || saved_store.save(path, &file_key)).await??;
+        *self.api_keys.lock() = store;
+        info!("[RESPONSE] created API key `{}`", request.name)?;
+        Ok(CreateResponse {
+            name: request.name,
+            key,
+        })
+    }
+
+    /// Revokes an API key after the user confirms it.
+    #[inline]
+    async fn revoke_api_key(self, request: RevokeRequest) -> Result<bool> {
+        info!("[REQUEST] processing `apiKeys/revoke`: {:?}", request)?;
+        self.confirm(
+            "apiKeys/revoke",
+            Prompt::change_settings(
+                "apiKeys/revoke",
+                format!("Revoke API key `{}`", request.name),
+            ),
+        )
+        .await?;
+        let (path, file_key) = {
+            let state = self.state.lock();
+            (state.config.api_keys_path(), state.key()?)
+        };
+        let mut store = self.api_keys.lock().clone();
+        if !store.revoke(&request.name) {
+            return Ok(false);
+        }
+        let saved_store = store.clone();
+        task::spawn_blocking(move || saved_store.save(path, &file_key)).await??;
+        *self.api_keys.lock() = store;
+        info!("[RESPONSE] revoked API key `{}`", request.name)?;
+        Ok(true)
+    }
+
+    /// Lists the public information of every API key.
+    #[inline]
+    async fn list_api_keys(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(
+            move || async move { Ok::<Vec<ApiKeyInfo>, Error>(server.api_keys.lock().list()) },
+        )
+        .await
+    }
+
+    /// Generates the signed derivation report listing every receiving key derived so far.
+    #[inline]
+    async fn derivation_report(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(move || async move {
+            info!("[REQUEST] processing `derivationReport`")?;
+            let receiving_keys = server
+                .state
+                .lock()
+                .unlocked_mut()?
+                .signer
+                .receiving_keys(ReceivingKeyRequest::GetAll);
+            let report = DerivationReport::new(receiving_keys.iter().map(receiving_key_to_base58));
+            let signed = Signed::new(&server.identity, report)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            info!(
+                "[RESPONSE] responding to `derivationReport` with {} addresses",
+                signed.payload.addresses.len()
+            )?;
+            Ok(signed)
+        })
+        .await
+    }
+
+    /// Generates the signed state digest which users can share to report synchronization bugs.
+    #[inline]
+    async fn state_digest(request: Request<Self>) -> Result<Response, tide::Error> {
+        Self::authenticate(&request, Permission::Owner).await?;
+        let server = request.state().clone();
+        into_body(move || async move {
+            info!("[REQUEST] processing `stateDigest`")?;
+            let (config, receiving_keys) = {
+                let mut state = server.state.lock();
+                (
+                    state.config.clone(),
+                    state
+                        .unlocked_mut()?
+                        .signer
+                        .receiving_keys(ReceivingKeyRequest::GetAll)
+                        .len(),
+                )
+            };
+            let sync = server.sync_status.lock().clone();
+            let digest =
+                task::spawn_blocking(move || StateDigest::collect(&config, sync, receiving_keys))
+                    .await?;
+            let signed = Signed::new(&server.identity, digest)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            info!("[RESPONSE] responding to `stateDigest`")?;
+            Ok(signed)
+        })
+        .await
     }
 
     /// Runs the receiving key sampling protocol on the signer.
     #[inline]
     async fn receiving_keys(self, request: ReceivingKeyRequest) -> Result<Vec<ReceivingKey>> {
-        info!("[REQUEST] processing `receivingKeys`: {:?}", request)?;
-        let response = self.state.lock().signer.receiving_keys(request);
+        info!(
+            "[REQUEST] processing `receivingKeys`: {:?}",
+            Redacted(&request)
+        )?;
+        let _span = Span::new("receivingKeys");
+        let response = self
+            .state
+            .lock()
+            .unlocked_mut()?
+            .signer
+            .receiving_keys(request);
         info!(
             "[RESPONSE] responding to `receivingKeys` with: {:?}",
-            response
+            Redacted(&response)
         )?;
         Ok(response)
     }
 }
 
+/// CORS Policy
+///
+/// Applies the CORS policy for the [`origin_url`](Config::origin_url) of the current
+/// configuration, which can change when the configuration file is reloaded.
+struct CorsPolicy;
+
+#[tide::utils::async_trait]
+impl<A> Middleware<Server<A>> for CorsPolicy
+where
+    A: Authorizer,
+{
+    #[inline]
+    async fn handle(&self, request: Request<Server<A>>, next: Next<'_, Server<A>>) -> tide::Result {
+        let cors = request.state().cors.read().clone();
+        cors.handle(request, next).await
+    }
+}
+
+/// Error Body
+///
+/// Writes the [`SignerError`] of every failed request to the response body as an
+/// [`ErrorReport`](crate::error::ErrorReport), which carries its stable error code.
+struct ErrorBody;
+
+#[tide::utils::async_trait]
+impl<A> Middleware<Server<A>> for ErrorBody
+where
+    A: Authorizer,
+{
+    #[inline]
+    async fn handle(&self, request: Request<Server<A>>, next: Next<'_, Server<A>>) -> tide::Result {
+        let mut response = next.run(request).await;
+        if let Some(err) = response.downcast_error::<SignerError>() {
+            let body = Body::from_json(err)?;
+            response.set_body(body);
+        }
+        Ok(response)
+    }
+}
+
+/// Response Signer
+///
+/// Signs every response of the service with the signer identity as described in the
+/// [`identity`] module. The event stream never ends, so its events are not signed.
+struct ResponseSigner;
+
+#[tide::utils::async_trait]
+impl<A> Middleware<Server<A>> for ResponseSigner
+where
+    A: Authorizer,
+{
+    #[inline]
+    async fn handle(&self, request: Request<Server<A>>, next: Next<'_, Server<A>>) -> tide::Result {
+        let signer = request.state().identity.clone();
+        let challenge = request
+            .header(identity::CHALLENGE_HEADER)
+            .map(|header| header.last().as_str().to_owned())
+            .unwrap_or_default();
+        let mut response = next.run(request).await;
+        if response.content_type() == Some(tide::http::mime::SSE) {
+            return Ok(response);
+        }
+        let body = response.take_body();
+        let mime = body.mime().clone();
+        let bytes = body.into_bytes().await?;
+        let signature = signer.sign(&identity::response_message(&challenge, &bytes));
+        let mut body = Body::from_bytes(bytes);
+        body.set_mime(mime);
+        response.set_body(body);
+        response.insert_header(identity::PUBLIC_KEY_HEADER, signer.public_key());
+        response.insert_header(identity::SIGNATURE_HEADER, signature);
+        Ok(response)
+    }
+}
+
+/// Authenticated Dapp
+///
+/// The [`DappAuthenticator`] attaches the pairing identifier of the dapp to the requests it
+/// authenticates.
+#[derive(Clone, Debug)]
+struct AuthenticatedDapp(String);
+
+/// Dapp Authenticator
+///
+/// Authenticates the requests without an API key which carry the dapp headers, attaching the
+/// [`AuthenticatedDapp`] to them. When [`require_dapp_auth`](Config::require_dapp_auth) is set,
+/// requests which are not authenticated by a paired dapp are rejected. Dapps can always ask to
+/// pair.
+struct DappAuthenticator;
+
+#[tide::utils::async_trait]
+impl<A> Middleware<Server<A>> for DappAuthenticator
+where
+    A: Authorizer,
+{
+    #[inline]
+    async fn handle(
+        &self,
+        mut request: Request<Server<A>>,
+        next: Next<'_, Server<A>>,
+    ) -> tide::Result {
+        if request.header("Authorization").is_some()
+            || matches!(request.url().path(), "/version" | "/dapps/pair")
+        {
+            return Ok(next.run(request).await);
+        }
+        let required = request.state().state.lock().config.require_dapp_auth;
+        let header = |name| {
+            request
+                .header(name)
+                .map(|header| header.last().as_str().to_owned())
+        };
+        let (id, timestamp, mac) = match (
+            header(dapp::DAPP_HEADER),
+            header(dapp::TIMESTAMP_HEADER).and_then(|timestamp| timestamp.parse().ok()),
+            header(dapp::MAC_HEADER),
+        ) {
+            (Some(id), Some(timestamp), Some(mac)) => (id, timestamp, mac),
+            (None, _, _) if !required => return Ok(next.run(request).await),
+            _ => {
+                warn!(
+                    "[DAPP] rejected unauthenticated request to {}",
+                    request.url().path()
+                )?;
+                return Err(Error::AuthorizationError.into());
+            }
+        };
+        let body = request.body_bytes().await?;
+        let authenticated = request.state().dapps.lock().authenticate(
+            &id,
+            request.method().as_ref(),
+            request.url().path(),
+            timestamp,
+            &body,
+            &mac,
+        );
+        if !authenticated {
+            warn!("[DAPP] rejected request from dapp `{}`", id)?;
+            return Err(Error::AuthorizationError.into());
+        }
+        if !request.state().replays.lock().insert(&id, timestamp, &mac) {
+            warn!("[DAPP] rejected replayed request from dapp `{}`", id)?;
+            return Err(Error::AuthorizationError.into());
+        }
+        request.set_body(body);
+        request.set_ext(AuthenticatedDapp(id));
+        Ok(next.run(request).await)
+    }
+}
+
+/// Returns the unlock factors which are mixed into the storage key next to the password.
+#[inline]
+fn unlock_factors<'s>(
+    device_secret: Option<&'s [u8]>,
+    keyfile: Option<&'s SecretVec<u8>>,
+) -> Vec<&'s [u8]> {
+    device_secret
+        .into_iter()
+        .chain(keyfile.map(|keyfile| keyfile.expose_secret().as_slice()))
+        .collect()
+}
+
+/// Returns `true` if `request` was sent from the loopback interface.
+#[inline]
+fn is_loopback_peer<S>(request: &Request<S>) -> bool {
+    request
+        .peer_addr()
+        .and_then(|peer| peer.parse::<SocketAddr>().ok())
+        .map_or(false, |peer| peer.ip().is_loopback())
+}
+
+/// Returns `true` if the IPv6 loopback interface is available.
+#[inline]
+fn is_ipv6_available() -> bool {
+    std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).is_ok()
+}
+
+/// Builds the CORS policy which only allows the [`origin_url`](Config::origin_url) of `config`.
+#[inline]
+fn cors_policy(config: &Config) -> CorsMiddleware {
+    CorsMiddleware::new()
+        .allow_methods("GET, POST".parse::<HeaderValue>().unwrap())
+        .allow_origin(match &config.origin_url {
+            Some(origin_url) => Origin::from(origin_url.as_str()),
+            _ => Origin::from("*"),
+        })
+        .allow_credentials(false)
+        .expose_headers(
+            format!(
+                "{}, {}",
+                identity::PUBLIC_KEY_HEADER,
+                identity::SIGNATURE_HEADER
+            )
+            .parse::<HeaderValue>()
+            .unwrap(),
+        )
+}
+
+/// Returns the authorization prompt expiry of `config`.
+#[inline]
+fn prompt_expiry(config: &Config) -> Option<Duration> {
+    match config.prompt_expiry_minutes {
+        0 => None,
+        minutes => Some(Duration::from_secs(60 * minutes)),
+    }
+}
+
+/// Returns the first address of `socket_addresses` outside of the loopback interface.
+#[inline]
+fn remote_address(socket_addresses: &[SocketAddr]) -> Option<&SocketAddr> {
+    socket_addresses
+        .iter()
+        .find(|socket_address| !socket_address.ip().is_loopback())
+}
+
+/// Checks that the service may listen at `socket_addresses`, which is only allowed outside of
+/// the loopback interface if `config` allows remote access and an API key store exists.
+///
+/// The API key store is encrypted, so this can only check that it exists before the signer is
+/// unlocked. The service checks that it holds an active key with [`check_remote_api_keys`] once
+/// the store is decrypted.
+#[inline]
+pub(crate) async fn check_bind_addresses(
+    config: &Config,
+    socket_addresses: &[SocketAddr],
+) -> Result<()> {
+    let socket_address = match remote_address(socket_addresses) {
+        Some(socket_address) => socket_address,
+        _ => return Ok(()),
+    };
+    if !config.allow_remote {
+        error!(
+            "refusing to listen at {} outside of the loopback interface: set `allow_remote` to \
+             serve remote clients",
+            socket_address
+        )?;
+        return Err(Error::RemoteAccessNotAllowed);
+    }
+    if fs::metadata(config.api_keys_path()).await.is_err()
+        && fs::metadata(config.legacy_api_keys_path()).await.is_err()
+    {
+        return Err(no_remote_api_keys(socket_address)?);
+    }
+    warn!(
+        "listening at {} for remote clients, which must authenticate with an API key",
+        socket_address
+    )?;
+    Ok(())
+}
+
+/// Checks that `api_keys` holds an active key if any of `socket_addresses` is outside of the
+/// loopback interface.
+#[inline]
+fn check_remote_api_keys(socket_addresses: &[SocketAddr], api_keys: &ApiKeyStore) -> Result<()> {
+    match remote_address(socket_addresses) {
+        Some(socket_address) if !api_keys.has_active_keys() => {
+            Err(no_remote_api_keys(socket_address)?)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Logs that the service refuses to listen at `socket_address` without an API key for remote
+/// clients, and returns the matching error.
+#[inline]
+fn no_remote_api_keys(socket_address: &SocketAddr) -> Result<Error> {
+    error!(
+        "refusing to listen at {} without an API key for remote clients: create one while the \
+         signer listens on the loopback interface",
+        socket_address
+    )?;
+    Ok(Error::RemoteAccessWithoutApiKeys)
+}
+
+/// Runs `f` unless the user rejects the request of `ticket` first, in which case `f` is dropped
+/// and [`Error::Rejected`] is returned.
+#[inline]
+async fn unless_rejected<T, F>(ticket: &Ticket, f: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match future::select(Box::pin(f), Box::pin(ticket.rejected())).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => {
+            warn!("[AUTH] request {} was rejected by the user", ticket.id())?;
+            Err(Error::Rejected)
+        }
+    }
+}
+
 /// Generates the JSON body for the output of `f`, returning an HTTP reponse.
 #[inline]
 async fn into_body<R, F, Fut>(f: F) -> Result<Response, tide::Error>
@@ -393,29 +3899,318 @@ where
     Ok(Body::from_json(&f().await?)?.into())
 }
 
+/// Service Handle
+///
+/// The handle holds the state which the service shares with the frontend hosting it.
+#[derive(Clone, Debug, Default)]
+pub struct Handle {
+    /// Approval Sessions
+    pub sessions: Arc<SessionStore>,
+
+    /// Remembered Approval Decisions
+    pub exemptions: Arc<ExemptionStore>,
+
+    /// Lock Signal
+    ///
+    /// This is notified by [`lock`](Self::lock).
+    pub lock_signal: Arc<Notify>,
+
+    /// Signer Status
+    ///
+    /// The signer is locked until the service has unlocked it.
+    pub status: Arc<StatusStore>,
+
+    /// Signer Events
+    pub events: Arc<EventBus>,
+
+    /// Authorization Request Queue
+    pub queue: Arc<RequestQueue>,
+
+    /// Account and Asset Labels
+    ///
+    /// The labels are available once the service has unlocked the signer.
+    pub labels: Arc<LabelStore>,
+
+    /// Transaction Memos
+    ///
+    /// The memos are available once the service has unlocked the signer.
+    pub memos: Arc<MemoStore>,
+
+    /// Shielded Balances
+    ///
+    /// The balances of the active wallet are available once the service has unlocked the signer.
+    pub balances: Arc<BalanceStore>,
+
+    /// Public Address
+    ///
+    /// The address is available once the service has unlocked the signer.
+    pub public_address: Arc<RwLock<Option<PublicAddress>>>,
+
+    /// Active Wallet Name
+    ///
+    /// The name is available once the service has unlocked the signer.
+    pub wallet: Arc<RwLock<Option<String>>>,
+
+    /// Transaction Proofs
+    ///
+    /// The proofs are available once the service has unlocked the signer.
+    pub proofs: Arc<ProofStore>,
+
+    /// Ledger Synchronization Progress
+    ///
+    /// The progress is available once the service has unlocked the signer.
+    pub sync_progress: Arc<ProgressTracker>,
+
+    /// Background Synchronization Controls
+    pub sync_control: Arc<SyncControl>,
+}
+
+impl Handle {
+    /// Locks the signer: rejects the pending requests, revokes the approval sessions and the
+    /// remembered decisions, and has the service drop the decrypted signer state until the
+    /// authorizer logs in again.
+    ///
+    /// The revocations are saved on a best-effort basis, so that locking never fails.
+    #[inline]
+    pub fn lock(&self) {
+        self.queue.reject_all();
+        let _ = self.sessions.revoke_all();
+        let _ = self.exemptions.revoke_all();
+        self.lock_signal.notify_one();
+    }
+
+    /// Fails with [`SignerError::Locked`] while the signer is locked, so that frontends do not
+    /// read the wallet files until the user logged in again.
+    #[inline]
+    pub fn ensure_unlocked(&self) -> Result<(), SignerError> {
+        if self.status.get().locked {
+            return Err(SignerError::Locked);
+        }
+        Ok(())
+    }
+
+    /// Subscribes to the signer status.
+    #[inline]
+    pub fn status_receiver(&self) -> StatusReceiver {
+        StatusReceiver::new(&self.status, &self.queue)
+    }
+
+    /// Returns the name of the active wallet, which is the default wallet until the service has
+    /// unlocked the signer.
+    #[inline]
+    pub fn active_wallet(&self) -> String {
+        self.wallet
+            .read()
+            .clone()
+            .unwrap_or_else(|| DEFAULT_WALLET.into())
+    }
+}
+
 /// Starts the signer server with `config` and `authorizer`.
 #[inline]
 pub async fn start<A>(config: Config, authorizer: A) -> Result<()>
 where
     A: Authorizer,
 {
-    info!("performing service setup with {:#?}", config)?;
-    let socket_address = config.service_url.parse::<SocketAddr>()?;
-    let cors = CorsMiddleware::new()
-        .allow_methods("GET, POST".parse::<HeaderValue>().unwrap())
-        .allow_origin(match &config.origin_url {
-            Some(origin_url) => Origin::from(origin_url.as_str()),
-            _ => Origin::from("*"),
-        })
-        .allow_credentials(false);
-    let mut api = tide::Server::with_state(Server::build(config, authorizer).await?);
-    api.with(cors);
+    start_with_handle(config, authorizer, Handle::default()).await
+}
+
+/// Starts the signer server with `config` and `authorizer`, sharing the state in `handle`.
+#[inline]
+pub async fn start_with_handle<A>(config: Config, authorizer: A, handle: Handle) -> Result<()>
+where
+    A: Authorizer,
+{
+    let transport = bind_http(&config).await?;
+    start_with_transport(config, authorizer, handle, transport).await
+}
+
+/// Binds the HTTP transport at the [`service_url`](Config::service_url) of `config`, falling back
+/// to the next free ports if the configured port is taken.
+#[inline]
+async fn bind_http(config: &Config) -> Result<HttpTransport> {
+    let mut socket_addresses = config.service_addresses()?;
+    if socket_addresses.iter().any(SocketAddr::is_ipv4)
+        && socket_addresses.iter().any(SocketAddr::is_ipv6)
+        && !is_ipv6_available()
+    {
+        warn!("IPv6 is not available, only listening at the IPv4 addresses")?;
+        socket_addresses.retain(SocketAddr::is_ipv4);
+    }
+    check_bind_addresses(config, &socket_addresses).await?;
+    let configured_port = socket_addresses.first().map(SocketAddr::port);
+    let listeners = task::spawn_blocking(move || discovery::bind(&socket_addresses)).await??;
+    let transport = HttpTransport::new(listeners);
+    let socket_addresses = transport.addresses();
+    if socket_addresses.first().map(SocketAddr::port) != configured_port {
+        warn!(
+            "configured port is taken, falling back to {:?}",
+            socket_addresses
+        )?;
+    }
+    Ok(transport)
+}
+
+/// Starts the signer server with `config` and `authorizer`, sharing the state in `handle` and
+/// serving the API over `transport`.
+#[inline]
+pub async fn start_with_transport<A, T>(
+    config: Config,
+    authorizer: A,
+    handle: Handle,
+    transport: T,
+) -> Result<()>
+where
+    A: Authorizer,
+    T: Transport,
+{
+    info!("performing service setup with {:#?}", config.redacted())?;
+    if let Some(endpoint) = &config.otlp_endpoint {
+        if crate::telemetry::init(endpoint) {
+            info!("exporting request traces to {}", endpoint)?;
+        } else {
+            warn!("unable to export request traces to {}", endpoint)?;
+        }
+    }
+    crate::log::set_level(config.log_level);
+    crate::log::set_file(config.log_directory(), config.log_rotation);
+    let node_url = config.node_url.clone();
+    let node_endpoints = config.node_endpoints();
+    let discovery_path = config.discovery_path();
+    let config_path = config.config_path();
+    let deep_link_inbox_path = config.deep_link_inbox_path();
+    let loaded_config = config.clone();
+    let mut server = Server::build(config, authorizer, handle).await?;
+    let socket_addresses = transport.addresses();
+    check_remote_api_keys(&socket_addresses, &server.api_keys.lock())?;
+    server.status.set_locked(false);
+    server.events.publish(SignerEvent::Unlocked);
+    if !socket_addresses.is_empty() {
+        let discovery = Discovery::new(&socket_addresses);
+        let saved_discovery = discovery.clone();
+        let identity = server.identity.clone();
+        task::spawn_blocking(move || saved_discovery.save(&identity, discovery_path)).await??;
+        server.discovery = Some(Arc::new(discovery));
+    }
+    let mut api = tide::Server::with_state(server);
+    if let Some(node_url) = node_url {
+        info!("synchronizing with the ledger of the node at {}", node_url)?;
+        task::spawn(
+            api.state()
+                .clone()
+                .run_ledger_sync(NodeConnection::new(node_endpoints)),
+        );
+    }
+    task::spawn(
+        api.state()
+            .clone()
+            .run_config_watch(config_path, loaded_config),
+    );
+    task::spawn(api.state().clone().run_lock_watch());
+    task::spawn(api.state().clone().run_unlock_expiry());
+    task::spawn(
+        api.state()
+            .clone()
+            .run_deep_link_inbox(deep_link_inbox_path),
+    );
+    api.with(CorsPolicy);
+    api.with(ResponseSigner);
+    api.with(ErrorBody);
+    api.with(DappAuthenticator);
     api.at("/version").get(|_| into_body(Server::<A>::version));
-    api.at("/sync").post(|r| Server::execute(r, Server::sync));
-    api.at("/sign").post(|r| Server::execute(r, Server::sign));
+    api.at("/discovery/verify")
+        .post(|r| Server::execute(r, Permission::View, Server::verify_discovery));
+    api.at("/sync")
+        .post(|r| Server::execute(r, Permission::View, Server::sync));
+    api.at("/syncStatus").get(Server::<A>::sync_status);
+    api.at("/syncProgress").get(Server::<A>::sync_progress);
+    api.at("/pauseSync")
+        .post(|r| Server::execute(r, Permission::Owner, Server::pause_sync));
+    api.at("/resumeSync")
+        .post(|r| Server::execute(r, Permission::Owner, Server::resume_sync));
+    api.at("/balances").get(Server::<A>::list_balances);
+    api.at("/events").get(Server::<A>::event_stream);
+    api.at("/compactStorage")
+        .post(|r| Server::execute(r, Permission::Owner, Server::compact_storage));
+    api.at("/resyncWallet")
+        .post(|r| Server::execute(r, Permission::Owner, Server::resync_wallet));
+    api.at("/networkProbe")
+        .post(|r| Server::execute(r, Permission::Owner, Server::network_probe));
+    api.at("/wallets").get(Server::<A>::list_wallets);
+    api.at("/wallets/create")
+        .post(|r| Server::execute(r, Permission::Owner, Server::create_wallet));
+    api.at("/wallets/switch")
+        .post(|r| Server::execute(r, Permission::Owner, Server::switch_wallet));
+    api.at("/hidden/create")
+        .post(|r| Server::execute(r, Permission::Owner, Server::create_hidden_wallet));
+    api.at("/hidden/open")
+        .post(|r| Server::execute(r, Permission::Owner, Server::open_hidden_wallet));
+    api.at("/duress/setup")
+        .post(|r| Server::execute(r, Permission::Owner, Server::setup_duress));
+    api.at("/duress/remove")
+        .post(|r| Server::execute(r, Permission::Owner, Server::remove_duress));
+    api.at("/pendingRequests")
+        .get(Server::<A>::pending_requests);
+    api.at("/sessions").get(Server::<A>::list_sessions);
+    api.at("/sessions/request")
+        .post(|r| Server::execute_as(r, Permission::Send, Server::request_session));
+    api.at("/sessions/revoke")
+        .post(|r| Server::execute(r, Permission::Owner, Server::revoke_session));
+    api.at("/sign")
+        .post(|r| Server::execute_as(r, Permission::Send, Server::sign));
+    api.at("/signWithMemo")
+        .post(|r| Server::execute_as(r, Permission::Send, Server::sign_with_memo));
+    api.at("/signWithFee")
+        .post(|r| Server::execute_as(r, Permission::Send, Server::sign_with_fee));
+    api.at("/airgap/sign")
+        .post(|r| Server::execute_as(r, Permission::Send, Server::airgap_sign));
+    api.at("/signFile")
+        .post(|r| Server::execute_as(r, Permission::Owner, Server::sign_file));
+    api.at("/signMessage")
+        .post(|r| Server::execute(r, Permission::Send, Server::sign_message));
+    api.at("/decodeTransaction")
+        .post(|r| Server::execute(r, Permission::View, Server::decode_transaction));
+    api.at("/confirmationCode")
+        .post(|r| Server::execute(r, Permission::View, Server::confirmation_code));
     api.at("/receivingKeys")
-        .post(|r| Server::execute(r, Server::receiving_keys));
-    info!("serving signer API at {}", socket_address)?;
-    api.listen(socket_address).await?;
+        .post(|r| Server::execute(r, Permission::Invoice, Server::receiving_keys));
+    api.at("/approve")
+        .post(|r| Server::execute(r, Permission::Owner, Server::approve));
+    api.at("/apiKeys").get(Server::<A>::list_api_keys);
+    api.at("/stateDigest").get(Server::<A>::state_digest);
+    api.at("/derivationReport")
+        .get(Server::<A>::derivation_report);
+    api.at("/apiKeys/create")
+        .post(|r| Server::execute(r, Permission::Owner, Server::create_api_key));
+    api.at("/apiKeys/revoke")
+        .post(|r| Server::execute(r, Permission::Owner, Server::revoke_api_key));
+    api.at("/promptPayload/:hash")
+        .get(Server::<A>::prompt_payload);
+    api.at("/polkadot/accounts")
+        .get(Server::<A>::polkadot_accounts);
+    api.at("/polkadot/publicAddress")
+        .get(Server::<A>::public_address);
+    api.at("/polkadot/signPayload")
+        .post(|r| Server::execute(r, Permission::Send, Server::polkadot_sign_payload));
+    api.at("/polkadot/signRaw")
+        .post(|r| Server::execute(r, Permission::Send, Server::polkadot_sign_raw));
+    api.at("/dapps").get(Server::<A>::list_dapps);
+    api.at("/dapps/pair")
+        .post(|r| Server::execute_as(r, Permission::View, Server::pair_dapp));
+    api.at("/dapps/unpair")
+        .post(|r| Server::execute(r, Permission::Owner, Server::unpair_dapp));
+    api.at("/pairing/devices").get(Server::<A>::list_devices);
+    api.at("/pairing/pending")
+        .get(Server::<A>::pending_approval);
+    api.at("/pairing/start")
+        .post(|r| Server::execute(r, Permission::Owner, Server::start_pairing));
+    api.at("/pairing/complete")
+        .post(|r| Server::execute(r, Permission::Owner, Server::complete_pairing));
+    api.at("/pairing/unpair")
+        .post(|r| Server::execute(r, Permission::Owner, Server::unpair));
+    api.at("/pairing/decide")
+        .post(|r| Server::execute(r, Permission::Owner, Server::device_decision));
+    info!("serving signer API at {:?}", socket_addresses)?;
+    transport.serve(api).await?;
     Ok(())
 }
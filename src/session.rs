@@ -0,0 +1,238 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-Dapp Approval Sessions
+//!
+//! An approval session lets a paired dapp have transactions signed without the wallet password for
+//! a bounded time and up to a bounded total value, so that repeated small interactions with a
+//! dapp do not each require the password. Sessions are granted by the user through an
+//! authorization prompt and can be revoked at any time. Once the signer is unlocked, they are
+//! stored encrypted next to the wallet file, so that they survive a restart.
+//!
+//! Sessions are bound to the pairing identifier of the dapp, see the [`dapp`](crate::dapp) module,
+//! and only apply to requests authenticated with the MAC of that pairing. The `Origin` header is
+//! only recorded for display, since any local process can forge it.
+
+use crate::storage::cipher::{self, FileKey};
+use manta_crypto::rand::OsRng;
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use std::{path::PathBuf, sync::Arc};
+
+/// Maximum Session Duration in Minutes
+pub const MAX_DURATION_MINUTES: u64 = 24 * 60;
+
+/// Returns the current UNIX timestamp.
+#[inline]
+fn now() -> i64 {
+    chrono::offset::Utc::now().timestamp()
+}
+
+/// Session Request
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SessionRequest {
+    /// Session Duration in Minutes
+    ///
+    /// This is capped at [`MAX_DURATION_MINUTES`].
+    pub duration_minutes: u64,
+
+    /// Maximum Total Value Signed during the Session
+    pub value_limit: u128,
+}
+
+/// Approval Session
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Session {
+    /// Pairing Identifier of the Dapp
+    pub dapp: String,
+
+    /// Origin
+    ///
+    /// This is the `Origin` header of the request which asked for the session, and is only shown
+    /// to the user.
+    pub origin: Option<String>,
+
+    /// Expiry Time as a UNIX Timestamp
+    pub expires_at: i64,
+
+    /// Maximum Total Value Signed during the Session
+    pub value_limit: u128,
+
+    /// Total Value Signed so Far
+    pub spent: u128,
+}
+
+impl Session {
+    /// Returns `true` if the session has not expired yet.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        now() < self.expires_at
+    }
+}
+
+/// Session Revocation Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct RevokeSessionRequest {
+    /// Pairing Identifier of the Dapp
+    pub dapp: String,
+}
+
+/// Session Store
+///
+/// The store is shared with the frontend through the service [`Handle`](crate::service::Handle)
+/// and is opened by the service once the signer is unlocked. Until then, sessions only live in
+/// memory.
+#[derive(Debug, Default)]
+pub struct SessionStore {
+    /// Sessions
+    sessions: Mutex<Vec<Session>>,
+
+    /// Encrypted Session File Path and Storage Key
+    storage: Mutex<Option<(PathBuf, Arc<FileKey>)>>,
+}
+
+impl SessionStore {
+    /// Loads the active sessions stored encrypted under `key` at `path`, starting without
+    /// sessions if it does not exist or holds sessions of an earlier format, and saves later
+    /// changes there.
+    #[inline]
+    pub fn open(&self, path: PathBuf, key: Arc<FileKey>) -> Result<(), cipher::Error> {
+        let mut sessions = match cipher::load_or_quarantine::<_, Vec<Session>>(&path, &key) {
+            Ok(sessions) => sessions.unwrap_or_default(),
+            Err(cipher::Error::Serialization) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        sessions.retain(Session::is_active);
+        *self.sessions.lock() = sessions;
+        *self.storage.lock() = Some((path, key));
+        Ok(())
+    }
+
+    /// Saves `sessions` if the store is open.
+    #[inline]
+    fn save(&self, sessions: &[Session]) -> Result<(), cipher::Error> {
+        match &*self.storage.lock() {
+            Some((path, key)) => cipher::save(path, key, &sessions, 0, &mut OsRng),
+            _ => Ok(()),
+        }
+    }
+
+    /// Grants a session to the paired dapp with the given `dapp` identifier from `origin` as
+    /// described by `request`, replacing any previous session of the same dapp.
+    #[inline]
+    pub fn grant(
+        &self,
+        dapp: String,
+        origin: Option<String>,
+        request: SessionRequest,
+    ) -> Result<Session, cipher::Error> {
+        let session = Session {
+            dapp,
+            origin,
+            expires_at: now() + 60 * request.duration_minutes.min(MAX_DURATION_MINUTES) as i64,
+            value_limit: request.value_limit,
+            spent: 0,
+        };
+        let mut sessions = self.sessions.lock();
+        let mut updated = sessions.clone();
+        updated.retain(|s| s.dapp != session.dapp);
+        updated.push(session.clone());
+        self.save(&updated)?;
+        *sessions = updated;
+        Ok(session)
+    }
+
+    /// Adds the active sessions of `imported` which do not outlast [`MAX_DURATION_MINUTES`],
+    /// replacing the sessions of the same dapps, and returns how many were added.
+    #[inline]
+    pub fn import(&self, imported: Vec<Session>) -> Result<usize, cipher::Error> {
+        let latest_expiry = now() + 60 * MAX_DURATION_MINUTES as i64;
+        let imported = imported
+            .into_iter()
+            .filter(|session| {
+                session.is_active()
+                    && session.expires_at <= latest_expiry
+                    && session.spent <= session.value_limit
+            })
+            .collect::<Vec<_>>();
+        let mut sessions = self.sessions.lock();
+        let mut updated = sessions.clone();
+        updated.retain(|s| !imported.iter().any(|session| session.dapp == s.dapp));
+        let count = imported.len();
+        updated.extend(imported);
+        self.save(&updated)?;
+        *sessions = updated;
+        Ok(count)
+    }
+
+    /// Revokes the session of the paired dapp with the given `dapp` identifier, returning `false`
+    /// if it has none.
+    #[inline]
+    pub fn revoke(&self, dapp: &str) -> Result<bool, cipher::Error> {
+        let mut sessions = self.sessions.lock();
+        let count = sessions.len();
+        sessions.retain(|session| session.dapp != dapp);
+        if sessions.len() == count {
+            return Ok(false);
+        }
+        self.save(&sessions)?;
+        Ok(true)
+    }
+
+    /// Revokes every session.
+    #[inline]
+    pub fn revoke_all(&self) -> Result<(), cipher::Error> {
+        let mut sessions = self.sessions.lock();
+        sessions.clear();
+        self.save(&sessions)
+    }
+
+    /// Lists the active sessions, dropping the expired ones.
+    #[inline]
+    pub fn list(&self) -> Vec<Session> {
+        let mut sessions = self.sessions.lock();
+        sessions.retain(Session::is_active);
+        sessions.clone()
+    }
+
+    /// Authorizes signing a transaction of `value` for the paired dapp with the given `dapp`
+    /// identifier, returning `true` and recording the value if it has an active session with
+    /// enough value left. The caller must have checked that the request was authenticated by the
+    /// pairing of the dapp.
+    ///
+    /// The transaction is not authorized if the recorded value cannot be saved.
+    #[inline]
+    pub fn authorize(&self, dapp: &str, value: u128) -> bool {
+        let mut sessions = self.sessions.lock();
+        sessions.retain(Session::is_active);
+        let mut updated = sessions.clone();
+        match updated.iter_mut().find(|session| session.dapp == dapp) {
+            Some(session) => match session.spent.checked_add(value) {
+                Some(spent) if spent <= session.value_limit => session.spent = spent,
+                _ => return false,
+            },
+            _ => return false,
+        }
+        if self.save(&updated).is_err() {
+            return false;
+        }
+        *sessions = updated;
+        true
+    }
+}
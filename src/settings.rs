@@ -0,0 +1,251 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Settings Export and Import
+//!
+//! Users moving to another computer export the [`Settings`] of the signer, which hold no secrets,
+//! to a JSON file and import them after restoring their account from the recovery phrase on the
+//! new computer. The settings are the configuration file without the machine-specific paths, the
+//! account and asset labels, the remembered approval decisions and approval sessions, and the
+//! frontend preferences. Paired dapps, API keys and the signer identity hold secrets and are not
+//! exported.
+//!
+//! Imported settings are merged into the existing ones. Only the [`HARMLESS_SETTINGS`] are merged
+//! without asking, since a settings file can come from anywhere and the other settings control
+//! who can reach the signer and how it checks approvals. The frontend shows the
+//! [`sensitive_changes`](Settings::sensitive_changes) to the user, and they are only imported
+//! once confirmed. Since approval grants let dapps sign without the password, they are likewise
+//! only imported when the user asks for them.
+
+use crate::{
+    config::Config, error::SignerError, exemption::Exemption, label::Labels, service::Handle,
+    session::Session,
+};
+use manta_util::serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{io, path::Path};
+
+/// Settings File Format Version
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Machine-Specific Configuration Settings
+///
+/// These settings are neither exported nor imported.
+pub const MACHINE_SETTINGS: [&str; 1] = ["data_path"];
+
+/// Harmless Configuration Settings
+///
+/// These settings are imported without asking the user. Every other setting is security-relevant
+/// and is only imported once the user confirms it.
+pub const HARMLESS_SETTINGS: [&str; 5] = [
+    "backup_generations",
+    "log_level",
+    "log_rotation",
+    "prompt_expiry_minutes",
+    "sync_schedule",
+];
+
+/// Returns `true` if the configuration setting with the given `name` is harmless.
+#[inline]
+pub fn is_harmless(name: &str) -> bool {
+    HARMLESS_SETTINGS.contains(&name)
+}
+
+/// Exported Settings
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Settings {
+    /// File Format Version
+    pub version: u32,
+
+    /// Signer Version
+    pub signer_version: String,
+
+    /// Export Time as a UNIX Timestamp
+    pub exported_at: i64,
+
+    /// Configuration File Settings
+    pub config: Map<String, Value>,
+
+    /// Account and Asset Labels
+    pub labels: Labels,
+
+    /// Remembered Approval Decisions
+    pub exemptions: Vec<Exemption>,
+
+    /// Approval Sessions
+    pub sessions: Vec<Session>,
+
+    /// Frontend Preferences
+    pub preferences: Option<Value>,
+}
+
+impl Settings {
+    /// Collects the settings of the signer with `config` and `handle`, and the frontend
+    /// `preferences`. The signer has to be unlocked so that the labels and grants can be read.
+    #[inline]
+    pub fn export(
+        config: &Config,
+        handle: &Handle,
+        preferences: Option<Value>,
+    ) -> Result<Self, SignerError> {
+        if handle.status.get().locked {
+            return Err(SignerError::Locked);
+        }
+        let mut settings = Config::read_settings(config.config_path())?.unwrap_or_default();
+        for name in MACHINE_SETTINGS.iter() {
+            settings.remove(*name);
+        }
+        Ok(Self {
+            version: FORMAT_VERSION,
+            signer_version: crate::VERSION.into(),
+            exported_at: chrono::offset::Utc::now().timestamp(),
+            config: settings,
+            labels: handle.labels.get(),
+            exemptions: handle.exemptions.list(),
+            sessions: handle.sessions.list(),
+            preferences,
+        })
+    }
+
+    /// Loads the settings file at `path`.
+    #[inline]
+    pub fn load<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let settings = serde_json::from_slice::<Self>(&std::fs::read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if settings.version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported settings version {}", settings.version),
+            ));
+        }
+        Ok(settings)
+    }
+
+    /// Saves the settings file to `path`.
+    #[inline]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::write(
+            path,
+            serde_json::to_vec_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+        )
+    }
+
+    /// Returns the security-relevant configuration settings of the file which differ from the
+    /// ones in the configuration file of `config`, with their imported values. The user has to
+    /// confirm these before they are imported.
+    #[inline]
+    pub fn sensitive_changes(&self, config: &Config) -> Result<Map<String, Value>, SignerError> {
+        let settings = Config::read_settings(config.config_path())?.unwrap_or_default();
+        Ok(self
+            .config
+            .iter()
+            .filter(|(name, value)| {
+                !MACHINE_SETTINGS.contains(&name.as_str())
+                    && !is_harmless(name)
+                    && settings.get(*name) != Some(*value)
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect())
+    }
+
+    /// Merges the settings into the configuration file of `config` and the stores of `handle`,
+    /// importing the security-relevant configuration settings only if `sensitive` is set and the
+    /// approval grants only if `grants` is set. The frontend preferences are left to the caller.
+    ///
+    /// The running signer picks up the reloadable configuration settings from the configuration
+    /// file, and the other ones after a restart.
+    #[inline]
+    pub fn import(
+        self,
+        config: &Config,
+        handle: &Handle,
+        sensitive: bool,
+        grants: bool,
+    ) -> Result<ImportReport, SignerError> {
+        if handle.status.get().locked {
+            return Err(SignerError::Locked);
+        }
+        let path = config.config_path();
+        let mut settings = Config::read_settings(&path)?.unwrap_or_default();
+        let mut imported = self.config;
+        for name in MACHINE_SETTINGS.iter() {
+            imported.remove(*name);
+        }
+        let skipped = if sensitive {
+            Vec::new()
+        } else {
+            let skipped = imported
+                .keys()
+                .filter(|name| !is_harmless(name))
+                .cloned()
+                .collect::<Vec<_>>();
+            for name in &skipped {
+                imported.remove(name);
+            }
+            skipped
+        };
+        let mut report = ImportReport {
+            config: imported.keys().cloned().collect(),
+            skipped,
+            ..Default::default()
+        };
+        settings.extend(imported);
+        config
+            .with_settings(settings.clone())
+            .map_err(|err| SignerError::Config(err.to_string()))?;
+        crate::storage::write_atomic(
+            &path,
+            &serde_json::to_vec_pretty(&settings)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )?;
+        report.labels = self.labels.accounts.len() + self.labels.assets.len();
+        handle.labels.merge(self.labels)?;
+        if grants {
+            report.exemptions = handle.exemptions.import(self.exemptions)?;
+            report.sessions = handle.sessions.import(self.sessions)?;
+        }
+        Ok(report)
+    }
+}
+
+/// Settings Import Report
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ImportReport {
+    /// Names of the Imported Configuration Settings
+    pub config: Vec<String>,
+
+    /// Names of the Security-Relevant Configuration Settings which were not Imported
+    pub skipped: Vec<String>,
+
+    /// Number of Imported Labels
+    pub labels: usize,
+
+    /// Number of Imported Remembered Approval Decisions
+    pub exemptions: usize,
+
+    /// Number of Imported Approval Sessions
+    pub sessions: usize,
+}
@@ -0,0 +1,312 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Signer Status
+//!
+//! The service publishes whether it is locked, synchronizing with the ledger, or proving a
+//! transaction, the node it synchronizes with and the [`ConnectionState`] of that node, and why
+//! the background synchronization is held, in a [`StatusStore`], and the number of
+//! requests waiting for the user's authorization through the [`RequestQueue`]. Frontends combine
+//! them with a [`StatusReceiver`] to show the [`SignerStatus`] at a glance, or to render the
+//! complete [`SignerState`] on every change.
+
+use crate::{queue::RequestQueue, schedule::SyncHold};
+use futures::future::{self, Either};
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use tokio::sync::watch;
+
+/// Signer Status
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum SignerStatus {
+    /// The Signer Waits for the User to Log In
+    Locked,
+
+    /// The Signer is Ready
+    Unlocked,
+
+    /// The Signer is Synchronizing with the Ledger
+    Syncing,
+
+    /// Requests are Waiting for the User's Authorization
+    Pending,
+}
+
+/// Node Connection State
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(
+    crate = "manta_util::serde",
+    content = "content",
+    deny_unknown_fields,
+    tag = "type"
+)]
+pub enum ConnectionState {
+    /// No Node is Configured
+    Disconnected,
+
+    /// The Last Request to the Node Succeeded
+    Connected,
+
+    /// The Node is Unreachable and the Signer Retries after a Backoff
+    Reconnecting {
+        /// Number of Consecutive Failed Attempts
+        attempt: u32,
+
+        /// Seconds until the Next Attempt
+        retry_in: u64,
+    },
+}
+
+impl Default for ConnectionState {
+    #[inline]
+    fn default() -> Self {
+        Self::Disconnected
+    }
+}
+
+/// Signer State
+///
+/// This is the complete state which frontends render, see [`StatusReceiver::state`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SignerState {
+    /// Signer Status
+    pub status: SignerStatus,
+
+    /// Locked Flag
+    pub locked: bool,
+
+    /// Synchronizing Flag
+    pub syncing: bool,
+
+    /// Proving Flag
+    pub proving: bool,
+
+    /// Number of Pending Requests
+    pub pending: usize,
+
+    /// Node Endpoint
+    ///
+    /// This is the node the signer synchronizes with, if any.
+    pub network: Option<String>,
+
+    /// Node Connection State
+    pub connection: ConnectionState,
+
+    /// Reason the Background Synchronization is Held
+    pub sync_hold: Option<SyncHold>,
+}
+
+/// Status Flags
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct StatusFlags {
+    /// Locked Flag
+    pub locked: bool,
+
+    /// Synchronizing Flag
+    pub syncing: bool,
+
+    /// Proving Flag
+    pub proving: bool,
+
+    /// Node Endpoint
+    pub network: Option<String>,
+
+    /// Node Connection State
+    pub connection: ConnectionState,
+
+    /// Reason the Background Synchronization is Held
+    pub sync_hold: Option<SyncHold>,
+}
+
+impl Default for StatusFlags {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            locked: true,
+            syncing: false,
+            proving: false,
+            network: None,
+            connection: ConnectionState::Disconnected,
+            sync_hold: None,
+        }
+    }
+}
+
+impl StatusFlags {
+    /// Returns the status of the signer with these flags and `pending` requests. Pending requests
+    /// take precedence over the lock, and the lock over the synchronization.
+    #[inline]
+    pub fn status(&self, pending: usize) -> SignerStatus {
+        if pending > 0 {
+            SignerStatus::Pending
+        } else if self.locked {
+            SignerStatus::Locked
+        } else if self.syncing {
+            SignerStatus::Syncing
+        } else {
+            SignerStatus::Unlocked
+        }
+    }
+
+    /// Returns the state of the signer with these flags and `pending` requests.
+    #[inline]
+    pub fn state(&self, pending: usize) -> SignerState {
+        SignerState {
+            status: self.status(pending),
+            locked: self.locked,
+            syncing: self.syncing,
+            proving: self.proving,
+            pending,
+            network: self.network.clone(),
+            connection: self.connection,
+            sync_hold: self.sync_hold,
+        }
+    }
+}
+
+/// Status Store
+#[derive(Debug)]
+pub struct StatusStore {
+    /// Status Flags Sender
+    sender: Mutex<watch::Sender<StatusFlags>>,
+
+    /// Status Flags Receiver
+    ///
+    /// This receiver keeps the channel open while there are no subscribers.
+    receiver: watch::Receiver<StatusFlags>,
+}
+
+impl Default for StatusStore {
+    #[inline]
+    fn default() -> Self {
+        let (sender, receiver) = watch::channel(StatusFlags::default());
+        Self {
+            sender: Mutex::new(sender),
+            receiver,
+        }
+    }
+}
+
+impl StatusStore {
+    /// Returns the current status flags.
+    #[inline]
+    pub fn get(&self) -> StatusFlags {
+        self.receiver.borrow().clone()
+    }
+
+    /// Applies `update` to the status flags, notifying the subscribers if they changed.
+    #[inline]
+    fn update<F>(&self, update: F)
+    where
+        F: FnOnce(&mut StatusFlags),
+    {
+        let sender = self.sender.lock();
+        let mut flags = self.get();
+        update(&mut flags);
+        if flags != self.get() {
+            let _ = sender.send(flags);
+        }
+    }
+
+    /// Sets the locked flag to `locked`.
+    #[inline]
+    pub fn set_locked(&self, locked: bool) {
+        self.update(|flags| flags.locked = locked)
+    }
+
+    /// Sets the synchronizing flag to `syncing`.
+    #[inline]
+    pub fn set_syncing(&self, syncing: bool) {
+        self.update(|flags| flags.syncing = syncing)
+    }
+
+    /// Sets the proving flag to `proving`.
+    #[inline]
+    pub fn set_proving(&self, proving: bool) {
+        self.update(|flags| flags.proving = proving)
+    }
+
+    /// Sets the node endpoint to `network`.
+    #[inline]
+    pub fn set_network(&self, network: Option<String>) {
+        self.update(|flags| flags.network = network)
+    }
+
+    /// Sets the node connection state to `connection`.
+    #[inline]
+    pub fn set_connection(&self, connection: ConnectionState) {
+        self.update(|flags| flags.connection = connection)
+    }
+
+    /// Sets the reason the background synchronization is held to `hold`.
+    #[inline]
+    pub fn set_sync_hold(&self, hold: Option<SyncHold>) {
+        self.update(|flags| flags.sync_hold = hold)
+    }
+
+    /// Subscribes to the status flags.
+    #[inline]
+    pub fn subscribe(&self) -> watch::Receiver<StatusFlags> {
+        self.receiver.clone()
+    }
+}
+
+/// Status Receiver
+#[derive(Debug)]
+pub struct StatusReceiver {
+    /// Status Flags Receiver
+    flags: watch::Receiver<StatusFlags>,
+
+    /// Pending Request Count Receiver
+    pending: watch::Receiver<usize>,
+}
+
+impl StatusReceiver {
+    /// Builds a new [`StatusReceiver`] for the flags in `status` and the requests in `queue`.
+    #[inline]
+    pub fn new(status: &StatusStore, queue: &RequestQueue) -> Self {
+        Self {
+            flags: status.subscribe(),
+            pending: queue.subscribe(),
+        }
+    }
+
+    /// Returns the current signer status.
+    #[inline]
+    pub fn status(&self) -> SignerStatus {
+        self.flags.borrow().status(*self.pending.borrow())
+    }
+
+    /// Returns the current signer state.
+    #[inline]
+    pub fn state(&self) -> SignerState {
+        self.flags.borrow().state(*self.pending.borrow())
+    }
+
+    /// Waits until the status flags or the number of pending requests change, returning `false`
+    /// once their stores were dropped.
+    #[inline]
+    pub async fn changed(&mut self) -> bool {
+        let flags = Box::pin(self.flags.changed());
+        let pending = Box::pin(self.pending.changed());
+        match future::select(flags, pending).await {
+            Either::Left((result, _)) | Either::Right((result, _)) => result.is_ok(),
+        }
+    }
+}
@@ -0,0 +1,428 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioned Storage Format
+//!
+//! Every data directory carries a [`Manifest`] recording the format version of the wallet files
+//! stored inside it. Every [`Migration`] in [`MIGRATIONS`] which is newer than the recorded
+//! version runs in order so that format changes never require recreating the wallet. At startup,
+//! [`migrate`] runs the migrations which only need the configuration. Migrations which re-encrypt
+//! files need the password, so [`migrate_unlocked`] runs them and the ones after them once the
+//! user entered it.
+
+use crate::{api_key::ApiKeyStore, config::Config, pairing::DeviceStore, storage::cipher::FileKey};
+use futures::future::BoxFuture;
+use manta_accounting::fs::{cocoon::File as LegacyFile, File as _};
+use manta_crypto::rand::OsRng;
+use manta_pay::signer::base::SignerState;
+use manta_util::serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fs::{self as sync_fs, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+
+pub mod cipher;
+pub mod keywrap;
+pub mod rotation;
+
+/// Current Storage Format Version
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Legacy Storage Format Version
+///
+/// Data directories created before the [`Manifest`] was introduced have no manifest and are
+/// treated as this version.
+pub const LEGACY_VERSION: u32 = 0;
+
+/// Default Number of Backup Generations
+pub const DEFAULT_BACKUP_GENERATIONS: usize = 3;
+
+/// Returns `path` with `suffix` appended to its file name.
+#[inline]
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path
+        .file_name()
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(OsString::new);
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Returns the path of the backup `generation` of the file at `path`, where generation `1` is the
+/// most recent one.
+#[inline]
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    with_suffix(path, &format!(".{}.bak", generation))
+}
+
+/// Writes `contents` to `path` atomically, keeping the previous `generations` versions of the file
+/// as backups.
+///
+/// The contents are first written to a temporary file and flushed to disk, then the existing
+/// backups are rotated and the temporary file is renamed over `path`. At no point in this
+/// process is `path` missing or partially written.
+#[inline]
+pub fn write_atomic(path: &Path, contents: &[u8], generations: usize) -> io::Result<()> {
+    let temporary = with_suffix(path, ".tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    if generations > 0 && path.exists() {
+        for generation in (1..generations).rev() {
+            let source = backup_path(path, generation);
+            if source.exists() {
+                sync_fs::rename(&source, backup_path(path, generation + 1))?;
+            }
+        }
+        let backup = backup_path(path, 1);
+        let staging = with_suffix(&backup, ".tmp");
+        sync_fs::copy(path, &staging)?;
+        File::open(&staging)?.sync_all()?;
+        sync_fs::rename(staging, backup)?;
+    }
+    sync_fs::rename(&temporary, path)?;
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Checks the integrity of the wallet file at `path` without decrypting it.
+///
+/// Files in the legacy format can only be checked for being non-empty.
+#[inline]
+pub async fn check_integrity<P>(path: P) -> Result<(), cipher::Error>
+where
+    P: AsRef<Path>,
+{
+    let bytes = fs::read(path).await?;
+    if cipher::has_header(&bytes) {
+        cipher::verify_integrity(&bytes)
+    } else if bytes.is_empty() {
+        Err(cipher::Error::Truncated)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the generations of the backups of `path` which pass the integrity check, looking at
+/// most `generations` backups back.
+#[inline]
+pub async fn valid_backups(path: &Path, generations: usize) -> Vec<usize> {
+    let mut backups = Vec::new();
+    for generation in 1..=generations {
+        if check_integrity(backup_path(path, generation)).await.is_ok() {
+            backups.push(generation);
+        }
+    }
+    backups
+}
+
+/// Moves the corrupted file at `path` aside so that it is kept for inspection, returning its new
+/// path.
+#[inline]
+pub async fn quarantine(path: &Path) -> io::Result<PathBuf> {
+    let target = with_suffix(path, ".corrupt");
+    fs::rename(path, &target).await?;
+    Ok(target)
+}
+
+/// Restores the backup `generation` of `path` over the corrupted file, which is kept next to it
+/// for inspection.
+#[inline]
+pub async fn restore_backup(path: &Path, generation: usize) -> io::Result<()> {
+    let staging = with_suffix(path, ".tmp");
+    fs::copy(backup_path(path, generation), &staging).await?;
+    fs::copy(path, with_suffix(path, ".corrupt")).await?;
+    fs::rename(staging, path).await
+}
+
+/// Removes the leftover temporary files of `path` and its backups older than `generations`,
+/// returning the removed paths.
+#[inline]
+pub fn prune(path: &Path, generations: usize) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    let (directory, file_name) = match (path.parent(), path.file_name()) {
+        (Some(directory), Some(file_name)) => (directory, file_name.to_string_lossy().into_owned()),
+        _ => return Ok(removed),
+    };
+    for entry in sync_fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let suffix = match name.strip_prefix(&file_name) {
+            Some(suffix) => suffix,
+            _ => continue,
+        };
+        let is_stale = suffix.ends_with(".tmp")
+            || suffix
+                .strip_prefix('.')
+                .and_then(|suffix| suffix.strip_suffix(".bak"))
+                .and_then(|generation| generation.parse::<usize>().ok())
+                .map(|generation| generation > generations)
+                .unwrap_or(false);
+        if is_stale {
+            sync_fs::remove_file(entry.path())?;
+            removed.push(entry.path());
+        }
+    }
+    Ok(removed)
+}
+
+/// Compaction Report
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct CompactionReport {
+    /// Wallet File Size in Bytes before Compaction
+    pub size_before: u64,
+
+    /// Wallet File Size in Bytes after Compaction
+    pub size_after: u64,
+
+    /// Removed Files
+    pub removed: Vec<String>,
+}
+
+/// Storage Manifest
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct Manifest {
+    /// Storage Format Version
+    pub version: u32,
+}
+
+impl Manifest {
+    /// Builds a new [`Manifest`] for the [`CURRENT_VERSION`].
+    #[inline]
+    pub const fn current() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+        }
+    }
+
+    /// Loads the manifest from `path`, returning `None` if it does not exist.
+    #[inline]
+    pub async fn load<P>(path: P) -> io::Result<Option<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        match fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the manifest to `path`.
+    #[inline]
+    pub async fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(
+            path,
+            serde_json::to_vec(self).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+        )
+        .await
+    }
+}
+
+/// Migration Future
+pub type MigrationFuture<'c> = BoxFuture<'c, io::Result<()>>;
+
+/// Unlock Secrets
+///
+/// These are only known once the user entered the password, and are passed to the
+/// [`Unlock`](MigrationStep::Unlock) migrations.
+#[derive(Clone)]
+pub struct UnlockSecrets {
+    /// Password Hash
+    ///
+    /// The legacy wallet files are encrypted under this hash.
+    pub password_hash: Vec<u8>,
+
+    /// Storage Secret
+    ///
+    /// See [`cipher::storage_secret`] for how it is derived from the password hash.
+    pub storage_secret: Vec<u8>,
+}
+
+/// Migration Step
+#[derive(Clone, Copy)]
+pub enum MigrationStep {
+    /// Migration which only needs the Configuration
+    Startup(fn(&Config) -> MigrationFuture<'_>),
+
+    /// Migration which needs the [`UnlockSecrets`]
+    ///
+    /// The migration returns [`cipher::Error::Decryption`] if the secrets do not open the signer
+    /// state, in which case it is retried at the next unlock.
+    Unlock(fn(&Config, &UnlockSecrets) -> Result<(), cipher::Error>),
+}
+
+/// Storage Migration
+#[derive(Clone, Copy)]
+pub struct Migration {
+    /// Storage Format Version after the Migration
+    pub version: u32,
+
+    /// Description
+    pub description: &'static str,
+
+    /// Migration Step
+    pub run: MigrationStep,
+}
+
+/// Migration Registry
+///
+/// Migrations must be listed in increasing order of [`version`](Migration::version) and the last
+/// one must end at [`CURRENT_VERSION`].
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "adopt the versioned storage manifest",
+        run: MigrationStep::Startup(adopt_manifest),
+    },
+    Migration {
+        version: 2,
+        description: "re-encrypt the legacy wallet files with XChaCha20-Poly1305",
+        run: MigrationStep::Unlock(adopt_file_cipher),
+    },
+];
+
+/// Adopts the storage manifest for a legacy data directory.
+///
+/// The wallet files themselves are unchanged, so only the manifest needs to be written, which
+/// [`migrate`] does after every migration.
+#[inline]
+fn adopt_manifest(config: &Config) -> MigrationFuture<'_> {
+    let _ = config;
+    Box::pin(async move { Ok(()) })
+}
+
+/// Re-encrypts the legacy wallet files of `config` with the [`cipher`] format.
+///
+/// The signer state and the TOTP secret were encrypted with the legacy file format under the
+/// password hash, and the API keys and paired devices were stored in plaintext. They are all
+/// encrypted under the storage key of the signer state, which is generated here if the signer
+/// state is still in the legacy format. The legacy signer state is kept as its most recent
+/// backup generation. A TOTP secret saved under another password is moved aside, so that a new
+/// one is enrolled.
+#[inline]
+fn adopt_file_cipher(config: &Config, secrets: &UnlockSecrets) -> Result<(), cipher::Error> {
+    let data_path = &config.data_path;
+    let key = if cipher::is_current_format(data_path)? {
+        FileKey::decrypt(&secrets.storage_secret, &sync_fs::read(data_path)?)?.0
+    } else {
+        let state: SignerState = LegacyFile::load(data_path, &secrets.password_hash)
+            .map_err(|_| cipher::Error::Decryption)?;
+        let key = FileKey::generate(&secrets.storage_secret, &mut OsRng)?;
+        cipher::save(
+            data_path,
+            &key,
+            &state,
+            config.backup_generations.max(1),
+            &mut OsRng,
+        )?;
+        key
+    };
+    let totp_path = config.totp_path();
+    if totp_path.exists() && !cipher::is_current_format(&totp_path)? {
+        let secret: Result<Vec<u8>, _> = LegacyFile::load(&totp_path, &secrets.password_hash);
+        match secret {
+            Ok(secret) => cipher::save(&totp_path, &key, &secret, 0, &mut OsRng)?,
+            _ => sync_fs::rename(&totp_path, with_suffix(&totp_path, ".corrupt"))?,
+        }
+    }
+    let legacy_api_keys_path = config.legacy_api_keys_path();
+    if let Some(store) = ApiKeyStore::load_legacy(&legacy_api_keys_path)? {
+        store.save(config.api_keys_path(), &key)?;
+        sync_fs::remove_file(legacy_api_keys_path)?;
+    }
+    let legacy_devices_path = config.legacy_devices_path();
+    if let Some(store) = DeviceStore::load_legacy(&legacy_devices_path)? {
+        store.save(config.devices_path(), &key)?;
+        sync_fs::remove_file(legacy_devices_path)?;
+    }
+    Ok(())
+}
+
+/// Loads the storage format version of the data directory of `config`, checking that it is
+/// supported.
+#[inline]
+async fn load_version(config: &Config) -> io::Result<u32> {
+    let version = Manifest::load(config.manifest_path())
+        .await?
+        .map(|manifest| manifest.version)
+        .unwrap_or(LEGACY_VERSION);
+    if version > CURRENT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "Storage format version {} is newer than the supported version {}.",
+                version, CURRENT_VERSION
+            ),
+        ));
+    }
+    Ok(version)
+}
+
+/// Runs the pending migrations on the data directory of `config` up to the first
+/// [`Unlock`](MigrationStep::Unlock) migration, returning the storage format version after
+/// migration.
+#[inline]
+pub async fn migrate(config: &Config) -> io::Result<u32> {
+    let mut version = load_version(config).await?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        match migration.run {
+            MigrationStep::Startup(run) => run(config).await?,
+            MigrationStep::Unlock(_) => break,
+        }
+        version = migration.version;
+        Manifest { version }.save(config.manifest_path()).await?;
+    }
+    Ok(version)
+}
+
+/// Runs all pending migrations on the data directory of `config` with the `secrets` of the
+/// entered password, returning the storage format version after migration.
+#[inline]
+pub async fn migrate_unlocked(
+    config: &Config,
+    secrets: &UnlockSecrets,
+) -> Result<u32, cipher::Error> {
+    let mut version = load_version(config).await?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        match migration.run {
+            MigrationStep::Startup(run) => run(config).await?,
+            MigrationStep::Unlock(run) => {
+                let (config, secrets) = (config.clone(), secrets.clone());
+                tokio::task::spawn_blocking(move || run(&config, &secrets))
+                    .await
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))??
+            }
+        }
+        version = migration.version;
+        Manifest { version }.save(config.manifest_path()).await?;
+    }
+    Ok(version)
+}
@@ -0,0 +1,559 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Wallet File Encryption
+//!
+//! Wallet files are encrypted with XChaCha20-Poly1305 under a key derived with Argon2id. Every
+//! file starts with a [`Header`] which records the format version, the KDF parameters, the salt,
+//! and a random 24-byte nonce. The header is authenticated as associated data so that none of
+//! these values can be tampered with. Starting with format version [`CHECKSUM_VERSION`], files
+//! end with a SHA-256 checksum so that corruption can be detected without the password.
+
+use crate::secret::{ExposeSecret, Secret};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use manta_crypto::rand::{CryptoRng, RngCore};
+use manta_util::{
+    from_variant_impl,
+    serde::{de::DeserializeOwned, Serialize},
+};
+use sha2::{Digest, Sha256};
+use std::{fs, io, path::Path};
+
+/// Magic Bytes
+pub const MAGIC: [u8; 4] = *b"MSGE";
+
+/// Current Encryption Format Version
+pub const FORMAT_VERSION: u8 = 2;
+
+/// First Format Version with a Trailing Checksum
+pub const CHECKSUM_VERSION: u8 = 2;
+
+/// Checksum Length in Bytes
+pub const CHECKSUM_LENGTH: usize = 32;
+
+/// Authentication Tag Length in Bytes
+pub const TAG_LENGTH: usize = 16;
+
+/// Salt Length in Bytes
+pub const SALT_LENGTH: usize = 16;
+
+/// Nonce Length in Bytes
+pub const NONCE_LENGTH: usize = 24;
+
+/// Key Length in Bytes
+pub const KEY_LENGTH: usize = 32;
+
+/// Header Length in Bytes
+pub const HEADER_LENGTH: usize = MAGIC.len() + 1 + 12 + SALT_LENGTH + NONCE_LENGTH;
+
+/// Encryption Error
+#[derive(Debug)]
+pub enum Error {
+    /// Generic I/O Error
+    Io(io::Error),
+
+    /// Invalid or Missing Header
+    InvalidHeader,
+
+    /// Unsupported Format Version
+    UnsupportedVersion(u8),
+
+    /// Truncated File
+    Truncated,
+
+    /// Checksum Mismatch
+    ChecksumMismatch,
+
+    /// Key Derivation Error
+    KeyDerivation,
+
+    /// Serialization Error
+    Serialization,
+
+    /// Encryption Error
+    Encryption,
+
+    /// Decryption Error
+    ///
+    /// This is returned when the key is wrong or the file has been tampered with.
+    Decryption,
+}
+
+from_variant_impl!(Error, Io, io::Error);
+
+/// Argon2id Key Derivation Parameters
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct KdfParameters {
+    /// Memory Cost in KiB
+    pub m_cost: u32,
+
+    /// Number of Iterations
+    pub t_cost: u32,
+
+    /// Degree of Parallelism
+    pub p_cost: u32,
+}
+
+impl Default for KdfParameters {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Encrypted File Header
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Header {
+    /// Format Version
+    pub version: u8,
+
+    /// Key Derivation Parameters
+    pub kdf: KdfParameters,
+
+    /// Key Derivation Salt
+    pub salt: [u8; SALT_LENGTH],
+
+    /// Encryption Nonce
+    pub nonce: [u8; NONCE_LENGTH],
+}
+
+impl Header {
+    /// Encodes `self` into its byte representation.
+    #[inline]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.kdf.m_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.t_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.p_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    /// Decodes a header from the front of `bytes`, returning the header and the remaining
+    /// ciphertext. For format versions with a trailing checksum, the checksum is verified and
+    /// stripped from the returned ciphertext.
+    #[inline]
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        if !has_header(bytes) {
+            return Err(Error::InvalidHeader);
+        }
+        if bytes.len() < HEADER_LENGTH {
+            return Err(Error::Truncated);
+        }
+        let version = bytes[MAGIC.len()];
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let bytes = if version >= CHECKSUM_VERSION {
+            if bytes.len() < HEADER_LENGTH + CHECKSUM_LENGTH {
+                return Err(Error::Truncated);
+            }
+            let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+            if Sha256::digest(body).as_slice() != checksum {
+                return Err(Error::ChecksumMismatch);
+            }
+            body
+        } else {
+            bytes
+        };
+        let (header, ciphertext) = bytes.split_at(HEADER_LENGTH);
+        if ciphertext.len() < TAG_LENGTH {
+            return Err(Error::Truncated);
+        }
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes(
+                header[offset..offset + 4]
+                    .try_into()
+                    .expect("The header length is checked above."),
+            )
+        };
+        let kdf_offset = MAGIC.len() + 1;
+        let salt_offset = kdf_offset + 12;
+        let nonce_offset = salt_offset + SALT_LENGTH;
+        Ok((
+            Self {
+                version,
+                kdf: KdfParameters {
+                    m_cost: u32_at(kdf_offset),
+                    t_cost: u32_at(kdf_offset + 4),
+                    p_cost: u32_at(kdf_offset + 8),
+                },
+                salt: header[salt_offset..nonce_offset]
+                    .try_into()
+                    .expect("The header length is checked above."),
+                nonce: header[nonce_offset..]
+                    .try_into()
+                    .expect("The header length is checked above."),
+            },
+            ciphertext,
+        ))
+    }
+}
+
+/// Verifies the integrity of the encrypted file `bytes` without decrypting them.
+///
+/// Files using a format version older than [`CHECKSUM_VERSION`] can only be checked for a
+/// well-formed header and a plausible length.
+#[inline]
+pub fn verify_integrity(bytes: &[u8]) -> Result<(), Error> {
+    Header::decode(bytes).map(|_| ())
+}
+
+/// Returns `true` if `bytes` starts with the encrypted file [`MAGIC`] bytes.
+#[inline]
+pub fn has_header(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// File Encryption Key
+///
+/// The key is derived once on unlock and kept in memory together with its derivation parameters
+/// so that subsequent saves only need to sample a fresh nonce.
+pub struct FileKey {
+    /// Key Derivation Parameters
+    kdf: KdfParameters,
+
+    /// Key Derivation Salt
+    salt: [u8; SALT_LENGTH],
+
+    /// Derived Key
+    key: Secret<[u8; KEY_LENGTH]>,
+}
+
+impl FileKey {
+    /// Derives a [`FileKey`] from `secret` using `kdf` and `salt`.
+    #[inline]
+    pub fn derive(
+        secret: &[u8],
+        kdf: KdfParameters,
+        salt: [u8; SALT_LENGTH],
+    ) -> Result<Self, Error> {
+        let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(KEY_LENGTH))
+            .map_err(|_| Error::KeyDerivation)?;
+        let mut key = [0; KEY_LENGTH];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(secret, &salt, &mut key)
+            .map_err(|_| Error::KeyDerivation)?;
+        Ok(Self {
+            kdf,
+            salt,
+            key: Secret::new(key),
+        })
+    }
+
+    /// Derives a [`FileKey`] from `secret` with the default KDF parameters and a fresh salt
+    /// sampled from `rng`.
+    #[inline]
+    pub fn generate<R>(secret: &[u8], rng: &mut R) -> Result<Self, Error>
+    where
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        let mut salt = [0; SALT_LENGTH];
+        rng.fill_bytes(&mut salt);
+        Self::derive(secret, Default::default(), salt)
+    }
+
+    /// Encrypts `plaintext` under `self` using a fresh nonce sampled from `rng`.
+    #[inline]
+    pub fn encrypt<R>(&self, plaintext: &[u8], rng: &mut R) -> Result<Vec<u8>, Error>
+    where
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        let mut nonce = [0; NONCE_LENGTH];
+        rng.fill_bytes(&mut nonce);
+        let mut bytes = Header {
+            version: FORMAT_VERSION,
+            kdf: self.kdf,
+            salt: self.salt,
+            nonce,
+        }
+        .encode();
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(self.key.expose_secret()))
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &bytes,
+                },
+            )
+            .map_err(|_| Error::Encryption)?;
+        bytes.extend_from_slice(&ciphertext);
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum);
+        Ok(bytes)
+    }
+
+    /// Decrypts `bytes` by deriving the key from `secret` with the parameters stored in the
+    /// header, returning the key and the plaintext.
+    #[inline]
+    pub fn decrypt(secret: &[u8], bytes: &[u8]) -> Result<(Self, Vec<u8>), Error> {
+        let (header, _) = Header::decode(bytes)?;
+        let key = Self::derive(secret, header.kdf, header.salt)?;
+        let plaintext = key.open(bytes)?;
+        Ok((key, plaintext))
+    }
+
+    /// Returns `true` if files with the given `header` are encrypted under `self`.
+    #[inline]
+    pub fn matches(&self, header: &Header) -> bool {
+        header.kdf == self.kdf && header.salt == self.salt
+    }
+
+    /// Decrypts `bytes` which were encrypted under `self`, returning the plaintext.
+    #[inline]
+    pub fn open(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let (header, ciphertext) = Header::decode(bytes)?;
+        if !self.matches(&header) {
+            return Err(Error::Decryption);
+        }
+        XChaCha20Poly1305::new(Key::from_slice(self.key.expose_secret()))
+            .decrypt(
+                XNonce::from_slice(&header.nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &bytes[..HEADER_LENGTH],
+                },
+            )
+            .map_err(|_| Error::Decryption)
+    }
+}
+
+/// Returns the secret from which the storage key is derived by appending the additional unlock
+/// `factors`, like the wrapped device secret or the keyfile digest, to the `password_hash`.
+#[inline]
+pub fn storage_secret(password_hash: &[u8], factors: &[&[u8]]) -> Vec<u8> {
+    let mut secret = password_hash.to_vec();
+    for factor in factors {
+        secret.extend_from_slice(factor);
+    }
+    secret
+}
+
+/// Reads the file at `path` and decrypts it with `key`, returning the deserialized value.
+///
+/// This is used for files which are encrypted under the same key as the wallet file, so that
+/// the key does not have to be derived again.
+#[inline]
+pub fn load_with_key<P, T>(path: P, key: &FileKey) -> Result<T, Error>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    bincode::deserialize(&key.open(&fs::read(path)?)?).map_err(|_| Error::Serialization)
+}
+
+/// Reads the file at `path` and decrypts it with `key` like [`load_with_key`], returning `None` if
+/// it does not exist.
+///
+/// A file encrypted under another key was written for a previous account, for example before the
+/// account was re-imported from its recovery phrase with another password, and can never be
+/// decrypted with `key`. It is moved aside with a `.corrupt` suffix, so that it is kept for
+/// inspection, and treated as missing, so that the caller starts over instead of failing on every
+/// unlock.
+#[inline]
+pub fn load_or_quarantine<P, T>(path: P, key: &FileKey) -> Result<Option<T>, Error>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    if !key.matches(&Header::decode(&bytes)?.0) {
+        fs::rename(path, super::with_suffix(path, ".corrupt"))?;
+        return Ok(None);
+    }
+    bincode::deserialize(&key.open(&bytes)?)
+        .map(Some)
+        .map_err(|_| Error::Serialization)
+}
+
+/// Serializes `value`, encrypts it under `key`, and atomically writes it to `path`, keeping the
+/// previous `generations` versions as backups.
+///
+/// See [`write_atomic`](super::write_atomic) for more on the backup behavior.
+#[inline]
+pub fn save<P, T, R>(
+    path: P,
+    key: &FileKey,
+    value: &T,
+    generations: usize,
+    rng: &mut R,
+) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    T: Serialize,
+    R: CryptoRng + RngCore + ?Sized,
+{
+    let plaintext = bincode::serialize(value).map_err(|_| Error::Serialization)?;
+    super::write_atomic(path.as_ref(), &key.encrypt(&plaintext, rng)?, generations)?;
+    Ok(())
+}
+
+/// Reads the file at `path` and decrypts it with the key derived from `secret`, returning the key
+/// and the deserialized value.
+#[inline]
+pub fn load<P, T>(path: P, secret: &[u8]) -> Result<(FileKey, T), Error>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    let (key, plaintext) = FileKey::decrypt(secret, &fs::read(path)?)?;
+    Ok((
+        key,
+        bincode::deserialize(&plaintext).map_err(|_| Error::Serialization)?,
+    ))
+}
+
+/// Returns `true` if the file at `path` uses the encrypted file format of this module rather than
+/// the legacy format.
+#[inline]
+pub fn is_current_format<P>(path: P) -> io::Result<bool>
+where
+    P: AsRef<Path>,
+{
+    let mut magic = [0; MAGIC.len()];
+    match io::Read::read_exact(&mut fs::File::open(path)?, &mut magic) {
+        Ok(()) => Ok(has_header(&magic)),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use manta_crypto::rand::OsRng;
+
+    /// Key Derivation Parameters which keep the Tests Fast
+    const TEST_KDF: KdfParameters = KdfParameters {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    /// Returns a header with distinct bytes in every field.
+    #[inline]
+    fn sample_header(version: u8) -> Header {
+        Header {
+            version,
+            kdf: KdfParameters {
+                m_cost: 0x01020304,
+                t_cost: 5,
+                p_cost: 6,
+            },
+            salt: [7; SALT_LENGTH],
+            nonce: [8; NONCE_LENGTH],
+        }
+    }
+
+    /// Checks the byte layout of an encoded header.
+    #[test]
+    fn header_encoding() {
+        let bytes = sample_header(FORMAT_VERSION).encode();
+        assert_eq!(bytes.len(), HEADER_LENGTH);
+        assert_eq!(&bytes[..4], b"MSGE");
+        assert_eq!(bytes[4], FORMAT_VERSION);
+        assert_eq!(&bytes[5..17], &[4u8, 3, 2, 1, 5, 0, 0, 0, 6, 0, 0, 0]);
+        assert_eq!(&bytes[17..17 + SALT_LENGTH], &[7u8; SALT_LENGTH]);
+        assert_eq!(&bytes[17 + SALT_LENGTH..], &[8u8; NONCE_LENGTH]);
+    }
+
+    /// Checks that decoding an encoded header returns the header and the ciphertext, stripping
+    /// the checksum of the current format.
+    #[test]
+    fn header_round_trip() {
+        let ciphertext = [9; TAG_LENGTH + 3];
+        for version in 1..=FORMAT_VERSION {
+            let header = sample_header(version);
+            let mut bytes = header.encode();
+            bytes.extend_from_slice(&ciphertext);
+            if version >= CHECKSUM_VERSION {
+                let checksum = Sha256::digest(&bytes);
+                bytes.extend_from_slice(&checksum);
+            }
+            let (decoded, rest) = Header::decode(&bytes).expect("The header is valid.");
+            assert_eq!(decoded, header);
+            assert_eq!(rest, &ciphertext);
+        }
+    }
+
+    /// Checks that malformed headers are rejected.
+    #[test]
+    fn header_rejects_malformed_bytes() {
+        let mut bytes = sample_header(FORMAT_VERSION).encode();
+        bytes.extend_from_slice(&[9; TAG_LENGTH]);
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum);
+        assert!(matches!(
+            Header::decode(b"cocoon"),
+            Err(Error::InvalidHeader)
+        ));
+        assert!(matches!(
+            Header::decode(&bytes[..HEADER_LENGTH - 1]),
+            Err(Error::Truncated)
+        ));
+        let mut tampered = bytes.clone();
+        tampered[HEADER_LENGTH] ^= 1;
+        assert!(matches!(
+            Header::decode(&tampered),
+            Err(Error::ChecksumMismatch)
+        ));
+        let mut unsupported = bytes;
+        unsupported[MAGIC.len()] = FORMAT_VERSION + 1;
+        assert!(matches!(
+            Header::decode(&unsupported),
+            Err(Error::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+    }
+
+    /// Checks that encrypted files only open under the key they were encrypted with.
+    #[test]
+    fn encryption_round_trip() {
+        let key =
+            FileKey::derive(b"secret", TEST_KDF, [1; SALT_LENGTH]).expect("Valid parameters.");
+        let bytes = key
+            .encrypt(b"plaintext", &mut OsRng)
+            .expect("Encryption succeeds.");
+        assert!(verify_integrity(&bytes).is_ok());
+        assert_eq!(key.open(&bytes).expect("The key matches."), b"plaintext");
+        let (derived, plaintext) =
+            FileKey::decrypt(b"secret", &bytes).expect("The secret matches.");
+        assert!(derived.matches(&Header::decode(&bytes).expect("Valid header.").0));
+        assert_eq!(plaintext, b"plaintext");
+        assert!(matches!(
+            FileKey::decrypt(b"other secret", &bytes),
+            Err(Error::Decryption)
+        ));
+        let other =
+            FileKey::derive(b"secret", TEST_KDF, [2; SALT_LENGTH]).expect("Valid parameters.");
+        assert!(matches!(other.open(&bytes), Err(Error::Decryption)));
+    }
+}
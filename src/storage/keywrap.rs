@@ -0,0 +1,306 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Hardware Key Wrapping
+//!
+//! When [`hardware_key_wrapping`](crate::config::Config::hardware_key_wrapping) is enabled for a
+//! new account, a random device secret is mixed into the secret from which the storage key is
+//! derived. The device secret is only stored wrapped by a key which never leaves the hardware of
+//! the device, so unlocking the wallet files needs both the password and the device they were
+//! created on. Copies of the wallet files are useless on other machines.
+//!
+//! The supported hardware is the Secure Enclave on macOS and the TPM on Windows.
+
+use data_encoding::HEXLOWER;
+use manta_crypto::rand::{OsRng, RngCore};
+use manta_util::{
+    from_variant_impl,
+    serde::{Deserialize, Serialize},
+};
+use std::{io, path::Path};
+
+/// Device Secret Length in Bytes
+pub const DEVICE_SECRET_LENGTH: usize = 32;
+
+/// Key Wrapping Error
+#[derive(Debug)]
+pub enum Error {
+    /// Unsupported Platform Error
+    ///
+    /// There is no hardware key store on this platform.
+    Unsupported,
+
+    /// Missing Hardware Key Error
+    ///
+    /// The hardware key which wrapped the device secret does not exist on this device.
+    KeyNotFound,
+
+    /// Hardware Key Store Error
+    Platform(String),
+
+    /// Malformed Wrapped Secret Error
+    Malformed,
+
+    /// Generic I/O Error
+    Io(io::Error),
+}
+
+from_variant_impl!(Error, Io, io::Error);
+
+/// Wrapped Device Secret
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+struct WrappedSecret {
+    /// Hardware Key Name
+    key_name: String,
+
+    /// Hex-Encoded Wrapped Device Secret
+    wrapped: String,
+}
+
+/// Generates a fresh device secret, wraps it with a new hardware key, and saves it to `path`,
+/// returning the device secret.
+#[inline]
+pub fn create<P>(path: P) -> Result<Vec<u8>, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut id = [0; 8];
+    OsRng.fill_bytes(&mut id);
+    let key_name = format!("network.manta.signer.{}", HEXLOWER.encode(&id));
+    let mut secret = vec![0; DEVICE_SECRET_LENGTH];
+    OsRng.fill_bytes(&mut secret);
+    let wrapped = WrappedSecret {
+        wrapped: HEXLOWER.encode(&platform::wrap(&key_name, &secret)?),
+        key_name,
+    };
+    crate::storage::write_atomic(
+        path.as_ref(),
+        &serde_json::to_vec_pretty(&wrapped).map_err(|_| Error::Malformed)?,
+        0,
+    )?;
+    Ok(secret)
+}
+
+/// Loads the wrapped device secret from `path` and unwraps it with its hardware key, returning
+/// `None` if there is no wrapped device secret.
+#[inline]
+pub fn load<P>(path: P) -> Result<Option<Vec<u8>>, Error>
+where
+    P: AsRef<Path>,
+{
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let wrapped: WrappedSecret = serde_json::from_slice(&bytes).map_err(|_| Error::Malformed)?;
+    let ciphertext = HEXLOWER
+        .decode(wrapped.wrapped.as_bytes())
+        .map_err(|_| Error::Malformed)?;
+    platform::unwrap(&wrapped.key_name, &ciphertext).map(Some)
+}
+
+/// Secure Enclave Key Wrapping
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Error;
+    use security_framework::{
+        item::{ItemClass, ItemSearchOptions, Location, Reference, SearchResult},
+        key::{Algorithm, GenerateKeyOptions, KeyType, SecKey, Token},
+    };
+
+    /// Wrapping Algorithm
+    const ALGORITHM: Algorithm = Algorithm::ECIESEncryptionCofactorVariableIVX963SHA256AESGCM;
+
+    /// Converts a Security framework error into an [`Error`].
+    #[inline]
+    fn platform_error<E>(err: E) -> Error
+    where
+        E: ToString,
+    {
+        Error::Platform(err.to_string())
+    }
+
+    /// Creates a Secure Enclave key called `key_name` and wraps `secret` with it.
+    #[inline]
+    pub fn wrap(key_name: &str, secret: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut options = GenerateKeyOptions::default();
+        options
+            .set_key_type(KeyType::ec())
+            .set_size_in_bits(256)
+            .set_label(key_name)
+            .set_token(Token::SecureEnclave)
+            .set_location(Location::DataProtectionKeychain);
+        SecKey::new(&options)
+            .map_err(platform_error)?
+            .public_key()
+            .ok_or_else(|| Error::Platform("Secure Enclave key has no public key".into()))?
+            .encrypt_data(ALGORITHM, secret)
+            .map_err(platform_error)
+    }
+
+    /// Unwraps `wrapped` with the Secure Enclave key called `key_name`.
+    #[inline]
+    pub fn unwrap(key_name: &str, wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+        ItemSearchOptions::new()
+            .class(ItemClass::key())
+            .label(key_name)
+            .load_refs(true)
+            .limit(1)
+            .search()
+            .map_err(|_| Error::KeyNotFound)?
+            .into_iter()
+            .find_map(|result| match result {
+                SearchResult::Ref(Reference::Key(key)) => Some(key),
+                _ => None,
+            })
+            .ok_or(Error::KeyNotFound)?
+            .decrypt_data(ALGORITHM, wrapped)
+            .map_err(platform_error)
+    }
+}
+
+/// TPM Key Wrapping
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::Error;
+    use windows::{
+        core::HSTRING,
+        Win32::Security::Cryptography::{
+            NCryptCreatePersistedKey, NCryptDecrypt, NCryptEncrypt, NCryptFinalizeKey,
+            NCryptFreeObject, NCryptOpenKey, NCryptOpenStorageProvider, BCRYPT_RSA_ALGORITHM,
+            CERT_KEY_SPEC, MS_PLATFORM_CRYPTO_PROVIDER, NCRYPT_FLAGS, NCRYPT_HANDLE,
+            NCRYPT_KEY_HANDLE, NCRYPT_PAD_PKCS1_FLAG, NCRYPT_PROV_HANDLE,
+        },
+    };
+
+    /// Converts a platform crypto provider error into an [`Error`].
+    #[inline]
+    fn platform_error(err: windows::core::Error) -> Error {
+        Error::Platform(err.to_string())
+    }
+
+    /// Opens the platform crypto provider, which keeps its keys in the TPM.
+    #[inline]
+    fn open_provider() -> Result<NCRYPT_PROV_HANDLE, Error> {
+        let mut provider = NCRYPT_PROV_HANDLE::default();
+        unsafe { NCryptOpenStorageProvider(&mut provider, MS_PLATFORM_CRYPTO_PROVIDER, 0) }
+            .map_err(platform_error)?;
+        Ok(provider)
+    }
+
+    /// Runs `f` on the output buffer of an `NCryptEncrypt` or `NCryptDecrypt` call, first to get
+    /// the output size and then to fill the output.
+    #[inline]
+    fn with_output<F>(mut f: F) -> Result<Vec<u8>, Error>
+    where
+        F: FnMut(Option<&mut [u8]>, &mut u32) -> windows::core::Result<()>,
+    {
+        let mut size = 0;
+        f(None, &mut size).map_err(platform_error)?;
+        let mut output = vec![0; size as usize];
+        f(Some(&mut output), &mut size).map_err(platform_error)?;
+        output.truncate(size as usize);
+        Ok(output)
+    }
+
+    /// Frees the provider and key handles.
+    #[inline]
+    fn free(provider: NCRYPT_PROV_HANDLE, key: NCRYPT_KEY_HANDLE) {
+        unsafe {
+            let _ = NCryptFreeObject(NCRYPT_HANDLE(key.0));
+            let _ = NCryptFreeObject(NCRYPT_HANDLE(provider.0));
+        }
+    }
+
+    /// Creates a TPM key called `key_name` and wraps `secret` with it.
+    #[inline]
+    pub fn wrap(key_name: &str, secret: &[u8]) -> Result<Vec<u8>, Error> {
+        let provider = open_provider()?;
+        let mut key = NCRYPT_KEY_HANDLE::default();
+        let result = unsafe {
+            NCryptCreatePersistedKey(
+                provider,
+                &mut key,
+                BCRYPT_RSA_ALGORITHM,
+                &HSTRING::from(key_name),
+                CERT_KEY_SPEC(0),
+                NCRYPT_FLAGS(0),
+            )
+            .and_then(|_| NCryptFinalizeKey(key, NCRYPT_FLAGS(0)))
+        }
+        .map_err(platform_error)
+        .and_then(|_| {
+            with_output(|output, size| unsafe {
+                NCryptEncrypt(key, Some(secret), None, output, size, NCRYPT_PAD_PKCS1_FLAG)
+            })
+        });
+        free(provider, key);
+        result
+    }
+
+    /// Unwraps `wrapped` with the TPM key called `key_name`.
+    #[inline]
+    pub fn unwrap(key_name: &str, wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+        let provider = open_provider()?;
+        let mut key = NCRYPT_KEY_HANDLE::default();
+        let result = unsafe {
+            NCryptOpenKey(
+                provider,
+                &mut key,
+                &HSTRING::from(key_name),
+                CERT_KEY_SPEC(0),
+                NCRYPT_FLAGS(0),
+            )
+        }
+        .map_err(|_| Error::KeyNotFound)
+        .and_then(|_| {
+            with_output(|output, size| unsafe {
+                NCryptDecrypt(
+                    key,
+                    Some(wrapped),
+                    None,
+                    output,
+                    size,
+                    NCRYPT_PAD_PKCS1_FLAG,
+                )
+            })
+        });
+        free(provider, key);
+        result
+    }
+}
+
+/// Unsupported Platform
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::Error;
+
+    /// Returns [`Error::Unsupported`].
+    #[inline]
+    pub fn wrap(key_name: &str, secret: &[u8]) -> Result<Vec<u8>, Error> {
+        let _ = (key_name, secret);
+        Err(Error::Unsupported)
+    }
+
+    /// Returns [`Error::Unsupported`].
+    #[inline]
+    pub fn unwrap(key_name: &str, wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+        let _ = (key_name, wrapped);
+        Err(Error::Unsupported)
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage Key Rotation
+//!
+//! The wallet files of an account are encrypted under one [`FileKey`] which is derived from the
+//! storage secret with a random salt. Once the last rotation is older than
+//! [`key_rotation_days`](crate::config::Config::key_rotation_days), the signer derives a fresh
+//! key with a new salt when it is unlocked, and re-encrypts every file of the data directory which
+//! was encrypted under the previous key. This limits how long a captured ciphertext matches the
+//! files on disk and regularly exercises the decryption path which recovery depends on. The key
+//! can only be derived while the password is known, so the rotation runs at unlock.
+//!
+//! The signer state is re-encrypted last and then decrypted again with a key derived from the
+//! storage secret. The [`RotationStatus`] marks the rotation as in progress until it completes,
+//! so that after an interruption the next unlock re-encrypts the remaining files under the key
+//! of the signer state. Backup generations keep the key they were written with, so restoring a
+//! backup of the signer state marks the rotation as in progress as well, and the next unlock
+//! re-encrypts the other files under the key of the restored state.
+//!
+//! The files are encrypted directly under the key derived from the storage secret, rather than
+//! under a random data key wrapped by it. A wrapped data key would make the rotation cheap, but
+//! it would never change the key of the files themselves, which is what limits the lifetime of a
+//! captured ciphertext, so the signer re-encrypts every file instead.
+
+use crate::storage::{
+    cipher::{self, Error, FileKey, Header},
+    write_atomic,
+};
+use manta_crypto::rand::{CryptoRng, RngCore};
+use manta_util::serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// Default Number of Days between Key Rotations
+pub const DEFAULT_ROTATION_DAYS: u64 = 30;
+
+/// Key Rotation Status
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct RotationStatus {
+    /// Time of the Last Completed Rotation as a UNIX Timestamp
+    pub rotated_at: Option<i64>,
+
+    /// Rotation in Progress Flag
+    pub in_progress: bool,
+}
+
+impl RotationStatus {
+    /// Loads the rotation status from `path`, returning the default status if it does not exist
+    /// or cannot be read.
+    #[inline]
+    pub fn load<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the rotation status to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        write_atomic(
+            path.as_ref(),
+            &serde_json::to_vec_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )
+    }
+
+    /// Returns `true` if the key was never rotated or the last rotation is older than `days`. A
+    /// value of zero disables the rotation.
+    #[inline]
+    pub fn is_due(&self, days: u64) -> bool {
+        if days == 0 {
+            return false;
+        }
+        match self.rotated_at {
+            Some(rotated_at) => {
+                chrono::offset::Utc::now().timestamp() - rotated_at
+                    >= i64::try_from(days.saturating_mul(24 * 60 * 60)).unwrap_or(i64::MAX)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Re-encrypts the files of `directory` under a fresh key derived from `secret`, starting with
+/// the files encrypted under `current` and ending with the signer state at `data_path`, keeping
+/// `generations` backups of every file. Returns the new key and the number of re-encrypted files.
+#[inline]
+pub fn rotate<R>(
+    directory: &Path,
+    data_path: &Path,
+    secret: &[u8],
+    current: &FileKey,
+    generations: usize,
+    rng: &mut R,
+) -> Result<(FileKey, usize), Error>
+where
+    R: CryptoRng + RngCore + ?Sized,
+{
+    let key = FileKey::generate(secret, rng)?;
+    let count = reencrypt(directory, data_path, None, current, &key, generations, rng)?;
+    let plaintext = current.open(&fs::read(data_path)?)?;
+    write_atomic(data_path, &key.encrypt(&plaintext, rng)?, generations)?;
+    if FileKey::decrypt(secret, &fs::read(data_path)?)?.1 != plaintext {
+        return Err(Error::Decryption);
+    }
+    Ok((key, count + 1))
+}
+
+/// Finishes an interrupted rotation by re-encrypting the files of `directory` which decrypt with
+/// a key derived from `secret` under the `current` key of the signer state at `data_path`,
+/// keeping `generations` backups of every file. Returns the number of re-encrypted files.
+#[inline]
+pub fn resume<R>(
+    directory: &Path,
+    data_path: &Path,
+    secret: &[u8],
+    current: &FileKey,
+    generations: usize,
+    rng: &mut R,
+) -> Result<usize, Error>
+where
+    R: CryptoRng + RngCore + ?Sized,
+{
+    reencrypt(
+        directory,
+        data_path,
+        Some(secret),
+        current,
+        current,
+        generations,
+        rng,
+    )
+}
+
+/// Re-encrypts the files of `directory` other than `data_path` under `target`, keeping
+/// `generations` backups of every file. Files encrypted under `source` are re-encrypted, and if
+/// `secret` is given, also the files which decrypt with a key derived from it. Backups,
+/// temporary files and the files of other accounts are skipped.
+#[inline]
+fn reencrypt<R>(
+    directory: &Path,
+    data_path: &Path,
+    secret: Option<&[u8]>,
+    source: &FileKey,
+    target: &FileKey,
+    generations: usize,
+    rng: &mut R,
+) -> Result<usize, Error>
+where
+    R: CryptoRng + RngCore + ?Sized,
+{
+    let mut derived = Vec::<FileKey>::new();
+    let mut count = 0;
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if path == data_path
+            || !path.is_file()
+            || [".bak", ".tmp", ".corrupt"]
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+            || !cipher::is_current_format(&path)?
+        {
+            continue;
+        }
+        let bytes = fs::read(&path)?;
+        let header = match Header::decode(&bytes) {
+            Ok((header, _)) if !target.matches(&header) => header,
+            _ => continue,
+        };
+        let key = if source.matches(&header) {
+            source
+        } else if let Some(secret) = secret {
+            if !derived.iter().any(|key| key.matches(&header)) {
+                derived.push(FileKey::derive(secret, header.kdf, header.salt)?);
+            }
+            derived
+                .iter()
+                .find(|key| key.matches(&header))
+                .expect("The derived key was pushed above.")
+        } else {
+            continue;
+        };
+        let plaintext = match key.open(&bytes) {
+            Ok(plaintext) => plaintext,
+            Err(Error::Decryption) => continue,
+            Err(err) => return Err(err),
+        };
+        write_atomic(&path, &target.encrypt(&plaintext, rng)?, generations)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::cipher::KdfParameters;
+    use manta_crypto::rand::OsRng;
+    use std::path::PathBuf;
+
+    /// Storage Secret of the Account under Test
+    const SECRET: &[u8] = b"storage secret";
+
+    /// Temporary Directory which is Removed on Drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// Creates a fresh temporary directory.
+        #[inline]
+        fn new() -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("manta-signer-rotation-{:016x}", OsRng.next_u64()));
+            fs::create_dir_all(&path).expect("Unable to create the temporary directory.");
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        #[inline]
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Derives a key from `secret` with a salt filled with `salt`.
+    #[inline]
+    fn key(secret: &[u8], salt: u8) -> FileKey {
+        FileKey::derive(
+            secret,
+            KdfParameters::default(),
+            [salt; cipher::SALT_LENGTH],
+        )
+        .expect("The default parameters are valid.")
+    }
+
+    /// Encrypts `plaintext` under `key` to `path`.
+    #[inline]
+    fn write(path: &Path, key: &FileKey, plaintext: &[u8]) {
+        fs::write(
+            path,
+            key.encrypt(plaintext, &mut OsRng)
+                .expect("Encryption succeeds."),
+        )
+        .expect("Unable to write the test file.");
+    }
+
+    /// Checks that the rotation re-encrypts the files of the account, including the signer state,
+    /// and leaves backups and the files of other accounts untouched.
+    #[test]
+    fn rotate_reencrypts_the_account_files() {
+        let directory = TempDir::new();
+        let data_path = directory.0.join("storage.dat");
+        let current = key(SECRET, 1);
+        let other_account = key(b"other secret", 2);
+        write(&data_path, &current, b"state");
+        write(&directory.0.join("labels.dat"), &current, b"labels");
+        write(&directory.0.join("devices.dat"), &current, b"devices");
+        write(&directory.0.join("other.dat"), &other_account, b"other");
+        write(&directory.0.join("labels.dat.bak"), &current, b"backup");
+        let (rotated, count) = rotate(&directory.0, &data_path, SECRET, &current, 0, &mut OsRng)
+            .expect("Rotation succeeds.");
+        assert_eq!(count, 3);
+        for (name, plaintext) in [
+            ("storage.dat", &b"state"[..]),
+            ("labels.dat", b"labels"),
+            ("devices.dat", b"devices"),
+        ] {
+            let bytes = fs::read(directory.0.join(name)).expect("The file exists.");
+            assert_eq!(
+                rotated.open(&bytes).expect("The file is rotated."),
+                plaintext
+            );
+            assert!(matches!(current.open(&bytes), Err(Error::Decryption)));
+        }
+        let other = fs::read(directory.0.join("other.dat")).expect("The file exists.");
+        assert_eq!(
+            other_account.open(&other).expect("The file is untouched."),
+            b"other"
+        );
+        let backup = fs::read(directory.0.join("labels.dat.bak")).expect("The file exists.");
+        assert_eq!(
+            current.open(&backup).expect("The backup is untouched."),
+            b"backup"
+        );
+        let (_, state) = FileKey::decrypt(SECRET, &fs::read(&data_path).expect("The file exists."))
+            .expect("The state decrypts with the storage secret.");
+        assert_eq!(state, b"state");
+    }
+
+    /// Checks that resuming an interrupted rotation re-encrypts the files left under an earlier
+    /// key of the account, and only those.
+    #[test]
+    fn resume_finishes_an_interrupted_rotation() {
+        let directory = TempDir::new();
+        let data_path = directory.0.join("storage.dat");
+        let previous = key(SECRET, 1);
+        let current = key(SECRET, 2);
+        let other_account = key(b"other secret", 3);
+        write(&data_path, &current, b"state");
+        write(&directory.0.join("labels.dat"), &current, b"labels");
+        write(&directory.0.join("devices.dat"), &previous, b"devices");
+        write(&directory.0.join("other.dat"), &other_account, b"other");
+        let count = resume(&directory.0, &data_path, SECRET, &current, 0, &mut OsRng)
+            .expect("Resuming succeeds.");
+        assert_eq!(count, 1);
+        for (name, plaintext) in [
+            ("storage.dat", &b"state"[..]),
+            ("labels.dat", b"labels"),
+            ("devices.dat", b"devices"),
+        ] {
+            let bytes = fs::read(directory.0.join(name)).expect("The file exists.");
+            assert_eq!(
+                current.open(&bytes).expect("The file is rotated."),
+                plaintext
+            );
+        }
+        let other = fs::read(directory.0.join("other.dat")).expect("The file exists.");
+        assert_eq!(
+            other_account.open(&other).expect("The file is untouched."),
+            b"other"
+        );
+        assert_eq!(
+            resume(&directory.0, &data_path, SECRET, &current, 0, &mut OsRng)
+                .expect("Resuming succeeds."),
+            0
+        );
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Polkadot.js Compatible Signing
+//!
+//! The signer holds an sr25519 account key which it uses to sign ordinary extrinsics and raw
+//! messages through the `signPayload` and `signRaw` methods of the polkadot.js extension `signer`
+//! interface. Addresses are SS58-encoded with the prefix from the
+//! [`Config`](crate::config::Config).
+//!
+//! The account key is derived from the recovery phrase along the [`DERIVATION_PATH`] when the
+//! account is created or re-imported, so that the phrase recovers it like the shielded wallet, and
+//! is stored encrypted next to the wallet file. Accounts sampled at random by earlier versions are
+//! still loaded for signing, but their address is not published since the phrase cannot recover
+//! them.
+
+use bip39::Language;
+use blake2::{digest::consts::U32, Blake2b, Blake2b512, Digest};
+use core::sync::atomic::{AtomicU64, Ordering};
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use hmac::Hmac;
+use manta_pay::key::Mnemonic;
+use manta_util::serde::{Deserialize, Serialize};
+use schnorrkel::{derive::ChainCode, signing_context, ExpansionMode, Keypair, MiniSecretKey};
+use sha2::Sha512;
+
+/// Account Secret Key Length in Bytes
+pub const SECRET_KEY_LENGTH: usize = schnorrkel::MINI_SECRET_KEY_LENGTH;
+
+/// Derivation Path of the Account
+///
+/// This is the hard derivation which polkadot.js applies when the recovery phrase is imported
+/// with the `//polkadot` path.
+pub const DERIVATION_PATH: &str = "//polkadot";
+
+/// Number of PBKDF2 Rounds used to Expand the Recovery Phrase
+const SEED_ROUNDS: u32 = 2048;
+
+/// Default SS58 Address Prefix
+///
+/// This is the generic Substrate prefix.
+pub const DEFAULT_SS58_PREFIX: u16 = 42;
+
+/// Signing Context used by Substrate for sr25519 Signatures
+const SIGNING_CONTEXT: &[u8] = b"substrate";
+
+/// Payloads longer than this are hashed with BLAKE2b-256 before signing.
+const MAX_UNHASHED_PAYLOAD_LENGTH: usize = 256;
+
+/// Signature Type Prefix of sr25519 in a `MultiSignature`
+const SR25519_SIGNATURE_PREFIX: u8 = 1;
+
+/// Maximum Length of Text Messages in Bytes
+///
+/// Text messages are shown in full in the authorization prompt, so they are kept well below the
+/// [`MAX_PROMPT_SIZE`](crate::prompt::MAX_PROMPT_SIZE).
+pub const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Wrapping Prefix of Raw Messages
+const BYTES_PREFIX: &[u8] = b"<Bytes>";
+
+/// Wrapping Suffix of Raw Messages
+const BYTES_SUFFIX: &[u8] = b"</Bytes>";
+
+/// Signing Error
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub enum Error {
+    /// Unknown Address
+    ///
+    /// The payload is not addressed to the signer account.
+    UnknownAddress,
+
+    /// Invalid Hex Encoding
+    InvalidHex,
+
+    /// Invalid Number Encoding
+    InvalidNumber,
+
+    /// Message Too Long
+    ///
+    /// The message is longer than [`MAX_MESSAGE_LENGTH`].
+    MessageTooLong,
+
+    /// Unknown Chain
+    ///
+    /// The genesis hash of the payload is not the one of the configured
+    /// [`ChainSpec`](crate::chain::ChainSpec).
+    UnknownChain,
+}
+
+/// Encodes `public_key` as an SS58 address with `prefix`.
+#[inline]
+pub fn ss58_encode(prefix: u16, public_key: &[u8]) -> String {
+    let mut bytes = match prefix {
+        0..=63 => vec![prefix as u8],
+        _ => vec![
+            ((prefix & 0b1111_1100) >> 2) as u8 | 0b0100_0000,
+            (prefix >> 8) as u8 | ((prefix & 0b11) << 6) as u8,
+        ],
+    };
+    bytes.extend_from_slice(public_key);
+    let mut hasher = Blake2b512::new();
+    hasher.update(b"SS58PRE");
+    hasher.update(&bytes);
+    let checksum = hasher.finalize();
+    bytes.extend_from_slice(&checksum[..2]);
+    bs58::encode(bytes).into_string()
+}
+
+/// Decodes the `0x`-prefixed hex string `value`.
+#[inline]
+fn decode_hex(value: &str) -> Result<Vec<u8>, Error> {
+    HEXLOWER_PERMISSIVE
+        .decode(value.trim_start_matches("0x").as_bytes())
+        .map_err(|_| Error::InvalidHex)
+}
+
+/// Decodes the `0x`-prefixed hex number `value`.
+#[inline]
+fn decode_number(value: &str) -> Result<u128, Error> {
+    u128::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| Error::InvalidNumber)
+}
+
+/// Appends the SCALE compact encoding of `value` to `buffer`.
+#[inline]
+fn encode_compact(value: u128, buffer: &mut Vec<u8>) {
+    match value {
+        0..=0x3f => buffer.push((value as u8) << 2),
+        0x40..=0x3fff => buffer.extend_from_slice(&(((value as u16) << 2) | 0b01).to_le_bytes()),
+        0x4000..=0x3fff_ffff => {
+            buffer.extend_from_slice(&(((value as u32) << 2) | 0b10).to_le_bytes())
+        }
+        _ => {
+            let bytes = value.to_le_bytes();
+            let length = 16 - (value.leading_zeros() / 8) as usize;
+            buffer.push((((length - 4) as u8) << 2) | 0b11);
+            buffer.extend_from_slice(&bytes[..length]);
+        }
+    }
+}
+
+/// Returns the chain code of the hard derivation junction `name`, which is its SCALE encoding
+/// padded to 32 bytes, or its BLAKE2b-256 hash if the encoding is longer.
+#[inline]
+fn chain_code(name: &str) -> ChainCode {
+    let mut encoded = Vec::new();
+    encode_compact(name.len() as u128, &mut encoded);
+    encoded.extend_from_slice(name.as_bytes());
+    let mut code = [0; 32];
+    if encoded.len() > code.len() {
+        code.copy_from_slice(&Blake2b::<U32>::digest(&encoded));
+    } else {
+        code[..encoded.len()].copy_from_slice(&encoded);
+    }
+    ChainCode(code)
+}
+
+/// Derives the secret key bytes of the [`Account`] of the recovery phrase of `mnemonic`,
+/// returning `None` if the phrase cannot be read.
+///
+/// The phrase entropy is expanded with PBKDF2 and an empty password as in `substrate-bip39`, and
+/// the resulting key is hard-derived along the [`DERIVATION_PATH`], so that importing the phrase
+/// into polkadot.js with that path yields the same account.
+#[inline]
+pub fn derive_secret_bytes(mnemonic: &Mnemonic) -> Option<Vec<u8>> {
+    let phrase = serde_json::to_value(mnemonic).ok()?;
+    let entropy = bip39::Mnemonic::parse_in_normalized(Language::English, phrase.as_str()?)
+        .ok()?
+        .to_entropy();
+    let mut seed = [0; 64];
+    pbkdf2::pbkdf2::<Hmac<Sha512>>(&entropy, b"mnemonic", SEED_ROUNDS, &mut seed);
+    let mut secret = MiniSecretKey::from_bytes(&seed[..SECRET_KEY_LENGTH])
+        .ok()?
+        .expand(ExpansionMode::Ed25519);
+    let mut derived = None;
+    for junction in DERIVATION_PATH.split("//").skip(1) {
+        let (mini_secret, _) = secret.hard_derive_mini_secret_key(Some(chain_code(junction)), b"");
+        secret = mini_secret.expand(ExpansionMode::Ed25519);
+        derived = Some(mini_secret);
+    }
+    seed.iter_mut().for_each(|byte| *byte = 0);
+    Some(derived?.to_bytes().to_vec())
+}
+
+/// Injected Account
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct InjectedAccount {
+    /// SS58-Encoded Address
+    pub address: String,
+
+    /// Account Name
+    pub name: String,
+
+    /// Key Type
+    #[serde(rename = "type")]
+    pub key_type: String,
+}
+
+/// Public Address
+///
+/// This is the transparent address which receives public tokens, for example from exchanges.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PublicAddress {
+    /// SS58-Encoded Address
+    pub address: String,
+
+    /// SS58 Network Prefix
+    pub ss58_prefix: u16,
+}
+
+/// Extrinsic Signing Payload
+///
+/// This mirrors `SignerPayloadJSON` from polkadot.js. Unknown fields are ignored since newer
+/// versions of polkadot.js add fields which do not change the signed payload of chains using the
+/// default signed extensions.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", rename_all = "camelCase")]
+pub struct SignerPayloadJson {
+    /// SS58-Encoded Signer Address
+    pub address: String,
+
+    /// Hex-Encoded Checkpoint Block Hash
+    pub block_hash: String,
+
+    /// Hex-Encoded Checkpoint Block Number
+    pub block_number: String,
+
+    /// Hex-Encoded Era
+    pub era: String,
+
+    /// Hex-Encoded Genesis Hash
+    pub genesis_hash: String,
+
+    /// Hex-Encoded Call
+    pub method: String,
+
+    /// Hex-Encoded Nonce
+    pub nonce: String,
+
+    /// Signed Extensions
+    pub signed_extensions: Vec<String>,
+
+    /// Hex-Encoded Runtime Specification Version
+    pub spec_version: String,
+
+    /// Hex-Encoded Tip
+    pub tip: String,
+
+    /// Hex-Encoded Transaction Version
+    pub transaction_version: String,
+
+    /// Extrinsic Version
+    pub version: u8,
+}
+
+impl SignerPayloadJson {
+    /// Returns the SCALE-encoded payload which is signed for the extrinsic.
+    #[inline]
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut payload = decode_hex(&self.method)?;
+        payload.extend(decode_hex(&self.era)?);
+        encode_compact(decode_number(&self.nonce)?, &mut payload);
+        encode_compact(decode_number(&self.tip)?, &mut payload);
+        payload.extend_from_slice(&(decode_number(&self.spec_version)? as u32).to_le_bytes());
+        payload
+            .extend_from_slice(&(decode_number(&self.transaction_version)? as u32).to_le_bytes());
+        payload.extend(decode_hex(&self.genesis_hash)?);
+        payload.extend(decode_hex(&self.block_hash)?);
+        Ok(payload)
+    }
+}
+
+/// Raw Signing Payload
+///
+/// This mirrors `SignerPayloadRaw` from polkadot.js.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SignerPayloadRaw {
+    /// SS58-Encoded Signer Address
+    pub address: String,
+
+    /// Hex-Encoded Data
+    pub data: String,
+
+    /// Data Type
+    #[serde(rename = "type")]
+    pub data_type: String,
+}
+
+/// Text Message Signing Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SignMessageRequest {
+    /// Message
+    pub message: String,
+}
+
+/// Text Message Signature
+///
+/// The signature is over the message wrapped into `<Bytes>` tags, so it can be checked with
+/// `signatureVerify` from polkadot.js.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct MessageSignature {
+    /// SS58-Encoded Signer Address
+    pub address: String,
+
+    /// Message
+    pub message: String,
+
+    /// Hex-Encoded Signature
+    pub signature: String,
+}
+
+/// Signing Result
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SignerResult {
+    /// Request Identifier
+    pub id: u64,
+
+    /// Hex-Encoded Signature
+    pub signature: String,
+}
+
+/// Substrate Account
+pub struct Account {
+    /// Account Keypair
+    keypair: Keypair,
+
+    /// Account Address
+    address: String,
+
+    /// SS58 Network Prefix
+    prefix: u16,
+
+    /// Recoverable Flag
+    ///
+    /// This is `true` if the account is derived from the recovery phrase.
+    recoverable: bool,
+
+    /// Next Request Identifier
+    next_id: AtomicU64,
+}
+
+impl Account {
+    /// Builds an [`Account`] from the secret key `bytes` with the SS58 `prefix`, returning `None`
+    /// if they are not a valid secret key. The account is `recoverable` if the key was derived
+    /// with [`derive_secret_bytes`].
+    #[inline]
+    pub fn from_secret_bytes(bytes: &[u8], prefix: u16, recoverable: bool) -> Option<Self> {
+        let keypair = MiniSecretKey::from_bytes(bytes)
+            .ok()?
+            .expand_to_keypair(ExpansionMode::Ed25519);
+        Some(Self {
+            address: ss58_encode(prefix, &keypair.public.to_bytes()),
+            keypair,
+            prefix,
+            recoverable,
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the SS58-encoded address.
+    #[inline]
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Returns `true` if the account is derived from the recovery phrase.
+    #[inline]
+    pub fn is_recoverable(&self) -> bool {
+        self.recoverable
+    }
+
+    /// Returns the public address with its SS58 network prefix, or `None` if the account cannot
+    /// be recovered from the recovery phrase, so that no funds are sent to it.
+    #[inline]
+    pub fn public_address(&self) -> Option<PublicAddress> {
+        self.recoverable.then(|| PublicAddress {
+            address: self.address.clone(),
+            ss58_prefix: self.prefix,
+        })
+    }
+
+    /// Returns the public key bytes.
+    #[inline]
+    pub fn public_key(&self) -> [u8; 32] {
+        self.keypair.public.to_bytes()
+    }
+
+    /// Returns the account as injected into the polkadot.js extension interface.
+    #[inline]
+    pub fn injected(&self) -> InjectedAccount {
+        InjectedAccount {
+            address: self.address.clone(),
+            name: "Manta Signer".into(),
+            key_type: "sr25519".into(),
+        }
+    }
+
+    /// Signs `message` returning the signature bytes.
+    #[inline]
+    fn sign(&self, message: &[u8]) -> [u8; 64] {
+        self.keypair
+            .sign(signing_context(SIGNING_CONTEXT).bytes(message))
+            .to_bytes()
+    }
+
+    /// Returns the next request identifier.
+    #[inline]
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Checks that `address` is the address of this account.
+    #[inline]
+    fn check_address(&self, address: &str) -> Result<(), Error> {
+        if address == self.address {
+            Ok(())
+        } else {
+            Err(Error::UnknownAddress)
+        }
+    }
+
+    /// Signs the extrinsic `payload`, returning the `MultiSignature`-encoded signature.
+    #[inline]
+    pub fn sign_payload(&self, payload: &SignerPayloadJson) -> Result<SignerResult, Error> {
+        self.check_address(&payload.address)?;
+        let mut message = payload.encode()?;
+        if message.len() > MAX_UNHASHED_PAYLOAD_LENGTH {
+            message = Blake2b::<U32>::digest(&message).to_vec();
+        }
+        let mut signature = vec![SR25519_SIGNATURE_PREFIX];
+        signature.extend_from_slice(&self.sign(&message));
+        Ok(SignerResult {
+            id: self.next_id(),
+            signature: format!("0x{}", HEXLOWER.encode(&signature)),
+        })
+    }
+
+    /// Signs the text `message` to prove ownership of the account address.
+    #[inline]
+    pub fn sign_message(&self, message: String) -> Result<MessageSignature, Error> {
+        if message.len() > MAX_MESSAGE_LENGTH {
+            return Err(Error::MessageTooLong);
+        }
+        let signature = self.sign(&[BYTES_PREFIX, message.as_bytes(), BYTES_SUFFIX].concat());
+        Ok(MessageSignature {
+            address: self.address.clone(),
+            message,
+            signature: format!("0x{}", HEXLOWER.encode(&signature)),
+        })
+    }
+
+    /// Signs the raw `payload`, wrapping byte payloads into `<Bytes>` tags if they are not
+    /// wrapped yet.
+    #[inline]
+    pub fn sign_raw(&self, payload: &SignerPayloadRaw) -> Result<SignerResult, Error> {
+        self.check_address(&payload.address)?;
+        let data = decode_hex(&payload.data)?;
+        let message = if payload.data_type == "bytes"
+            && !(data.starts_with(BYTES_PREFIX) && data.ends_with(BYTES_SUFFIX))
+        {
+            [BYTES_PREFIX, &data, BYTES_SUFFIX].concat()
+        } else {
+            data
+        };
+        Ok(SignerResult {
+            id: self.next_id(),
+            signature: format!("0x{}", HEXLOWER.encode(&self.sign(&message))),
+        })
+    }
+}
@@ -0,0 +1,497 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Ledger Synchronization
+//!
+//! When a node RPC endpoint is configured, the signer pulls ledger diffs from the node itself
+//! with the `mantaPay_pull_ledger_diff` method instead of waiting for the client to push them
+//! through the `/sync` endpoint. The [`SyncStatus`] is kept next to the wallet file so that
+//! synchronization resumes where it stopped.
+//!
+//! The [`NodeConnection`] fails over to the next configured endpoint whenever a request to the
+//! node fails, and waits for an exponentially growing backoff between consecutive failures, so
+//! that synchronization recovers by itself once any endpoint is reachable again.
+//!
+//! The [`ProgressTracker`] combines the [`SyncStatus`] with the block height of the node into
+//! the [`SyncProgress`] which frontends show while the signer catches up with the ledger.
+
+use crate::status::ConnectionState;
+use core::time::Duration;
+use manta_pay::{
+    config::{EncryptedNote, Utxo, VoidNumber},
+    signer::Checkpoint,
+};
+use manta_util::serde::{de::DeserializeOwned, Deserialize, Serialize};
+use parking_lot::Mutex;
+use serde_json::json;
+use std::{io, path::Path, time::Instant};
+
+/// Default Synchronization Interval
+///
+/// The node is polled once per interval after the signer has caught up with the ledger, see
+/// [`SyncSchedule`](crate::schedule::SyncSchedule).
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(12);
+
+/// Backoff after the First Failed Request to the Node
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Maximum Backoff between Requests to an Unreachable Node
+pub const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Maximum Number of Receivers Pulled per Request
+pub const MAX_RECEIVERS: u64 = 1024;
+
+/// Maximum Number of Senders Pulled per Request
+pub const MAX_SENDERS: u64 = 1024;
+
+/// Ledger Diff Pull Method
+pub const PULL_METHOD: &str = "mantaPay_pull_ledger_diff";
+
+/// Block Header Method
+pub const HEADER_METHOD: &str = "chain_getHeader";
+
+/// Interval between the Progress Reports Sent to Frontends while Catching Up
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Ledger Pull Response
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct PullResponse {
+    /// Pull Continuation Flag
+    ///
+    /// The node sets this flag when there are more diffs to pull after this response.
+    pub should_continue: bool,
+
+    /// Ledger Receiver Chunk
+    pub receivers: Vec<(Utxo, EncryptedNote)>,
+
+    /// Ledger Sender Chunk
+    pub senders: Vec<VoidNumber>,
+}
+
+/// Block Header
+#[derive(Deserialize)]
+#[serde(crate = "manta_util::serde")]
+struct Header {
+    /// Hex-Encoded Block Number
+    number: String,
+}
+
+/// JSON-RPC Response
+#[derive(Deserialize)]
+#[serde(crate = "manta_util::serde")]
+struct RpcResponse<T> {
+    /// Result
+    result: Option<T>,
+
+    /// Error
+    error: Option<serde_json::Value>,
+}
+
+/// Converts `err` into an [`io::Error`].
+#[inline]
+fn into_io_error<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+/// Node RPC Client
+#[derive(Clone, Debug)]
+pub struct NodeClient {
+    /// Node RPC URL
+    url: String,
+
+    /// HTTP Agent
+    agent: ureq::Agent,
+}
+
+impl NodeClient {
+    /// Builds a new [`NodeClient`] for the HTTP RPC endpoint at `url`.
+    #[inline]
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    /// Returns the node RPC URL.
+    #[inline]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Sends the JSON-RPC request for `method` with `params`, returning the raw response body.
+    ///
+    /// This method blocks on the network and should be run on a blocking task.
+    #[inline]
+    pub fn send(&self, method: &str, params: serde_json::Value) -> io::Result<String> {
+        self.agent
+            .post(&self.url)
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .map_err(into_io_error)?
+            .into_string()
+    }
+
+    /// Calls the JSON-RPC `method` with `params`, returning its result.
+    ///
+    /// This method blocks on the network and should be run on a blocking task.
+    #[inline]
+    pub fn call<T>(&self, method: &str, params: serde_json::Value) -> io::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let response = serde_json::from_str::<RpcResponse<T>>(&self.send(method, params)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        match (response.result, response.error) {
+            (Some(result), _) => Ok(result),
+            (_, Some(error)) => Err(into_io_error(error.to_string())),
+            _ => Err(into_io_error("node returned an empty response")),
+        }
+    }
+
+    /// Pulls the ledger diff starting from `checkpoint`.
+    ///
+    /// This method blocks on the network and should be run on a blocking task.
+    #[inline]
+    pub fn pull(&self, checkpoint: &Checkpoint) -> io::Result<PullResponse> {
+        self.call(PULL_METHOD, json!([checkpoint, MAX_RECEIVERS, MAX_SENDERS]))
+    }
+
+    /// Returns the height of the best block of the node.
+    ///
+    /// This method blocks on the network and should be run on a blocking task.
+    #[inline]
+    pub fn block_height(&self) -> io::Result<u64> {
+        let header = self.call::<Header>(HEADER_METHOD, json!([]))?;
+        u64::from_str_radix(header.number.trim_start_matches("0x"), 16)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Node Connection
+///
+/// The connection holds the client of the current endpoint out of a list of endpoints and moves
+/// to the next one after every failed request.
+#[derive(Clone, Debug, Default)]
+pub struct NodeConnection {
+    /// Node RPC URLs in Order of Preference
+    endpoints: Vec<String>,
+
+    /// Client of the Current Endpoint
+    client: Option<NodeClient>,
+
+    /// Number of Consecutive Failed Requests
+    failures: u32,
+}
+
+impl NodeConnection {
+    /// Builds a new [`NodeConnection`] which starts with the first of `endpoints`.
+    #[inline]
+    pub fn new(endpoints: Vec<String>) -> Self {
+        let mut connection = Self::default();
+        connection.set_endpoints(endpoints);
+        connection
+    }
+
+    /// Replaces the endpoints with `endpoints`, starting over from the first of them if they
+    /// changed.
+    #[inline]
+    pub fn set_endpoints(&mut self, endpoints: Vec<String>) {
+        if endpoints != self.endpoints {
+            self.client = endpoints.first().cloned().map(NodeClient::new);
+            self.endpoints = endpoints;
+            self.failures = 0;
+        }
+    }
+
+    /// Returns the client of the current endpoint, if there is any endpoint.
+    #[inline]
+    pub fn client(&self) -> Option<&NodeClient> {
+        self.client.as_ref()
+    }
+
+    /// Records a successful request, returning `true` if the previous requests had failed.
+    #[inline]
+    pub fn succeed(&mut self) -> bool {
+        core::mem::take(&mut self.failures) > 0
+    }
+
+    /// Records a failed request, moving to the next endpoint, and returns the backoff to wait
+    /// before the next request.
+    #[inline]
+    pub fn fail(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        if let Some(client) = &self.client {
+            let current = self
+                .endpoints
+                .iter()
+                .position(|url| url == client.url())
+                .unwrap_or_default();
+            let next = (current + 1) % self.endpoints.len();
+            if next != current {
+                self.client = Some(NodeClient::new(self.endpoints[next].clone()));
+            }
+        }
+        self.backoff()
+    }
+
+    /// Returns the backoff after the current number of consecutive failures, which doubles from
+    /// [`INITIAL_BACKOFF`] with every failure up to [`MAX_BACKOFF`].
+    #[inline]
+    pub fn backoff(&self) -> Duration {
+        match self.failures {
+            0 => Duration::ZERO,
+            failures => (INITIAL_BACKOFF * (1 << (failures - 1).min(16))).min(MAX_BACKOFF),
+        }
+    }
+
+    /// Returns the state of the connection.
+    #[inline]
+    pub fn state(&self) -> ConnectionState {
+        match (&self.client, self.failures) {
+            (None, _) => ConnectionState::Disconnected,
+            (_, 0) => ConnectionState::Connected,
+            (_, attempt) => ConnectionState::Reconnecting {
+                attempt,
+                retry_in: self.backoff().as_secs(),
+            },
+        }
+    }
+}
+
+/// Synchronization Status
+///
+/// The status is saved after every synchronized batch, once the signer state containing the
+/// batch has been saved, so that restarting the signer resumes from the [`checkpoint`].
+///
+/// [`checkpoint`]: Self::checkpoint
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SyncStatus {
+    /// Last Synchronized Checkpoint
+    pub checkpoint: Checkpoint,
+
+    /// Number of Synchronized Batches
+    pub batches: u64,
+
+    /// Number of Synchronized Receivers
+    pub receivers: u64,
+
+    /// Number of Synchronized Senders
+    pub senders: u64,
+
+    /// Time of the Last Synchronized Batch as a UNIX Timestamp
+    pub synced_at: Option<i64>,
+
+    /// Caught-Up Flag
+    ///
+    /// This flag is set when the last batch reached the end of the ledger.
+    pub caught_up: bool,
+    /// Synchronized Block Height
+    ///
+    /// This is the height of the best block of the node when the signer last reached the end of
+    /// the ledger pulled from it.
+    #[serde(default)]
+    pub synced_height: Option<u64>,
+
+    /// Number of Notes Received so Far
+    #[serde(default)]
+    pub notes: u64,
+}
+
+impl SyncStatus {
+    /// Loads the status from `path`, returning the default status if it does not exist.
+    ///
+    /// Files which only contain a [`Checkpoint`], as written by earlier versions, are upgraded.
+    #[inline]
+    pub fn load<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .or_else(|_| {
+                    serde_json::from_slice(&bytes).map(|checkpoint| Self {
+                        checkpoint,
+                        ..Default::default()
+                    })
+                })
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the status to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        crate::storage::write_atomic(
+            path.as_ref(),
+            &serde_json::to_vec_pretty(self).map_err(into_io_error)?,
+            0,
+        )
+    }
+
+    /// Records a synchronized batch of `receivers` and `senders`, in which `notes` were received
+    /// for the wallet, which ended at `checkpoint`. The batch reached the end of the ledger at the
+    /// block `height` if it is known.
+    #[inline]
+    pub fn record(
+        &mut self,
+        checkpoint: Checkpoint,
+        receivers: usize,
+        senders: usize,
+        notes: usize,
+        caught_up: bool,
+        height: Option<u64>,
+    ) {
+        self.checkpoint = checkpoint;
+        self.batches += 1;
+        self.receivers += receivers as u64;
+        self.senders += senders as u64;
+        self.notes += notes as u64;
+        self.synced_at = Some(chrono::offset::Utc::now().timestamp());
+        self.caught_up = caught_up;
+        if caught_up && height.is_some() {
+            self.synced_height = height;
+        }
+    }
+}
+
+/// Synchronization Progress
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct SyncProgress {
+    /// Block Height of the Node
+    pub block_height: Option<u64>,
+
+    /// Block Height up to which the Wallet Processed the Ledger
+    pub synced_height: Option<u64>,
+
+    /// Estimated Remaining Time in Seconds
+    ///
+    /// This is only known while catching up, and after the signer caught up with the ledger once
+    /// since it started, from the speed of that catch-up.
+    pub remaining_seconds: Option<u64>,
+
+    /// Number of Notes Received so Far
+    pub notes: u64,
+
+    /// Catching-Up Flag
+    pub catching_up: bool,
+}
+
+/// Catch-Up Tracking State
+#[derive(Debug, Default)]
+struct Tracking {
+    /// Block Height of the Node
+    block_height: Option<u64>,
+
+    /// Block Height up to which the Wallet Processed the Ledger
+    synced_height: Option<u64>,
+
+    /// Number of Notes Received so Far
+    notes: u64,
+
+    /// Start Time and Height of the Current Catch-Up
+    catch_up: Option<(Instant, Option<u64>)>,
+
+    /// Speed of the Last Catch-Up in Blocks per Second
+    rate: Option<f64>,
+}
+
+/// Synchronization Progress Tracker
+#[derive(Debug, Default)]
+pub struct ProgressTracker {
+    /// Tracking State
+    tracking: Mutex<Tracking>,
+}
+
+impl ProgressTracker {
+    /// Starts tracking from `status`, forgetting the current catch-up.
+    #[inline]
+    pub fn reset(&self, status: &SyncStatus) {
+        let mut tracking = self.tracking.lock();
+        tracking.synced_height = status.synced_height;
+        tracking.notes = status.notes;
+        tracking.catch_up = None;
+    }
+
+    /// Sets the block height of the node to `height`.
+    #[inline]
+    pub fn set_block_height(&self, height: u64) {
+        self.tracking.lock().block_height = Some(height);
+    }
+
+    /// Updates the progress with `status` after a synchronized batch, measuring the speed of the
+    /// catch-up once the batch reached the end of the ledger.
+    #[inline]
+    pub fn record(&self, status: &SyncStatus) {
+        let mut tracking = self.tracking.lock();
+        if status.caught_up {
+            if let Some((started_at, Some(start))) = tracking.catch_up.take() {
+                let elapsed = started_at.elapsed().as_secs_f64();
+                match status.synced_height {
+                    Some(end) if end > start && elapsed > 0.0 => {
+                        tracking.rate = Some((end - start) as f64 / elapsed)
+                    }
+                    _ => {}
+                }
+            }
+        } else if tracking.catch_up.is_none() {
+            let start = match status.batches {
+                1 => Some(0),
+                _ => tracking.synced_height,
+            };
+            tracking.catch_up = Some((Instant::now(), start));
+        }
+        tracking.synced_height = status.synced_height;
+        tracking.notes = status.notes;
+    }
+
+    /// Returns the current progress.
+    #[inline]
+    pub fn progress(&self) -> SyncProgress {
+        let tracking = self.tracking.lock();
+        let remaining_seconds = match (&tracking.catch_up, tracking.block_height, tracking.rate) {
+            (Some((started_at, Some(start))), Some(height), Some(rate)) => Some(
+                ((height.saturating_sub(*start) as f64 / rate) - started_at.elapsed().as_secs_f64())
+                    .max(0.0) as u64,
+            ),
+            _ => None,
+        };
+        SyncProgress {
+            block_height: tracking.block_height,
+            synced_height: tracking.synced_height,
+            remaining_seconds,
+            notes: tracking.notes,
+            catching_up: tracking.catch_up.is_some(),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Telemetry
+//!
+//! When the `otlp` feature is enabled and an endpoint is configured, the spans created with
+//! [`Span::new`] are exported over OTLP. Otherwise, spans are no-ops.
+//!
+//! Every other kind of data which could leave the machine is listed in [`TelemetryConfig`] and
+//! is only collected after the user opts in to its category.
+
+#[cfg(feature = "otlp")]
+use opentelemetry::{
+    global,
+    trace::{TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+
+use manta_util::serde::{Deserialize, Serialize};
+use std::{io, path::Path};
+
+/// Tracer Name
+pub const TRACER_NAME: &str = "manta-signer";
+
+/// Telemetry Configuration
+///
+/// Every category is disabled by default.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// Anonymous Usage Statistics Opt-In
+    #[serde(default)]
+    pub usage_stats: bool,
+
+    /// Error Report Opt-In
+    #[serde(default)]
+    pub error_reports: bool,
+
+    /// Update Check Opt-In
+    #[serde(default)]
+    pub update_pings: bool,
+}
+
+impl TelemetryConfig {
+    /// Loads the telemetry configuration from `path`, returning the defaults, which disable every
+    /// category, if it does not exist or is malformed.
+    #[inline]
+    pub fn load<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the telemetry configuration to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        crate::storage::write_atomic(
+            path.as_ref(),
+            &serde_json::to_vec_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )
+    }
+
+    /// Returns `true` if any category is enabled.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.usage_stats || self.error_reports || self.update_pings
+    }
+}
+
+/// Installs the OTLP span exporter sending spans to `endpoint`, returning `true` if the exporter
+/// was installed.
+///
+/// This function always returns `false` when the `otlp` feature is disabled.
+#[inline]
+pub fn init(endpoint: &str) -> bool {
+    #[cfg(feature = "otlp")]
+    {
+        use opentelemetry::sdk::{trace, Resource};
+        use opentelemetry_otlp::WithExportConfig;
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                KeyValue::new("service.name", TRACER_NAME),
+                KeyValue::new("service.version", crate::VERSION),
+            ])))
+            .install_batch(opentelemetry::runtime::Tokio)
+            .is_ok()
+    }
+    #[cfg(not(feature = "otlp"))]
+    {
+        let _ = endpoint;
+        false
+    }
+}
+
+/// Tracing Span
+///
+/// The span is ended when it is dropped.
+pub struct Span {
+    /// Span Context
+    #[cfg(feature = "otlp")]
+    context: Context,
+}
+
+impl Span {
+    /// Starts a new root span with the given `name`.
+    #[inline]
+    pub fn new(name: &'static str) -> Self {
+        #[cfg(feature = "otlp")]
+        {
+            Self {
+                context: Context::current_with_span(global::tracer(TRACER_NAME).start(name)),
+            }
+        }
+        #[cfg(not(feature = "otlp"))]
+        {
+            let _ = name;
+            Self {}
+        }
+    }
+
+    /// Starts a new span with the given `name` as a child of `self`.
+    #[inline]
+    pub fn child(&self, name: &'static str) -> Self {
+        #[cfg(feature = "otlp")]
+        {
+            Self {
+                context: self.context.with_span(
+                    global::tracer(TRACER_NAME).start_with_context(name, &self.context),
+                ),
+            }
+        }
+        #[cfg(not(feature = "otlp"))]
+        {
+            let _ = name;
+            Self {}
+        }
+    }
+
+    /// Records the attribute `key` with `value` on `self`.
+    #[inline]
+    pub fn record<V>(&self, key: &'static str, value: V)
+    where
+        V: ToString,
+    {
+        #[cfg(feature = "otlp")]
+        self.context
+            .span()
+            .set_attribute(KeyValue::new(key, value.to_string()));
+        #[cfg(not(feature = "otlp"))]
+        {
+            let _ = (key, value);
+        }
+    }
+}
+
+impl Drop for Span {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        self.context.span().end();
+    }
+}
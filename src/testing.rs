@@ -0,0 +1,313 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Testing Utilities
+//!
+//! The [`MockAuthorizer`] approves every prompt with a fixed password, and the [`TestService`]
+//! runs a real signer service in-process on a random local port against a temporary directory,
+//! so that dapps can run end-to-end integration tests against it.
+//!
+//! This module is only available with the `testing` feature.
+
+use crate::{
+    config::Config,
+    secret::{Authorizer, ConfirmationFuture, Password, PasswordFuture, SecretString, UnitFuture},
+    service::{self, Handle},
+};
+use async_std::task::{self, JoinHandle};
+use core::time::Duration;
+use futures::future::{self, Either};
+use manta_crypto::rand::{OsRng, RngCore};
+use manta_util::serde::Serialize;
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// Interval between two Checks whether the Service is Ready
+pub const READY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Maximum Time to Wait for the Service to Start
+///
+/// The first start downloads the proving keys, which takes a while.
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Returns the directory where the test services share the proving keys they download.
+#[inline]
+pub fn parameter_cache_directory() -> PathBuf {
+    std::env::temp_dir().join("manta-signer-test-parameters")
+}
+
+/// Links the shared parameter cache into `directory`, so that only the first test service
+/// downloads the proving keys. Services download their own keys if linking is not supported.
+#[inline]
+fn link_parameter_cache(directory: &Path) {
+    #[cfg(unix)]
+    {
+        let cache = parameter_cache_directory();
+        if std::fs::create_dir_all(&cache).is_ok() {
+            let _ = std::os::unix::fs::symlink(cache, directory.join("sdk"));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = directory;
+}
+
+/// Mock Authorizer
+///
+/// The authorizer approves every prompt with a fixed password, optionally after a simulated user
+/// delay.
+pub struct MockAuthorizer {
+    /// Password
+    password: SecretString,
+
+    /// Simulated User Delay
+    delay: Duration,
+}
+
+impl MockAuthorizer {
+    /// Builds a new [`MockAuthorizer`] which approves every prompt with `password`.
+    #[inline]
+    pub fn new(password: SecretString) -> Self {
+        Self {
+            password,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Builds a new [`MockAuthorizer`] which approves every prompt with a random password.
+    #[inline]
+    pub fn random() -> Self {
+        Self::new(SecretString::new(format!("{:016x}", OsRng.next_u64())))
+    }
+
+    /// Approves every prompt only after `delay`, to simulate the time the user takes.
+    #[inline]
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl Authorizer for MockAuthorizer {
+    #[inline]
+    fn password(&mut self) -> PasswordFuture {
+        Box::pin(async move { Password::from_known(self.password.clone()) })
+    }
+
+    #[inline]
+    fn wake<T>(&mut self, prompt: &T) -> UnitFuture
+    where
+        T: Serialize,
+    {
+        let _ = prompt;
+        Box::pin(async move { task::sleep(self.delay).await })
+    }
+
+    #[inline]
+    fn confirm<T>(&mut self, prompt: &T) -> ConfirmationFuture
+    where
+        T: Serialize,
+    {
+        let _ = prompt;
+        Box::pin(async move {
+            task::sleep(self.delay).await;
+            true
+        })
+    }
+}
+
+/// Returns a free local port.
+#[inline]
+fn free_port() -> io::Result<u16> {
+    Ok(
+        TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?
+            .local_addr()?
+            .port(),
+    )
+}
+
+/// Test Service
+///
+/// The service runs until [`stop`](Self::stop) is called or the process exits. Its temporary
+/// directory is removed when it is stopped.
+pub struct TestService {
+    /// Service Configuration
+    config: Config,
+
+    /// Service Handle
+    handle: Handle,
+
+    /// Temporary Data Directory
+    directory: PathBuf,
+
+    /// Service Task
+    task: JoinHandle<service::Result<()>>,
+}
+
+impl TestService {
+    /// Starts a service with a fresh account in a temporary directory on a random local port,
+    /// approving every prompt with `authorizer`, and waits until it is ready.
+    #[inline]
+    pub async fn start<A>(authorizer: A) -> service::Result<Self>
+    where
+        A: Authorizer,
+    {
+        Self::start_with(authorizer, |_| {}).await
+    }
+
+    /// Starts a service like [`start`](Self::start) after applying `configure` to its
+    /// configuration.
+    #[inline]
+    pub async fn start_with<A, F>(authorizer: A, configure: F) -> service::Result<Self>
+    where
+        A: Authorizer,
+        F: FnOnce(&mut Config),
+    {
+        let directory =
+            std::env::temp_dir().join(format!("manta-signer-test-{:016x}", OsRng.next_u64()));
+        std::fs::create_dir_all(&directory)?;
+        link_parameter_cache(&directory);
+        let mut config = Config::try_default()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+        config.data_path = directory.join("storage.dat");
+        config.service_url = format!("127.0.0.1:{}", free_port()?);
+        config.prompt_expiry_minutes = 0;
+        config.lockout_threshold = 0;
+        configure(&mut config);
+        let handle = Handle::default();
+        let task = task::spawn(service::start_with_handle(
+            config.clone(),
+            authorizer,
+            handle.clone(),
+        ));
+        let version_url = format!("http://{}/version", config.service_url);
+        let ready = Box::pin(async move {
+            let startup = Instant::now();
+            while startup.elapsed() < STARTUP_TIMEOUT {
+                let version_url = version_url.clone();
+                if task::spawn_blocking(move || ureq::get(&version_url).call().is_ok()).await {
+                    return true;
+                }
+                task::sleep(READY_INTERVAL).await;
+            }
+            false
+        });
+        match future::select(task, ready).await {
+            Either::Left((result, _)) => {
+                let _ = std::fs::remove_dir_all(&directory);
+                result?;
+                Err(io::Error::new(io::ErrorKind::Other, "the service stopped").into())
+            }
+            Either::Right((true, task)) => Ok(Self {
+                config,
+                handle,
+                directory,
+                task,
+            }),
+            Either::Right((false, task)) => {
+                task.cancel().await;
+                let _ = std::fs::remove_dir_all(&directory);
+                Err(
+                    io::Error::new(io::ErrorKind::TimedOut, "the service did not start in time")
+                        .into(),
+                )
+            }
+        }
+    }
+
+    /// Returns the base URL of the service.
+    #[inline]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.config.service_url)
+    }
+
+    /// Returns the configuration of the service.
+    #[inline]
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the handle with the state the service shares with its frontend.
+    #[inline]
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Returns the temporary data directory of the service.
+    #[inline]
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Stops the service and removes its temporary directory.
+    #[inline]
+    pub async fn stop(self) {
+        self.task.cancel().await;
+        let _ = std::fs::remove_dir_all(&self.directory);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::secret::ExposeSecret;
+
+    /// Checks that the mock authorizer approves every prompt with its password.
+    #[async_std::test]
+    async fn mock_authorizer_approves() {
+        let mut authorizer = MockAuthorizer::new(SecretString::new("password".into()));
+        let password = authorizer
+            .password()
+            .await
+            .known()
+            .expect("The mock password is known.");
+        assert_eq!(password.expose_secret(), "password");
+        authorizer.wake(&"prompt").await;
+        assert!(authorizer.confirm(&"prompt").await);
+    }
+
+    /// Checks that the mock authorizer waits for the simulated user delay.
+    #[async_std::test]
+    async fn mock_authorizer_waits_for_the_delay() {
+        let delay = Duration::from_millis(50);
+        let mut authorizer = MockAuthorizer::random().with_delay(delay);
+        let start = Instant::now();
+        assert!(authorizer.confirm(&"prompt").await);
+        assert!(start.elapsed() >= delay);
+    }
+
+    /// Checks that the test service starts, serves its API and removes its directory once it is
+    /// stopped.
+    #[async_std::test]
+    #[ignore = "downloads the proving keys on the first run"]
+    async fn test_service_starts_and_stops() {
+        let service = TestService::start(MockAuthorizer::random())
+            .await
+            .expect("The test service starts.");
+        let directory = service.directory().to_owned();
+        assert!(directory.is_dir());
+        let version_url = format!("{}/version", service.url());
+        assert!(
+            task::spawn_blocking(move || ureq::get(&version_url).call().is_ok()).await,
+            "The service answers version requests."
+        );
+        service.stop().await;
+        assert!(!directory.exists());
+    }
+}
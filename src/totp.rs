@@ -0,0 +1,303 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Time-Based One-Time Approval Codes
+//!
+//! This module implements [RFC 6238](https://datatracker.ietf.org/doc/html/rfc6238) TOTP codes
+//! which can be used as a second factor to approve signing requests on a remote signer.
+
+use crate::secret::{ConstantTimeEq, ExposeSecret, SecretVec};
+use core::time::Duration;
+use hmac::{Hmac, Mac};
+use manta_crypto::rand::{CryptoRng, RngCore};
+use manta_util::serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use sha1::Sha1;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// Number of Digits in a TOTP Code
+pub const DIGITS: u32 = 6;
+
+/// TOTP Time Step in Seconds
+pub const TIME_STEP: u64 = 30;
+
+/// Number of Time Steps of Clock Skew Tolerated in Either Direction
+pub const SKEW: u64 = 1;
+
+/// TOTP Secret Length in Bytes
+pub const SECRET_LENGTH: usize = 20;
+
+/// Approval Timeout
+///
+/// Signing requests which are not approved with a valid TOTP code within this interval are
+/// rejected.
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Maximum Number of Wrong Codes per Signing Request
+///
+/// The pending signing request is rejected once this many wrong or replayed codes were submitted
+/// for it, so that the six digit code cannot be guessed within the [`APPROVAL_TIMEOUT`].
+pub const MAX_FAILED_CODES: u32 = 5;
+
+/// Returns the current UNIX time in seconds.
+#[inline]
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("The system clock is set before the UNIX epoch.")
+        .as_secs()
+}
+
+/// TOTP Secret
+pub struct TotpSecret(SecretVec<u8>);
+
+impl TotpSecret {
+    /// Builds a new [`TotpSecret`] from `bytes`.
+    #[inline]
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(SecretVec::new(bytes))
+    }
+
+    /// Samples a new [`TotpSecret`] from `rng`.
+    #[inline]
+    pub fn gen<R>(rng: &mut R) -> Self
+    where
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        let mut bytes = vec![0; SECRET_LENGTH];
+        rng.fill_bytes(&mut bytes);
+        Self::new(bytes)
+    }
+
+    /// Returns the underlying secret bytes.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.expose_secret()
+    }
+
+    /// Computes the HOTP code for the given time `step`.
+    #[inline]
+    pub fn code(&self, step: u64) -> u32 {
+        let mut mac = Hmac::<Sha1>::new_from_slice(self.as_bytes())
+            .expect("HMAC can take a key of any size.");
+        mac.update(&step.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        binary % 10u32.pow(DIGITS)
+    }
+
+    /// Verifies `code` against the codes around `unix_time`, returning the matching time step if
+    /// the code is valid.
+    #[inline]
+    pub fn verify(&self, code: &str, unix_time: u64) -> Option<u64> {
+        let code = code.trim();
+        let current = unix_time / TIME_STEP;
+        let mut matched = None;
+        for step in current.saturating_sub(SKEW)..=current + SKEW {
+            let expected = format!("{:0width$}", self.code(step), width = DIGITS as usize);
+            if bool::from(expected.as_bytes().ct_eq(code.as_bytes())) {
+                matched = Some(step);
+            }
+        }
+        matched
+    }
+
+    /// Returns the `otpauth://` provisioning URI for `account` which authenticator apps can
+    /// enroll, usually after rendering it as a QR code.
+    #[inline]
+    pub fn provisioning_uri(&self, account: &str) -> String {
+        format!(
+            "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+            issuer = crate::config::PATH_IDENTIFIER,
+            account = account,
+            secret = data_encoding::BASE32_NOPAD.encode(self.as_bytes()),
+            digits = DIGITS,
+            period = TIME_STEP,
+        )
+    }
+}
+
+/// Approval Request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct ApprovalRequest {
+    /// TOTP Code
+    pub code: String,
+}
+
+/// TOTP Approval Gate
+///
+/// Signing requests wait on this gate until a valid code is submitted with [`approve`] or the
+/// [`APPROVAL_TIMEOUT`] elapses. The pending request is rejected after [`MAX_FAILED_CODES`] wrong
+/// codes.
+///
+/// [`approve`]: Self::approve
+pub struct Approval {
+    /// TOTP Secret
+    secret: TotpSecret,
+
+    /// Pending Approval Sender
+    pending: Mutex<Option<oneshot::Sender<()>>>,
+
+    /// Last Accepted Time Step
+    ///
+    /// Codes from this time step or earlier are rejected to prevent replays.
+    last_step: Mutex<Option<u64>>,
+
+    /// Number of Wrong Codes Submitted for the Pending Request
+    failures: Mutex<u32>,
+}
+
+impl Approval {
+    /// Builds a new [`Approval`] gate from `secret`.
+    #[inline]
+    pub fn new(secret: TotpSecret) -> Self {
+        Self {
+            secret,
+            pending: Default::default(),
+            last_step: Default::default(),
+            failures: Default::default(),
+        }
+    }
+
+    /// Waits for a valid TOTP code to be submitted, returning `false` if the request timed out.
+    #[inline]
+    pub async fn wait(&self) -> bool {
+        let (sender, receiver) = oneshot::channel();
+        *self.failures.lock() = 0;
+        *self.pending.lock() = Some(sender);
+        let approved = matches!(
+            tokio::time::timeout(APPROVAL_TIMEOUT, receiver).await,
+            Ok(Ok(()))
+        );
+        if !approved {
+            self.pending.lock().take();
+        }
+        approved
+    }
+
+    /// Approves the pending request if `code` is valid, returning `true` if a request was
+    /// approved.
+    ///
+    /// Wrong and replayed codes count towards the [`MAX_FAILED_CODES`] of the pending request,
+    /// which is rejected once they are reached.
+    #[inline]
+    pub fn approve(&self, code: &str) -> bool {
+        let step = match self.secret.verify(code, unix_time()) {
+            Some(step) => step,
+            _ => {
+                self.fail();
+                return false;
+            }
+        };
+        let mut last_step = self.last_step.lock();
+        if matches!(*last_step, Some(last) if step <= last) {
+            self.fail();
+            return false;
+        }
+        match self.pending.lock().take() {
+            Some(sender) if sender.send(()).is_ok() => {
+                *last_step = Some(step);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Records a wrong code for the pending request, rejecting the request once
+    /// [`MAX_FAILED_CODES`] is reached.
+    #[inline]
+    fn fail(&self) {
+        let mut pending = self.pending.lock();
+        if pending.is_none() {
+            return;
+        }
+        let mut failures = self.failures.lock();
+        *failures += 1;
+        if *failures >= MAX_FAILED_CODES {
+            pending.take();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Shared Secret of the SHA-1 Test Vectors of RFC 6238
+    const RFC_6238_SECRET: &[u8] = b"12345678901234567890";
+
+    /// Checks the SHA-1 test vectors of RFC 6238, truncated to [`DIGITS`] digits.
+    #[test]
+    fn rfc_6238_test_vectors() {
+        let secret = TotpSecret::new(RFC_6238_SECRET.to_vec());
+        for (time, code) in [
+            (59, 287082),
+            (1111111109, 81804),
+            (1111111111, 50471),
+            (1234567890, 5924),
+            (2000000000, 279037),
+            (20000000000, 353130),
+        ] {
+            assert_eq!(secret.code(time / TIME_STEP), code, "time: {}", time);
+            assert_eq!(
+                secret.verify(&format!("{:06}", code), time),
+                Some(time / TIME_STEP),
+                "time: {}",
+                time
+            );
+        }
+    }
+
+    /// Checks that codes outside of the tolerated clock skew are rejected.
+    #[test]
+    fn rejects_codes_outside_of_skew() {
+        let secret = TotpSecret::new(RFC_6238_SECRET.to_vec());
+        let code = format!("{:06}", secret.code(59 / TIME_STEP));
+        assert_eq!(secret.verify(&code, 59 + TIME_STEP), Some(1));
+        assert_eq!(secret.verify(&code, 59 + 2 * TIME_STEP), None);
+    }
+
+    /// Checks that the pending request is rejected after [`MAX_FAILED_CODES`] wrong codes.
+    #[async_std::test]
+    async fn rejects_pending_request_after_wrong_codes() {
+        let approval = Arc::new(Approval::new(TotpSecret::new(RFC_6238_SECRET.to_vec())));
+        let waiting = async_std::task::spawn({
+            let approval = approval.clone();
+            async move { approval.wait().await }
+        });
+        while approval.pending.lock().is_none() {
+            async_std::task::yield_now().await;
+        }
+        let secret = TotpSecret::new(RFC_6238_SECRET.to_vec());
+        let code = secret.code(unix_time() / TIME_STEP);
+        let valid = format!("{:06}", code);
+        let wrong = format!("{:06}", (code + 1) % 10u32.pow(DIGITS));
+        for _ in 0..MAX_FAILED_CODES {
+            assert!(!approval.approve(&wrong));
+        }
+        assert!(!approval.approve(&valid));
+        assert!(!waiting.await);
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Service Transports
+//!
+//! The signer API is a [`tide::Server`] with the routes, the authorization and the signing
+//! pipeline of the service. A [`Transport`] only decides how requests reach it, so that new
+//! transports do not duplicate the pipeline. The [`HttpTransport`] serves it over TCP and the
+//! [`InProcessTransport`] lets an [`InProcessClient`] in the same process call it directly.
+
+use futures::future::BoxFuture;
+use http_types::{Request, Response};
+use std::{
+    io,
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+};
+use tokio::sync::watch;
+
+/// Service Transport
+pub trait Transport: 'static + Send {
+    /// Returns the socket addresses the transport listens at, which are published in the
+    /// [`Discovery`](crate::discovery::Discovery) file. By default, there are none and no
+    /// discovery file is written.
+    #[inline]
+    fn addresses(&self) -> Vec<SocketAddr> {
+        Vec::new()
+    }
+
+    /// Serves `api` until the transport is closed.
+    fn serve<S>(self, api: tide::Server<S>) -> BoxFuture<'static, io::Result<()>>
+    where
+        S: Clone + Send + Sync + 'static;
+}
+
+/// HTTP Transport
+pub struct HttpTransport {
+    /// Bound Listeners with their Addresses
+    listeners: Vec<(SocketAddr, TcpListener)>,
+}
+
+impl HttpTransport {
+    /// Builds a new [`HttpTransport`] which serves the API with the bound `listeners`.
+    #[inline]
+    pub fn new(listeners: Vec<(SocketAddr, TcpListener)>) -> Self {
+        Self { listeners }
+    }
+}
+
+impl Transport for HttpTransport {
+    #[inline]
+    fn addresses(&self) -> Vec<SocketAddr> {
+        self.listeners.iter().map(|(address, _)| *address).collect()
+    }
+
+    #[inline]
+    fn serve<S>(self, api: tide::Server<S>) -> BoxFuture<'static, io::Result<()>>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let listeners = self
+            .listeners
+            .into_iter()
+            .map(|(_, listener)| listener)
+            .collect::<Vec<_>>();
+        Box::pin(async move { api.listen(listeners).await })
+    }
+}
+
+/// Request Responder
+type Responder = Arc<dyn Fn(Request) -> BoxFuture<'static, Response> + Send + Sync>;
+
+/// Peer Address of In-Process Requests
+///
+/// In-process requests come from the local user, like requests from the loopback interface.
+pub const IN_PROCESS_PEER_ADDRESS: &str = "127.0.0.1:0";
+
+/// Builds a connected pair of an [`InProcessTransport`] for the service and an
+/// [`InProcessClient`] which sends requests to it.
+#[inline]
+pub fn in_process() -> (InProcessTransport, InProcessClient) {
+    let (sender, receiver) = watch::channel(None);
+    (InProcessTransport { sender }, InProcessClient { receiver })
+}
+
+/// In-Process Transport
+pub struct InProcessTransport {
+    /// Responder Sender
+    sender: watch::Sender<Option<Responder>>,
+}
+
+impl Transport for InProcessTransport {
+    #[inline]
+    fn serve<S>(self, api: tide::Server<S>) -> BoxFuture<'static, io::Result<()>>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let responder: Responder = Arc::new(move |request| {
+            let api = api.clone();
+            Box::pin(async move {
+                api.respond(request)
+                    .await
+                    .unwrap_or_else(|err| Response::new(err.status()))
+            })
+        });
+        Box::pin(async move {
+            if self.sender.send(Some(responder)).is_err() {
+                return Ok(());
+            }
+            self.sender.closed().await;
+            Ok(())
+        })
+    }
+}
+
+/// In-Process Client
+#[derive(Clone)]
+pub struct InProcessClient {
+    /// Responder Receiver
+    receiver: watch::Receiver<Option<Responder>>,
+}
+
+impl InProcessClient {
+    /// Sends `request` to the service, waiting until it is serving.
+    #[inline]
+    pub async fn send(&self, mut request: Request) -> io::Result<Response> {
+        request.set_peer_addr(Some(IN_PROCESS_PEER_ADDRESS));
+        let mut receiver = self.receiver.clone();
+        loop {
+            let responder = receiver.borrow().clone();
+            if let Some(responder) = responder {
+                return Ok(responder(request).await);
+            }
+            receiver
+                .changed()
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "the service stopped"))?;
+        }
+    }
+}
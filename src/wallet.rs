@@ -0,0 +1,148 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Wallet Container
+//!
+//! The signer can hold several wallets, each derived from its own recovery phrase, under one
+//! password. Every wallet has its own state file, synchronization status, balances, labels,
+//! approval grants, memos, proofs, audit log and polkadot.js compatible account, so accounts and
+//! history are isolated, but all of them are encrypted under the key of the
+//! [`DEFAULT_WALLET`] which is unlocked at login. The [`WalletRegistry`] records which wallets
+//! exist and which one is active.
+
+use manta_util::serde::{Deserialize, Serialize};
+use std::{io, path::Path};
+
+/// Default Wallet Name
+///
+/// The default wallet is the one stored at the configured data path.
+pub const DEFAULT_WALLET: &str = "default";
+
+/// Maximum Wallet Name Length
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// Returns `true` if `name` is a valid wallet name. Names are made of ASCII letters, digits, `-`
+/// and `_` since they are part of the wallet file names.
+#[inline]
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LENGTH).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Wallet Information
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct WalletInfo {
+    /// Wallet Name
+    pub name: String,
+
+    /// Creation Time as a UNIX Timestamp
+    pub created_at: i64,
+}
+
+/// Wallet Selection Request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct WalletRequest {
+    /// Wallet Name
+    pub name: String,
+}
+
+/// Wallet Registry
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_util::serde", deny_unknown_fields)]
+pub struct WalletRegistry {
+    /// Active Wallet Name
+    pub active: String,
+
+    /// Wallets in Creation Order
+    pub wallets: Vec<WalletInfo>,
+}
+
+impl Default for WalletRegistry {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_WALLET.into(),
+            wallets: vec![WalletInfo {
+                name: DEFAULT_WALLET.into(),
+                created_at: 0,
+            }],
+        }
+    }
+}
+
+impl WalletRegistry {
+    /// Loads the registry from `path`, returning the registry holding only the
+    /// [`DEFAULT_WALLET`] if it does not exist.
+    #[inline]
+    pub fn load<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the registry to `path` atomically.
+    #[inline]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        crate::storage::write_atomic(
+            path.as_ref(),
+            &serde_json::to_vec_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )
+    }
+
+    /// Returns `true` if the registry contains a wallet called `name`.
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.wallets.iter().any(|wallet| wallet.name == name)
+    }
+
+    /// Adds a new wallet called `name`, returning `false` if the name is invalid or taken.
+    #[inline]
+    pub fn add(&mut self, name: String) -> bool {
+        if !is_valid_name(&name) || self.contains(&name) {
+            return false;
+        }
+        self.wallets.push(WalletInfo {
+            name,
+            created_at: chrono::offset::Utc::now().timestamp(),
+        });
+        true
+    }
+
+    /// Makes the wallet called `name` active, returning `false` if it does not exist.
+    #[inline]
+    pub fn set_active(&mut self, name: &str) -> bool {
+        if !self.contains(name) {
+            return false;
+        }
+        self.active = name.into();
+        true
+    }
+}
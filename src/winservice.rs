@@ -0,0 +1,176 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Windows Service
+//!
+//! The signer can run as a Windows service under a managed account, without an interactive
+//! session. The service runs the [`daemon`], which starts locked and is controlled over its
+//! control pipe with `manta-signer ctl`, and reports its warnings and errors to the Windows
+//! event log under the [`SERVICE_NAME`] source.
+//!
+//! The service is registered with the service control manager to run `manta-signer service`,
+//! for example with:
+//!
+//! ```text
+//! sc.exe create manta-signer binPath= "C:\Program Files\manta-signer\manta-signer.exe service" obj= "NT SERVICE\manta-signer"
+//! New-EventLog -LogName Application -Source manta-signer
+//! ```
+
+use crate::{
+    config::Config,
+    daemon,
+    log::{self, Level},
+};
+use async_std::task;
+use core::time::Duration;
+use futures::future::{self, Either};
+use std::{ffi::OsString, sync::Arc};
+use tokio::sync::Notify;
+use windows::{
+    core::{HSTRING, PCWSTR},
+    Win32::{
+        Foundation::PSID,
+        System::EventLog::{
+            DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+            EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+        },
+    },
+};
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+};
+
+/// Service Name
+///
+/// This is also the event log source of the service.
+pub const SERVICE_NAME: &str = "manta-signer";
+
+/// Reports `message` with the given `level` to the Windows event log. Trace messages are not
+/// reported.
+#[inline]
+pub fn report(level: Level, message: &str) {
+    let kind = match level {
+        Level::Trace | Level::Debug => return,
+        Level::Info => EVENTLOG_INFORMATION_TYPE,
+        Level::Warn => EVENTLOG_WARNING_TYPE,
+        Level::Error => EVENTLOG_ERROR_TYPE,
+    };
+    let source = HSTRING::from(SERVICE_NAME);
+    let message = HSTRING::from(message);
+    unsafe {
+        if let Ok(handle) = RegisterEventSourceW(PCWSTR::null(), &source) {
+            ReportEventW(
+                handle,
+                kind,
+                0,
+                0,
+                PSID::default(),
+                0,
+                Some(&[PCWSTR(message.as_ptr())]),
+                None,
+            );
+            DeregisterEventSource(handle);
+        }
+    }
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Runs the service, returning once the service control manager stopped it.
+///
+/// This must be called by the process which the service control manager started.
+#[inline]
+pub fn run() -> windows_service::Result<()> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+/// Runs the service, ignoring the arguments from the service control manager.
+#[inline]
+fn service_main(_: Vec<OsString>) {
+    if let Err(err) = run_service() {
+        report(
+            Level::Error,
+            &format!("unable to run the signer service: {}", err),
+        );
+    }
+}
+
+/// Returns the status of the service in `state` with the given `exit_code`.
+#[inline]
+fn status(state: ServiceState, exit_code: u32) -> ServiceStatus {
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: if state == ServiceState::Running {
+            ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+        } else {
+            ServiceControlAccept::empty()
+        },
+        exit_code: ServiceExitCode::Win32(exit_code),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    }
+}
+
+/// Runs the daemon until the service control manager stops the service.
+#[inline]
+fn run_service() -> windows_service::Result<()> {
+    log::set_sink(|level, line| {
+        if level >= Level::Warn {
+            report(level, line)
+        }
+    });
+    let stop = Arc::new(Notify::new());
+    let stop_signal = stop.clone();
+    let status_handle =
+        service_control_handler::register(SERVICE_NAME, move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                stop_signal.notify_one();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })?;
+    status_handle.set_service_status(status(ServiceState::Running, 0))?;
+    report(Level::Info, "The signer service started locked.");
+    let result = match Config::load_default() {
+        Ok(config) => task::block_on(async move {
+            match future::select(Box::pin(daemon::run(config)), Box::pin(stop.notified())).await {
+                Either::Left((result, _)) => result.map_err(|err| format!("{:?}", err)),
+                Either::Right(_) => Ok(()),
+            }
+        }),
+        Err(err) => Err(format!("unable to load the configuration: {}", err)),
+    };
+    let exit_code = match result {
+        Ok(()) => {
+            report(Level::Info, "The signer service stopped.");
+            0
+        }
+        Err(err) => {
+            report(Level::Error, &format!("The signer service failed: {}", err));
+            1
+        }
+    };
+    status_handle.set_service_status(status(ServiceState::Stopped, exit_code))
+}
@@ -15,5 +15,6 @@
 // along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
 
 fn main() {
-    tauri_build::build()
+    #[cfg(feature = "gui")]
+    tauri_build::build();
 }
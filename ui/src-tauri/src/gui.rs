@@ -0,0 +1,1778 @@
+// Copyright 2019-2022 Manta Network.
+// This file is part of manta-signer.
+//
+// manta-signer is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// manta-signer is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
+
+//! Desktop UI
+
+use alloc::sync::Arc;
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use manta_signer::{
+    audit::{self, ExportFormat, ExportRange},
+    balance::BalanceReport,
+    config::{Config, Recovery, RecoveryChoice, Setup},
+    deeplink, diagnostics, discovery,
+    error::SignerError,
+    exemption::{Exemption, RememberChoice},
+    fee::FeeTier,
+    frontend::{ApprovalChoices, BackupQuizStore, PasswordRelay, PasswordStore},
+    label::{LabelTarget, Labels},
+    log::{self, Level},
+    memo::{self, HistoryEntry, HistoryQuery},
+    mnemonic::{self, MnemonicReport},
+    pairing::PairingOffer,
+    palette::{self, CommandId, CommandMatch},
+    password::PasswordStrength,
+    proof,
+    secret::{
+        read_keyfile, Authorizer, ConfirmationFuture, ExposeSecret, FeeTierFuture, MemoFuture,
+        PasswordFuture, RecoveryFuture, RememberFuture, Secret, SecretString, UnitFuture,
+    },
+    serde::{Deserialize, Serialize},
+    queue::PendingRequest,
+    service::{self, Handle},
+    session::Session,
+    settings::{ImportReport, Settings},
+    status::{SignerState, SignerStatus},
+    storage,
+    substrate::PublicAddress,
+    sync::{SyncProgress, PROGRESS_INTERVAL},
+    telemetry::TelemetryConfig,
+};
+use qrcode::{render::svg, QrCode};
+use serde_json::{Map, Value};
+use std::{io, path::PathBuf, sync::RwLock};
+use tauri::{
+    async_runtime::{channel, spawn, Mutex, Receiver, Sender},
+    AppHandle, ClipboardManager, CustomMenuItem, GlobalShortcutManager, Icon, Manager, RunEvent,
+    State, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTraySubmenu, Window, WindowBuilder,
+    WindowEvent, WindowUrl,
+};
+
+/// Main Window Label
+const MAIN_WINDOW: &str = "main";
+
+/// Approval Popup Window Label
+const APPROVAL_WINDOW: &str = "approval";
+
+/// Approval Popup Window Size
+const APPROVAL_WINDOW_SIZE: (f64, f64) = (360.0, 280.0);
+
+/// About Window Label
+const ABOUT_WINDOW: &str = "about";
+
+/// Tray Menu Item Identifier Prefix of the Log Levels
+const LOG_LEVEL_ITEM_PREFIX: &str = "log-level-";
+
+/// Tray Menu Item Identifier of the Signer Status
+const STATUS_ITEM: &str = "status";
+
+/// Tray Menu Item Identifier Prefix of the Pending Requests
+const PENDING_ITEM_PREFIX: &str = "pending-";
+
+/// Maximum Length in Characters of the Pending Request Previews in the Tray Menu
+const PENDING_ITEM_LENGTH: usize = 48;
+
+/// Default Global Shortcut which Summons the Main Window
+const DEFAULT_SUMMON_SHORTCUT: &str = "CmdOrCtrl+Shift+M";
+
+/// Prompt Route
+///
+/// Setup, recovery, and enrollment prompts are always shown in the main window. Authorization
+/// prompts are shown in the window selected by the route. Paired mobile approvers receive their
+/// prompts from the signer service independently of the route.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_signer::serde", deny_unknown_fields)]
+pub enum PromptRoute {
+    /// Main Window
+    Main,
+
+    /// Compact Always-on-Top Approval Popup
+    Popup,
+}
+
+impl Default for PromptRoute {
+    #[inline]
+    fn default() -> Self {
+        Self::Main
+    }
+}
+
+/// UI Preferences
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(crate = "manta_signer::serde", deny_unknown_fields)]
+pub struct Preferences {
+    /// Authorization Prompt Route
+    #[serde(default)]
+    pub prompt_route: PromptRoute,
+
+    /// Global Shortcut which Summons the Main Window
+    ///
+    /// This is an accelerator such as `CmdOrCtrl+Shift+M`, or `None` to disable the shortcut.
+    #[serde(default = "Preferences::default_summon_shortcut")]
+    pub summon_shortcut: Option<String>,
+
+    /// Start Minimized Flag
+    ///
+    /// When enabled, the app starts hidden in the tray. The main window is still shown when the
+    /// signer needs a new account or a recovery decision.
+    #[serde(default)]
+    pub start_minimized: bool,
+
+    /// Minimize to Tray Flag
+    ///
+    /// When enabled, closing the main window hides it in the tray instead of quitting the app.
+    #[serde(default)]
+    pub close_to_tray: bool,
+
+    /// Privacy Screen Flag
+    ///
+    /// When enabled, windows obscure the prompts and the recovery phrase while they are not
+    /// focused.
+    #[serde(default)]
+    pub privacy_screen: bool,
+}
+
+impl Default for Preferences {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            prompt_route: Default::default(),
+            summon_shortcut: Self::default_summon_shortcut(),
+            start_minimized: false,
+            close_to_tray: false,
+            privacy_screen: false,
+        }
+    }
+}
+
+impl Preferences {
+    /// Returns the default value of [`summon_shortcut`](Self::summon_shortcut) for
+    /// deserialization.
+    #[inline]
+    fn default_summon_shortcut() -> Option<String> {
+        Some(DEFAULT_SUMMON_SHORTCUT.into())
+    }
+
+    /// Returns the path of the preferences file for `config`.
+    #[inline]
+    pub fn path(config: &Config) -> PathBuf {
+        config.data_directory().join("ui.json")
+    }
+
+    /// Loads the preferences from `path`, returning the defaults if they do not exist or are
+    /// malformed.
+    #[inline]
+    pub fn load(path: &PathBuf) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the preferences to `path` atomically.
+    #[inline]
+    pub fn save(&self, path: &PathBuf) -> io::Result<()> {
+        storage::write_atomic(
+            path,
+            &serde_json::to_vec_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            0,
+        )
+    }
+}
+
+/// Shared UI Preferences
+#[derive(Clone, Default)]
+pub struct PreferenceStore(Arc<RwLock<Preferences>>);
+
+impl PreferenceStore {
+    /// Returns a copy of the current preferences.
+    #[inline]
+    pub fn get(&self) -> Preferences {
+        self.0.read().expect("Preference lock is poisoned.").clone()
+    }
+
+    /// Returns the current prompt route.
+    #[inline]
+    pub fn prompt_route(&self) -> PromptRoute {
+        self.0
+            .read()
+            .expect("Preference lock is poisoned.")
+            .prompt_route
+    }
+
+    /// Changes the preferences with `update` and saves them for `config`.
+    #[inline]
+    pub fn update<F>(&self, config: &Config, update: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Preferences),
+    {
+        let updated = {
+            let mut preferences = self.0.write().expect("Preference lock is poisoned.");
+            update(&mut preferences);
+            preferences.clone()
+        };
+        updated.save(&Preferences::path(config))
+    }
+}
+
+/// User
+pub struct User {
+    /// Application Handle
+    app: AppHandle,
+
+    /// UI Preferences
+    preferences: PreferenceStore,
+
+    /// Password Relay
+    password: PasswordRelay,
+
+    /// Recovery Choice Receiver
+    recovery: Receiver<RecoveryChoice>,
+
+    /// Confirmation Answer Receiver
+    confirmation: Receiver<bool>,
+
+    /// TOTP Enrollment Acknowledgement Receiver
+    enrollment: Receiver<()>,
+}
+
+impl User {
+    /// Builds a new [`User`] from `app`, `preferences`, `password`, `recovery`, `confirmation`,
+    /// and `enrollment`.
+    #[inline]
+    pub fn new(
+        app: AppHandle,
+        preferences: PreferenceStore,
+        password: PasswordRelay,
+        recovery: Receiver<RecoveryChoice>,
+        confirmation: Receiver<bool>,
+        enrollment: Receiver<()>,
+    ) -> Self {
+        Self {
+            app,
+            preferences,
+            password,
+            recovery,
+            confirmation,
+            enrollment,
+        }
+    }
+
+    /// Returns the window with the given `label`.
+    #[inline]
+    fn window(&self, label: &str) -> Window {
+        self.app
+            .get_window(label)
+            .expect("All signer windows are declared in the Tauri configuration.")
+    }
+
+    /// Shows the authorization `prompt` with the `event` in the window selected by the prompt
+    /// route.
+    #[inline]
+    fn show_prompt<T>(&self, event: &'static str, prompt: &T)
+    where
+        T: Serialize,
+    {
+        self.app.state::<ApprovalChoices>().clear();
+        match self.preferences.prompt_route() {
+            PromptRoute::Main => {
+                let window = self.window(MAIN_WINDOW);
+                window.emit(event, prompt).unwrap();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            PromptRoute::Popup => {
+                let _ = self
+                    .app
+                    .state::<ApprovalPopup>()
+                    .open(&self.app, event, prompt);
+            }
+        }
+    }
+
+    /// Closes the approval popup once its prompt is answered.
+    #[inline]
+    fn close_popup(&self) {
+        let _ = self.app.state::<ApprovalPopup>().close(&self.app);
+    }
+
+    /// Emits a `message` of the given `kind` to the main window.
+    #[inline]
+    fn emit<T>(&self, kind: &'static str, message: &T)
+    where
+        T: Serialize,
+    {
+        self.window(MAIN_WINDOW).emit(kind, message).unwrap()
+    }
+}
+
+impl Authorizer for User {
+    #[inline]
+    fn password(&mut self) -> PasswordFuture {
+        Box::pin(async move {
+            let password = self.password.request().await;
+            if !password.is_known() {
+                self.close_popup();
+            }
+            password
+        })
+    }
+
+    #[inline]
+    fn setup<'s>(&'s mut self, setup: &'s Setup) -> UnitFuture<'s> {
+        self.emit("connect", setup);
+        if !matches!(setup, Setup::Login) {
+            let _ = self.window(MAIN_WINDOW).show();
+        }
+        Box::pin(async move {
+            if let Setup::CreateAccount(mnemonic) = setup {
+                self.app.state::<BackupQuizStore>().start(mnemonic).await;
+            }
+        })
+    }
+
+    #[inline]
+    fn wake<T>(&mut self, prompt: &T) -> UnitFuture
+    where
+        T: Serialize,
+    {
+        self.show_prompt("authorize", prompt);
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn confirm<T>(&mut self, prompt: &T) -> ConfirmationFuture
+    where
+        T: Serialize,
+    {
+        while self.confirmation.try_recv().is_ok() {}
+        self.show_prompt("confirm", prompt);
+        Box::pin(async move {
+            let approved = self.confirmation.recv().await.unwrap_or(false);
+            self.close_popup();
+            approved
+        })
+    }
+
+    #[inline]
+    fn recover<'s>(&'s mut self, recovery: &'s Recovery) -> RecoveryFuture<'s> {
+        self.emit("recover", recovery);
+        let _ = self.window(MAIN_WINDOW).show();
+        Box::pin(async move {
+            self.recovery
+                .recv()
+                .await
+                .unwrap_or(RecoveryChoice::Abort)
+        })
+    }
+
+    #[inline]
+    fn enroll_totp<'s>(&'s mut self, uri: &'s str) -> UnitFuture<'s> {
+        while self.enrollment.try_recv().is_ok() {}
+        self.emit("enroll_totp", &uri);
+        let _ = self.window(MAIN_WINDOW).show();
+        Box::pin(async move {
+            let _ = self.enrollment.recv().await;
+        })
+    }
+
+    #[inline]
+    fn show_pairing<'s>(&'s mut self, offer: &'s PairingOffer) -> UnitFuture<'s> {
+        Box::pin(async move {
+            match QrCode::new(offer.uri.as_bytes()) {
+                Ok(code) => {
+                    self.emit(
+                        "pairing_offer",
+                        &PairingCode {
+                            svg: code.render::<svg::Color>().min_dimensions(240, 240).build(),
+                            expires_at: offer.expires_at,
+                        },
+                    );
+                    let _ = self.window(MAIN_WINDOW).show();
+                }
+                Err(err) => {
+                    let _ = log::log(
+                        &mut log::stdout(),
+                        Level::Warn,
+                        format!("unable to render the pairing offer: {}", err),
+                    )
+                    .await;
+                }
+            }
+        })
+    }
+
+    #[inline]
+    fn cooldown(&mut self, remaining: Duration) -> UnitFuture {
+        let seconds = (remaining.as_millis() as u64 + 999) / 1000;
+        self.app.emit_all("cooldown", seconds).unwrap();
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn memo(&mut self) -> MemoFuture {
+        let memo = self.app.state::<ApprovalChoices>().take_memo();
+        Box::pin(async move { memo })
+    }
+
+    #[inline]
+    fn fee_tier(&mut self) -> FeeTierFuture {
+        let tier = self.app.state::<ApprovalChoices>().take_fee_tier();
+        Box::pin(async move { tier })
+    }
+
+    #[inline]
+    fn remember(&mut self) -> RememberFuture {
+        let choice = self.app.state::<ApprovalChoices>().take_remember();
+        Box::pin(async move { choice })
+    }
+
+    #[inline]
+    fn dismiss(&mut self) -> UnitFuture {
+        self.password.dismiss();
+        match self.preferences.prompt_route() {
+            PromptRoute::Main => {
+                let window = self.window(MAIN_WINDOW);
+                window.emit("dismiss", ()).unwrap();
+                let _ = window.hide();
+            }
+            PromptRoute::Popup => self.close_popup(),
+        }
+        Box::pin(async move {})
+    }
+
+    #[inline]
+    fn sleep(&mut self) -> UnitFuture {
+        Box::pin(async move {
+            self.password.validate().await;
+            self.close_popup();
+        })
+    }
+}
+
+/// Pairing QR Code
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(crate = "manta_signer::serde", deny_unknown_fields)]
+pub struct PairingCode {
+    /// SVG Rendering of the Pairing URI
+    pub svg: String,
+
+    /// Expiration Time of the Pairing Offer as a UNIX Timestamp
+    pub expires_at: i64,
+}
+
+/// Approval Popup Prompt
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(crate = "manta_signer::serde", deny_unknown_fields)]
+pub struct PopupPrompt {
+    /// Prompt Event
+    ///
+    /// This is `authorize` when the password is requested and `confirm` otherwise.
+    pub event: String,
+
+    /// Authorization Prompt
+    pub prompt: serde_json::Value,
+}
+
+/// Approval Popup
+///
+/// The popup is an always-on-top window which is created for every authorization prompt routed
+/// to it, and closed once the prompt is answered or dismissed. Since the prompt is shown before
+/// the new window listens for events, the window fetches it with [`current_prompt`].
+#[derive(Default)]
+pub struct ApprovalPopup {
+    /// Current Prompt
+    prompt: std::sync::Mutex<Option<PopupPrompt>>,
+
+    /// Closing Flag
+    ///
+    /// This is set while the signer closes the popup, so that closing it does not reject the
+    /// next prompt like the user closing it does.
+    closing: AtomicBool,
+}
+
+impl ApprovalPopup {
+    /// Shows `prompt` with the `event` in the popup, creating the popup if it is not open.
+    #[inline]
+    fn open<T>(&self, app: &AppHandle, event: &str, prompt: &T) -> tauri::Result<()>
+    where
+        T: Serialize,
+    {
+        let popup_prompt = PopupPrompt {
+            event: event.into(),
+            prompt: serde_json::to_value(prompt)?,
+        };
+        *self.prompt.lock().expect("Popup lock is poisoned.") = Some(popup_prompt);
+        let window = match app.get_window(APPROVAL_WINDOW) {
+            Some(window) => {
+                window.emit(event, prompt)?;
+                window
+            }
+            _ => {
+                self.closing.store(false, Ordering::SeqCst);
+                WindowBuilder::new(app, APPROVAL_WINDOW, WindowUrl::App("index.html".into()))
+                    .title("Manta Signer Approval")
+                    .inner_size(APPROVAL_WINDOW_SIZE.0, APPROVAL_WINDOW_SIZE.1)
+                    .always_on_top(true)
+                    .center()
+                    .resizable(false)
+                    .skip_taskbar(true)
+                    .build()?
+            }
+        };
+        window.show()?;
+        window.set_focus()
+    }
+
+    /// Closes the popup if it is open.
+    #[inline]
+    fn close(&self, app: &AppHandle) -> tauri::Result<()> {
+        self.prompt.lock().expect("Popup lock is poisoned.").take();
+        match app.get_window(APPROVAL_WINDOW) {
+            Some(window) => {
+                self.closing.store(true, Ordering::SeqCst);
+                window.close()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the signer is closing the popup, resetting the closing flag.
+    #[inline]
+    fn take_closing(&self) -> bool {
+        self.closing.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Returns the prompt of the approval popup.
+#[tauri::command]
+async fn current_prompt(
+    popup: State<'_, ApprovalPopup>,
+) -> Result<Option<PopupPrompt>, SignerError> {
+    Ok(popup
+        .prompt
+        .lock()
+        .expect("Popup lock is poisoned.")
+        .clone())
+}
+
+/// Recovery Choice Storage
+pub struct RecoveryStore(Sender<RecoveryChoice>);
+
+/// Confirmation Answer Storage
+pub struct ConfirmationStore(Sender<bool>);
+
+/// TOTP Enrollment Acknowledgement Storage
+pub struct EnrollmentStore(Sender<()>);
+
+/// Default Time in Seconds before Sensitive Clipboard Contents are Cleared
+const DEFAULT_CLIPBOARD_TTL: u64 = 30;
+
+/// Maximum Time in Seconds before Sensitive Clipboard Contents are Cleared
+const MAX_CLIPBOARD_TTL: u64 = 300;
+
+/// Sensitive Clipboard Contents
+///
+/// Holds the last sensitive value copied by the signer so that it can be cleared from the
+/// clipboard later, unless the user has copied something else in the meantime.
+#[derive(Clone, Default)]
+pub struct SensitiveClipboard(Arc<Mutex<Option<SecretString>>>);
+
+impl SensitiveClipboard {
+    /// Copies `value` to the clipboard and clears it after `ttl`.
+    #[inline]
+    pub async fn copy(
+        &self,
+        app: AppHandle,
+        value: String,
+        ttl: Duration,
+    ) -> Result<(), SignerError> {
+        app.clipboard_manager()
+            .write_text(value.clone())
+            .map_err(SignerError::internal)?;
+        *self.0.lock().await = Some(Secret::new(value));
+        let clipboard = self.clone();
+        spawn(async move {
+            tauri::async_runtime::spawn_blocking(move || std::thread::sleep(ttl))
+                .await
+                .ok();
+            clipboard.clear(&app).await;
+        });
+        Ok(())
+    }
+
+    /// Clears the clipboard if it still holds the last sensitive value.
+    #[inline]
+    pub async fn clear(&self, app: &AppHandle) {
+        if let Some(value) = self.0.lock().await.take() {
+            let mut clipboard = app.clipboard_manager();
+            if matches!(clipboard.read_text(), Ok(Some(text)) if text == *value.expose_secret()) {
+                let _ = clipboard.write_text(String::new());
+            }
+        }
+    }
+}
+
+/// Sends the current `password` and the optional keyfile at the `keyfile` path into storage from
+/// the UI.
+#[tauri::command]
+async fn send_password(
+    password_store: State<'_, PasswordStore>,
+    password: String,
+    keyfile: Option<PathBuf>,
+) -> Result<bool, SignerError> {
+    let keyfile = match keyfile {
+        Some(path) => Some(
+            tauri::async_runtime::spawn_blocking(move || read_keyfile(path))
+                .await
+                .map_err(SignerError::internal)??,
+        ),
+        _ => None,
+    };
+    Ok(password_store.load(Secret::new(password), keyfile).await)
+}
+
+/// Copies the sensitive `value` to the clipboard, clearing it after `ttl` seconds or when the
+/// signer is locked or closed.
+#[tauri::command]
+async fn copy_sensitive(
+    app: AppHandle,
+    clipboard: State<'_, SensitiveClipboard>,
+    value: String,
+    ttl: Option<u64>,
+) -> Result<(), SignerError> {
+    let ttl = ttl.unwrap_or(DEFAULT_CLIPBOARD_TTL).min(MAX_CLIPBOARD_TTL);
+    clipboard.copy(app, value, Duration::from_secs(ttl)).await
+}
+
+/// Excludes `window` from screen captures and recordings if `enabled` is `true`, and includes it
+/// again otherwise.
+///
+/// This is unsupported on other platforms than Windows and macOS.
+#[inline]
+fn set_window_capture_protection(window: &Window, enabled: bool) -> Result<(), SignerError> {
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::{
+            Foundation::HWND,
+            UI::WindowsAndMessaging::{SetWindowDisplayAffinity, WDA_EXCLUDEFROMCAPTURE, WDA_NONE},
+        };
+        let hwnd = HWND(window.hwnd().map_err(SignerError::internal)?.0);
+        let affinity = if enabled {
+            WDA_EXCLUDEFROMCAPTURE
+        } else {
+            WDA_NONE
+        };
+        unsafe { SetWindowDisplayAffinity(hwnd, affinity) }
+            .ok()
+            .map_err(SignerError::internal)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use objc::{msg_send, runtime::Object, sel, sel_impl};
+        /// `NSWindowSharingNone`
+        const SHARING_NONE: u64 = 0;
+        /// `NSWindowSharingReadOnly`
+        const SHARING_READ_ONLY: u64 = 1;
+        let ns_window = window.ns_window().map_err(SignerError::internal)? as *mut Object;
+        let sharing_type = if enabled {
+            SHARING_NONE
+        } else {
+            SHARING_READ_ONLY
+        };
+        unsafe {
+            let _: () = msg_send![ns_window, setSharingType: sharing_type];
+        }
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let _ = (window, enabled);
+        Err(SignerError::Unsupported(
+            "screen capture protection is not supported on this platform".into(),
+        ))
+    }
+}
+
+/// Excludes the calling `window` from screen captures while sensitive values like the recovery
+/// phrase are displayed if `enabled` is `true`, and includes it again otherwise.
+#[tauri::command]
+async fn set_capture_protection(window: Window, enabled: bool) -> Result<(), SignerError> {
+    set_window_capture_protection(&window, enabled)
+}
+
+/// Returns the positions of the words of the new recovery phrase which the user has to enter,
+/// starting at zero.
+#[tauri::command]
+async fn backup_quiz_indices(
+    quiz: State<'_, BackupQuizStore>,
+) -> Result<Vec<usize>, SignerError> {
+    quiz.indices()
+        .await
+        .ok_or_else(|| SignerError::NotFound("there is no new recovery phrase to verify".into()))
+}
+
+/// Checks the `words` entered by the user against the asked words of the new recovery phrase,
+/// marking the account as backed up if they match.
+#[tauri::command]
+async fn verify_mnemonic_words(
+    config: State<'_, Config>,
+    quiz: State<'_, BackupQuizStore>,
+    words: Vec<String>,
+) -> Result<bool, SignerError> {
+    quiz.verify(config.backup_status_path(), &words)
+        .await?
+        .ok_or_else(|| SignerError::NotFound("there is no new recovery phrase to verify".into()))
+}
+
+/// Checks the word count, the words and the checksum of the recovery `phrase` the user is
+/// entering, reporting the position of every unknown word.
+#[tauri::command]
+async fn validate_mnemonic(phrase: String) -> Result<MnemonicReport, SignerError> {
+    Ok(mnemonic::validate(&phrase))
+}
+
+/// Sets the `memo` which is attached to the transaction the user is about to authorize.
+#[tauri::command]
+async fn set_approval_memo(
+    choices: State<'_, ApprovalChoices>,
+    memo: Option<String>,
+) -> Result<(), SignerError> {
+    choices.set_memo(memo.as_deref());
+    Ok(())
+}
+
+/// Sets the network fee `tier` of the transaction the user is about to authorize.
+#[tauri::command]
+async fn set_fee_tier(
+    choices: State<'_, ApprovalChoices>,
+    tier: Option<FeeTier>,
+) -> Result<(), SignerError> {
+    choices.set_fee_tier(tier);
+    Ok(())
+}
+
+/// Sets the `choice` to remember the decision on the transaction the user is about to authorize.
+#[tauri::command]
+async fn set_remember_choice(
+    choices: State<'_, ApprovalChoices>,
+    choice: Option<RememberChoice>,
+) -> Result<(), SignerError> {
+    choices.set_remember(choice);
+    Ok(())
+}
+
+/// Returns the signing history which matches `query`, newest first, with the memos and asset
+/// labels of its transactions.
+#[tauri::command]
+async fn get_history(
+    config: State<'_, Config>,
+    handle: State<'_, Handle>,
+    query: HistoryQuery,
+) -> Result<Vec<HistoryEntry>, SignerError> {
+    handle.ensure_unlocked()?;
+    let audit_log_path = config.audit_log_path(&handle.active_wallet());
+    let handle = handle.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        memo::history(audit_log_path, &query, &handle.labels.get(), &handle.memos)
+    })
+    .await
+    .map_err(SignerError::internal)?
+    .map_err(SignerError::from)
+}
+
+/// Returns the shielded balance of every asset and the public balance of the native asset.
+#[tauri::command]
+async fn get_balances(
+    config: State<'_, Config>,
+    handle: State<'_, Handle>,
+) -> Result<BalanceReport, SignerError> {
+    handle.ensure_unlocked()?;
+    let node_url = config.node_url.clone();
+    let balances = handle.balances.clone();
+    tauri::async_runtime::spawn_blocking(move || balances.report(node_url))
+        .await
+        .map_err(SignerError::internal)
+}
+
+/// Returns the public address which receives public tokens, for example from exchanges.
+///
+/// There is no public address while the signer is locked or if its polkadot.js account was not
+/// derived from the recovery phrase.
+#[tauri::command]
+async fn get_public_address(handle: State<'_, Handle>) -> Result<PublicAddress, SignerError> {
+    handle
+        .public_address
+        .read()
+        .clone()
+        .ok_or(SignerError::Locked)
+}
+
+/// Proves `rounds` sample transfers of every circuit and reports the timings, using
+/// [`DEFAULT_ROUNDS`](manta_signer::benchmark::DEFAULT_ROUNDS) if `rounds` is missing.
+#[cfg(feature = "benchmark")]
+#[tauri::command]
+async fn benchmark_proving(
+    config: State<'_, Config>,
+    rounds: Option<usize>,
+) -> Result<manta_signer::benchmark::BenchmarkReport, SignerError> {
+    use manta_signer::benchmark;
+    let data_directory = config.data_directory().to_owned();
+    tauri::async_runtime::spawn_blocking(move || {
+        benchmark::run(data_directory, rounds.unwrap_or(benchmark::DEFAULT_ROUNDS))
+    })
+    .await
+    .map_err(SignerError::internal)?
+    .map_err(SignerError::from)
+}
+
+/// Reports that the proving benchmark is not part of this build.
+#[cfg(not(feature = "benchmark"))]
+#[tauri::command]
+async fn benchmark_proving(rounds: Option<usize>) -> Result<(), SignerError> {
+    let _ = rounds;
+    Err(SignerError::Unsupported(
+        "the proving benchmark is only available in builds with the `benchmark` feature".into(),
+    ))
+}
+
+/// Evaluates the strength of a new account `password`.
+#[tauri::command]
+async fn check_password_strength(password: String) -> Result<PasswordStrength, SignerError> {
+    Ok(PasswordStrength::evaluate(&password, &["manta", "signer"]))
+}
+
+/// Stops the server from prompting for the password.
+#[tauri::command]
+async fn stop_password_prompt(password_store: State<'_, PasswordStore>) -> Result<(), SignerError> {
+    password_store.clear().await;
+    Ok(())
+}
+
+/// Sends the recovery `choice` made in the UI to the service.
+#[tauri::command]
+async fn send_recovery_choice(
+    recovery_store: State<'_, RecoveryStore>,
+    choice: RecoveryChoice,
+) -> Result<(), SignerError> {
+    recovery_store
+        .0
+        .send(choice)
+        .await
+        .map_err(SignerError::internal)
+}
+
+/// Tells the service that the user has enrolled the TOTP secret into their authenticator app.
+#[tauri::command]
+async fn acknowledge_totp_enrollment(
+    enrollment_store: State<'_, EnrollmentStore>,
+) -> Result<(), SignerError> {
+    enrollment_store
+        .0
+        .send(())
+        .await
+        .map_err(SignerError::internal)
+}
+
+/// Sends the answer of the user to the pending confirmation prompt to the service.
+#[tauri::command]
+async fn send_confirmation(
+    confirmation_store: State<'_, ConfirmationStore>,
+    approved: bool,
+) -> Result<(), SignerError> {
+    confirmation_store
+        .0
+        .send(approved)
+        .await
+        .map_err(SignerError::internal)
+}
+
+/// Returns the current UI preferences.
+#[tauri::command]
+async fn get_preferences(
+    preferences: State<'_, PreferenceStore>,
+) -> Result<Preferences, SignerError> {
+    Ok(preferences.get())
+}
+
+/// Routes future authorization prompts along `route`, saving the preference.
+#[tauri::command]
+async fn set_prompt_route(
+    config: State<'_, Config>,
+    preferences: State<'_, PreferenceStore>,
+    route: PromptRoute,
+) -> Result<(), SignerError> {
+    preferences
+        .update(&config, |preferences| preferences.prompt_route = route)
+        .map_err(SignerError::from)
+}
+
+/// Starts the app hidden in the tray from the next start on if `enabled`, saving the preference.
+#[tauri::command]
+async fn set_start_minimized(
+    config: State<'_, Config>,
+    preferences: State<'_, PreferenceStore>,
+    enabled: bool,
+) -> Result<(), SignerError> {
+    preferences
+        .update(&config, |preferences| preferences.start_minimized = enabled)
+        .map_err(SignerError::from)
+}
+
+/// Hides the main window in the tray instead of quitting the app when it is closed if
+/// `enabled`, saving the preference.
+#[tauri::command]
+async fn set_close_to_tray(
+    config: State<'_, Config>,
+    preferences: State<'_, PreferenceStore>,
+    enabled: bool,
+) -> Result<(), SignerError> {
+    preferences
+        .update(&config, |preferences| preferences.close_to_tray = enabled)
+        .map_err(SignerError::from)
+}
+
+/// Registers the global `shortcut` which shows and focuses the main window.
+#[inline]
+fn register_summon_shortcut(app: &AppHandle, shortcut: &str) -> tauri::Result<()> {
+    let handle = app.clone();
+    app.global_shortcut_manager().register(shortcut, move || {
+        if let Some(window) = handle.get_window(MAIN_WINDOW) {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    })
+}
+
+/// Replaces the global shortcut which summons the main window with `shortcut`, or disables it
+/// for `None`, saving the preference.
+#[tauri::command]
+async fn set_summon_shortcut(
+    app: AppHandle,
+    config: State<'_, Config>,
+    preferences: State<'_, PreferenceStore>,
+    shortcut: Option<String>,
+) -> Result<(), SignerError> {
+    change_summon_shortcut(&app, &config, &preferences, shortcut)
+}
+
+/// Replaces the global shortcut which summons the main window with `shortcut`, or disables it
+/// for `None`, saving the preference in `preferences` for `config`.
+#[inline]
+fn change_summon_shortcut(
+    app: &AppHandle,
+    config: &Config,
+    preferences: &PreferenceStore,
+    shortcut: Option<String>,
+) -> Result<(), SignerError> {
+    let previous = preferences.get().summon_shortcut;
+    if previous == shortcut {
+        return Ok(());
+    }
+    if let Some(shortcut) = &shortcut {
+        register_summon_shortcut(app, shortcut).map_err(SignerError::internal)?;
+    }
+    if let Some(previous) = &previous {
+        app.global_shortcut_manager()
+            .unregister(previous)
+            .map_err(SignerError::internal)?;
+    }
+    preferences
+        .update(config, |preferences| preferences.summon_shortcut = shortcut)
+        .map_err(SignerError::from)
+}
+
+/// Obscures the prompts and the recovery phrase while the windows are not focused if `enabled`,
+/// saving the preference.
+#[tauri::command]
+async fn set_privacy_screen(
+    app: AppHandle,
+    config: State<'_, Config>,
+    preferences: State<'_, PreferenceStore>,
+    enabled: bool,
+) -> Result<(), SignerError> {
+    preferences
+        .update(&config, |preferences| preferences.privacy_screen = enabled)
+        .map_err(SignerError::from)?;
+    if !enabled {
+        app.emit_all("privacy", false)
+            .map_err(SignerError::internal)?;
+    }
+    Ok(())
+}
+
+/// Returns the path of a new file named `prefix-<timestamp>.extension` in the downloads
+/// directory, falling back to the data directory of `config`.
+#[inline]
+fn download_path(config: &Config, prefix: &str, extension: &str) -> PathBuf {
+    tauri::api::path::download_dir()
+        .unwrap_or_else(|| config.data_directory().to_owned())
+        .join(format!("{}-{}.{}", prefix, unix_timestamp(), extension))
+}
+
+/// Generates a diagnostics bundle in the downloads directory, returning its path.
+#[tauri::command]
+async fn generate_diagnostics(config: State<'_, Config>) -> Result<String, SignerError> {
+    let config = config.inner().clone();
+    let path = download_path(&config, "manta-signer-diagnostics", "zip");
+    let bundle_path = path.clone();
+    tauri::async_runtime::spawn_blocking(move || diagnostics::write_bundle(&config, bundle_path))
+        .await
+        .map_err(SignerError::internal)??;
+    Ok(path.display().to_string())
+}
+
+/// Deletes the rotated log files and empties the current one, returning the number of bytes
+/// which were freed.
+#[tauri::command]
+async fn purge_logs(config: State<'_, Config>) -> Result<u64, SignerError> {
+    let log_directory = config.log_directory();
+    tauri::async_runtime::spawn_blocking(move || log::purge(log_directory))
+        .await
+        .map_err(SignerError::internal)?
+        .map_err(SignerError::from)
+}
+
+/// Exports the audit log records in `range` as `format` to the downloads directory, returning
+/// the path of the exported file.
+#[tauri::command]
+async fn export_audit_log(
+    config: State<'_, Config>,
+    handle: State<'_, Handle>,
+    format: ExportFormat,
+    range: ExportRange,
+) -> Result<String, SignerError> {
+    let labels = handle.labels.get();
+    let audit_log_path = config.audit_log_path(&handle.active_wallet());
+    let path = download_path(&config, "manta-signer-audit", format.extension());
+    let export_path = path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        std::fs::write(
+            export_path,
+            audit::export(audit_log_path, format, range, &labels)?,
+        )
+    })
+    .await
+    .map_err(SignerError::internal)??;
+    Ok(path.display().to_string())
+}
+
+/// Exports the settings of the signer and the UI preferences to the downloads directory,
+/// returning the path of the exported file.
+#[tauri::command]
+async fn export_settings(
+    config: State<'_, Config>,
+    handle: State<'_, Handle>,
+    preferences: State<'_, PreferenceStore>,
+) -> Result<String, SignerError> {
+    let path = download_path(&config, "manta-signer-settings", "json");
+    let export_path = path.clone();
+    let config = config.inner().clone();
+    let handle = handle.inner().clone();
+    let preferences = serde_json::to_value(preferences.get())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        Settings::export(&config, &handle, Some(preferences))?
+            .save(export_path)
+            .map_err(SignerError::from)
+    })
+    .await
+    .map_err(SignerError::internal)??;
+    Ok(path.display().to_string())
+}
+
+/// Asks the user to confirm importing the security-relevant configuration settings in `changes`.
+#[inline]
+async fn confirm_sensitive_settings(app: &AppHandle, changes: &Map<String, Value>) -> bool {
+    let (sender, mut receiver) = channel(1);
+    tauri::api::dialog::ask(
+        app.get_window(MAIN_WINDOW).as_ref(),
+        "Import Security Settings",
+        format!(
+            "The settings file changes the following security settings:\n\n{}\n\nOnly import \
+             them if you exported this file yourself. Import them?",
+            changes
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        move |confirmed| {
+            let _ = sender.try_send(confirmed);
+        },
+    );
+    receiver.recv().await.unwrap_or(false)
+}
+
+/// Imports the settings file at `path` exported on another computer, including the approval
+/// grants only if `grants` is set. The security-relevant configuration settings are only
+/// imported once the user confirms them, see [`Settings::sensitive_changes`].
+#[tauri::command]
+async fn import_settings(
+    app: AppHandle,
+    config: State<'_, Config>,
+    handle: State<'_, Handle>,
+    preferences: State<'_, PreferenceStore>,
+    path: String,
+    grants: bool,
+) -> Result<ImportReport, SignerError> {
+    let load_config = config.inner().clone();
+    let (mut settings, changes) = tauri::async_runtime::spawn_blocking(move || {
+        let settings = Settings::load(path)?;
+        let changes = settings.sensitive_changes(&load_config)?;
+        Ok::<_, SignerError>((settings, changes))
+    })
+    .await
+    .map_err(SignerError::internal)??;
+    let sensitive = changes.is_empty() || confirm_sensitive_settings(&app, &changes).await;
+    let import_config = config.inner().clone();
+    let import_handle = handle.inner().clone();
+    let (report, imported) = tauri::async_runtime::spawn_blocking(move || {
+        let imported = settings
+            .preferences
+            .take()
+            .map(serde_json::from_value::<Preferences>)
+            .transpose()?;
+        let report = settings.import(&import_config, &import_handle, sensitive, grants)?;
+        Ok::<_, SignerError>((report, imported))
+    })
+    .await
+    .map_err(SignerError::internal)??;
+    if let Some(imported) = imported {
+        change_summon_shortcut(
+            &app,
+            &config,
+            &preferences,
+            imported.summon_shortcut.clone(),
+        )?;
+        preferences.update(&config, |preferences| *preferences = imported)?;
+    }
+    Ok(report)
+}
+
+/// Exports the proof of the transaction approved in the audit record with hash `tx_id` to the
+/// downloads directory, returning the path of the exported file.
+#[tauri::command]
+async fn export_transaction_proof(
+    config: State<'_, Config>,
+    handle: State<'_, Handle>,
+    tx_id: String,
+) -> Result<String, SignerError> {
+    handle.ensure_unlocked()?;
+    let audit_log_path = config.audit_log_path(&handle.active_wallet());
+    let path = download_path(&config, "manta-signer-proof", "json");
+    let export_path = path.clone();
+    let handle = handle.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let package = proof::export(
+            audit_log_path,
+            &tx_id,
+            &handle.labels.get(),
+            &handle.memos,
+            &handle.proofs,
+        )?
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "no proof is stored for this transaction",
+            )
+        })?;
+        std::fs::write(
+            export_path,
+            serde_json::to_vec_pretty(&package)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+        )
+    })
+    .await
+    .map_err(SignerError::internal)??;
+    Ok(path.display().to_string())
+}
+
+/// Returns the saved telemetry configuration.
+#[tauri::command]
+async fn get_telemetry(config: State<'_, Config>) -> Result<TelemetryConfig, SignerError> {
+    Ok(TelemetryConfig::load(config.telemetry_path()))
+}
+
+/// Saves the `telemetry` configuration, which takes effect the next time the signer starts.
+#[tauri::command]
+async fn set_telemetry(
+    config: State<'_, Config>,
+    telemetry: TelemetryConfig,
+) -> Result<(), SignerError> {
+    telemetry
+        .save(config.telemetry_path())
+        .map_err(SignerError::from)
+}
+
+/// Returns the minimum level of the log lines which the signer logs.
+#[tauri::command]
+async fn get_log_level() -> Result<Level, SignerError> {
+    Ok(log::level())
+}
+
+/// Sets the minimum `level` of the log lines which the signer logs, until it restarts or the log
+/// level in the configuration file changes.
+#[tauri::command]
+async fn set_log_level(app: AppHandle, level: Level) -> Result<(), SignerError> {
+    select_log_level(&app, level).map_err(SignerError::internal)
+}
+
+/// Returns the tray menu item identifier of the log `level`.
+#[inline]
+fn log_level_item(level: Level) -> String {
+    format!("{}{:?}", LOG_LEVEL_ITEM_PREFIX, level).to_lowercase()
+}
+
+/// Builds the tray submenu which switches the log level, from the least to the most verbose
+/// level, marking the `active` level.
+#[inline]
+fn log_level_menu(active: Level) -> SystemTrayMenu {
+    Level::ALL
+        .iter()
+        .rev()
+        .fold(SystemTrayMenu::new(), |menu, level| {
+            let item = CustomMenuItem::new(log_level_item(*level), format!("{:?}", level));
+            menu.add_item(if *level == active {
+                item.selected()
+            } else {
+                item
+            })
+        })
+}
+
+/// Sets the minimum log `level` of the signer and marks it in the tray menu.
+#[inline]
+fn select_log_level(app: &AppHandle, level: Level) -> tauri::Result<()> {
+    log::set_level(level);
+    let tray = app.tray_handle();
+    for other in Level::ALL {
+        tray.get_item(&log_level_item(other))
+            .set_selected(other == level)?;
+    }
+    Ok(())
+}
+
+/// Returns the tray menu item identifier of the pending request with the given `id`.
+#[inline]
+fn pending_item(id: u64) -> String {
+    format!("{}{}", PENDING_ITEM_PREFIX, id)
+}
+
+/// Builds the tray submenu which lists the `pending` requests in arrival order.
+#[inline]
+fn pending_menu(pending: &[PendingRequest]) -> SystemTrayMenu {
+    if pending.is_empty() {
+        return SystemTrayMenu::new()
+            .add_item(CustomMenuItem::new("pending-none", "No Pending Requests").disabled());
+    }
+    pending.iter().fold(SystemTrayMenu::new(), |menu, request| {
+        let mut preview = request
+            .preview
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(PENDING_ITEM_LENGTH)
+            .collect::<String>();
+        if request.preview.chars().count() > preview.chars().count() {
+            preview.push_str("...");
+        }
+        menu.add_item(CustomMenuItem::new(
+            pending_item(request.id),
+            format!("{}: {}", request.kind, preview),
+        ))
+    })
+}
+
+/// Builds the tray menu for the signer `status` with the `pending` requests, marking the active
+/// `log_level`.
+#[inline]
+fn tray_menu(status: SignerStatus, pending: &[PendingRequest], log_level: Level) -> SystemTrayMenu {
+    let status = match status {
+        SignerStatus::Locked => "Locked".into(),
+        SignerStatus::Unlocked => "Unlocked".into(),
+        SignerStatus::Syncing => "Syncing".into(),
+        SignerStatus::Pending => format!("{} Pending", pending.len()),
+    };
+    SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new(STATUS_ITEM, format!("Status: {}", status)).disabled())
+        .add_submenu(SystemTraySubmenu::new(
+            format!("Pending Requests ({})", pending.len()),
+            pending_menu(pending),
+        ))
+        .add_submenu(SystemTraySubmenu::new(
+            "Log Level",
+            log_level_menu(log_level),
+        ))
+        .add_item(CustomMenuItem::new("show", "Open Signer"))
+        .add_item(CustomMenuItem::new("lock", "Lock"))
+        .add_item(CustomMenuItem::new("about", "About"))
+        .add_item(CustomMenuItem::new("exit", "Quit"))
+}
+
+/// Shows the signer `status` with the tray icon and rebuilds the tray menu with the pending
+/// requests.
+#[inline]
+fn show_status(app: &AppHandle, status: SignerStatus) -> tauri::Result<()> {
+    let icon: &[u8] = match status {
+        SignerStatus::Locked => include_bytes!("../icons/tray-locked.png"),
+        SignerStatus::Unlocked => include_bytes!("../icons/tray.png"),
+        SignerStatus::Syncing => include_bytes!("../icons/tray-syncing.png"),
+        SignerStatus::Pending => include_bytes!("../icons/tray-pending.png"),
+    };
+    let tray = app.tray_handle();
+    tray.set_icon(Icon::Raw(icon.to_vec()))?;
+    tray.set_menu(tray_menu(
+        status,
+        &app.state::<Handle>().queue.list(),
+        log::level(),
+    ))
+}
+
+/// Shows and focuses the window which shows the current authorization prompt.
+#[inline]
+fn focus_prompt(app: &AppHandle) -> tauri::Result<()> {
+    let window = app
+        .get_window(APPROVAL_WINDOW)
+        .or_else(|| app.get_window(MAIN_WINDOW));
+    if let Some(window) = window {
+        window.show()?;
+        window.set_focus()?;
+    }
+    Ok(())
+}
+
+/// Locks the signer, see [`Handle::lock`], and hides the pending prompts. The main window asks
+/// the user to log in again.
+#[inline]
+fn lock_signer(app: &AppHandle) -> tauri::Result<()> {
+    app.state::<Handle>().lock();
+    app.state::<ApprovalPopup>().close(app)
+}
+
+/// Quits the app, asking the user first if there are pending authorization requests, which are
+/// rejected when the app quits.
+#[inline]
+fn quit(app: &AppHandle) {
+    let pending = app.state::<Handle>().queue.len();
+    if pending == 0 {
+        app.exit(0);
+        return;
+    }
+    let handle = app.clone();
+    tauri::api::dialog::ask(
+        app.get_window(MAIN_WINDOW).as_ref(),
+        "Quit Manta Signer",
+        format!(
+            "There are {} pending signing requests, which are rejected when the signer quits. \
+             Quit anyway?",
+            pending
+        ),
+        move |confirmed| {
+            if confirmed {
+                handle.exit(0)
+            }
+        },
+    );
+}
+
+/// Returns the current signer state, which is also emitted as the `signer-state` event whenever
+/// it changes.
+#[tauri::command]
+async fn get_signer_state(handle: State<'_, Handle>) -> Result<SignerState, SignerError> {
+    Ok(handle.status_receiver().state())
+}
+
+/// Returns the ledger synchronization progress, which is also emitted as the `sync-progress`
+/// event while the signer catches up with the ledger.
+#[tauri::command]
+async fn get_sync_status(handle: State<'_, Handle>) -> Result<SyncProgress, SignerError> {
+    Ok(handle.sync_progress.progress())
+}
+
+/// Pauses the background synchronization with the ledger until it is resumed.
+#[tauri::command]
+async fn pause_sync(handle: State<'_, Handle>) -> Result<(), SignerError> {
+    handle.sync_control.pause();
+    Ok(())
+}
+
+/// Resumes the background synchronization with the ledger.
+#[tauri::command]
+async fn resume_sync(handle: State<'_, Handle>) -> Result<(), SignerError> {
+    handle.sync_control.resume();
+    Ok(())
+}
+
+/// Locks the signer until the user logs in again with the password.
+#[tauri::command]
+async fn lock_now(app: AppHandle) -> Result<(), SignerError> {
+    lock_signer(&app).map_err(SignerError::internal)
+}
+
+/// Searches the command palette for `query`.
+#[tauri::command]
+async fn search_commands(query: String) -> Result<Vec<CommandMatch>, SignerError> {
+    Ok(palette::search(&query))
+}
+
+/// Runs the palette command `id`, returning a message describing the result.
+#[tauri::command]
+async fn run_command(
+    app: AppHandle,
+    config: State<'_, Config>,
+    password_store: State<'_, PasswordStore>,
+    id: CommandId,
+) -> Result<String, SignerError> {
+    match id {
+        CommandId::Lock => {
+            password_store.clear().await;
+            app.state::<SensitiveClipboard>().clear(&app).await;
+            if let Some(window) = app.get_window(MAIN_WINDOW) {
+                let _ = window.hide();
+            }
+            lock_signer(&app).map_err(SignerError::internal)?;
+            Ok("Signer locked.".into())
+        }
+        CommandId::OpenDataDirectory => {
+            let directory = config.data_directory().display().to_string();
+            tauri::api::shell::open(&app.shell_scope(), &directory, None)
+                .map_err(SignerError::internal)?;
+            Ok(directory)
+        }
+        CommandId::ExportHistory => {
+            let path = download_path(&config, "manta-signer-history", "log");
+            let history_path = path.clone();
+            tauri::async_runtime::spawn_blocking(move || palette::export_history(history_path))
+                .await
+                .map_err(SignerError::internal)??;
+            Ok(path.display().to_string())
+        }
+        CommandId::VerifyParameters => {
+            let config = config.inner().clone();
+            tauri::async_runtime::spawn_blocking(move || palette::verify_parameters(&config))
+                .await
+                .map_err(SignerError::internal)?
+        }
+        CommandId::GenerateDiagnostics => generate_diagnostics(config).await,
+    }
+}
+
+/// Lists the requests waiting for the user's authorization in arrival order.
+#[tauri::command]
+async fn get_pending_requests(
+    handle: State<'_, Handle>,
+) -> Result<Vec<PendingRequest>, SignerError> {
+    Ok(handle.queue.list())
+}
+
+/// Rejects the pending request with the given `id`, returning `false` if there is none. The
+/// client which sent the request receives a rejection error.
+#[tauri::command]
+async fn reject_request(handle: State<'_, Handle>, id: u64) -> Result<bool, SignerError> {
+    Ok(handle.queue.reject(id))
+}
+
+/// Lists the active approval sessions.
+#[tauri::command]
+async fn list_sessions(handle: State<'_, Handle>) -> Result<Vec<Session>, SignerError> {
+    Ok(handle.sessions.list())
+}
+
+/// Revokes the approval session of the paired dapp with the given `dapp` identifier.
+#[tauri::command]
+async fn revoke_session(handle: State<'_, Handle>, dapp: String) -> Result<bool, SignerError> {
+    Ok(handle.sessions.revoke(&dapp)?)
+}
+
+/// Lists the active remembered approval decisions.
+#[tauri::command]
+async fn list_exemptions(handle: State<'_, Handle>) -> Result<Vec<Exemption>, SignerError> {
+    Ok(handle.exemptions.list())
+}
+
+/// Revokes the remembered approval decision with the given `id`.
+#[tauri::command]
+async fn revoke_exemption(handle: State<'_, Handle>, id: u64) -> Result<bool, SignerError> {
+    Ok(handle.exemptions.revoke(id)?)
+}
+
+/// Returns the account and asset labels.
+#[tauri::command]
+async fn get_labels(handle: State<'_, Handle>) -> Result<Labels, SignerError> {
+    Ok(handle.labels.get())
+}
+
+/// Sets the label of `target` to `label`, removing it if `label` is `None` or empty, and returns
+/// the updated labels.
+#[tauri::command]
+async fn set_label(
+    handle: State<'_, Handle>,
+    target: LabelTarget,
+    label: Option<String>,
+) -> Result<Labels, SignerError> {
+    let labels = handle.labels.clone();
+    tauri::async_runtime::spawn_blocking(move || labels.set(target, label))
+        .await
+        .map_err(SignerError::internal)?
+        .map_err(SignerError::from)
+}
+
+/// Returns the current UNIX timestamp in seconds for naming generated files.
+#[inline]
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Runs the main Tauri application.
+pub fn run() {
+    let mut config = Config::load_default().expect("Unable to load the server configuration.");
+    config.telemetry = TelemetryConfig::load(config.telemetry_path());
+
+    let scheme = format!("{}:", deeplink::SCHEME);
+    let links = std::env::args()
+        .skip(1)
+        .filter(|arg| arg.starts_with(&scheme))
+        .collect::<Vec<_>>();
+    if !links.is_empty() {
+        for link in &links {
+            deeplink::forward(&config.deep_link_inbox_path(), link)
+                .expect("Unable to forward the deep link.");
+        }
+        if discovery::is_running(config.discovery_path()) {
+            return;
+        }
+    }
+
+    let mut app = tauri::Builder::default()
+        .system_tray(SystemTray::new().with_menu(tray_menu(
+            SignerStatus::Locked,
+            &[],
+            config.log_level,
+        )))
+        .on_system_tray_event(move |app, event| {
+            if let SystemTrayEvent::MenuItemClick { id, .. } = event {
+                match id.as_str() {
+                    "lock" => lock_signer(app).unwrap(),
+                    "about" => app.get_window(ABOUT_WINDOW).unwrap().show().unwrap(),
+                    "show" => {
+                        let window = app.get_window(MAIN_WINDOW).unwrap();
+                        window.show().unwrap();
+                        window.set_focus().unwrap();
+                    }
+                    "exit" => quit(app),
+                    id if id.starts_with(PENDING_ITEM_PREFIX) => focus_prompt(app).unwrap(),
+                    id => {
+                        if let Some(level) = Level::ALL
+                            .iter()
+                            .copied()
+                            .find(|level| log_level_item(*level) == id)
+                        {
+                            select_log_level(app, level).unwrap()
+                        }
+                    }
+                }
+            }
+        })
+        .manage(PasswordStore::default())
+        .manage(SensitiveClipboard::default())
+        .manage(BackupQuizStore::default())
+        .manage(ApprovalChoices::default())
+        .manage(ApprovalPopup::default())
+        .manage(PreferenceStore(Arc::new(RwLock::new(Preferences::load(
+            &Preferences::path(&config),
+        )))))
+        .manage(config)
+        .manage(Handle::default())
+        .setup(|app| {
+            tauri::async_runtime::spawn_blocking(|| {
+                let _ = deeplink::register();
+            });
+            let handle = app.handle();
+            let preferences = app.state::<PreferenceStore>().inner().clone();
+            let config = app.state::<Config>().inner().clone();
+            let password_store = app.state::<PasswordStore>().handle();
+            let service_handle = app.state::<Handle>().inner().clone();
+            if let Some(shortcut) = preferences.get().summon_shortcut {
+                let _ = register_summon_shortcut(&handle, &shortcut);
+            }
+            let mut status = service_handle.status_receiver();
+            let status_handle = handle.clone();
+            spawn(async move {
+                loop {
+                    let _ = show_status(&status_handle, status.status());
+                    let _ = status_handle.emit_all("signer-state", status.state());
+                    if !status.changed().await {
+                        break;
+                    }
+                }
+            });
+            let progress_handle = handle.clone();
+            let progress_service_handle = service_handle.clone();
+            spawn(async move {
+                loop {
+                    async_std::task::sleep(PROGRESS_INTERVAL).await;
+                    let progress = progress_service_handle.sync_progress.progress();
+                    if progress.catching_up {
+                        let _ = progress_handle.emit_all("sync-progress", progress);
+                    }
+                }
+            });
+            let (recovery_sender, recovery) = channel(1);
+            app.manage(RecoveryStore(recovery_sender));
+            let (confirmation_sender, confirmation) = channel(1);
+            app.manage(ConfirmationStore(confirmation_sender));
+            let (enrollment_sender, enrollment) = channel(1);
+            app.manage(EnrollmentStore(enrollment_sender));
+            spawn(async move {
+                let password = password_store.into_relay().await;
+                let result = service::start_with_handle(
+                    config,
+                    User::new(
+                        handle.clone(),
+                        preferences,
+                        password,
+                        recovery,
+                        confirmation,
+                        enrollment,
+                    ),
+                    service_handle,
+                )
+                .await;
+                if let Err(err) = result {
+                    let err = SignerError::from(err);
+                    handle.emit_all("error", &err).unwrap();
+                    panic!("Unable to build manta-signer service: {}", err);
+                }
+            });
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            acknowledge_totp_enrollment,
+            backup_quiz_indices,
+            benchmark_proving,
+            check_password_strength,
+            copy_sensitive,
+            current_prompt,
+            export_audit_log,
+            export_transaction_proof,
+            export_settings,
+            import_settings,
+            generate_diagnostics,
+            get_balances,
+            get_history,
+            get_labels,
+            get_log_level,
+            get_pending_requests,
+            get_preferences,
+            get_public_address,
+            get_signer_state,
+            get_sync_status,
+            get_telemetry,
+            list_exemptions,
+            list_sessions,
+            lock_now,
+            pause_sync,
+            purge_logs,
+            reject_request,
+            resume_sync,
+            revoke_exemption,
+            revoke_session,
+            run_command,
+            search_commands,
+            send_confirmation,
+            send_password,
+            send_recovery_choice,
+            set_approval_memo,
+            set_capture_protection,
+            set_close_to_tray,
+            set_fee_tier,
+            set_label,
+            set_log_level,
+            set_privacy_screen,
+            set_prompt_route,
+            set_remember_choice,
+            set_start_minimized,
+            set_summon_shortcut,
+            set_telemetry,
+            stop_password_prompt,
+            verify_mnemonic_words,
+            validate_mnemonic,
+        ])
+        .build(tauri::generate_context!())
+        .expect("Error while building UI.");
+
+    #[cfg(target_os = "macos")]
+    app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+
+    app.run(|app, event| match event {
+        RunEvent::Ready => {
+            app.get_window(ABOUT_WINDOW).unwrap().hide().unwrap();
+            if app.state::<PreferenceStore>().get().start_minimized {
+                app.get_window(MAIN_WINDOW).unwrap().hide().unwrap();
+            }
+        }
+        RunEvent::WindowEvent {
+            label,
+            event: WindowEvent::Focused(focused),
+            ..
+        } => {
+            if app.state::<PreferenceStore>().get().privacy_screen {
+                if let Some(window) = app.get_window(&label) {
+                    let _ = window.emit("privacy", !focused);
+                }
+            }
+        }
+        RunEvent::WindowEvent {
+            label,
+            event: WindowEvent::CloseRequested { api, .. },
+            ..
+        } => {
+            if label == APPROVAL_WINDOW {
+                if !app.state::<ApprovalPopup>().take_closing() {
+                    let app = app.clone();
+                    spawn(async move {
+                        let _ = app.state::<ConfirmationStore>().0.try_send(false);
+                        app.state::<PasswordStore>().clear().await
+                    });
+                }
+                return;
+            }
+            api.prevent_close();
+            match label.as_str() {
+                ABOUT_WINDOW => app.get_window(&label).unwrap().hide().unwrap(),
+                MAIN_WINDOW if app.state::<PreferenceStore>().get().close_to_tray => {
+                    app.get_window(&label).unwrap().hide().unwrap()
+                }
+                MAIN_WINDOW => quit(app),
+                _ => unreachable!("There are no other windows."),
+            }
+        }
+        RunEvent::Exit => {
+            let app = app.clone();
+            tauri::async_runtime::block_on(async move {
+                app.state::<SensitiveClipboard>().clear(&app).await
+            });
+        }
+        _ => (),
+    })
+}
@@ -15,267 +15,41 @@
 // along with manta-signer. If not, see <http://www.gnu.org/licenses/>.
 
 //! Manta Signer UI
+//!
+//! With the default `gui` feature, this is the desktop app. Without it, the binary runs the
+//! signer daemon headless and has no Tauri dependencies, so that it builds on servers and in
+//! containers without the system webview libraries.
 
 #![cfg_attr(doc_cfg, feature(doc_cfg))]
 #![forbid(rustdoc::broken_intra_doc_links)]
 #![forbid(missing_docs)]
 #![cfg_attr(
-    all(not(debug_assertions), target_os = "windows"),
+    all(not(debug_assertions), target_os = "windows", feature = "gui"),
     windows_subsystem = "windows"
 )]
 
 extern crate alloc;
 
-use alloc::sync::Arc;
-use manta_signer::{
-    config::{Config, Setup},
-    secret::{Authorizer, Password, PasswordFuture, Secret, SecretString, UnitFuture},
-    serde::Serialize,
-    service,
-};
-use tauri::{
-    async_runtime::{channel, spawn, Mutex, Receiver, Sender},
-    CustomMenuItem, Manager, RunEvent, State, SystemTray, SystemTrayEvent, SystemTrayMenu, Window,
-    WindowEvent,
-};
+#[cfg(feature = "gui")]
+mod gui;
 
-/// User
-pub struct User {
-    /// Main Window
-    window: Window,
+#[cfg(not(feature = "gui"))]
+use manta_signer::{config::Config, daemon};
 
-    /// Password Receiver
-    password: Receiver<Password>,
-
-    /// Password Retry Sender
-    retry: Sender<bool>,
-
-    /// Waiting Flag
-    waiting: bool,
-}
-
-impl User {
-    /// Builds a new [`User`] from `window`, `password`, and `retry`.
-    #[inline]
-    pub fn new(window: Window, password: Receiver<Password>, retry: Sender<bool>) -> Self {
-        Self {
-            window,
-            password,
-            retry,
-            waiting: false,
-        }
-    }
-
-    /// Emits a `message` of the given `kind` to the window.
-    #[inline]
-    fn emit<T>(&self, kind: &'static str, message: &T)
-    where
-        T: Serialize,
-    {
-        self.window.emit(kind, message).unwrap()
-    }
-
-    /// Sends a the `retry` message to have the user retry the password.
-    #[inline]
-    async fn should_retry(&mut self, retry: bool) {
-        self.retry
-            .send(retry)
-            .await
-            .expect("Failed to send retry message.");
-    }
-
-    /// Requests password from user, sending a retry message if the previous password did not match
-    /// correctly.
-    #[inline]
-    async fn request_password(&mut self) -> Password {
-        if self.waiting {
-            self.should_retry(true).await;
-        }
-        let password = self
-            .password
-            .recv()
-            .await
-            .expect("Failed to receive retry message.");
-        self.waiting = password.is_known();
-        password
-    }
-
-    /// Sends validation message when password was correctly matched.
-    #[inline]
-    async fn validate_password(&mut self) {
-        self.waiting = false;
-        self.should_retry(false).await;
-    }
-}
-
-impl Authorizer for User {
-    #[inline]
-    fn password(&mut self) -> PasswordFuture {
-        Box::pin(async move { self.request_password().await })
-    }
-
-    #[inline]
-    fn setup<'s>(&'s mut self, setup: &'s Setup) -> UnitFuture<'s> {
-        self.emit("connect", setup);
-        Box::pin(async move {})
-    }
-
-    #[inline]
-    fn wake<T>(&mut self, prompt: &T) -> UnitFuture
-    where
-        T: Serialize,
-    {
-        self.emit("authorize", prompt);
-        Box::pin(async move {})
-    }
-
-    #[inline]
-    fn sleep(&mut self) -> UnitFuture {
-        Box::pin(async move { self.validate_password().await })
-    }
-}
-
-/// Password Storage Channel
-struct PasswordStoreChannel {
-    /// Password Sender
-    password: Sender<Password>,
-
-    /// Retry Receiver
-    retry: Receiver<bool>,
-}
-
-/// Password Storage Type
-type PasswordStoreType = Arc<Mutex<Option<PasswordStoreChannel>>>;
-
-/// Password Storage Handle
-pub struct PasswordStoreHandle(PasswordStoreType);
-
-impl PasswordStoreHandle {
-    /// Constructs the opposite end of `self` for the password storage handle.
-    #[inline]
-    pub async fn into_channel(self) -> (Receiver<Password>, Sender<bool>) {
-        let (password, receiver) = channel(1);
-        let (sender, retry) = channel(1);
-        *self.0.lock().await = Some(PasswordStoreChannel { password, retry });
-        (receiver, sender)
-    }
+/// Runs the signer.
+fn main() {
+    #[cfg(feature = "gui")]
+    gui::run();
+    #[cfg(not(feature = "gui"))]
+    headless();
 }
 
-/// Password Storage
-#[derive(Default)]
-pub struct PasswordStore(PasswordStoreType);
-
-impl PasswordStore {
-    /// Returns a handle for setting up a [`PasswordStore`].
-    #[inline]
-    pub fn handle(&self) -> PasswordStoreHandle {
-        PasswordStoreHandle(self.0.clone())
-    }
-
-    /// Loads the password store with `password`, returning `true` if the password was correct.
-    #[inline]
-    pub async fn load(&self, password: SecretString) -> bool {
-        if let Some(store) = &mut *self.0.lock().await {
-            let _ = store.password.send(Password::from_known(password)).await;
-            store.retry.recv().await.unwrap()
-        } else {
-            false
-        }
-    }
-
-    /// Loads the password with `password`, not requesting a retry.
-    #[inline]
-    pub async fn load_exact(&self, password: SecretString) {
-        if let Some(store) = &mut *self.0.lock().await {
-            let _ = store.password.send(Password::from_known(password)).await;
-        }
+/// Runs the signer daemon without a window, see the [`daemon`] module.
+#[cfg(not(feature = "gui"))]
+fn headless() {
+    let config = Config::load_default().expect("Unable to load the server configuration.");
+    if let Err(err) = async_std::task::block_on(daemon::run(config)) {
+        eprintln!("error: {:?}", err);
+        std::process::exit(1);
     }
-
-    /// Clears the password from the store.
-    #[inline]
-    pub async fn clear(&self) {
-        if let Some(store) = &mut *self.0.lock().await {
-            let _ = store.password.send(Password::from_unknown()).await;
-        }
-    }
-}
-
-/// Sends the current `password` into storage from the UI.
-#[tauri::command]
-async fn send_password(
-    password_store: State<'_, PasswordStore>,
-    password: String,
-) -> Result<bool, ()> {
-    Ok(password_store.load(Secret::new(password)).await)
-}
-
-/// Stops the server from prompting for the password.
-#[tauri::command]
-async fn stop_password_prompt(password_store: State<'_, PasswordStore>) -> Result<(), ()> {
-    password_store.clear().await;
-    Ok(())
-}
-
-/// Runs the main Tauri application.
-fn main() {
-    let config =
-        Config::try_default().expect("Unable to generate the default server configuration.");
-
-    let mut app = tauri::Builder::default()
-        .system_tray(
-            SystemTray::new().with_menu(
-                SystemTrayMenu::new()
-                    .add_item(CustomMenuItem::new("about", "About"))
-                    .add_item(CustomMenuItem::new("exit", "Quit")),
-            ),
-        )
-        .on_system_tray_event(move |app, event| {
-            if let SystemTrayEvent::MenuItemClick { id, .. } = event {
-                match id.as_str() {
-                    "about" => app.get_window("about").unwrap().show().unwrap(),
-                    "exit" => app.exit(0),
-                    _ => {}
-                }
-            }
-        })
-        .manage(PasswordStore::default())
-        .manage(config)
-        .setup(|app| {
-            let window = app.get_window("main").unwrap();
-            let config = app.state::<Config>().inner().clone();
-            let password_store = app.state::<PasswordStore>().handle();
-            spawn(async move {
-                let (password, retry) = password_store.into_channel().await;
-                service::start(config, User::new(window, password, retry))
-                    .await
-                    .expect("Unable to build manta-signer service.");
-            });
-            Ok(())
-        })
-        .invoke_handler(tauri::generate_handler![
-            send_password,
-            stop_password_prompt,
-        ])
-        .build(tauri::generate_context!())
-        .expect("Error while building UI.");
-
-    #[cfg(target_os = "macos")]
-    app.set_activation_policy(tauri::ActivationPolicy::Accessory);
-
-    app.run(|app, event| match event {
-        RunEvent::Ready => app.get_window("about").unwrap().hide().unwrap(),
-        RunEvent::WindowEvent {
-            label,
-            event: WindowEvent::CloseRequested { api, .. },
-            ..
-        } => {
-            api.prevent_close();
-            match label.as_str() {
-                "about" => app.get_window(&label).unwrap().hide().unwrap(),
-                "main" => app.exit(0),
-                _ => unreachable!("There are no other windows."),
-            }
-        }
-        _ => (),
-    })
 }
@@ -2,7 +2,10 @@ import './App.css';
 import Authorize from './pages/Authorize';
 import CreateAccount from './pages/CreateAccount';
 import Loading from './pages/Loading';
+import PairDevice from './pages/PairDevice';
+import Recovery from './pages/Recovery';
 import SignIn from './pages/SignIn';
+import TotpEnrollment from './pages/TotpEnrollment';
 import { Container } from 'semantic-ui-react';
 import { appWindow } from '@tauri-apps/api/window';
 import { invoke } from '@tauri-apps/api/tauri';
@@ -13,15 +16,104 @@ const LOADING_PAGE = 0;
 const CREATE_ACCOUNT_PAGE = 1;
 const LOGIN_PAGE = 2;
 const AUTHORIZE_PAGE = 3;
+const RECOVERY_PAGE = 4;
+const TOTP_ENROLLMENT_PAGE = 5;
+const PAIR_DEVICE_PAGE = 6;
+
+const APPROVAL_WINDOW = 'approval';
+
+// Renders the text of an authorization prompt, falling back to its JSON for unknown types.
+const promptSummary = (prompt) => {
+  const content = prompt.content;
+  switch (prompt.type) {
+    case 'SignTransaction':
+      return content.summary;
+    case 'SignMessage':
+      return `Sign message from ${content.address}\nMessage:\n${content.message}`;
+    case 'SignExtrinsic':
+      return `Sign extrinsic from ${content.address}\nGenesis hash: ${content.genesis_hash}\nCall: ${content.call}`;
+    case 'ExportViewingKey':
+      return `Export the viewing key to ${content.origin || 'an unknown origin'}`;
+    case 'PairDapp':
+      return `Pair dapp \`${content.name}\` from ${content.origin || 'an unknown origin'}`;
+    case 'ChangeSettings':
+      return content.description;
+    case 'Oversized':
+      return `Oversized request of ${content.size} bytes with ${content.items} items and a total value of ${content.total_value}.\nPayload hash: ${content.hash}\n${content.preview}...`;
+    default:
+      return JSON.stringify(prompt, null, 2);
+  }
+};
 
 function App() {
   const [currentPage, setCurrentPage] = useState(LOADING_PAGE);
   const [isConnected, setIsConnected] = useState(false);
   const [recoveryPhrase, setRecoveryPhrase] = useState(null);
+  const [recovery, setRecovery] = useState(null);
+  const [totpUri, setTotpUri] = useState(null);
+  const [pairingCode, setPairingCode] = useState(null);
   const [authorizationSummary, setAuthorizationSummary] = useState(null);
+  const [feeQuote, setFeeQuote] = useState(null);
+  const [confirmOnly, setConfirmOnly] = useState(false);
+  const [rememberable, setRememberable] = useState(false);
+  const [cooldown, setCooldown] = useState(0);
+  const [obscured, setObscured] = useState(false);
+
+  useEffect(() => {
+    listen('cooldown', (event) => {
+      console.log("[INFO]: Password cooldown: ", event.payload);
+      setCooldown(event.payload);
+      setTimeout(() => setCooldown(0), event.payload * 1000);
+    });
+  }, []);
+
+  useEffect(() => {
+    listen('privacy', (event) => setObscured(event.payload));
+  }, []);
+
+  useEffect(() => {
+    if (appWindow.label !== APPROVAL_WINDOW) return;
+    setIsConnected(true);
+    listenForTxAuthorizationRequests();
+    // The popup is created for its prompt, so the prompt was sent before it started listening.
+    invoke('current_prompt').then((current) => {
+      if (current) showPrompt(current.prompt, current.event === 'confirm');
+    });
+  }, []);
 
   useEffect(() => {
-    if (isConnected) return;
+    if (appWindow.label === APPROVAL_WINDOW) return;
+    listen('recover', (event) => {
+      console.log("[INFO]: Recover Event: ", event);
+      setRecovery(event.payload);
+      setCurrentPage(RECOVERY_PAGE);
+    });
+    listen('enroll_totp', (event) => {
+      console.log("[INFO]: TOTP Enrollment Event.");
+      setTotpUri(event.payload);
+    });
+    listen('pairing_offer', (event) => {
+      console.log("[INFO]: Pairing Offer Event.");
+      setPairingCode(event.payload);
+    });
+  }, []);
+
+  // The enrollment is sent while the sign-in finishes, so it stays in front until acknowledged.
+  useEffect(() => {
+    if (totpUri === null || currentPage === TOTP_ENROLLMENT_PAGE) return;
+    setCurrentPage(TOTP_ENROLLMENT_PAGE);
+    appWindow.show();
+  }, [totpUri, currentPage]);
+
+  // The offer is sent right after the pairing was confirmed, so it stays in front until closed.
+  useEffect(() => {
+    if (pairingCode === null || currentPage === PAIR_DEVICE_PAGE) return;
+    setCurrentPage(PAIR_DEVICE_PAGE);
+    appWindow.show();
+  }, [pairingCode, currentPage]);
+
+  useEffect(() => {
+    if (isConnected || appWindow.label === APPROVAL_WINDOW) return;
     const beginInitialConnectionPhase = async () => {
       await once('connect', (event) => {
         console.log("[INFO]: Connect Event: ", event);
@@ -48,19 +140,116 @@ function App() {
     setCurrentPage(LOADING_PAGE);
   };
 
+  const showPrompt = (prompt, confirm) => {
+    setAuthorizationSummary(promptSummary(prompt));
+    setFeeQuote(prompt.type === 'SignTransaction' ? prompt.content.fee : null);
+    setRememberable(prompt.type === 'SignTransaction');
+    setConfirmOnly(confirm);
+    setCurrentPage(AUTHORIZE_PAGE);
+    appWindow.show();
+  };
+
   const listenForTxAuthorizationRequests = () => {
     console.log("[INFO]: Setup listener.");
+    if (appWindow.label !== APPROVAL_WINDOW) {
+      listen('connect', (event) => {
+        switch (event.payload.type) {
+          case 'CreateAccount':
+            console.log("[INFO]: New wallet.");
+            setRecoveryPhrase(event.payload.content);
+            setCurrentPage(CREATE_ACCOUNT_PAGE);
+            appWindow.show();
+            break;
+          case 'Login':
+            console.log("[INFO]: Locked.");
+            setCurrentPage(LOGIN_PAGE);
+            appWindow.show();
+            break;
+          default:
+            break;
+        }
+      });
+    }
     listen('authorize', (event) => {
       console.log("[INFO]: Wake: ", event);
-      setAuthorizationSummary(event.payload);
-      setCurrentPage(AUTHORIZE_PAGE);
-      appWindow.show();
+      showPrompt(event.payload, false);
+    });
+    listen('confirm', (event) => {
+      console.log("[INFO]: Confirm: ", event);
+      showPrompt(event.payload, true);
+    });
+    listen('dismiss', () => {
+      console.log("[INFO]: Authorization prompt expired.");
+      hideWindow();
     });
   };
 
-  const sendPassword = async (password) => {
+  const sendPassword = async (password, keyfile = null) => {
     console.log("[INFO]: Send password to signer server.");
-    return await invoke('send_password', { password: password });
+    const shouldRetry = await invoke('send_password', { password: password, keyfile: keyfile });
+    setCooldown(0);
+    return shouldRetry;
+  };
+
+  const sendConfirmation = async (approved) => {
+    console.log("[INFO]: Send confirmation to signer server.");
+    await invoke('send_confirmation', { approved: approved });
+  };
+
+  const checkPasswordStrength = async (password) => {
+    return await invoke('check_password_strength', { password: password });
+  };
+
+  const setFeeTier = async (tier) => {
+    await invoke('set_fee_tier', { tier: tier });
+  };
+
+  const setApprovalMemo = async (memo) => {
+    await invoke('set_approval_memo', { memo: memo });
+  };
+
+  const setRememberChoice = async (choice) => {
+    await invoke('set_remember_choice', { choice: choice });
+  };
+
+  const validateMnemonic = async (phrase) => {
+    return await invoke('validate_mnemonic', { phrase: phrase });
+  };
+
+  const sendRecoveryChoice = async (choice) => {
+    console.log("[INFO]: Send recovery choice to signer server.");
+    await invoke('send_recovery_choice', { choice: choice });
+    setRecovery(null);
+    setCurrentPage(LOADING_PAGE);
+  };
+
+  const acknowledgeTotpEnrollment = async () => {
+    console.log("[INFO]: Acknowledge TOTP enrollment.");
+    await invoke('acknowledge_totp_enrollment');
+    setTotpUri(null);
+    hideWindow();
+  };
+
+  const closePairingOffer = () => {
+    console.log("[INFO]: Close pairing offer.");
+    setPairingCode(null);
+    hideWindow();
+  };
+
+  const getBackupQuizIndices = async () => {
+    return await invoke('backup_quiz_indices');
+  };
+
+  const verifyMnemonicWords = async (words) => {
+    return await invoke('verify_mnemonic_words', { words: words });
+  };
+
+  const setCaptureProtection = async (enabled) => {
+    try {
+      await invoke('set_capture_protection', { enabled: enabled });
+    } catch (error) {
+      console.log("[WARN]: Unable to set screen capture protection: ", error);
+    }
   };
 
   const stopPasswordPrompt = async () => {
@@ -68,6 +257,16 @@ function App() {
     await invoke('stop_password_prompt');
   };
 
+  const rejectRequest = async () => {
+    const pending = await invoke('get_pending_requests');
+    if (pending.length > 0) {
+      console.log("[INFO]: Reject request: ", pending[0].id);
+      await invoke('reject_request', { id: pending[0].id });
+    } else {
+      await stopPasswordPrompt();
+    }
+  };
+
   const endInitialConnectionPhase = async () => {
     console.log("[INFO]: End Initial Connection Phase");
     setIsConnected(true);
@@ -76,7 +275,7 @@ function App() {
   };
 
   return (
-    <div className="App">
+    <div className={obscured ? 'App obscured' : 'App'}>
       <Container className="page">
         {currentPage === LOADING_PAGE && (
           <Loading/>
@@ -85,20 +284,55 @@ function App() {
           <CreateAccount
             recoveryPhrase={recoveryPhrase}
             sendPassword={sendPassword}
-            endInitialConnectionPhase={endInitialConnectionPhase}
+            checkPasswordStrength={checkPasswordStrength}
+            setCaptureProtection={setCaptureProtection}
+            getBackupQuizIndices={getBackupQuizIndices}
+            verifyMnemonicWords={verifyMnemonicWords}
+            allowKeyfile={!isConnected}
+            endInitialConnectionPhase={isConnected ? hideWindow : endInitialConnectionPhase}
           />
         )}
         {currentPage === LOGIN_PAGE && (
           <SignIn
+            cooldown={cooldown}
             sendPassword={sendPassword}
-            endInitialConnectionPhase={endInitialConnectionPhase}
+            endInitialConnectionPhase={isConnected ? hideWindow : endInitialConnectionPhase}
+          />
+        )}
+        {currentPage === RECOVERY_PAGE && (
+          <Recovery
+            recovery={recovery}
+            validateMnemonic={validateMnemonic}
+            sendRecoveryChoice={sendRecoveryChoice}
+          />
+        )}
+        {currentPage === TOTP_ENROLLMENT_PAGE && (
+          <TotpEnrollment
+            uri={totpUri}
+            setCaptureProtection={setCaptureProtection}
+            acknowledgeTotpEnrollment={acknowledgeTotpEnrollment}
+          />
+        )}
+        {currentPage === PAIR_DEVICE_PAGE && (
+          <PairDevice
+            pairingCode={pairingCode}
+            setCaptureProtection={setCaptureProtection}
+            closePairingOffer={closePairingOffer}
           />
         )}
         {currentPage === AUTHORIZE_PAGE && (
           <Authorize
             summary={authorizationSummary}
+            feeQuote={feeQuote}
+            setFeeTier={setFeeTier}
+            cooldown={cooldown}
+            confirmOnly={confirmOnly}
+            sendConfirmation={sendConfirmation}
             sendPassword={sendPassword}
-            stopPasswordPrompt={stopPasswordPrompt}
+            setApprovalMemo={setApprovalMemo}
+            rememberable={rememberable}
+            setRememberChoice={setRememberChoice}
+            rejectRequest={rejectRequest}
             hideWindow={hideWindow}
           />
         )}
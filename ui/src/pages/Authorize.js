@@ -1,20 +1,56 @@
 import React, { useState } from 'react';
-import { Button, Header, Input } from 'semantic-ui-react';
+import { Button, Header, Input, Label } from 'semantic-ui-react';
 
 const Authorize = ({
   summary,
+  feeQuote,
+  setFeeTier,
+  cooldown,
+  confirmOnly,
+  sendConfirmation,
   sendPassword,
-  stopPasswordPrompt,
+  setApprovalMemo,
+  rememberable,
+  setRememberChoice,
+  rejectRequest,
   hideWindow,
 }) => {
   const [password, setPassword] = useState('');
   const [passwordInvalid, setPasswordInvalid] = useState(false)
+  const [memo, setMemo] = useState('');
+  const [feeTier, setSelectedFeeTier] = useState(null);
+  const [rememberScope, setRememberScope] = useState(null);
+  const [rememberMinutes, setRememberMinutes] = useState(15);
+  const [rememberLimit, setRememberLimit] = useState('');
+
+  const resetChoices = () => {
+    setMemo('');
+    setSelectedFeeTier(null);
+    setRememberScope(null);
+    setRememberLimit('');
+  };
 
   const onClickAuthorize = async () => {
     console.log("[INFO]: Authorizing.");
+    await setApprovalMemo(memo);
+    if (feeQuote) await setFeeTier(feeTier);
+    if (rememberable) {
+      await setRememberChoice(rememberScope && {
+        scope: { type: rememberScope },
+        duration_minutes: Number(rememberMinutes) || 0,
+        value_limit: Number(rememberLimit) || 0,
+      });
+    }
+    if (confirmOnly) {
+      await sendConfirmation(true);
+      resetChoices();
+      hideWindow();
+      return;
+    }
     const shouldRetry = await sendPassword(password);
     if (!shouldRetry) {
       setPassword('');
+      resetChoices();
       setPasswordInvalid(false)
       hideWindow();
     } else {
@@ -25,8 +61,13 @@ const Authorize = ({
   const onClickDecline = async () => {
     console.log("[INFO]: Declining Transaction.");
     setPassword('');
+    resetChoices();
     setPasswordInvalid(false)
-    await stopPasswordPrompt();
+    if (confirmOnly) {
+      await sendConfirmation(false);
+    } else {
+      await rejectRequest();
+    }
     hideWindow();
   };
 
@@ -39,19 +80,73 @@ const Authorize = ({
     <>
       <Header>Authorize</Header>
       <div className="authorize-summary">{summary}</div>
+      {feeQuote && (
+        <Button.Group className="fee-tiers">
+          {feeQuote.options.map((option) => (
+            <Button
+              key={option.tier}
+              active={(feeTier || 'Standard') === option.tier}
+              onClick={() => setSelectedFeeTier(option.tier)}
+            >
+              {option.tier}
+            </Button>
+          ))}
+        </Button.Group>
+      )}
+      {rememberable && (
+        <>
+          <Button.Group className="remember-scopes">
+            <Button active={rememberScope === null} onClick={() => setRememberScope(null)}>
+              Ask Again
+            </Button>
+            <Button active={rememberScope === 'Origin'} onClick={() => setRememberScope('Origin')}>
+              Remember Dapp
+            </Button>
+            <Button active={rememberScope === 'Asset'} onClick={() => setRememberScope('Asset')}>
+              Remember Asset
+            </Button>
+          </Button.Group>
+          {rememberScope && (
+            <>
+              <Input
+                type="number"
+                label="Minutes"
+                value={rememberMinutes}
+                onChange={(e) => setRememberMinutes(e.target.value)}
+              />
+              <Input
+                type="number"
+                label="Max Value"
+                placeholder="Largest value signed without asking"
+                value={rememberLimit}
+                onChange={(e) => setRememberLimit(e.target.value)}
+              />
+            </>
+          )}
+        </>
+      )}
+      {!confirmOnly && (
+        <Input
+          type="password"
+          label="Password"
+          value={password}
+          onChange={(e) => onChangePassword(e.target.value)}
+          error={passwordInvalid}
+        />
+      )}
       <Input
-        type="password"
-        label="Password"
-        value={password}
-        onChange={(e) => onChangePassword(e.target.value)}
-        error={passwordInvalid}
+        label="Memo"
+        placeholder="Optional note for your history"
+        value={memo}
+        onChange={(e) => setMemo(e.target.value)}
       />
-      <Button className="button" onClick={onClickAuthorize}>
+      <Button className="button" onClick={onClickAuthorize} disabled={cooldown > 0}>
         Authorize
       </Button>
       <Button className="button" onClick={onClickDecline}>
         Decline
       </Button>
+      {cooldown > 0 && (<><br/><Label basic color='red' pointing>Too many failed attempts. Try again in {cooldown} seconds.</Label></>)}
     </>
   );
 };
@@ -1,28 +1,92 @@
-import { useState } from 'react';
+import { useState, useEffect } from 'react';
 import { Button, Input, Label, Header } from 'semantic-ui-react';
+import { open } from '@tauri-apps/api/dialog';
 
-const MIN_PASSWORD_LENGTH = 8;
-
-const CreateAccount = ({ recoveryPhrase, sendPassword, endInitialConnectionPhase }) => {
+const CreateAccount = ({ recoveryPhrase, sendPassword, checkPasswordStrength, setCaptureProtection, getBackupQuizIndices, verifyMnemonicWords, allowKeyfile, endInitialConnectionPhase }) => {
   const [password, setPassword] = useState('');
+  const [strength, setStrength] = useState(null);
   const [createdAccount, setCreatedAccount] = useState(false);
+  const [passwordInvalid, setPasswordInvalid] = useState(false);
+  const [keyfile, setKeyfile] = useState(null);
+  const [keyfileError, setKeyfileError] = useState(null);
+  const [quizIndices, setQuizIndices] = useState(null);
+  const [quizWords, setQuizWords] = useState([]);
+  const [quizFailed, setQuizFailed] = useState(false);
+
+  useEffect(() => {
+    if (!createdAccount) return;
+    setCaptureProtection(true);
+    return () => setCaptureProtection(false);
+  }, [createdAccount]);
 
-  const isValid = (password) => {
-    console.log("[INFO]: Check password validity.")
-    return password.length >= MIN_PASSWORD_LENGTH;
+  const onClickKeyfile = async () => {
+    setKeyfile(await open({ multiple: false, directory: false }));
+    setKeyfileError(null);
+  };
+
+  const onChangePassword = async (value) => {
+    setPassword(value);
+    setPasswordInvalid(false);
+    setStrength(value.length > 0 ? await checkPasswordStrength(value) : null);
   };
 
   const onClickCreateAccount = async () => {
     console.log("[INFO]: Creating account.")
-    if (isValid(password)) {
-      await sendPassword(password);
+    const result = await checkPasswordStrength(password);
+    setStrength(result);
+    if (result.acceptable) {
+      let shouldRetry;
+      try {
+        shouldRetry = await sendPassword(password, allowKeyfile ? keyfile : null);
+      } catch (error) {
+        setKeyfileError(error);
+        return;
+      }
+      if (shouldRetry) {
+        setPasswordInvalid(true);
+        return;
+      }
       setPassword('');
+      setStrength(null);
+      setKeyfile(null);
+      setPasswordInvalid(false);
       setCreatedAccount(true);
     }
   };
 
   const onClickConfirmRecoveryPhrase = async () => {
-    console.log("[INFO]: Confirming recovery phrase.")
+    let indices;
+    try {
+      indices = await getBackupQuizIndices();
+    } catch (error) {
+      console.log("[ERROR]: Unable to start the recovery phrase quiz: ", error);
+      return;
+    }
+    setQuizIndices(indices);
+    setQuizWords(indices.map(() => ''));
+    setQuizFailed(false);
+  };
+
+  const onChangeQuizWord = (position, value) => {
+    setQuizWords(quizWords.map((word, i) => i === position ? value : word));
+    setQuizFailed(false);
+  };
+
+  const onClickVerifyRecoveryPhrase = async () => {
+    console.log("[INFO]: Verifying recovery phrase.")
+    let verified;
+    try {
+      verified = await verifyMnemonicWords(quizWords);
+    } catch (error) {
+      console.log("[ERROR]: Unable to verify recovery phrase: ", error);
+      verified = false;
+    }
+    if (!verified) {
+      setQuizFailed(true);
+      return;
+    }
+    setQuizIndices(null);
+    await setCaptureProtection(false);
     await endInitialConnectionPhase();
   };
 
@@ -34,15 +98,32 @@ const CreateAccount = ({ recoveryPhrase, sendPassword, endInitialConnectionPhase
           <Input
             type="password"
             label="Password"
-            onChange={(e) => setPassword(e.target.value)}
+            onChange={(e) => onChangePassword(e.target.value)}
+            error={passwordInvalid}
           />
+          {allowKeyfile && (
+            <Button className="button" onClick={onClickKeyfile}>
+              {keyfile ? `Keyfile: ${keyfile.split(/[\\/]/).pop()}` : 'Add keyfile'}
+            </Button>
+          )}
           <Button className="button" onClick={onClickCreateAccount}>
             Create Account
           </Button>
-          {password.length > 0 && !isValid(password) && (<><br/><Label basic color='red' pointing> `Please enter a minimum of ${MIN_PASSWORD_LENGTH} characters.`</Label></>)}
+          {passwordInvalid && (<><br/><Label basic color='red' pointing>New wallets use the password of the signer.</Label></>)}
+          {keyfileError && (<><br/><Label basic color='red' pointing>{keyfileError}</Label></>)}
+          {password.length > 0 && strength && (
+            <>
+              <br/>
+              <Label basic color={strength.acceptable ? 'green' : 'red'} pointing>
+                Strength {strength.score}/4, cracked in {strength.crack_time_display}.
+                {strength.warning && (<> {strength.warning}</>)}
+                {!strength.acceptable && strength.suggestions.map((suggestion) => (<> {suggestion}</>))}
+              </Label>
+            </>
+          )}
         </>
       )}
-      {createdAccount && (
+      {createdAccount && !quizIndices && (
         <>
           <Header className="recovery-phrase-header">
             Recovery Phrase
@@ -62,6 +143,30 @@ const CreateAccount = ({ recoveryPhrase, sendPassword, endInitialConnectionPhase
           </Button>
         </>
       )}
+      {createdAccount && quizIndices && (
+        <>
+          <Header> Verify Recovery Phrase </Header>
+          <div className="recovery-phrase-info">
+            <p>Enter the following words of your recovery phrase.</p>
+          </div>
+          {quizIndices.map((index, position) => (
+            <Input
+              key={index}
+              label={`Word #${index + 1}`}
+              value={quizWords[position]}
+              onChange={(e) => onChangeQuizWord(position, e.target.value)}
+              error={quizFailed}
+            />
+          ))}
+          <Button className="button" onClick={onClickVerifyRecoveryPhrase}>
+            Verify
+          </Button>
+          <Button className="button" onClick={() => setQuizIndices(null)}>
+            Show recovery phrase again
+          </Button>
+          {quizFailed && (<><br/><Label basic color='red' pointing>The words do not match your recovery phrase.</Label></>)}
+        </>
+      )}
     </>
   );
 };
@@ -0,0 +1,36 @@
+import { useEffect } from 'react';
+import { Button, Header } from 'semantic-ui-react';
+
+const PairDevice = ({ pairingCode, setCaptureProtection, closePairingOffer }) => {
+  useEffect(() => {
+    setCaptureProtection(true);
+    return () => setCaptureProtection(false);
+  }, []);
+
+  const expiresAt = new Date(pairingCode.expires_at * 1000).toLocaleTimeString();
+
+  const onClickDone = async () => {
+    console.log("[INFO]: Pairing offer closed.");
+    await setCaptureProtection(false);
+    closePairingOffer();
+  };
+
+  return (
+    <div>
+      <Header>Pair a companion device</Header>
+      <p>
+        Scan this code with the companion app before {expiresAt}. Anyone who sees the code can
+        pair a device with this signer until then.
+      </p>
+      <img
+        alt="Pairing QR code"
+        src={`data:image/svg+xml;utf8,${encodeURIComponent(pairingCode.svg)}`}
+      />
+      <Button className="button" onClick={onClickDone}>
+        Done
+      </Button>
+    </div>
+  );
+};
+
+export default PairDevice;
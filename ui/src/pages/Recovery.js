@@ -0,0 +1,74 @@
+import React, { useState } from 'react';
+import { Button, Header, Label, TextArea, Form } from 'semantic-ui-react';
+
+const Recovery = ({ recovery, validateMnemonic, sendRecoveryChoice }) => {
+  const [phrase, setPhrase] = useState('');
+  const [phraseInvalid, setPhraseInvalid] = useState(false);
+
+  const onClickRestore = async (generation) => {
+    console.log("[INFO]: Restoring backup: ", generation);
+    await sendRecoveryChoice({ type: 'RestoreBackup', content: generation });
+  };
+
+  const onClickImport = async () => {
+    const report = await validateMnemonic(phrase);
+    if (report.issues.length > 0) {
+      setPhraseInvalid(true);
+      return;
+    }
+    console.log("[INFO]: Re-importing account from recovery phrase.");
+    const normalized = phrase.trim().toLowerCase().split(/\s+/).join(' ');
+    setPhrase('');
+    await sendRecoveryChoice({ type: 'ImportMnemonic', content: normalized });
+  };
+
+  const onClickAbort = async () => {
+    console.log("[INFO]: Aborting recovery.");
+    await sendRecoveryChoice({ type: 'Abort' });
+  };
+
+  const onChangePhrase = phrase => {
+    setPhrase(phrase);
+    setPhraseInvalid(false);
+  };
+
+  return (
+    <div>
+      <Header>Recover wallet</Header>
+      <p>The wallet file failed its integrity check: {recovery.reason}</p>
+      {recovery.backups.length > 0 ? (
+        <>
+          <Header as="h4">Restore a backup</Header>
+          {recovery.backups.map((generation) => (
+            <Button
+              key={generation}
+              className="button"
+              onClick={() => onClickRestore(generation)}
+            >
+              {generation === 1 ? 'Latest backup' : `Backup ${generation}`}
+            </Button>
+          ))}
+        </>
+      ) : (
+        <p>There is no valid backup of the wallet file.</p>
+      )}
+      <Header as="h4">Import the recovery phrase</Header>
+      <Form>
+        <TextArea
+          placeholder="Recovery phrase"
+          value={phrase}
+          onChange={(e) => onChangePhrase(e.target.value)}
+        />
+      </Form>
+      <Button className="button" onClick={onClickImport} disabled={phrase.trim() === ''}>
+        Import
+      </Button>
+      <Button className="button" onClick={onClickAbort}>
+        Quit
+      </Button>
+      {phraseInvalid && (<><br/><Label basic color='red' pointing>This recovery phrase is not valid.</Label></>)}
+    </div>
+  );
+};
+
+export default Recovery;
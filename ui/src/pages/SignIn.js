@@ -1,12 +1,26 @@
 import React, { useState } from 'react';
-import { Button, Input, Header, Form } from 'semantic-ui-react';
+import { Button, Input, Header, Form, Label } from 'semantic-ui-react';
+import { open } from '@tauri-apps/api/dialog';
 
-const SignIn = ({ sendPassword, endInitialConnectionPhase }) => {
+const SignIn = ({ cooldown, sendPassword, endInitialConnectionPhase }) => {
   const [password, setPassword] = useState('');
   const [passwordInvalid, setPasswordInvalid] = useState(null);
+  const [keyfile, setKeyfile] = useState(null);
+  const [keyfileError, setKeyfileError] = useState(null);
+
+  const onClickKeyfile = async () => {
+    setKeyfile(await open({ multiple: false, directory: false }));
+    setKeyfileError(null);
+  };
 
   const onClickSignIn = async () => {
-    const shouldRetry = await sendPassword(password);
+    let shouldRetry;
+    try {
+      shouldRetry = await sendPassword(password, keyfile);
+    } catch (error) {
+      setKeyfileError(error);
+      return;
+    }
     if (!shouldRetry) {
       setPassword('');
       console.log("[INFO]: End Initial Connection Phase");
@@ -34,9 +48,14 @@ const SignIn = ({ sendPassword, endInitialConnectionPhase }) => {
           error={passwordInvalid}
         />
       </Form.Field>
-      <Button className="button" onClick={onClickSignIn}>
+      <Button className="button" onClick={onClickKeyfile}>
+        {keyfile ? `Keyfile: ${keyfile.split(/[\\/]/).pop()}` : 'Add keyfile'}
+      </Button>
+      <Button className="button" onClick={onClickSignIn} disabled={cooldown > 0}>
         Sign in
       </Button>
+      {keyfileError && (<><br/><Label basic color='red' pointing>{keyfileError}</Label></>)}
+      {cooldown > 0 && (<><br/><Label basic color='red' pointing>Too many failed attempts. Try again in {cooldown} seconds.</Label></>)}
     </div>
   );
 };
@@ -0,0 +1,48 @@
+import { useEffect } from 'react';
+import { Button, Header } from 'semantic-ui-react';
+
+// Extracts the base32 secret from an `otpauth://` provisioning URI for manual entry.
+const totpSecret = (uri) => {
+  try {
+    return new URL(uri).searchParams.get('secret');
+  } catch (error) {
+    return null;
+  }
+};
+
+const TotpEnrollment = ({ uri, setCaptureProtection, acknowledgeTotpEnrollment }) => {
+  useEffect(() => {
+    setCaptureProtection(true);
+    return () => setCaptureProtection(false);
+  }, []);
+
+  const secret = totpSecret(uri);
+
+  const onClickDone = async () => {
+    console.log("[INFO]: TOTP secret enrolled.");
+    await setCaptureProtection(false);
+    await acknowledgeTotpEnrollment();
+  };
+
+  return (
+    <div>
+      <Header>Enroll your authenticator app</Header>
+      <p>
+        Signing requests now need a code from your authenticator app. Add this account to the app
+        by opening the link below on the same device, or by entering the secret manually.
+      </p>
+      <div className="recovery-phrase">{uri}</div>
+      {secret && (
+        <>
+          <Header as="h4">Secret</Header>
+          <div className="recovery-phrase">{secret}</div>
+        </>
+      )}
+      <Button className="button" onClick={onClickDone}>
+        Done
+      </Button>
+    </div>
+  );
+};
+
+export default TotpEnrollment;